@@ -4,6 +4,48 @@ require_relative "utaformatix/version"
 require_relative "utaformatix/core"
 
 module UtaFormatix
-  class Error < StandardError; end
-  # Your code goes here...
+  class Error < StandardError
+    class IllegalFile
+      class XmlElementNotFound
+        # @return [String] The name of the element.
+        attr_reader :name
+      end
+
+      class IllegalXmlValue
+        # @return [String] The name of the element.
+        attr_reader :name
+      end
+
+      class IllegalXmlAttribute
+        # @return [String] The name of the element.
+        attr_reader :name
+        # @return [String] The name of the attribute.
+        attr_reader :attribute
+      end
+    end
+  end
+
+  class Core
+    # Calls the block of `*_async` methods with the result, or the error, on a new thread once
+    # the conversion completes.
+    module AsyncCallback
+      Core.instance_methods(false).grep(/_async\z/).each do |name|
+        define_method(name) do |*args, **kwargs, &block|
+          promise = super(*args, **kwargs)
+          return promise unless block
+
+          Thread.new do
+            result = promise.value
+          rescue UtaFormatix::Error => e
+            block.call(nil, e)
+          else
+            block.call(result, nil)
+          end
+          promise
+        end
+      end
+    end
+
+    prepend AsyncCallback
+  end
 end
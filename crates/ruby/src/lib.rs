@@ -1,15 +1,37 @@
 use duplicate::{duplicate, duplicate_item};
-use magnus::{value::ReprValue, Error, Module, Object, RArray, RHash, Value};
+use magnus::{value::ReprValue, Class, Error, Module, Object, RArray, RHash, TryConvert, Value};
 use once_cell::sync::Lazy;
-use utaformatix::{base::UtaFormatix, ParseOptions};
+use options::{to_format, to_generate_options, to_parse_options};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use utaformatix::{base::UtaFormatix, ErrorKind, Format, GenerateOptions, ParseOptions, UfData};
+
+mod options;
 
 type RubyResult<T> = Result<T, magnus::Error>;
 
+/// Engine of `UtaFormatix::Core`.
+///
+/// `UtaFormatix` is safe to share between threads: requests are queued to its JS threads and
+/// matched with their responses by nonces, and the GVL is released while waiting. So a `Core`
+/// can be used from any number of Ruby threads, and a process rarely needs more than
+/// `Core.default`.
 #[magnus::wrap(class = "UtaFormatix::Core")]
 struct Core {
-    inner: UtaFormatix,
+    inner: Arc<UtaFormatix>,
 }
 
+/// Result of a conversion running on the Tokio runtime, filled once it completes.
+type Slot = Arc<(Mutex<Option<utaformatix::Result<UfData>>>, Condvar)>;
+
+/// Conversion running in the background, returned by the `*_async` methods of `Core`.
+#[magnus::wrap(class = "UtaFormatix::Promise", free_immediately)]
+struct Promise {
+    slot: Slot,
+}
+
+/// Engine shared by the `Core`s returned by `Core.default`, started on the first call.
+static DEFAULT: Lazy<Arc<UtaFormatix>> = Lazy::new(|| Arc::new(UtaFormatix::new()));
+
 static RUNTIME: Lazy<tokio::runtime::Runtime> = Lazy::new(|| {
     tokio::runtime::Builder::new_multi_thread()
         .enable_all()
@@ -41,6 +63,35 @@ where
     }
 }
 
+/// Converts a String or an Array of Strings (a file per track) into the sources of UST.
+fn to_ust_sources(source: Value) -> RubyResult<Vec<bytes::Bytes>> {
+    let ruby = magnus::Ruby::get().expect("Failed to get Ruby pointer");
+    let source = if source.is_kind_of(ruby.class_array()) {
+        RArray::from_value(source)
+            .expect("Failed to convert to RArray")
+            .into_iter()
+            .collect::<Vec<_>>()
+    } else {
+        vec![source]
+    };
+    Ok(source
+        .into_iter()
+        .map(|v| v.to_r_string())
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .map(|s| s.to_bytes())
+        .collect::<Vec<_>>())
+}
+
+fn to_ruby_hash(ufdata: &UfData) -> RubyResult<RHash> {
+    serde_magnus::serialize(ufdata).map_err(|e| {
+        magnus::Error::new(
+            magnus::Ruby::get().unwrap().exception_runtime_error(),
+            e.to_string(),
+        )
+    })
+}
+
 fn to_ruby_error(err: utaformatix::Error) -> magnus::Error {
     let ruby = magnus::Ruby::get().expect("Failed to get Ruby pointer");
     let error = ruby.define_module("UtaFormatix").unwrap();
@@ -48,28 +99,49 @@ fn to_ruby_error(err: utaformatix::Error) -> magnus::Error {
         .define_error("Error", ruby.exception_standard_error())
         .unwrap();
 
-    let exception = match err {
-        utaformatix::Error::IllegalFile(ref kind) => {
+    let exception_class = match err.kind() {
+        utaformatix::ErrorKind::IllegalFile(kind) => {
             let error = error
                 .define_error("IllegalFile", ruby.exception_runtime_error())
                 .unwrap();
 
             error
-                .define_error(kind.to_string(), ruby.exception_runtime_error())
+                .define_error(kind.as_ref(), ruby.exception_runtime_error())
                 .unwrap()
         }
-        ref err => error
-            .define_error(err.as_ref(), ruby.exception_runtime_error())
+        kind => error
+            .define_error(kind.as_ref(), ruby.exception_runtime_error())
             .unwrap(),
     };
 
-    magnus::Error::new(exception, err.to_string())
+    let exception = match exception_class.new_instance((err.to_string(),)) {
+        Ok(exception) => exception,
+        Err(e) => return e,
+    };
+    if let utaformatix::ErrorKind::IllegalFile(kind) = err.kind() {
+        if let Some(name) = kind.name() {
+            let _ = exception.ivar_set("@name", name);
+        }
+        if let Some(attribute) = kind.attribute() {
+            let _ = exception.ivar_set("@attribute", attribute);
+        }
+    }
+
+    magnus::Error::from(exception)
 }
 
 impl Core {
+    /// Starts a new engine with its own JS threads.
     fn new() -> Self {
         Self {
-            inner: UtaFormatix::new(),
+            inner: Arc::new(UtaFormatix::new()),
+        }
+    }
+
+    /// Returns a `Core` using the engine shared by the whole process.
+    fn default() -> Self {
+        Self {
+            inner: DEFAULT.clone(),
         }
     }
 
@@ -91,30 +163,19 @@ impl Core {
         [parse_vpr];
     )]
     pub fn fn_name(&self, args: &[Value]) -> RubyResult<RHash> {
-        let args = magnus::scan_args::scan_args::<(bytes::Bytes,), (), (), (), RHash, ()>(args)?;
+        let args =
+            magnus::scan_args::scan_args::<(bytes::Bytes,), (Option<Value>,), (), (), RHash, ()>(
+                args,
+            )?;
 
-        let mut options = ParseOptions::default();
-        let ruby = magnus::Ruby::get().expect("Failed to get Ruby pointer");
-        if let Some(pitch) = args.keywords.get(ruby.to_symbol("pitch")) {
-            options.pitch = pitch.to_bool();
-        }
-        if let Some(default_lyric) = args.keywords.get(ruby.to_symbol("default_lyric")) {
-            options.default_lyric = default_lyric.to_string();
-        }
-        let data = args.required.0.into_iter().collect::<Vec<_>>();
+        let options = to_parse_options(args.optional.0, args.keywords)?;
+        let data = args.required.0;
         let ufdata = without_gvl((self, data, options), |(this, data, options)| {
-            RUNTIME.block_on(this.inner.fn_name(&data, options))
+            RUNTIME.block_on(this.inner.fn_name(data, options))
         })
         .map_err(to_ruby_error)?;
 
-        let value: magnus::RHash = serde_magnus::serialize(&ufdata).map_err(|e| {
-            magnus::Error::new(
-                magnus::Ruby::get().unwrap().exception_runtime_error(),
-                e.to_string(),
-            )
-        })?;
-
-        Ok(value)
+        to_ruby_hash(&ufdata)
     }
 
     #[duplicate_item(
@@ -122,46 +183,192 @@ impl Core {
         [parse_ust];
     )]
     pub fn fn_name(&self, args: &[Value]) -> RubyResult<RHash> {
-        let args = magnus::scan_args::scan_args::<(Value,), (), (), (), RHash, ()>(args)?;
+        let args =
+            magnus::scan_args::scan_args::<(Value,), (Option<Value>,), (), (), RHash, ()>(args)?;
 
-        let mut options = ParseOptions::default();
-        if let Some(pitch) = args.keywords.get("pitch") {
-            options.pitch = pitch.to_bool();
-        }
-        if let Some(default_lyric) = args.keywords.get("default_lyric") {
-            options.default_lyric = default_lyric.to_string();
-        }
-        let ruby = magnus::Ruby::get().expect("Failed to get Ruby pointer");
-        let source = args.required.0;
-        let source = if source.is_kind_of(ruby.class_array()) {
-            RArray::from_value(source)
-                .expect("Failed to convert to RArray")
-                .into_iter()
-                .collect::<Vec<_>>()
-        } else {
-            vec![source]
-        };
-        let source = source
-            .into_iter()
-            .map(|v| v.to_r_string())
-            .collect::<Result<Vec<_>, _>>()?
-            .into_iter()
-            .map(|s| s.to_bytes().to_vec())
-            .collect::<Vec<_>>();
+        let options = to_parse_options(args.optional.0, args.keywords)?;
+        let source = to_ust_sources(args.required.0)?;
         let ufdata = without_gvl((self, source, options), |(this, source, options)| {
-            let slices = source.iter().map(|s| s.as_slice()).collect::<Vec<_>>();
-            RUNTIME.block_on(this.inner.fn_name(&slices, options))
+            RUNTIME.block_on(this.inner.fn_name(source, options))
         })
         .map_err(to_ruby_error)?;
 
-        let value: magnus::RHash = serde_magnus::serialize(&ufdata).map_err(|e| {
-            magnus::Error::new(
-                magnus::Ruby::get().unwrap().exception_runtime_error(),
-                e.to_string(),
+        to_ruby_hash(&ufdata)
+    }
+
+    /// Parses the source as the format, e.g. `parse(:vsqx, data)`.
+    pub fn parse(&self, args: &[Value]) -> RubyResult<RHash> {
+        let args =
+            magnus::scan_args::scan_args::<(Value, Value), (Option<Value>,), (), (), RHash, ()>(
+                args,
+            )?;
+
+        let format = to_format(args.required.0)?;
+        let options = to_parse_options(args.optional.0, args.keywords)?;
+        let ufdata = if format == Format::Ust {
+            let source = to_ust_sources(args.required.1)?;
+            without_gvl((self, source, options), |(this, source, options)| {
+                RUNTIME.block_on(this.inner.parse_ust(source, options))
+            })
+        } else {
+            let data = bytes::Bytes::try_convert(args.required.1)?;
+            without_gvl(
+                (self, format, data, options),
+                |(this, format, data, options)| {
+                    RUNTIME.block_on(this.inner.parse(format, data, options))
+                },
             )
-        })?;
+        }
+        .map_err(to_ruby_error)?;
 
-        Ok(value)
+        to_ruby_hash(&ufdata)
+    }
+
+    /// Generates the data as the format, e.g. `generate(:ust, ufdata)`.
+    ///
+    /// Returns an Array of Strings (a file per track) for UST and MusicXML, and a String
+    /// otherwise.
+    pub fn generate(&self, args: &[Value]) -> RubyResult<Value> {
+        let args =
+            magnus::scan_args::scan_args::<(Value, Value), (Option<Value>,), (), (), RHash, ()>(
+                args,
+            )?;
+
+        let format = to_format(args.required.0)?;
+        let data: UfData = serde_magnus::deserialize(args.required.1)?;
+        let options = to_generate_options(args.optional.0, args.keywords)?;
+        let files = without_gvl(
+            (self, format, data, options),
+            |(this, format, data, options)| {
+                RUNTIME.block_on(this.generate_files(format, &data, options))
+            },
+        )
+        .map_err(to_ruby_error)?;
+
+        let ruby = magnus::Ruby::get().expect("Failed to get Ruby pointer");
+        if matches!(format, Format::Ust | Format::MusicXml) {
+            Ok(ruby
+                .ary_from_iter(files.iter().map(|file| ruby.str_from_slice(file)))
+                .as_value())
+        } else {
+            let file = files.first().map_or(&[][..], |file| &file[..]);
+            Ok(ruby.str_from_slice(file).as_value())
+        }
+    }
+
+    async fn generate_files(
+        &self,
+        format: Format,
+        data: &UfData,
+        options: GenerateOptions,
+    ) -> utaformatix::Result<Vec<Vec<u8>>> {
+        let file = match format {
+            Format::MusicXml => return self.inner.generate_music_xml(data, options).await,
+            Format::Ust => return self.inner.generate_ust(data, options).await,
+            Format::Ppsf => return Err(ErrorKind::UnsupportedFileFormat.into()),
+            Format::StandardMid => self.inner.generate_standard_mid(data, options).await?,
+            Format::Ccs => self.inner.generate_ccs(data, options).await?,
+            Format::Dv => self.inner.generate_dv(data, options).await?,
+            Format::Ustx => self.inner.generate_ustx(data, options).await?,
+            Format::S5p => self.inner.generate_s5p(data, options).await?,
+            Format::Svp => self.inner.generate_svp(data, options).await?,
+            Format::Tssln => self.inner.generate_tssln(data, options).await?,
+            Format::UfData => self.inner.generate_uf_data(data, options).await?,
+            Format::VocaloidMid => self.inner.generate_vocaloid_mid(data, options).await?,
+            Format::Vsq => self.inner.generate_vsq(data, options).await?,
+            Format::Vsqx => self.inner.generate_vsqx(data, options).await?,
+            Format::Vpr => self.inner.generate_vpr(data, options).await?,
+        };
+        Ok(vec![file])
+    }
+
+    #[duplicate_item(
+        fn_name                    original_fn_name;
+        [parse_standard_mid_async] [parse_standard_mid];
+        [parse_music_xml_async]    [parse_music_xml];
+        [parse_ccs_async]          [parse_ccs];
+        [parse_dv_async]           [parse_dv];
+        [parse_ustx_async]         [parse_ustx];
+        [parse_ppsf_async]         [parse_ppsf];
+        [parse_s5p_async]          [parse_s5p];
+        [parse_svp_async]          [parse_svp];
+        [parse_tssln_async]        [parse_tssln];
+        [parse_uf_data_async]      [parse_uf_data];
+        [parse_vocaloid_mid_async] [parse_vocaloid_mid];
+        [parse_vsq_async]          [parse_vsq];
+        [parse_vsqx_async]         [parse_vsqx];
+        [parse_vpr_async]          [parse_vpr];
+    )]
+    pub fn fn_name(&self, args: &[Value]) -> RubyResult<Promise> {
+        let args =
+            magnus::scan_args::scan_args::<(bytes::Bytes,), (Option<Value>,), (), (), RHash, ()>(
+                args,
+            )?;
+
+        let options = to_parse_options(args.optional.0, args.keywords)?;
+        let data = args.required.0;
+        let inner = self.inner.clone();
+        Ok(Promise::spawn(async move {
+            inner.original_fn_name(data, options).await
+        }))
+    }
+
+    pub fn parse_ust_async(&self, args: &[Value]) -> RubyResult<Promise> {
+        let args =
+            magnus::scan_args::scan_args::<(Value,), (Option<Value>,), (), (), RHash, ()>(args)?;
+
+        let options = to_parse_options(args.optional.0, args.keywords)?;
+        let source = to_ust_sources(args.required.0)?;
+        let inner = self.inner.clone();
+        Ok(Promise::spawn(async move {
+            inner.parse_ust(source, options).await
+        }))
+    }
+}
+
+impl Promise {
+    /// Runs the conversion on the Tokio runtime, without waiting for it.
+    fn spawn(
+        future: impl std::future::Future<Output = utaformatix::Result<UfData>> + Send + 'static,
+    ) -> Self {
+        let slot: Slot = Arc::default();
+        let task = RUNTIME.spawn(future);
+        let task_slot = slot.clone();
+        RUNTIME.spawn(async move {
+            let result = task.await.unwrap_or_else(|e| {
+                Err(ErrorKind::Unexpected(format!("Conversion panicked: {e}")).into())
+            });
+            let (result_slot, condvar) = &*task_slot;
+            *result_slot.lock().unwrap_or_else(PoisonError::into_inner) = Some(result);
+            condvar.notify_all();
+        });
+
+        Self { slot }
+    }
+
+    /// Waits for the conversion without the GVL, and returns the result or raises its error.
+    fn value(&self) -> RubyResult<RHash> {
+        let result = without_gvl(self.slot.clone(), |slot| {
+            let (result, condvar) = &*slot;
+            let result = condvar
+                .wait_while(
+                    result.lock().unwrap_or_else(PoisonError::into_inner),
+                    |result| result.is_none(),
+                )
+                .unwrap_or_else(PoisonError::into_inner);
+            result.clone().expect("Result is not set")
+        });
+
+        to_ruby_hash(&result.map_err(to_ruby_error)?)
+    }
+
+    /// Returns whether the conversion has completed, without waiting for it.
+    fn is_done(&self) -> bool {
+        self.slot
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some()
     }
 }
 
@@ -175,6 +382,13 @@ fn init(ruby: &magnus::Ruby) -> Result<(), Error> {
     error_root.define_error("EmptyProject", ruby.exception_runtime_error())?;
     error_root.define_error("IllegalNotePosition", ruby.exception_runtime_error())?;
     error_root.define_error("NotesOverlapping", ruby.exception_runtime_error())?;
+    error_root.define_error("IllegalTimeSignature", ruby.exception_runtime_error())?;
+    error_root.define_error("IllegalTempo", ruby.exception_runtime_error())?;
+    error_root.define_error("IndexOutOfRange", ruby.exception_runtime_error())?;
+    error_root.define_error("KeyOutOfRange", ruby.exception_runtime_error())?;
+    error_root.define_error("LyricsMismatch", ruby.exception_runtime_error())?;
+    error_root.define_error("LyricsTypeUndetected", ruby.exception_runtime_error())?;
+    error_root.define_error("Timeout", ruby.exception_runtime_error())?;
     error_root.define_error("UnsupportedFileFormat", ruby.exception_runtime_error())?;
     error_root.define_error("UnsupportedLegacyPpsf", ruby.exception_runtime_error())?;
     error_root.define_error("Unexpected", ruby.exception_runtime_error())?;
@@ -186,9 +400,14 @@ fn init(ruby: &magnus::Ruby) -> Result<(), Error> {
     illegal_file.define_error("IllegalXmlAttribute", ruby.exception_runtime_error())?;
     illegal_file.define_error("IllegalMidiFile", ruby.exception_runtime_error())?;
     illegal_file.define_error("IllegalTsslnFile", ruby.exception_runtime_error())?;
+    illegal_file.define_error("IllegalUfDataFile", ruby.exception_runtime_error())?;
+    illegal_file.define_error("IllegalSimpleJsonFile", ruby.exception_runtime_error())?;
+
+    options::init(ruby, utaformatix_root)?;
 
     let core = utaformatix_root.define_class("Core", ruby.class_object())?;
     core.define_singleton_method("new", magnus::function!(Core::new, 0))?;
+    core.define_singleton_method("default", magnus::function!(Core::default, 0))?;
     duplicate! {
         [
             fn_name;
@@ -213,6 +432,36 @@ fn init(ruby: &magnus::Ruby) -> Result<(), Error> {
             magnus::method!(Core::fn_name, -1),
         )?;
     }
+    core.define_method("parse", magnus::method!(Core::parse, -1))?;
+    core.define_method("generate", magnus::method!(Core::generate, -1))?;
+    duplicate! {
+        [
+            fn_name;
+            [parse_standard_mid_async];
+            [parse_music_xml_async];
+            [parse_ccs_async];
+            [parse_dv_async];
+            [parse_ustx_async];
+            [parse_ppsf_async];
+            [parse_s5p_async];
+            [parse_svp_async];
+            [parse_tssln_async];
+            [parse_ust_async];
+            [parse_uf_data_async];
+            [parse_vocaloid_mid_async];
+            [parse_vsq_async];
+            [parse_vsqx_async];
+            [parse_vpr_async];
+        ]
+        core.define_method(
+            stringify!(fn_name),
+            magnus::method!(Core::fn_name, -1),
+        )?;
+    }
+
+    let promise = utaformatix_root.define_class("Promise", ruby.class_object())?;
+    promise.define_method("value", magnus::method!(Promise::value, 0))?;
+    promise.define_method("done?", magnus::method!(Promise::is_done, 0))?;
 
     Ok(())
 }
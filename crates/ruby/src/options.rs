@@ -0,0 +1,317 @@
+//! Ruby classes of formats and options: `UtaFormatix::Format`, `UtaFormatix::ParseOptions` and
+//! `UtaFormatix::GenerateOptions`.
+//!
+//! Options are built from the serde representation of the Rust structs, so every option of the
+//! Rust crate can be set from Ruby without being listed here.
+use crate::RubyResult;
+use magnus::{
+    r_hash::ForEach, value::ReprValue, Error, Module, Object, RArray, RHash, RModule, Symbol,
+    TryConvert, Value,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use utaformatix::{DefaultLyric, Format, GenerateOptions, ParseOptions};
+
+/// Formats, named after the methods of `Core`.
+const FORMATS: [(&str, Format); 15] = [
+    ("standard_mid", Format::StandardMid),
+    ("music_xml", Format::MusicXml),
+    ("ccs", Format::Ccs),
+    ("dv", Format::Dv),
+    ("ustx", Format::Ustx),
+    ("ppsf", Format::Ppsf),
+    ("s5p", Format::S5p),
+    ("svp", Format::Svp),
+    ("tssln", Format::Tssln),
+    ("uf_data", Format::UfData),
+    ("ust", Format::Ust),
+    ("vocaloid_mid", Format::VocaloidMid),
+    ("vsq", Format::Vsq),
+    ("vsqx", Format::Vsqx),
+    ("vpr", Format::Vpr),
+];
+
+#[magnus::wrap(class = "UtaFormatix::Format", free_immediately)]
+pub(crate) struct RFormat(Format);
+
+#[magnus::wrap(class = "UtaFormatix::ParseOptions", free_immediately)]
+pub(crate) struct RParseOptions(ParseOptions);
+
+#[magnus::wrap(class = "UtaFormatix::GenerateOptions", free_immediately)]
+pub(crate) struct RGenerateOptions(GenerateOptions);
+
+fn arg_error(message: String) -> Error {
+    let ruby = magnus::Ruby::get().expect("Failed to get Ruby pointer");
+    Error::new(ruby.exception_arg_error(), message)
+}
+
+/// Converts a `UtaFormatix::Format` or a Symbol named after the methods, e.g. `:vsqx` or
+/// `:music_xml`, into a [`Format`].
+pub(crate) fn to_format(value: Value) -> RubyResult<Format> {
+    if let Ok(format) = <&RFormat>::try_convert(value) {
+        return Ok(format.0);
+    }
+    let name = Symbol::try_convert(value)?.name()?;
+    FORMATS
+        .iter()
+        .find(|(format_name, _)| *format_name == name)
+        .map(|(_, format)| *format)
+        .ok_or_else(|| arg_error(format!("Unknown format: {name}")))
+}
+
+fn format_name(format: Format) -> &'static str {
+    FORMATS
+        .iter()
+        .find(|(_, other)| *other == format)
+        .map(|(name, _)| *name)
+        .expect("Format is not listed")
+}
+
+/// Converts a String (the lyric to fill with) or a Symbol (`:keep_empty`, `:copy_previous` or
+/// `:drop_note`) into a [`DefaultLyric`].
+fn to_default_lyric(value: Value) -> RubyResult<DefaultLyric> {
+    let ruby = magnus::Ruby::get().expect("Failed to get Ruby pointer");
+    if !value.is_kind_of(ruby.class_symbol()) {
+        return Ok(DefaultLyric::Fill(value.to_string()));
+    }
+    match &*Symbol::try_convert(value)?.name()? {
+        "keep_empty" => Ok(DefaultLyric::KeepEmpty),
+        "copy_previous" => Ok(DefaultLyric::CopyPrevious),
+        "drop_note" => Ok(DefaultLyric::DropNote),
+        name => Err(arg_error(format!("Unknown default lyric policy: {name}"))),
+    }
+}
+
+/// Builds the parse options from an optional `UtaFormatix::ParseOptions`, overridden by the
+/// keywords.
+pub(crate) fn to_parse_options(base: Option<Value>, keywords: RHash) -> RubyResult<ParseOptions> {
+    let base = match base {
+        Some(base) => <&RParseOptions>::try_convert(base)?.0.clone(),
+        None => ParseOptions::default(),
+    };
+    build_options(&base, keywords)
+}
+
+/// Builds the generate options from an optional `UtaFormatix::GenerateOptions`, overridden by
+/// the keywords.
+pub(crate) fn to_generate_options(
+    base: Option<Value>,
+    keywords: RHash,
+) -> RubyResult<GenerateOptions> {
+    let base = match base {
+        Some(base) => <&RGenerateOptions>::try_convert(base)?.0.clone(),
+        None => GenerateOptions::default(),
+    };
+    build_options(&base, keywords)
+}
+
+/// Overwrites the fields of the options with the keywords. Unknown keywords and invalid values
+/// raise `ArgumentError`.
+fn build_options<T: Serialize + DeserializeOwned>(base: &T, keywords: RHash) -> RubyResult<T> {
+    let mut json = serde_json::to_value(base).map_err(|e| arg_error(e.to_string()))?;
+    let fields = json
+        .as_object_mut()
+        .expect("Options are not serialized as an object");
+    keywords.foreach(|key: Symbol, value: Value| {
+        let name = key.name()?;
+        let field = camel_case(&name);
+        if !fields.contains_key(&field) {
+            return Err(arg_error(format!("Unknown option: {name}")));
+        }
+        let value = if name == "default_lyric" {
+            serde_json::to_value(to_default_lyric(value)?).map_err(|e| arg_error(e.to_string()))?
+        } else {
+            to_json(value)?
+        };
+        fields.insert(field, value);
+        Ok(ForEach::Continue)
+    })?;
+    serde_json::from_value(json).map_err(|e| arg_error(format!("Invalid options: {e}")))
+}
+
+/// Converts a Ruby object into JSON, with the keys of Hashes and Symbols converted from
+/// snake_case to camelCase, which the options use in serde.
+fn to_json(value: Value) -> RubyResult<serde_json::Value> {
+    let ruby = magnus::Ruby::get().expect("Failed to get Ruby pointer");
+    if value.is_nil() {
+        Ok(serde_json::Value::Null)
+    } else if value.is_kind_of(ruby.class_true_class()) {
+        Ok(serde_json::Value::Bool(true))
+    } else if value.is_kind_of(ruby.class_false_class()) {
+        Ok(serde_json::Value::Bool(false))
+    } else if value.is_kind_of(ruby.class_integer()) {
+        Ok(i64::try_convert(value)?.into())
+    } else if value.is_kind_of(ruby.class_float()) {
+        let float = f64::try_convert(value)?;
+        serde_json::Number::from_f64(float)
+            .map(serde_json::Value::Number)
+            .ok_or_else(|| arg_error(format!("Invalid number: {float}")))
+    } else if value.is_kind_of(ruby.class_string()) {
+        Ok(String::try_convert(value)?.into())
+    } else if value.is_kind_of(ruby.class_symbol()) {
+        Ok(camel_case(&Symbol::try_convert(value)?.name()?).into())
+    } else if let Some(array) = RArray::from_value(value) {
+        array
+            .to_vec::<Value>()?
+            .into_iter()
+            .map(to_json)
+            .collect::<RubyResult<Vec<_>>>()
+            .map(serde_json::Value::Array)
+    } else if let Some(hash) = RHash::from_value(value) {
+        let mut map = serde_json::Map::new();
+        hash.foreach(|key: Value, value: Value| {
+            let key = match Symbol::from_value(key) {
+                Some(symbol) => symbol.name()?.to_string(),
+                None => String::try_convert(key)?,
+            };
+            map.insert(camel_case(&key), to_json(value)?);
+            Ok(ForEach::Continue)
+        })?;
+        Ok(serde_json::Value::Object(map))
+    } else {
+        Err(arg_error(format!(
+            "Unsupported option value: {}",
+            value.inspect()
+        )))
+    }
+}
+
+/// Converts JSON into a Ruby object, with the keys of objects converted into snake_case
+/// Symbols.
+fn from_json(value: &serde_json::Value) -> RubyResult<Value> {
+    let ruby = magnus::Ruby::get().expect("Failed to get Ruby pointer");
+    Ok(match value {
+        serde_json::Value::Null => ruby.qnil().as_value(),
+        serde_json::Value::Bool(true) => ruby.qtrue().as_value(),
+        serde_json::Value::Bool(false) => ruby.qfalse().as_value(),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(integer) => ruby.integer_from_i64(integer).as_value(),
+            None => ruby
+                .float_from_f64(number.as_f64().unwrap_or_default())
+                .as_value(),
+        },
+        serde_json::Value::String(string) => ruby.str_new(string).as_value(),
+        serde_json::Value::Array(array) => {
+            let result = ruby.ary_new_capa(array.len());
+            for value in array {
+                result.push(from_json(value)?)?;
+            }
+            result.as_value()
+        }
+        serde_json::Value::Object(map) => {
+            let result = ruby.hash_new();
+            for (key, value) in map {
+                result.aset(ruby.to_symbol(snake_case(key)), from_json(value)?)?;
+            }
+            result.as_value()
+        }
+    })
+}
+
+fn camel_case(name: &str) -> String {
+    let mut words = name.split('_');
+    let mut result = words.next().unwrap_or_default().to_string();
+    for word in words {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            result.extend(first.to_uppercase());
+            result.push_str(chars.as_str());
+        }
+    }
+    result
+}
+
+fn snake_case(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            result.push('_');
+        }
+        result.push(c.to_ascii_lowercase());
+    }
+    result
+}
+
+impl RFormat {
+    fn new(name: Value) -> RubyResult<Self> {
+        to_format(name).map(Self)
+    }
+
+    fn all() -> Vec<Self> {
+        FORMATS.iter().map(|(_, format)| Self(*format)).collect()
+    }
+
+    fn to_sym(&self) -> Symbol {
+        magnus::Ruby::get()
+            .expect("Failed to get Ruby pointer")
+            .to_symbol(format_name(self.0))
+    }
+
+    fn extension(&self) -> &'static str {
+        self.0.extension()
+    }
+
+    fn eq(&self, other: Value) -> bool {
+        to_format(other).is_ok_and(|other| other == self.0)
+    }
+
+    fn inspect(&self) -> String {
+        format!("#<UtaFormatix::Format {}>", format_name(self.0))
+    }
+}
+
+impl RParseOptions {
+    fn new(args: &[Value]) -> RubyResult<Self> {
+        let args = magnus::scan_args::scan_args::<(), (), (), (), RHash, ()>(args)?;
+        to_parse_options(None, args.keywords).map(Self)
+    }
+
+    fn to_h(&self) -> RubyResult<Value> {
+        from_json(&serde_json::to_value(&self.0).map_err(|e| arg_error(e.to_string()))?)
+    }
+
+    /// Returns a copy with the options overridden by the keywords.
+    fn with(&self, args: &[Value]) -> RubyResult<Self> {
+        let args = magnus::scan_args::scan_args::<(), (), (), (), RHash, ()>(args)?;
+        build_options(&self.0, args.keywords).map(Self)
+    }
+}
+
+impl RGenerateOptions {
+    fn new(args: &[Value]) -> RubyResult<Self> {
+        let args = magnus::scan_args::scan_args::<(), (), (), (), RHash, ()>(args)?;
+        to_generate_options(None, args.keywords).map(Self)
+    }
+
+    fn to_h(&self) -> RubyResult<Value> {
+        from_json(&serde_json::to_value(&self.0).map_err(|e| arg_error(e.to_string()))?)
+    }
+
+    /// Returns a copy with the options overridden by the keywords.
+    fn with(&self, args: &[Value]) -> RubyResult<Self> {
+        let args = magnus::scan_args::scan_args::<(), (), (), (), RHash, ()>(args)?;
+        build_options(&self.0, args.keywords).map(Self)
+    }
+}
+
+pub(crate) fn init(ruby: &magnus::Ruby, utaformatix_root: RModule) -> RubyResult<()> {
+    let format = utaformatix_root.define_class("Format", ruby.class_object())?;
+    format.define_singleton_method("new", magnus::function!(RFormat::new, 1))?;
+    format.define_singleton_method("all", magnus::function!(RFormat::all, 0))?;
+    format.define_method("to_sym", magnus::method!(RFormat::to_sym, 0))?;
+    format.define_method("extension", magnus::method!(RFormat::extension, 0))?;
+    format.define_method("==", magnus::method!(RFormat::eq, 1))?;
+    format.define_method("inspect", magnus::method!(RFormat::inspect, 0))?;
+
+    let parse_options = utaformatix_root.define_class("ParseOptions", ruby.class_object())?;
+    parse_options.define_singleton_method("new", magnus::function!(RParseOptions::new, -1))?;
+    parse_options.define_method("to_h", magnus::method!(RParseOptions::to_h, 0))?;
+    parse_options.define_method("with", magnus::method!(RParseOptions::with, -1))?;
+
+    let generate_options = utaformatix_root.define_class("GenerateOptions", ruby.class_object())?;
+    generate_options
+        .define_singleton_method("new", magnus::function!(RGenerateOptions::new, -1))?;
+    generate_options.define_method("to_h", magnus::method!(RGenerateOptions::to_h, 0))?;
+    generate_options.define_method("with", magnus::method!(RGenerateOptions::with, -1))?;
+
+    Ok(())
+}
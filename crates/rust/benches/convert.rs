@@ -0,0 +1,221 @@
+use criterion::{
+    criterion_group, criterion_main, measurement::WallTime, BenchmarkGroup, Criterion,
+};
+use duplicate::duplicate_item;
+use tokio::runtime::Runtime;
+use utaformatix::{
+    base::UtaFormatix, Format, GenerateOptions, Key, Note, ParseOptions, Pitch, Tempo, Tick,
+    TimeSignature, Track, UfData, UfProject,
+};
+
+/// Creates a project with 50k notes and a pitch point every 5 ticks.
+fn large_project() -> UfData {
+    let notes = (0..50_000)
+        .map(|i| Note {
+            key: Key::new(48 + i % 24).unwrap(),
+            tick_on: Tick::new(i64::from(i) * 240).unwrap(),
+            tick_off: Tick::new(i64::from(i + 1) * 240).unwrap(),
+            lyric: "ら".to_string(),
+            phoneme: None,
+            phoneme_segments: None,
+        })
+        .collect::<Vec<_>>();
+    let ticks = (0..50_000 * 240 / 5).collect::<Vec<i32>>();
+    let values = ticks
+        .iter()
+        .map(|tick| Some(f64::from(tick % 480) / 480.0 - 0.5))
+        .collect();
+    UfData::new(UfProject {
+        name: "Large".to_string(),
+        tracks: vec![Track {
+            name: "Vocal".to_string(),
+            notes,
+            pitch: Some(Pitch {
+                ticks,
+                values,
+                is_absolute: false,
+            }),
+            muted: None,
+            solo: None,
+            order: None,
+            color: None,
+            voice: None,
+            parameters: None,
+        }],
+        time_signatures: vec![TimeSignature::default()],
+        tempos: vec![Tempo {
+            tick_position: Tick::ZERO,
+            bpm: 120,
+        }],
+        measure_prefix: 0,
+        key_signatures: vec![],
+    })
+}
+
+#[duplicate_item(
+    bench_name                function             path;
+    [parse_standard_mid]      [parse_standard_mid] ["generated/standard.mid"];
+    [parse_music_xml]         [parse_music_xml]    ["generated/musicXml.musicxml"];
+    [parse_ccs]               [parse_ccs]          ["generated/cevio.ccs"];
+    [parse_dv]                [parse_dv]           ["generated/dv.dv"];
+    [parse_ustx]              [parse_ustx]         ["generated/openutau.ustx"];
+    [parse_svp]               [parse_svp]          ["generated/synthV.svp"];
+    [parse_tssln]             [parse_tssln]        ["voisona.tssln"];
+    [parse_uf_data]           [parse_uf_data]      ["generated/ufdata.ufdata"];
+    [parse_vocaloid_mid]      [parse_vocaloid_mid] ["generated/vocaloid.mid"];
+    [parse_vsq]               [parse_vsq]          ["generated/vsq.vsq"];
+    [parse_vsqx]              [parse_vsqx]         ["generated/vsqx.vsqx"];
+    [parse_vpr]               [parse_vpr]          ["generated/vpr.vpr"];
+)]
+fn bench_name(group: &mut BenchmarkGroup<WallTime>, runtime: &Runtime, utaformatix: &UtaFormatix) {
+    let data = include_bytes!(concat!("../utaformatix-ts/testAssets/", path));
+    group.bench_function(stringify!(bench_name), |b| {
+        b.to_async(runtime)
+            .iter(|| utaformatix.function(data.as_slice(), ParseOptions::default()))
+    });
+}
+
+fn parse_ust(group: &mut BenchmarkGroup<WallTime>, runtime: &Runtime, utaformatix: &UtaFormatix) {
+    let data: [&[u8]; 1] = [include_bytes!(
+        "../utaformatix-ts/testAssets/generated/utau.ust"
+    )];
+    group.bench_function("parse_ust", |b| {
+        b.to_async(runtime)
+            .iter(|| utaformatix.parse_ust(data, ParseOptions::default()))
+    });
+}
+
+#[duplicate_item(
+    bench_name              function;
+    [generate_standard_mid] [generate_standard_mid];
+    [generate_uf_data]      [generate_uf_data];
+    [generate_ccs]          [generate_ccs];
+    [generate_dv]           [generate_dv];
+    [generate_ustx]         [generate_ustx];
+    [generate_s5p]          [generate_s5p];
+    [generate_svp]          [generate_svp];
+    [generate_tssln]        [generate_tssln];
+    [generate_vocaloid_mid] [generate_vocaloid_mid];
+    [generate_vsq]          [generate_vsq];
+    [generate_vsqx]         [generate_vsqx];
+    [generate_vpr]          [generate_vpr];
+    [generate_music_xml]    [generate_music_xml];
+    [generate_ust]          [generate_ust];
+)]
+fn bench_name(
+    group: &mut BenchmarkGroup<WallTime>,
+    runtime: &Runtime,
+    utaformatix: &UtaFormatix,
+    data: &UfData,
+) {
+    group.bench_function(stringify!(bench_name), |b| {
+        b.to_async(runtime)
+            .iter(|| utaformatix.function(data, GenerateOptions::default()))
+    });
+}
+
+/// Compares the native implementations with the JS engine.
+fn native_and_engine(
+    c: &mut Criterion,
+    runtime: &Runtime,
+    utaformatix: &UtaFormatix,
+    large: &UfData,
+) {
+    let data = include_bytes!("../utaformatix-ts/testAssets/generated/standard.mid");
+    let mut group = c.benchmark_group("parse_standard_mid");
+    group.bench_function("native", |b| {
+        b.to_async(runtime)
+            .iter(|| utaformatix.parse_standard_mid(data.as_slice(), ParseOptions::default()))
+    });
+    group.bench_function("engine", |b| {
+        b.to_async(runtime).iter(|| {
+            utaformatix.parse_with_engine(
+                Format::StandardMid,
+                data.as_slice(),
+                ParseOptions::default(),
+            )
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("generate_standard_mid_large");
+    group.sample_size(10);
+    group.bench_function("native", |b| {
+        b.to_async(runtime)
+            .iter(|| utaformatix.generate_standard_mid(large, GenerateOptions::default()))
+    });
+    group.bench_function("engine", |b| {
+        b.to_async(runtime).iter(|| {
+            utaformatix.generate_with_engine(Format::StandardMid, large, GenerateOptions::default())
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("generate_uf_data_large");
+    group.sample_size(10);
+    group.bench_function("native", |b| {
+        b.to_async(runtime)
+            .iter(|| utaformatix.generate_uf_data(large, GenerateOptions::default()))
+    });
+    group.bench_function("engine", |b| {
+        b.to_async(runtime).iter(|| {
+            utaformatix.generate_with_engine(Format::UfData, large, GenerateOptions::default())
+        })
+    });
+    group.finish();
+}
+
+fn convert(c: &mut Criterion) {
+    let runtime = Runtime::new().expect("Failed to create a runtime");
+    let utaformatix = UtaFormatix::new();
+
+    let mut group = c.benchmark_group("parse");
+    parse_standard_mid(&mut group, &runtime, &utaformatix);
+    parse_music_xml(&mut group, &runtime, &utaformatix);
+    parse_ccs(&mut group, &runtime, &utaformatix);
+    parse_dv(&mut group, &runtime, &utaformatix);
+    parse_ustx(&mut group, &runtime, &utaformatix);
+    parse_svp(&mut group, &runtime, &utaformatix);
+    parse_tssln(&mut group, &runtime, &utaformatix);
+    parse_uf_data(&mut group, &runtime, &utaformatix);
+    parse_vocaloid_mid(&mut group, &runtime, &utaformatix);
+    parse_vsq(&mut group, &runtime, &utaformatix);
+    parse_vsqx(&mut group, &runtime, &utaformatix);
+    parse_vpr(&mut group, &runtime, &utaformatix);
+    parse_ust(&mut group, &runtime, &utaformatix);
+    group.finish();
+
+    let small = runtime
+        .block_on(utaformatix.parse_standard_mid(
+            include_bytes!("../utaformatix-ts/testAssets/generated/standard.mid").as_slice(),
+            ParseOptions::default(),
+        ))
+        .expect("Failed to parse data");
+    let large = large_project();
+    for (name, data) in [("generate", &small), ("generate_large", &large)] {
+        let mut group = c.benchmark_group(name);
+        if name == "generate_large" {
+            group.sample_size(10);
+        }
+        generate_standard_mid(&mut group, &runtime, &utaformatix, data);
+        generate_uf_data(&mut group, &runtime, &utaformatix, data);
+        generate_ccs(&mut group, &runtime, &utaformatix, data);
+        generate_dv(&mut group, &runtime, &utaformatix, data);
+        generate_ustx(&mut group, &runtime, &utaformatix, data);
+        generate_s5p(&mut group, &runtime, &utaformatix, data);
+        generate_svp(&mut group, &runtime, &utaformatix, data);
+        generate_tssln(&mut group, &runtime, &utaformatix, data);
+        generate_vocaloid_mid(&mut group, &runtime, &utaformatix, data);
+        generate_vsq(&mut group, &runtime, &utaformatix, data);
+        generate_vsqx(&mut group, &runtime, &utaformatix, data);
+        generate_vpr(&mut group, &runtime, &utaformatix, data);
+        generate_music_xml(&mut group, &runtime, &utaformatix, data);
+        generate_ust(&mut group, &runtime, &utaformatix, data);
+        group.finish();
+    }
+
+    native_and_engine(c, &runtime, &utaformatix, &large);
+}
+
+criterion_group!(benches, convert);
+criterion_main!(benches);
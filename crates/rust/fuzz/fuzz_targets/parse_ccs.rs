@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use utaformatix::Format;
+
+fuzz_target!(|data: &[u8]| {
+    utaformatix_fuzz::parse(Format::Ccs, data);
+});
@@ -0,0 +1,26 @@
+//! Shared code of the fuzz targets.
+use once_cell::sync::Lazy;
+use tokio::sync::Mutex;
+use utaformatix::{base::UtaFormatix, Bytes, Error, Format, ParseOptions};
+
+static RUNTIME: Lazy<tokio::runtime::Runtime> = Lazy::new(|| {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to create Tokio runtime")
+});
+
+static UTAFORMATIX: Lazy<Mutex<UtaFormatix>> = Lazy::new(|| Mutex::new(UtaFormatix::new()));
+
+/// Parses the data, and checks that malformed input is reported as an error, not a panic.
+pub fn parse(format: Format, data: &[u8]) {
+    let data = Bytes::copy_from_slice(data);
+    let result = RUNTIME.block_on(async {
+        let utaformatix = UTAFORMATIX.lock().await;
+        utaformatix
+            .parse(format, data, ParseOptions::default())
+            .await
+    });
+    // Any error is fine, as long as it's returned instead of panicking.
+    let _: Result<_, Error> = result;
+}
@@ -28,3 +28,11 @@ await bundle.write({
   format: "iife",
   name: "utaformatix",
 });
+
+const utaformatixTs = await Bun.file(
+  `${import.meta.dirname}/node_modules/@sevenc-nanashi/utaformatix-ts/package.json`,
+).json();
+await Bun.write(
+  `${import.meta.dirname}/../src/utaformatix-ts.version`,
+  utaformatixTs.version,
+);
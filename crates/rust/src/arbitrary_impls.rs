@@ -0,0 +1,128 @@
+//! [`Arbitrary`] implementations which only generate valid data, for property testing.
+//!
+//! Notes are sorted and don't overlap, keys are in the MIDI range, tempos and time signatures
+//! are sorted and start at the beginning of the project.
+use crate::{
+    model::{
+        Key, Note, Pitch, Tempo, Tick, TimeSignature, Track, UfData, UfProject, TICKS_PER_BEAT,
+    },
+    project::Project,
+};
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+const LYRICS: [&str; 8] = ["あ", "か", "さ", "ら", "ん", "-", "a", "la"];
+const MAX_NOTES: usize = 64;
+
+impl<'a> Arbitrary<'a> for Note {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_note(u, Tick::ZERO)
+    }
+}
+
+/// Creates a note starting at `min_tick` or later.
+fn arbitrary_note(u: &mut Unstructured<'_>, min_tick: Tick) -> Result<Note> {
+    let tick_on = min_tick.saturating_add(u.int_in_range(0..=TICKS_PER_BEAT * 4)?);
+    Ok(Note {
+        key: Key::new(u.int_in_range(0..=127)?).unwrap_or_default(),
+        tick_on,
+        tick_off: tick_on.saturating_add(u.int_in_range(1..=TICKS_PER_BEAT * 4)?),
+        lyric: u.choose(&LYRICS)?.to_string(),
+        phoneme: None,
+        phoneme_segments: None,
+    })
+}
+
+impl<'a> Arbitrary<'a> for Track {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut notes: Vec<Note> = vec![];
+        for _ in 0..u.int_in_range(0..=MAX_NOTES)? {
+            let min_tick = notes.last().map_or(Tick::ZERO, |note| note.tick_off);
+            notes.push(arbitrary_note(u, min_tick)?);
+        }
+        let pitch = match (u.arbitrary::<bool>()?, notes.last()) {
+            (true, Some(last)) => {
+                let mut ticks = vec![];
+                let mut tick = 0;
+                while tick < last.tick_off.get() && ticks.len() < MAX_NOTES * 4 {
+                    ticks.push(tick as i32);
+                    tick += u.int_in_range(1..=TICKS_PER_BEAT)?;
+                }
+                let values = ticks
+                    .iter()
+                    .map(|_| Ok(Some(f64::from(u.int_in_range(-200..=200)?) / 100.0)))
+                    .collect::<Result<_>>()?;
+                Some(Pitch {
+                    ticks,
+                    values,
+                    is_absolute: false,
+                })
+            }
+            _ => None,
+        };
+
+        Ok(Track {
+            name: format!("Track {}", u.int_in_range(1..=16)?),
+            notes,
+            pitch,
+            muted: None,
+            solo: None,
+            order: None,
+            color: None,
+            voice: None,
+            parameters: None,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for UfProject {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let tracks = (0..u.int_in_range(1..=4)?)
+            .map(|_| u.arbitrary())
+            .collect::<Result<_>>()?;
+
+        let mut time_signatures = vec![TimeSignature::default()];
+        for _ in 0..u.int_in_range(0..=3)? {
+            let previous = time_signatures.last().map_or(0, |ts| ts.measure_position);
+            time_signatures.push(TimeSignature {
+                measure_position: previous + u.int_in_range(1..=8)?,
+                numerator: u.int_in_range(1..=8)?,
+                denominator: *u.choose(&[2, 4, 8])?,
+            });
+        }
+
+        let mut tempos = vec![Tempo {
+            tick_position: Tick::ZERO,
+            bpm: u.int_in_range(30..=300)?,
+        }];
+        for _ in 0..u.int_in_range(0..=3)? {
+            let previous = tempos
+                .last()
+                .map_or(Tick::ZERO, |tempo| tempo.tick_position);
+            tempos.push(Tempo {
+                tick_position: previous.saturating_add(u.int_in_range(1..=TICKS_PER_BEAT * 16)?),
+                bpm: u.int_in_range(30..=300)?,
+            });
+        }
+
+        Ok(UfProject {
+            name: "Project".to_string(),
+            tracks,
+            time_signatures,
+            tempos,
+            measure_prefix: 0,
+            key_signatures: vec![],
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for UfData {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(UfData::new(u.arbitrary()?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for Project {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Project::new(u.arbitrary()?))
+    }
+}
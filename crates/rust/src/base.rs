@@ -2,16 +2,294 @@
 use crate::error::Result;
 use crate::model::UfData;
 use crate::{
-    model::{Format, GenerateOptions, ParseOptions},
-    process::SyncThread,
+    model::{DefaultLyric, Format, FormatOptions, GenerateOptions, ParseOptions, SvpOptions},
+    process::{RunnerConfig, SyncThread},
 };
-use crate::{ConvertJapaneseLyricsOptions, JapaneseLyricsType};
+use crate::{
+    CompatibilityReport, ConvertJapaneseLyricsOptions, ErrorKind, JapaneseLyricsType,
+    LyricsTypeAnalysis,
+};
+use bytes::Bytes;
 use duplicate::duplicate_item;
-use tracing::info;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Represents the versions of this crate and what it embeds, returned by
+/// [`UtaFormatix::version_info`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionInfo {
+    /// Version of this crate.
+    pub crate_version: &'static str,
+    /// Version of the embedded utaformatix-ts.
+    pub utaformatix_ts_version: &'static str,
+    /// Version of UtaFormatix data which is generated.
+    pub uf_data_format_version: i32,
+}
+
+/// Files of a multi-file format which are received one at a time, returned by e.g.
+/// [`UtaFormatix::generate_music_xml_stream`].
+///
+/// The JS thread waits until each file is received before converting the next one, and stops
+/// generating when this is dropped.
+pub struct GeneratedFiles {
+    receiver: async_channel::Receiver<crate::process::Message<crate::process::ResponseMessageData>>,
+    timeout: Option<Duration>,
+    finished: bool,
+}
+
+impl GeneratedFiles {
+    /// Receives the next file, each representing a track. Returns `None` once every file is
+    /// received.
+    ///
+    /// Files received before an error are still valid, but the error ends the stream.
+    pub async fn next(&mut self) -> Option<Result<Vec<u8>>> {
+        if self.finished {
+            return None;
+        }
+        let response = match self.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, self.receiver.recv()).await {
+                Ok(response) => response,
+                Err(_) => {
+                    self.finished = true;
+                    return Some(Err(ErrorKind::Timeout.into()));
+                }
+            },
+            None => self.receiver.recv().await,
+        };
+        // A runner which panics responds with `ErrorKind::RunnerPanicked`, so this only happens
+        // if the JS thread has stopped.
+        let Ok(crate::process::Message { message, .. }) = response else {
+            self.finished = true;
+            return Some(Err(no_response()));
+        };
+        match message {
+            crate::process::ResponseMessageData::GenerateFile(file) => Some(Ok(file)),
+            crate::process::ResponseMessageData::GenerateMultipleStream(result) => {
+                self.finished = true;
+                info!("Received response");
+                result.err().map(Err)
+            }
+            message => {
+                self.finished = true;
+                Some(Err(unexpected_response(&message)))
+            }
+        }
+    }
+}
+
+/// Returns the error of a request whose response channel was closed without a response.
+fn no_response() -> crate::Error {
+    ErrorKind::Unexpected("The JS thread stopped without responding".to_string()).into()
+}
+
+/// Returns the error of a request which got a response to another kind of request.
+fn unexpected_response(message: &crate::process::ResponseMessageData) -> crate::Error {
+    ErrorKind::Unexpected(format!("Unexpected response: {:?}", message)).into()
+}
 
 /// Represents the main interface to UtaFormatix.
+///
+/// With the `multi-thread` feature, which is enabled by default, requests are processed by JS
+/// threads with their own Tokio runtimes. Without it, no thread is started: the JS engine lives
+/// on the thread which awaits the requests, so `UtaFormatix` is neither `Send` nor `Sync`, and
+/// the futures must be run by a single-threaded executor, e.g. a current-thread Tokio runtime.
+/// This also means that a request blocks its thread while the JS engine runs it.
 pub struct UtaFormatix {
     inner: SyncThread,
+    timeout: Option<Duration>,
+    parse_options: ParseOptions,
+    generate_options: GenerateOptions,
+    #[cfg(feature = "cache")]
+    cache: Option<crate::cache::Cache>,
+}
+
+/// Builds a [`UtaFormatix`] with custom configuration, created by [`UtaFormatix::builder`].
+#[derive(Debug, Clone, Default)]
+pub struct UtaFormatixBuilder {
+    runner: RunnerConfig,
+    timeout: Option<Duration>,
+    parse_options: ParseOptions,
+    generate_options: GenerateOptions,
+    #[cfg(feature = "cache")]
+    cache: Option<crate::CacheConfig>,
+}
+
+impl UtaFormatixBuilder {
+    /// Sets the number of JS threads, which process requests in parallel. Defaults to 1.
+    ///
+    /// Each thread loads its own copy of the JS engine, so this increases memory usage.
+    /// This is ignored without the `multi-thread` feature.
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.runner.workers = workers.max(1);
+        self
+    }
+
+    /// Sets the maximum number of requests waiting for the JS threads, including the ones which
+    /// are ordered by priority. Sending a request waits while the queue is full. Defaults to
+    /// unbounded.
+    pub fn queue_bound(mut self, bound: usize) -> Self {
+        self.runner.queue_bound = Some(bound);
+        self
+    }
+
+    /// Sets how long to wait for a response of the JS threads, including the time spent in the
+    /// queue. Requests which take longer fail with [`ErrorKind::Timeout`], though the JS thread
+    /// keeps running them until they complete.
+    ///
+    /// This requires the Tokio runtime.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum number of iterations of a single loop in JS.
+    pub fn loop_iteration_limit(mut self, limit: u64) -> Self {
+        self.runner.loop_iteration_limit = Some(limit);
+        self
+    }
+
+    /// Sets the maximum depth of recursion in JS.
+    pub fn recursion_limit(mut self, limit: usize) -> Self {
+        self.runner.recursion_limit = Some(limit);
+        self
+    }
+
+    /// Sets the maximum size of the JS stack.
+    pub fn stack_size_limit(mut self, limit: usize) -> Self {
+        self.runner.stack_size_limit = Some(limit);
+        self
+    }
+
+    /// Recreates the JS context of a worker after it processes this number of requests, which
+    /// releases the memory that the context has accumulated. The next request waits until the
+    /// bundle is evaluated again.
+    pub fn recycle_after(mut self, requests: u64) -> Self {
+        self.runner.recycle_after = Some(requests.max(1));
+        self
+    }
+
+    /// Recreates the JS context of a worker after a request, if the resident memory of the
+    /// process has grown by more than `bytes` since the context was created.
+    ///
+    /// The memory is of the whole process, so other allocations count too. This is only
+    /// supported on Linux, and ignored on other platforms.
+    pub fn recycle_on_memory_growth(mut self, bytes: u64) -> Self {
+        self.runner.recycle_memory_growth = Some(bytes);
+        self
+    }
+
+    /// Calls `callback` when a JS thread panics, with the panic message and the request which
+    /// it was processing, e.g. to report crashes. The callback runs on the JS thread.
+    ///
+    /// The panic is logged regardless, and the request fails with
+    /// [`ErrorKind::RunnerPanicked`]. The thread then restarts its JS engine and keeps processing
+    /// the other requests.
+    #[cfg(feature = "multi-thread")]
+    pub fn on_crash(
+        mut self,
+        callback: impl Fn(&crate::CrashReport) + Send + Sync + 'static,
+    ) -> Self {
+        self.runner.crash.callback = Some(std::sync::Arc::new(callback));
+        self
+    }
+
+    /// Writes the request which a JS thread was processing when it panicked to a directory in
+    /// `dir`, named by the hash of the inputs, so the crash can be reproduced. The directory
+    /// contains the input files and `request.json` with the kind, the format, the options and
+    /// the panic message.
+    #[cfg(feature = "multi-thread")]
+    pub fn crash_dir(mut self, dir: impl Into<std::path::PathBuf>) -> Self {
+        self.runner.crash.dir = Some(dir.into());
+        self
+    }
+
+    /// Logs a warning with the kind, the format, the size of the input and the time spent in
+    /// the queue and in the JS engine for each request which the JS engine takes at least
+    /// `threshold` to process, e.g. to find files which are slow to convert.
+    ///
+    /// The time in the JS engine includes waiting for the caller to receive streamed files.
+    pub fn log_slow_calls(mut self, threshold: Duration) -> Self {
+        self.runner.slow_call_threshold = Some(threshold);
+        self
+    }
+
+    /// Restricts the text encodings which the JS bundle may use to encode and decode files, e.g.
+    /// `["Shift_JIS", "UTF-16LE"]`. Any encoding of the Encoding Standard is allowed by default.
+    ///
+    /// UTF-8 is always allowed. Labels are matched like `TextDecoder` does, and unknown ones are
+    /// ignored with a warning. Using another encoding throws a `RangeError` in the bundle, which
+    /// fails the request.
+    pub fn allowed_encodings(mut self, labels: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        let encodings = labels
+            .into_iter()
+            .filter_map(|label| {
+                let label = label.as_ref();
+                let encoding = encoding_rs::Encoding::for_label(label.trim().as_bytes());
+                if encoding.is_none() {
+                    warn!("Unknown encoding is ignored: {}", label);
+                }
+                encoding.map(|encoding| encoding.name().to_string())
+            })
+            .collect();
+        self.runner.encodings = Some(encodings);
+        self
+    }
+
+    /// Uses the JS bundle instead of the embedded one, e.g. to try a newer utaformatix-ts.
+    ///
+    /// The bundle must define `utaformatix` in the global scope, like the embedded one. If it
+    /// throws or doesn't define `utaformatix`, every request fails with
+    /// [`ErrorKind::Unexpected`].
+    pub fn bundle(mut self, bundle: impl Into<String>) -> Self {
+        self.runner.bundle = Some(bundle.into().into());
+        self
+    }
+
+    /// Sets the level of the logs from the JS threads. Defaults to [`tracing::Level::INFO`].
+    ///
+    /// This doesn't apply to `console` calls of the bundle, which are logged at their own level
+    /// with the `utaformatix::js` target.
+    pub fn log_level(mut self, level: tracing::Level) -> Self {
+        self.runner.log_level = level;
+        self
+    }
+
+    /// Sets the default options of the parse methods, which are used when a call passes `None`
+    /// as the options.
+    pub fn parse_options(mut self, options: ParseOptions) -> Self {
+        self.parse_options = options;
+        self
+    }
+
+    /// Sets the default options of the generate methods, which are used when a call passes
+    /// `None` as the options.
+    pub fn generate_options(mut self, options: GenerateOptions) -> Self {
+        self.generate_options = options;
+        self
+    }
+
+    /// Caches the results of conversions, so converting unchanged files again doesn't use the
+    /// JS threads. See [`crate::CacheConfig`].
+    #[cfg(feature = "cache")]
+    pub fn cache(mut self, config: crate::CacheConfig) -> Self {
+        self.cache = Some(config);
+        self
+    }
+
+    /// Creates a new instance of [`UtaFormatix`], which initializes the JS threads.
+    pub fn build(self) -> UtaFormatix {
+        UtaFormatix {
+            #[cfg(feature = "cache")]
+            cache: self
+                .cache
+                .map(|config| crate::cache::Cache::new(config, &self.runner)),
+            inner: SyncThread::new(self.runner),
+            timeout: self.timeout,
+            parse_options: self.parse_options,
+            generate_options: self.generate_options,
+        }
+    }
 }
 
 impl Default for UtaFormatix {
@@ -22,29 +300,48 @@ impl Default for UtaFormatix {
 
 macro_rules! send_and_receive {
     ($self:ident, $message:expr, $response:ident) => {{
-        let sent_nonce = $message.nonce;
+        let message = $message;
+        #[cfg(feature = "cache")]
+        let cache_key = $self
+            .cache
+            .as_ref()
+            .and_then(|cache| cache.key(&message.message));
+        #[cfg(feature = "cache")]
+        if let (Some(cache), Some(key)) = (&$self.cache, cache_key) {
+            if let Some(crate::process::ResponseMessageData::$response(result)) = cache.get(key) {
+                info!("Cache hit");
+                return result;
+            }
+        }
+        let (response_sender, response_receiver) = async_channel::bounded(1);
         $self
             .inner
             .request_sender
-            .send($message)
+            .send(crate::process::Request::new(message, response_sender))
             .await
             .map_err(anyhow::Error::from)?;
         info!("Sent message, waiting for response");
-        let result: Result<_> = loop {
-            let crate::process::Message { message, nonce } = $self
-                .inner
-                .response_receiver
-                .recv()
+        let receive = async {
+            $self.inner.run_pending().await;
+            response_receiver.recv().await
+        };
+        let response = match $self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, receive)
                 .await
-                .map_err(anyhow::Error::from)?;
-            if matches!(message, crate::process::ResponseMessageData::Panic) {
-                panic!("JS thread panicked!");
-            } else if sent_nonce == nonce {
-                let crate::process::ResponseMessageData::$response(response) = message else {
-                    panic!("Unexpected message: {:?}", message);
-                };
-                break response;
-            }
+                .map_err(|_| ErrorKind::Timeout)?,
+            None => receive.await,
+        };
+        // A runner which panics responds with `ErrorKind::RunnerPanicked`, so this only happens
+        // if the JS thread has stopped.
+        let Ok(crate::process::Message { message, .. }) = response else {
+            return Err(no_response());
+        };
+        #[cfg(feature = "cache")]
+        if let (Some(cache), Some(key)) = (&$self.cache, cache_key) {
+            cache.insert(key, &message);
+        }
+        let crate::process::ResponseMessageData::$response(result) = message else {
+            return Err(unexpected_response(&message));
         };
         info!("Received response");
 
@@ -59,13 +356,122 @@ impl UtaFormatix {
     /// of `UtaFormatix`, or use [`crate::Project`], which only creates one instance of
     /// `UtaFormatix`.
     pub fn new() -> Self {
-        let inner = SyncThread::new();
-        Self { inner }
+        Self::builder().build()
+    }
+
+    /// Returns a builder to create an instance with custom configuration.
+    pub fn builder() -> UtaFormatixBuilder {
+        UtaFormatixBuilder::default()
+    }
+
+    /// Returns the default parse options of this instance, set by
+    /// [`UtaFormatixBuilder::parse_options`].
+    pub fn parse_options(&self) -> ParseOptions {
+        self.parse_options.clone()
+    }
+
+    /// Returns the default generate options of this instance, set by
+    /// [`UtaFormatixBuilder::generate_options`].
+    pub fn generate_options(&self) -> GenerateOptions {
+        self.generate_options.clone()
+    }
+
+    /// Returns the options passed to a call, or the default options of this instance if the
+    /// call passes `None`.
+    fn parse_options_or_default(&self, options: impl Into<Option<ParseOptions>>) -> ParseOptions {
+        options.into().unwrap_or_else(|| self.parse_options.clone())
+    }
+
+    /// Returns the options passed to a call, or the default options of this instance if the
+    /// call passes `None`.
+    fn generate_options_or_default(
+        &self,
+        options: impl Into<Option<GenerateOptions>>,
+    ) -> GenerateOptions {
+        options
+            .into()
+            .unwrap_or_else(|| self.generate_options.clone())
+    }
+
+    /// Returns the statistics of the cache, or `None` if it is not enabled by
+    /// [`UtaFormatixBuilder::cache`].
+    #[cfg(feature = "cache")]
+    pub fn cache_stats(&self) -> Option<crate::CacheStats> {
+        self.cache.as_ref().map(crate::cache::Cache::stats)
+    }
+
+    /// Returns the versions of this crate and what it embeds, e.g. for bug reports.
+    ///
+    /// This doesn't use the JS thread.
+    pub fn version_info() -> VersionInfo {
+        VersionInfo {
+            crate_version: env!("CARGO_PKG_VERSION"),
+            utaformatix_ts_version: include_str!("./utaformatix-ts.version").trim(),
+            uf_data_format_version: UfData::FORMAT_VERSION,
+        }
+    }
+
+    /// Runs a tiny conversion on every worker, so that the startup cost is paid now instead of
+    /// by the first request, e.g. when a service boots.
+    ///
+    /// Workers which are busy with other requests are waited for up to 10 seconds, after which
+    /// the idle workers may warm up in their place.
+    ///
+    /// This fails if the bundle can't generate OpenUtau's projects, which the conversion uses.
+    pub async fn warm_up(&self) -> Result<()> {
+        #[cfg(feature = "multi-thread")]
+        let workers = self.inner.handles.len();
+        #[cfg(not(feature = "multi-thread"))]
+        let workers = 1;
+        let gate = std::sync::Arc::new(crate::process::WarmUpGate::new(workers));
+        let messages = (0..workers)
+            .map(|_| crate::process::RequestMessageData::WarmUp { gate: gate.clone() })
+            .collect();
+        for response in self.request_all(messages).await? {
+            let crate::process::ResponseMessageData::WarmUp(result) = response else {
+                return Err(unexpected_response(&response));
+            };
+            result?;
+        }
+        info!("Warmed up {} workers", workers);
+
+        Ok(())
+    }
+
+    /// Lists the features of the data which the target format can't represent, without
+    /// generating it. See [`UfData::check_conversion`].
+    ///
+    /// This doesn't use the JS thread.
+    pub fn check_conversion(data: &UfData, target: Format) -> CompatibilityReport {
+        data.check_conversion(target)
+    }
+
+    /// Parses a Standard MIDI file.
+    ///
+    /// This is implemented natively, so it doesn't use the JS thread.
+    pub async fn parse_standard_mid(
+        &self,
+        data: impl Into<Bytes>,
+        options: impl Into<Option<ParseOptions>>,
+    ) -> Result<crate::model::UfData> {
+        crate::native::standard_mid::parse(&data.into(), self.parse_options_or_default(options))
+            .map_err(|e| e.with_format(Format::StandardMid))
+    }
+
+    /// Parses a UtaFormatix data file.
+    ///
+    /// This is implemented natively, so it doesn't use the JS thread.
+    pub async fn parse_uf_data(
+        &self,
+        data: impl Into<Bytes>,
+        options: impl Into<Option<ParseOptions>>,
+    ) -> Result<crate::model::UfData> {
+        crate::native::uf_data::parse(&data.into(), self.parse_options_or_default(options))
+            .map_err(|e| e.with_format(Format::UfData))
     }
 
     #[duplicate_item(
         fn_name              format_enum           kind;
-        [parse_standard_mid] [Format::StandardMid] ["Standard MIDI"];
         [parse_music_xml]    [Format::MusicXml]    ["MusicXML"];
         [parse_ccs]          [Format::Ccs]         ["CeVIO's project"];
         [parse_dv]           [Format::Dv]          ["DeepVocal's project"];
@@ -74,7 +480,6 @@ impl UtaFormatix {
         [parse_s5p]          [Format::S5p]         ["Old Synthesizer V's project"];
         [parse_svp]          [Format::Svp]         ["Synthesizer V's project"];
         [parse_tssln]        [Format::Tssln]       ["VoiSona's project"];
-        [parse_uf_data]      [Format::UfData]      ["UtaFormatix data"];
         [parse_vocaloid_mid] [Format::VocaloidMid] ["VOCALOID 1's project"];
         [parse_vsq]          [Format::Vsq]         ["VOCALOID 2's project"];
         [parse_vsqx]         [Format::Vsqx]        ["VOCALOID 3/4's project"];
@@ -85,13 +490,13 @@ impl UtaFormatix {
     #[doc = " file."]
     pub async fn fn_name(
         &self,
-        data: &[u8],
-        options: ParseOptions,
+        data: impl Into<Bytes>,
+        options: impl Into<Option<ParseOptions>>,
     ) -> Result<crate::model::UfData> {
         let message =
-            crate::process::Message::new(crate::process::RequestMessageData::ParseSingle {
-                data: data.to_vec(),
-                options,
+            crate::process::Message::request(crate::process::RequestMessageData::ParseSingle {
+                data: data.into(),
+                options: self.parse_options_or_default(options),
                 format: format_enum,
             });
         send_and_receive!(self, message, Parse)
@@ -107,29 +512,116 @@ impl UtaFormatix {
     /// You can pass multiple files to parse at once, each file will be parsed as a track.
     pub async fn fn_name(
         &self,
-        data: &[&[u8]],
-        options: ParseOptions,
+        data: impl IntoIterator<Item = impl Into<Bytes>>,
+        options: impl Into<Option<ParseOptions>>,
     ) -> Result<crate::model::UfData> {
         let message =
-            crate::process::Message::new(crate::process::RequestMessageData::ParseMultiple {
-                data: data.iter().map(|d| d.to_vec()).collect(),
-                options,
+            crate::process::Message::request(crate::process::RequestMessageData::ParseMultiple {
+                data: data.into_iter().map(Into::into).collect(),
+                options: self.parse_options_or_default(options),
                 format: format_enum,
             });
 
         send_and_receive!(self, message, Parse)
     }
 
+    /// Parses files of different formats, e.g. a UST of the lead vocal and a MIDI file of the
+    /// harmony, into a single project with the tracks of every file in order.
+    ///
+    /// The name, the tempos and the time signatures are taken from the first file, and the
+    /// notes of the other files are shifted to its measure prefix. Use [`Format::detect`] to
+    /// guess the formats of the files.
+    ///
+    /// Returns [`ErrorKind::EmptyProject`] if there are no files.
+    pub async fn parse_mixed(
+        &self,
+        files: impl IntoIterator<Item = (Format, impl Into<Bytes>)>,
+        options: impl Into<Option<ParseOptions>>,
+    ) -> Result<UfData> {
+        let options = options.into();
+        let mut merged: Option<UfData> = None;
+        for (format, data) in files {
+            let data = self.parse(format, data, options.clone()).await?;
+            let Some(merged) = &mut merged else {
+                merged = Some(data);
+                continue;
+            };
+            let mut project = crate::Project::new(data);
+            project.set_measure_prefix(merged.project().measure_prefix)?;
+            merged
+                .project_mut()
+                .tracks
+                .append(&mut project.data.project_mut().tracks);
+        }
+        merged.ok_or_else(|| ErrorKind::EmptyProject.into())
+    }
+
+    /// Parses a file of the format with the method for it, e.g. when the format is only known at
+    /// runtime.
+    ///
+    /// UST projects are parsed from this file only; use [`UtaFormatix::parse_ust`] for projects
+    /// split into multiple files.
+    pub async fn parse(
+        &self,
+        format: Format,
+        data: impl Into<Bytes>,
+        options: impl Into<Option<ParseOptions>>,
+    ) -> Result<UfData> {
+        let (data, options) = (data.into(), options.into());
+        match format {
+            Format::StandardMid => self.parse_standard_mid(data, options).await,
+            Format::MusicXml => self.parse_music_xml(data, options).await,
+            Format::Ccs => self.parse_ccs(data, options).await,
+            Format::Dv => self.parse_dv(data, options).await,
+            Format::Ustx => self.parse_ustx(data, options).await,
+            Format::Ppsf => self.parse_ppsf(data, options).await,
+            Format::S5p => self.parse_s5p(data, options).await,
+            Format::Svp => self.parse_svp(data, options).await,
+            Format::Tssln => self.parse_tssln(data, options).await,
+            Format::UfData => self.parse_uf_data(data, options).await,
+            Format::Ust => self.parse_ust([data], options).await,
+            Format::VocaloidMid => self.parse_vocaloid_mid(data, options).await,
+            Format::Vsq => self.parse_vsq(data, options).await,
+            Format::Vsqx => self.parse_vsqx(data, options).await,
+            Format::Vpr => self.parse_vpr(data, options).await,
+        }
+    }
+
+    /// Generates a Standard MIDI file.
+    ///
+    /// This is implemented natively, so it doesn't use the JS thread. Fails with
+    /// [`ErrorKind::IllegalTimeSignature`] if a time signature has a numerator over 255, which
+    /// MIDI can't store, and with [`ErrorKind::IllegalTempo`] if a tempo isn't positive or is
+    /// slower than 4 BPM.
+    pub async fn generate_standard_mid(
+        &self,
+        data: &UfData,
+        options: impl Into<Option<GenerateOptions>>,
+    ) -> Result<Vec<u8>> {
+        crate::native::standard_mid::generate(data, self.generate_options_or_default(options))
+            .map_err(|e| e.with_format(Format::StandardMid))
+    }
+
+    /// Generates a UtaFormatix data file.
+    ///
+    /// This is implemented natively, so it doesn't use the JS thread.
+    pub async fn generate_uf_data(
+        &self,
+        data: &UfData,
+        options: impl Into<Option<GenerateOptions>>,
+    ) -> Result<Vec<u8>> {
+        crate::native::uf_data::generate(data, self.generate_options_or_default(options))
+            .map_err(|e| e.with_format(Format::UfData))
+    }
+
     #[duplicate_item(
         fn_name                  format_enum          kind;
-        [generate_standard_mid] [Format::StandardMid] ["Standard MIDI"];
         [generate_ccs]          [Format::Ccs]         ["CeVIO's project"];
         [generate_dv]           [Format::Dv]          ["DeepVocal's project"];
         [generate_ustx]         [Format::Ustx]        ["OpenUtau's project"];
         [generate_s5p]          [Format::S5p]         ["Old Synthesizer V's project"];
         [generate_svp]          [Format::Svp]         ["Synthesizer V's project"];
         [generate_tssln]        [Format::Tssln]       ["VoiSona's project"];
-        [generate_uf_data]      [Format::UfData]      ["UtaFormatix data"];
         [generate_vocaloid_mid] [Format::VocaloidMid] ["VOCALOID 1's project"];
         [generate_vsq]          [Format::Vsq]         ["VOCALOID 2's project"];
         [generate_vsqx]         [Format::Vsqx]        ["VOCALOID 3/4's project"];
@@ -138,11 +630,15 @@ impl UtaFormatix {
     #[doc = "Generates a "]
     #[doc = kind]
     #[doc = " file."]
-    pub async fn fn_name(&self, data: &UfData, options: GenerateOptions) -> Result<Vec<u8>> {
+    pub async fn fn_name(
+        &self,
+        data: &UfData,
+        options: impl Into<Option<GenerateOptions>>,
+    ) -> Result<Vec<u8>> {
         let message =
-            crate::process::Message::new(crate::process::RequestMessageData::GenerateSingle {
+            crate::process::Message::request(crate::process::RequestMessageData::GenerateSingle {
                 data: data.clone(),
-                options,
+                options: self.generate_options_or_default(options),
                 format: format_enum,
             });
 
@@ -158,24 +654,318 @@ impl UtaFormatix {
     #[doc = kind]
     #[doc = " file."]
     /// Returns the bytes of the generated file, each representing a track.
-    pub async fn fn_name(&self, data: &UfData, options: GenerateOptions) -> Result<Vec<Vec<u8>>> {
-        let message =
-            crate::process::Message::new(crate::process::RequestMessageData::GenerateMultiple {
+    ///
+    /// With multiple workers, the tracks are generated in parallel, unless the files are merged
+    /// by the format options.
+    pub async fn fn_name(
+        &self,
+        data: &UfData,
+        options: impl Into<Option<GenerateOptions>>,
+    ) -> Result<Vec<Vec<u8>>> {
+        let options = self.generate_options_or_default(options);
+        #[cfg(feature = "multi-thread")]
+        if self.splits_tracks(data, &options, format_enum) {
+            return self.generate_tracks(data, options, format_enum).await;
+        }
+        let message = crate::process::Message::request(
+            crate::process::RequestMessageData::GenerateMultiple {
                 data: data.clone(),
                 options,
                 format: format_enum,
-            });
+            },
+        );
 
         send_and_receive!(self, message, GenerateMultiple)
     }
 
-    /// Analyzes the type of Japanese lyrics.
-    /// Returns `None` if the lyrics type cannot be determined.
-    pub async fn analyze_japanese_lyrics_type(
+    /// Sends all the requests before waiting for any of them, so that the workers process them
+    /// in parallel, and returns the responses in order. Requests are answered from the cache
+    /// when possible, and successful responses are cached.
+    async fn request_all(
         &self,
-        data: UfData,
-    ) -> Result<Option<JapaneseLyricsType>> {
-        let message = crate::process::Message::new(
+        messages: Vec<crate::process::RequestMessageData>,
+    ) -> Result<Vec<crate::process::ResponseMessageData>> {
+        let mut responses = Vec::with_capacity(messages.len());
+        let mut receivers = vec![];
+        for message in messages {
+            #[cfg(feature = "cache")]
+            let cache_key = self.cache.as_ref().and_then(|cache| cache.key(&message));
+            #[cfg(feature = "cache")]
+            if let Some(response) = cache_key.and_then(|key| self.cache.as_ref()?.get(key)) {
+                info!("Cache hit");
+                responses.push(Some(response));
+                continue;
+            }
+            let (response_sender, response_receiver) = async_channel::bounded(1);
+            self.inner
+                .request_sender
+                .send(crate::process::Request::new(
+                    crate::process::Message::request(message),
+                    response_sender,
+                ))
+                .await
+                .map_err(anyhow::Error::from)?;
+            #[cfg(feature = "cache")]
+            receivers.push((responses.len(), response_receiver, cache_key));
+            #[cfg(not(feature = "cache"))]
+            receivers.push((responses.len(), response_receiver));
+            responses.push(None);
+        }
+        info!("Sent {} messages, waiting for responses", receivers.len());
+        let receive = async {
+            self.inner.run_pending().await;
+            for receiver in receivers {
+                #[cfg(feature = "cache")]
+                let (index, receiver, cache_key) = receiver;
+                #[cfg(not(feature = "cache"))]
+                let (index, receiver) = receiver;
+                // A runner which panics responds with `ErrorKind::RunnerPanicked`, so this only
+                // happens if the JS thread has stopped.
+                let Ok(crate::process::Message { message, .. }) = receiver.recv().await else {
+                    return Err(no_response());
+                };
+                #[cfg(feature = "cache")]
+                if let (Some(cache), Some(key)) = (&self.cache, cache_key) {
+                    cache.insert(key, &message);
+                }
+                responses[index] = Some(message);
+            }
+            Ok(())
+        };
+        match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, receive)
+                .await
+                .map_err(|_| ErrorKind::Timeout)??,
+            None => receive.await?,
+        };
+        info!("Received responses");
+
+        // Every request has been answered by the cache or a worker.
+        Ok(responses.into_iter().flatten().collect())
+    }
+
+    /// Returns whether a multi-file format is generated by a request per track, which the
+    /// workers run in parallel.
+    #[cfg(feature = "multi-thread")]
+    fn splits_tracks(&self, data: &UfData, options: &GenerateOptions, format: Format) -> bool {
+        let merges = options
+            .format_options
+            .as_ref()
+            .is_some_and(|format_options| format_options.merges(format));
+        self.inner.handles.len() > 1 && data.project().tracks.len() > 1 && !merges
+    }
+
+    /// Generates a multi-file format by a request per track, and returns the files in the order
+    /// of the tracks.
+    #[cfg(feature = "multi-thread")]
+    async fn generate_tracks(
+        &self,
+        data: &UfData,
+        options: GenerateOptions,
+        format: Format,
+    ) -> Result<Vec<Vec<u8>>> {
+        // The options are checked on the whole project first, so that errors have the indices
+        // of the tracks in it. The tracks are sent unprepared, since e.g. lyric markers are
+        // needed to write MusicXML directions.
+        options
+            .validate(format, data)
+            .map_err(|e| e.with_format(format))?;
+        let messages = data
+            .track_projects()
+            .map(
+                |data| crate::process::RequestMessageData::GenerateMultiple {
+                    data,
+                    options: options.clone(),
+                    format,
+                },
+            )
+            .collect();
+        // Each track is cached on its own by `request_all`.
+        let mut files = vec![];
+        for response in self.request_all(messages).await? {
+            let crate::process::ResponseMessageData::GenerateMultiple(result) = response else {
+                return Err(unexpected_response(&response));
+            };
+            files.extend(result?);
+        }
+        Ok(files)
+    }
+
+    #[duplicate_item(
+        fn_name                    format_enum     kind;
+        [generate_ccs_per_track]   [Format::Ccs]   ["CeVIO's project"];
+        [generate_tssln_per_track] [Format::Tssln] ["VoiSona's project"];
+    )]
+    #[doc = "Generates a "]
+    #[doc = kind]
+    #[doc = " file for each track, e.g. for a project per cast."]
+    /// Returns the names of the tracks and the files, in the order of the tracks. Each file has
+    /// the tempos and the time signatures of the whole project.
+    ///
+    /// With multiple workers, the files are generated in parallel.
+    pub async fn fn_name(
+        &self,
+        data: &UfData,
+        options: impl Into<Option<GenerateOptions>>,
+    ) -> Result<Vec<(String, Vec<u8>)>> {
+        let options = self.generate_options_or_default(options);
+        // The options are checked on the whole project first, so that errors have the indices
+        // of the tracks in it.
+        options
+            .validate(format_enum, data)
+            .map_err(|e| e.with_format(format_enum))?;
+        let messages = data
+            .track_projects()
+            .map(|data| crate::process::RequestMessageData::GenerateSingle {
+                data,
+                options: options.clone(),
+                format: format_enum,
+            })
+            .collect();
+        let responses = self.request_all(messages).await?;
+        let mut files = vec![];
+        for (track, response) in data.project().tracks.iter().zip(responses) {
+            let crate::process::ResponseMessageData::GenerateSingle(result) = response else {
+                return Err(unexpected_response(&response));
+            };
+            files.push((track.name.clone(), result?));
+        }
+        Ok(files)
+    }
+
+    #[duplicate_item(
+        fn_name                        format_enum          kind;
+        [generate_music_xml_stream]    [Format::MusicXml]    ["MusicXML"];
+        [generate_ust_stream]          [Format::Ust]         ["UTAU's project"];
+    )]
+    #[doc = "Generates a "]
+    #[doc = kind]
+    #[doc = " file, receiving the files one at a time instead of all at once."]
+    ///
+    /// Each track is converted by the JS engine only when the previous file is received, so that
+    /// only one track is converted and held at a time, which helps with projects with many
+    /// tracks. Files merged by the format options are still converted at once and received as
+    /// one file.
+    pub async fn fn_name(
+        &self,
+        data: &UfData,
+        options: impl Into<Option<GenerateOptions>>,
+    ) -> Result<GeneratedFiles> {
+        let message = crate::process::Message::request(
+            crate::process::RequestMessageData::GenerateMultipleStream {
+                data: data.clone(),
+                options: self.generate_options_or_default(options),
+                format: format_enum,
+            },
+        );
+        // Without the JS threads, every file is generated before this returns, so the channel
+        // must hold all of them.
+        #[cfg(feature = "multi-thread")]
+        let (response_sender, receiver) = async_channel::bounded(1);
+        #[cfg(not(feature = "multi-thread"))]
+        let (response_sender, receiver) = async_channel::unbounded();
+        self.inner
+            .request_sender
+            .send(crate::process::Request::new(message, response_sender))
+            .await
+            .map_err(anyhow::Error::from)?;
+        self.inner.run_pending().await;
+        info!("Sent message, streaming response");
+
+        Ok(GeneratedFiles {
+            receiver,
+            timeout: self.timeout,
+            finished: false,
+        })
+    }
+
+    /// Parses a file with the JS engine, even if the format is implemented natively.
+    ///
+    /// This is mainly useful for comparing native implementations with the engine.
+    /// For [`Format::Ust`], `data` is parsed as a single file.
+    pub async fn parse_with_engine(
+        &self,
+        format: Format,
+        data: impl Into<Bytes>,
+        options: impl Into<Option<ParseOptions>>,
+    ) -> Result<crate::model::UfData> {
+        let data = data.into();
+        let options = self.parse_options_or_default(options);
+        let message = if format == Format::Ust {
+            crate::process::Message::request(crate::process::RequestMessageData::ParseMultiple {
+                data: vec![data],
+                options,
+                format,
+            })
+        } else {
+            crate::process::Message::request(crate::process::RequestMessageData::ParseSingle {
+                data,
+                options,
+                format,
+            })
+        };
+
+        send_and_receive!(self, message, Parse)
+    }
+
+    /// Generates a single-file format with the JS engine, even if the format is implemented
+    /// natively.
+    ///
+    /// This is mainly useful for comparing native implementations with the engine.
+    pub async fn generate_with_engine(
+        &self,
+        format: Format,
+        data: &UfData,
+        options: impl Into<Option<GenerateOptions>>,
+    ) -> Result<Vec<u8>> {
+        let message =
+            crate::process::Message::request(crate::process::RequestMessageData::GenerateSingle {
+                data: data.clone(),
+                options: self.generate_options_or_default(options),
+                format,
+            });
+
+        send_and_receive!(self, message, GenerateSingle)
+    }
+
+    /// Converts an old Synthesizer V project (`.s5p`) to a Synthesizer V Studio project (`.svp`),
+    /// which is the usual way to migrate old projects.
+    ///
+    /// This parses and generates the pitch data, and keeps empty lyrics and breath notes as they
+    /// are. The voice database of each track is carried over, unless
+    /// [`SvpOptions::database_name`] is set.
+    pub async fn convert_s5p_to_svp(
+        &self,
+        data: impl Into<Bytes>,
+        options: SvpOptions,
+    ) -> Result<Vec<u8>> {
+        let data = data.into();
+        let parse_options = ParseOptions {
+            pitch: true,
+            default_lyric: DefaultLyric::KeepEmpty,
+            ..Default::default()
+        };
+        let parsed = self.parse_s5p(data.clone(), parse_options).await?;
+        let copies_database_names = options.database_name.is_none();
+        let generate_options = GenerateOptions {
+            pitch: true,
+            format_options: Some(FormatOptions::Svp(options)),
+            ..Default::default()
+        };
+        let generated = self.generate_svp(&parsed, generate_options).await?;
+        if copies_database_names {
+            crate::model::copy_s5p_database_names(generated, &data)
+                .map_err(|e| e.with_format(Format::Svp))
+        } else {
+            Ok(generated)
+        }
+    }
+
+    /// Analyzes the type of Japanese lyrics, of the whole project and each track.
+    ///
+    /// See [`LyricsTypeAnalysis::is_ambiguous`] to check whether the result is reliable.
+    pub async fn analyze_japanese_lyrics_type(&self, data: UfData) -> Result<LyricsTypeAnalysis> {
+        let message = crate::process::Message::request(
             crate::process::RequestMessageData::AnalyzeJapaneseLyricsType { data },
         );
 
@@ -183,14 +973,41 @@ impl UtaFormatix {
     }
 
     /// Converts Japanese lyrics.
+    ///
+    /// Only the lyrics of the notes are replaced, so the rest of the data, including the
+    /// extensions to UtaFormatix data which the engine doesn't know, is kept as it is.
     pub async fn convert_japanese_lyrics(
+        &self,
+        mut data: UfData,
+        source_type: JapaneseLyricsType,
+        target_type: JapaneseLyricsType,
+        options: ConvertJapaneseLyricsOptions,
+    ) -> Result<UfData> {
+        let converted = self
+            .convert_japanese_lyrics_with_engine(data.clone(), source_type, target_type, options)
+            .await?;
+        // The engine converts the lyrics note by note, so the notes stay in the same order.
+        for (track, converted) in data
+            .project_mut()
+            .tracks
+            .iter_mut()
+            .zip(&converted.project().tracks)
+        {
+            for (note, converted) in track.notes.iter_mut().zip(&converted.notes) {
+                note.lyric.clone_from(&converted.lyric);
+            }
+        }
+        Ok(data)
+    }
+
+    async fn convert_japanese_lyrics_with_engine(
         &self,
         data: UfData,
         source_type: JapaneseLyricsType,
         target_type: JapaneseLyricsType,
         options: ConvertJapaneseLyricsOptions,
     ) -> Result<UfData> {
-        let message = crate::process::Message::new(
+        let message = crate::process::Message::request(
             crate::process::RequestMessageData::ConvertJapaneseLyrics {
                 data,
                 source_type,
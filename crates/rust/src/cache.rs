@@ -0,0 +1,401 @@
+//! Cache of conversions done by the JS threads, keyed by the input and the options.
+use crate::{
+    model::{GenerateOptions, ParseOptions, PrefixMap, Priority, UfData},
+    process::{RequestMessageData, ResponseMessageData, RunnerConfig},
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, PoisonError,
+    },
+};
+use tracing::warn;
+
+/// Represents where cached results are stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheStorage {
+    /// Keeps results in memory, for the lifetime of the [`UtaFormatix`](crate::base::UtaFormatix).
+    Memory,
+    /// Keeps results as files in the directory, which is created if it doesn't exist, so they
+    /// are shared between processes, e.g. runs of a CI pipeline.
+    Directory(PathBuf),
+}
+
+/// Represents the configuration of the cache, set by
+/// [`UtaFormatixBuilder::cache`](crate::base::UtaFormatixBuilder::cache).
+///
+/// Results of parsing and generating with the JS threads are cached by the format, the options
+/// (except the priority), the content of the input, the bundle and the allowed encodings.
+/// Failed conversions and formats which are implemented natively are not cached. Requests
+/// which are split per track, e.g. by
+/// [`UtaFormatix::generate_ccs_per_track`](crate::base::UtaFormatix::generate_ccs_per_track),
+/// are cached per track.
+///
+/// Keys are SHA-256 hashes, and entries have a checksum, so a truncated or corrupted file in a
+/// [`CacheStorage::Directory`] is treated as a miss.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// Where results are stored.
+    pub storage: CacheStorage,
+    /// Maximum total size of the results in bytes. The oldest results are removed beyond this.
+    pub max_size: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            storage: CacheStorage::Memory,
+            max_size: 256 * 1024 * 1024,
+        }
+    }
+}
+
+/// Represents the statistics of the cache, returned by
+/// [`UtaFormatix::cache_stats`](crate::base::UtaFormatix::cache_stats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Number of requests answered from the cache.
+    pub hits: u64,
+    /// Number of requests sent to the JS threads because nothing was cached.
+    pub misses: u64,
+}
+
+pub(crate) struct Cache {
+    config: CacheConfig,
+    /// Hash of the configuration of the JS threads which changes their results, written at the
+    /// start of every key.
+    engine: CacheKey,
+    /// Only used for [`CacheStorage::Memory`].
+    entries: Mutex<MemoryEntries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Default)]
+struct MemoryEntries {
+    entries: HashMap<CacheKey, Vec<u8>>,
+    /// Keys of the entries, from the oldest.
+    order: VecDeque<CacheKey>,
+}
+
+/// SHA-256 hash of a request.
+pub(crate) type CacheKey = [u8; 32];
+
+/// Hashes the fields of a request into a [`CacheKey`].
+#[derive(Default)]
+struct KeyHasher(Sha256);
+
+impl KeyHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    /// Writes the bytes with their length, so adjacent fields can't be confused.
+    fn write_field(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+
+    fn finish(self) -> CacheKey {
+        self.0.finalize().into()
+    }
+}
+
+impl Cache {
+    pub(crate) fn new(config: CacheConfig, runner: &RunnerConfig) -> Self {
+        let mut hasher = KeyHasher::default();
+        match &runner.bundle {
+            Some(bundle) => hasher.write_field(bundle.as_bytes()),
+            None => hasher.write_field(b"embedded"),
+        }
+        match &runner.encodings {
+            Some(encodings) => {
+                hasher.write(&(encodings.len() as u64).to_le_bytes());
+                for encoding in encodings {
+                    hasher.write_field(encoding.as_bytes());
+                }
+            }
+            None => hasher.write_field(b"any"),
+        }
+        Self {
+            config,
+            engine: hasher.finish(),
+            entries: Mutex::default(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the cached response of the request, counting a hit or a miss.
+    pub(crate) fn get(&self, key: CacheKey) -> Option<ResponseMessageData> {
+        let entry = match &self.config.storage {
+            CacheStorage::Memory => {
+                let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+                entries.entries.get(&key).cloned()
+            }
+            CacheStorage::Directory(directory) => {
+                std::fs::read(directory.join(file_name(key))).ok()
+            }
+        };
+        let response = entry.and_then(|entry| decode(&entry));
+        let counter = if response.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        response
+    }
+
+    /// Stores the response if it is a successful conversion.
+    pub(crate) fn insert(&self, key: CacheKey, response: &ResponseMessageData) {
+        let Some(entry) = encode(response) else {
+            return;
+        };
+        if entry.len() as u64 > self.config.max_size {
+            return;
+        }
+        match &self.config.storage {
+            CacheStorage::Memory => {
+                let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+                let MemoryEntries {
+                    entries: map,
+                    order,
+                } = &mut *entries;
+                if map.insert(key, entry).is_none() {
+                    order.push_back(key);
+                }
+                let mut size = map.values().map(|entry| entry.len() as u64).sum::<u64>();
+                while size > self.config.max_size {
+                    let Some(oldest) = order.pop_front() else {
+                        break;
+                    };
+                    size -= map.remove(&oldest).map_or(0, |entry| entry.len() as u64);
+                }
+            }
+            CacheStorage::Directory(directory) => {
+                if let Err(e) = write_to_directory(directory, key, &entry, self.config.max_size) {
+                    warn!("Failed to write to the cache: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Returns the key of the request, or `None` if it is not cached.
+    pub(crate) fn key(&self, message: &RequestMessageData) -> Option<CacheKey> {
+        let mut hasher = KeyHasher::default();
+        hasher.write(env!("CARGO_PKG_VERSION").as_bytes());
+        hasher.write(&self.engine);
+        write_request(&mut hasher, message)?;
+        Some(hasher.finish())
+    }
+}
+
+/// Writes the request to the hasher, or returns `None` if it is not cached.
+fn write_request(hasher: &mut KeyHasher, message: &RequestMessageData) -> Option<()> {
+    match message {
+        RequestMessageData::ParseSingle {
+            data,
+            options,
+            format,
+        } => {
+            hasher.write_field(b"parse");
+            hasher.write_field(format!("{:?}", format).as_bytes());
+            hasher.write_field(&options_json(&ParseOptions {
+                priority: Priority::default(),
+                ..options.clone()
+            })?);
+            write_prefix_map(hasher, options.prefix_map.as_ref());
+            hasher.write_field(data);
+        }
+        RequestMessageData::ParseMultiple {
+            data,
+            options,
+            format,
+        } => {
+            hasher.write_field(b"parse_multiple");
+            hasher.write_field(format!("{:?}", format).as_bytes());
+            hasher.write_field(&options_json(&ParseOptions {
+                priority: Priority::default(),
+                ..options.clone()
+            })?);
+            write_prefix_map(hasher, options.prefix_map.as_ref());
+            for file in data {
+                hasher.write_field(file);
+            }
+        }
+        RequestMessageData::GenerateSingle {
+            data,
+            options,
+            format,
+        }
+        | RequestMessageData::GenerateMultiple {
+            data,
+            options,
+            format,
+        } => {
+            let kind: &[u8] = match message {
+                RequestMessageData::GenerateSingle { .. } => b"generate",
+                _ => b"generate_multiple",
+            };
+            hasher.write_field(kind);
+            hasher.write_field(format!("{:?}", format).as_bytes());
+            hasher.write_field(&options_json(&GenerateOptions {
+                priority: Priority::default(),
+                ..options.clone()
+            })?);
+            write_prefix_map(hasher, options.prefix_map.as_ref());
+            hasher.write_field(&serde_json::to_vec(data).ok()?);
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+/// Writes the prefix map of the options, which is skipped by their serialization.
+fn write_prefix_map(hasher: &mut KeyHasher, prefix_map: Option<&PrefixMap>) {
+    let Some(prefix_map) = prefix_map else {
+        hasher.write_field(b"");
+        return;
+    };
+    hasher.write_field(b"prefix_map");
+    for (key, prefix, suffix) in prefix_map.entries() {
+        hasher.write(&key.to_le_bytes());
+        hasher.write_field(prefix.as_bytes());
+        hasher.write_field(suffix.as_bytes());
+    }
+}
+
+/// Serializes the options through a [`serde_json::Value`], so equal options have equal keys.
+/// This relies on the `preserve_order` feature of serde_json being disabled, which makes the
+/// maps of the value sorted by key. Returns `None` if the options can't be serialized, so the
+/// request isn't cached instead of sharing a key with other options.
+fn options_json(options: &impl serde::Serialize) -> Option<Vec<u8>> {
+    let value = serde_json::to_value(options).ok()?;
+    serde_json::to_vec(&value).ok()
+}
+
+fn file_name(key: CacheKey) -> String {
+    let hex = key
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    format!("{}.bin", hex)
+}
+
+/// Writes the entry to a temporary file first and renames it, so that other processes never
+/// read a partially written entry, then removes the oldest entries beyond `max_size`.
+fn write_to_directory(
+    directory: &std::path::Path,
+    key: CacheKey,
+    entry: &[u8],
+    max_size: u64,
+) -> std::io::Result<()> {
+    std::fs::create_dir_all(directory)?;
+    let path = directory.join(file_name(key));
+    let temporary = directory.join(format!("{}.tmp", uuid::Uuid::new_v4()));
+    std::fs::write(&temporary, entry)?;
+    if let Err(e) = std::fs::rename(&temporary, &path) {
+        let _ = std::fs::remove_file(&temporary);
+        return Err(e);
+    }
+
+    let mut files = std::fs::read_dir(directory)?
+        .filter_map(|file| {
+            let file = file.ok()?;
+            let metadata = file.metadata().ok()?;
+            let is_entry = file.file_name().to_string_lossy().ends_with(".bin");
+            (metadata.is_file() && is_entry)
+                .then(|| (metadata.modified().ok(), metadata.len(), file.path()))
+        })
+        .collect::<Vec<_>>();
+    let mut size = files.iter().map(|(_, len, _)| len).sum::<u64>();
+    files.sort();
+    for (_, len, path) in files {
+        if size <= max_size {
+            break;
+        }
+        std::fs::remove_file(path)?;
+        size -= len;
+    }
+    Ok(())
+}
+
+const TAG_DATA: u8 = 0;
+const TAG_FILE: u8 = 1;
+const TAG_FILES: u8 = 2;
+
+/// Length of the SHA-256 checksum at the end of each entry.
+const CHECKSUM_LEN: usize = 32;
+
+/// Encodes the response as an entry, which is a tag, the content and the checksum of both.
+fn encode(response: &ResponseMessageData) -> Option<Vec<u8>> {
+    let mut entry = encode_content(response)?;
+    let checksum = Sha256::digest(&entry);
+    entry.extend_from_slice(&checksum);
+    Some(entry)
+}
+
+fn encode_content(response: &ResponseMessageData) -> Option<Vec<u8>> {
+    match response {
+        ResponseMessageData::Parse(Ok(data)) => {
+            let mut entry = vec![TAG_DATA];
+            entry.extend(serde_json::to_vec(data).ok()?);
+            Some(entry)
+        }
+        ResponseMessageData::GenerateSingle(Ok(file)) => {
+            let mut entry = vec![TAG_FILE];
+            entry.extend_from_slice(file);
+            Some(entry)
+        }
+        ResponseMessageData::GenerateMultiple(Ok(files)) => {
+            let mut entry = vec![TAG_FILES];
+            for file in files {
+                entry.extend_from_slice(&(file.len() as u64).to_le_bytes());
+                entry.extend_from_slice(file);
+            }
+            Some(entry)
+        }
+        _ => None,
+    }
+}
+
+/// Decodes an entry, or returns `None` if its checksum doesn't match, e.g. if it is truncated.
+fn decode(entry: &[u8]) -> Option<ResponseMessageData> {
+    let (entry, checksum) = entry.split_at(entry.len().checked_sub(CHECKSUM_LEN)?);
+    if Sha256::digest(entry).as_slice() != checksum {
+        return None;
+    }
+    let (tag, mut rest) = entry.split_first()?;
+    match *tag {
+        TAG_DATA => serde_json::from_slice::<UfData>(rest)
+            .ok()
+            .map(|data| ResponseMessageData::Parse(Ok(data))),
+        TAG_FILE => Some(ResponseMessageData::GenerateSingle(Ok(rest.to_vec()))),
+        TAG_FILES => {
+            let mut files = vec![];
+            while !rest.is_empty() {
+                let (len, tail) = rest.split_first_chunk::<8>()?;
+                let len = usize::try_from(u64::from_le_bytes(*len)).ok()?;
+                if tail.len() < len {
+                    return None;
+                }
+                let (file, tail) = tail.split_at(len);
+                files.push(file.to_vec());
+                rest = tail;
+            }
+            Some(ResponseMessageData::GenerateMultiple(Ok(files)))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,231 @@
+//! Reports of panics of the JS threads, with the request which caused them.
+use crate::{fnv::Fnv128, model::Format, process::RequestMessageData};
+use educe::Educe;
+use serde::Serialize;
+use std::{
+    any::Any,
+    cell::RefCell,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tracing::{error, warn};
+
+/// Represents a panic of a JS thread, passed to the callback set by
+/// [`UtaFormatixBuilder::on_crash`](crate::base::UtaFormatixBuilder::on_crash) and carried by
+/// [`ErrorKind::RunnerPanicked`](crate::ErrorKind::RunnerPanicked).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashReport {
+    /// The message of the panic.
+    pub message: String,
+    /// The request which was being processed, or `None` if the thread panicked outside of a
+    /// request, e.g. while creating the JS engine. This is only recorded if
+    /// [`UtaFormatixBuilder::on_crash`](crate::base::UtaFormatixBuilder::on_crash) or
+    /// [`UtaFormatixBuilder::crash_dir`](crate::base::UtaFormatixBuilder::crash_dir) is set.
+    pub request: Option<CrashedRequest>,
+    /// The directory which the request was written to, if
+    /// [`UtaFormatixBuilder::crash_dir`](crate::base::UtaFormatixBuilder::crash_dir) is set.
+    pub dump: Option<PathBuf>,
+}
+
+/// Represents the request which a JS thread was processing when it panicked.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashedRequest {
+    /// The kind of the request, e.g. `parse` or `generate_multiple`.
+    pub kind: &'static str,
+    /// The format which was parsed or generated, if any.
+    pub format: Option<Format>,
+    /// The sizes of the inputs in bytes. Data to generate from is measured as JSON.
+    pub sizes: Vec<usize>,
+    /// The FNV-1a hash of the kind, the format, the options and the inputs, which names the
+    /// directory of the dump. This is serialized as the name of the directory, i.e. 32
+    /// hexadecimal digits.
+    #[serde(serialize_with = "serialize_hash")]
+    pub hash: u128,
+}
+
+fn serialize_hash<S: serde::Serializer>(hash: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&format_args!("{:032x}", hash))
+}
+
+type CrashCallback = dyn Fn(&CrashReport) + Send + Sync;
+
+/// Configuration of crash reports, set by [`crate::base::UtaFormatixBuilder`].
+#[derive(Educe, Clone, Default)]
+#[educe(Debug)]
+pub(crate) struct CrashConfig {
+    #[educe(Debug(ignore))]
+    pub(crate) callback: Option<Arc<CrashCallback>>,
+    pub(crate) dir: Option<PathBuf>,
+}
+
+impl CrashConfig {
+    fn is_enabled(&self) -> bool {
+        self.callback.is_some() || self.dir.is_some()
+    }
+}
+
+thread_local! {
+    /// The request which the runner on this thread is processing, if crash reports are enabled.
+    static IN_FLIGHT: RefCell<Option<Arc<RequestMessageData>>> = const { RefCell::new(None) };
+}
+
+/// Records the request as the one in flight until dropped, unless the thread is panicking.
+/// The request is shared with the runner, so this doesn't copy it.
+pub(crate) struct InFlight(());
+
+impl InFlight {
+    pub(crate) fn new(config: &CrashConfig, message: &Arc<RequestMessageData>) -> Self {
+        if config.is_enabled() {
+            IN_FLIGHT.with_borrow_mut(|in_flight| *in_flight = Some(Arc::clone(message)));
+        }
+        Self(())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        // The request is kept for the report while unwinding.
+        if !std::thread::panicking() {
+            IN_FLIGHT.with_borrow_mut(Option::take);
+        }
+    }
+}
+
+/// Logs the panic of the runner on this thread, and reports it as configured. Returns the
+/// report, so that the request can fail with it.
+pub(crate) fn report(config: &CrashConfig, payload: &(dyn Any + Send)) -> CrashReport {
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".to_string());
+    let request = IN_FLIGHT.with_borrow_mut(Option::take);
+    error!(
+        "JS runner thread panicked: {} (request: {:?})",
+        message, request
+    );
+
+    let inputs = request.as_deref().map(inputs).unwrap_or_default();
+    let request = request.map(|request| {
+        let options = options(&request);
+        let mut hasher = Fnv128::default();
+        hasher.write_field(request.kind().as_bytes());
+        hasher.write_field(format!("{:?}", request.format()).as_bytes());
+        hasher.write_field(&serde_json::to_vec(&options).unwrap_or_default());
+        for input in &inputs {
+            hasher.write_field(&input.bytes);
+        }
+        (
+            CrashedRequest {
+                kind: request.kind(),
+                format: request.format(),
+                sizes: inputs.iter().map(|input| input.bytes.len()).collect(),
+                hash: hasher.finish(),
+            },
+            options,
+        )
+    });
+    let dump = match (&config.dir, &request) {
+        (Some(dir), Some((crashed, options))) => {
+            let path = dir.join(format!("{:032x}", crashed.hash));
+            match write_dump(&path, &message, crashed, options, &inputs) {
+                Ok(()) => Some(path),
+                Err(e) => {
+                    warn!("Failed to write the crash dump: {}", e);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+    let report = CrashReport {
+        message,
+        request: request.map(|(crashed, _)| crashed),
+        dump,
+    };
+    if let Some(callback) = &config.callback {
+        callback(&report);
+    }
+    report
+}
+
+struct Input {
+    name: String,
+    bytes: Vec<u8>,
+}
+
+fn inputs(request: &RequestMessageData) -> Vec<Input> {
+    let json = |data| Input {
+        name: "input.json".to_string(),
+        bytes: serde_json::to_vec(data).unwrap_or_default(),
+    };
+    match request {
+        RequestMessageData::ParseSingle { data, format, .. } => vec![Input {
+            name: format!("input.{}", format.extension()),
+            bytes: data.to_vec(),
+        }],
+        RequestMessageData::ParseMultiple { data, format, .. } => data
+            .iter()
+            .enumerate()
+            .map(|(i, file)| Input {
+                name: format!("input-{}.{}", i, format.extension()),
+                bytes: file.to_vec(),
+            })
+            .collect(),
+        RequestMessageData::GenerateSingle { data, .. }
+        | RequestMessageData::GenerateMultiple { data, .. }
+        | RequestMessageData::GenerateMultipleStream { data, .. }
+        | RequestMessageData::AnalyzeJapaneseLyricsType { data }
+        | RequestMessageData::ConvertJapaneseLyrics { data, .. } => vec![json(data)],
+        RequestMessageData::WarmUp { .. } => vec![],
+    }
+}
+
+/// Returns the options of the request as JSON, or `null` if it has none or they can't be
+/// serialized.
+fn options(request: &RequestMessageData) -> serde_json::Value {
+    let options = match request {
+        RequestMessageData::ParseSingle { options, .. }
+        | RequestMessageData::ParseMultiple { options, .. } => serde_json::to_value(options),
+        RequestMessageData::GenerateSingle { options, .. }
+        | RequestMessageData::GenerateMultiple { options, .. }
+        | RequestMessageData::GenerateMultipleStream { options, .. } => {
+            serde_json::to_value(options)
+        }
+        RequestMessageData::ConvertJapaneseLyrics {
+            source_type,
+            target_type,
+            options,
+            ..
+        } => serde_json::to_value((source_type, target_type, options)),
+        RequestMessageData::AnalyzeJapaneseLyricsType { .. }
+        | RequestMessageData::WarmUp { .. } => Ok(serde_json::Value::Null),
+    };
+    options.unwrap_or_default()
+}
+
+/// Writes the inputs and a `request.json` with the options, so the request can be reproduced.
+fn write_dump(
+    path: &Path,
+    message: &str,
+    crashed: &CrashedRequest,
+    options: &serde_json::Value,
+    inputs: &[Input],
+) -> std::io::Result<()> {
+    std::fs::create_dir_all(path)?;
+    for input in inputs {
+        std::fs::write(path.join(&input.name), &input.bytes)?;
+    }
+    let metadata = serde_json::json!({
+        "kind": crashed.kind,
+        "format": crashed.format,
+        "options": options,
+        "message": message,
+    });
+    std::fs::write(
+        path.join("request.json"),
+        serde_json::to_vec_pretty(&metadata)?,
+    )
+}
@@ -0,0 +1,276 @@
+//! Export of singing voice datasets, e.g. for NNSVS or DiffSinger.
+//!
+//! Each phrase of each track becomes a set of files sharing a name:
+//!
+//! - `<name>.lab`: HTK label of the phonemes, in 100 ns units.
+//! - `<name>.f0.csv`: F0 curve in Hz, sampled every hop. Rests are 0.
+//! - `<name>.txt`: Lyrics separated by spaces.
+//! - `<name>.json`: The phrase as a [`Segment`].
+//!
+//! Times in the files are relative to the start of the phrase.
+use crate::{
+    error::{ErrorKind, Result},
+    model::{Key, Note, Tick, Track, UfProject},
+    project::{sanitize_file_name, Project, TempoMap},
+};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::Path};
+
+/// Phoneme of rests, in labels.
+const REST_PHONEME: &str = "pau";
+
+/// Represents how lyrics are split into phonemes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type", content = "dictionary")]
+pub enum PhonemeSet {
+    /// Uses the lyric as a single phoneme.
+    #[default]
+    Lyric,
+    /// Uses the phonemes of the notes, separated by spaces, falling back to the lyric.
+    NotePhonemes,
+    /// Looks up the lyric in a dictionary, falling back to the lyric.
+    Dictionary(HashMap<String, Vec<String>>),
+}
+
+impl PhonemeSet {
+    fn phonemes(&self, note: &Note) -> Vec<String> {
+        let phonemes = match self {
+            Self::Lyric => None,
+            Self::NotePhonemes => note
+                .phoneme
+                .as_ref()
+                .map(|phoneme| phoneme.split_whitespace().map(str::to_string).collect()),
+            Self::Dictionary(dictionary) => dictionary.get(&note.lyric).cloned(),
+        };
+        phonemes
+            .filter(|phonemes: &Vec<String>| !phonemes.is_empty())
+            .unwrap_or_else(|| vec![note.lyric.clone()])
+    }
+}
+
+/// Represents the options for [`files`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatasetOptions {
+    /// How lyrics are split into phonemes. Phonemes of a note share its length evenly.
+    pub phoneme_set: PhonemeSet,
+    /// Interval of the F0 curve, in seconds.
+    pub hop_size: f64,
+    /// Minimum length of a rest which splits phrases, in seconds.
+    pub phrase_break: f64,
+    /// Whether to follow the pitch curve of the track for F0, if any.
+    pub use_pitch: bool,
+}
+impl Default for DatasetOptions {
+    fn default() -> Self {
+        Self {
+            phoneme_set: PhonemeSet::default(),
+            hop_size: 0.005,
+            phrase_break: 0.5,
+            use_pitch: true,
+        }
+    }
+}
+
+/// Represents a phrase, written as `<name>.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Segment {
+    /// Name of the track.
+    pub track: String,
+    /// Start of the phrase in the project, in seconds.
+    pub start: f64,
+    /// End of the phrase in the project, in seconds.
+    pub end: f64,
+    /// Notes of the phrase.
+    pub notes: Vec<SegmentNote>,
+}
+
+/// Represents a note of a [`Segment`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentNote {
+    /// Start of the note from the start of the phrase, in seconds.
+    pub start: f64,
+    /// End of the note from the start of the phrase, in seconds.
+    pub end: f64,
+    /// Key of the note, where 60 is the middle C.
+    pub key: Key,
+    /// Lyric of the note.
+    pub lyric: String,
+    /// Phonemes of the note.
+    pub phonemes: Vec<String>,
+}
+
+/// Represents a file of a dataset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatasetFile {
+    /// Name of the file, e.g. `Vocal_0001.lab`.
+    pub name: String,
+    /// Content of the file.
+    pub content: Vec<u8>,
+}
+
+/// Creates the files of the dataset of the project. See the module documentation for the
+/// layout.
+///
+/// Files are named `<track name>_<phrase number>`, with the phrase number starting from 1.
+pub fn files(project: &UfProject, options: &DatasetOptions) -> Result<Vec<DatasetFile>> {
+    if !(options.hop_size.is_finite() && options.hop_size > 0.0) {
+        return Err(anyhow!("Hop size must be positive: {}", options.hop_size).into());
+    }
+    let tempo_map = TempoMap::new(&project.tempos);
+    let mut files = vec![];
+    for track in &project.tracks {
+        let segments = segments(&tempo_map, track, options);
+        for (index, segment) in segments.iter().enumerate() {
+            let name = format!("{}_{:04}", sanitize_file_name(&track.name), index + 1);
+            files.push(DatasetFile {
+                name: format!("{}.lab", name),
+                content: lab(segment).into_bytes(),
+            });
+            files.push(DatasetFile {
+                name: format!("{}.f0.csv", name),
+                content: f0_csv(&tempo_map, track, segment, options).into_bytes(),
+            });
+            let lyrics = segment
+                .notes
+                .iter()
+                .map(|note| note.lyric.as_str())
+                .collect::<Vec<_>>();
+            files.push(DatasetFile {
+                name: format!("{}.txt", name),
+                content: lyrics.join(" ").into_bytes(),
+            });
+            files.push(DatasetFile {
+                name: format!("{}.json", name),
+                content: serde_json::to_vec_pretty(segment).map_err(anyhow::Error::from)?,
+            });
+        }
+    }
+
+    Ok(files)
+}
+
+/// Splits the notes of the track into phrases.
+fn segments(tempo_map: &TempoMap, track: &Track, options: &DatasetOptions) -> Vec<Segment> {
+    let mut notes = track.notes.iter().collect::<Vec<_>>();
+    notes.sort_by_key(|note| note.tick_on);
+    let seconds = |note: &Note| {
+        (
+            tempo_map.tick_to_seconds(note.tick_on.get()),
+            tempo_map.tick_to_seconds(note.tick_off.get()),
+        )
+    };
+
+    notes
+        .chunk_by(|previous, next| seconds(next).0 - seconds(previous).1 < options.phrase_break)
+        .map(|phrase| {
+            let start = seconds(phrase[0]).0;
+            let end = phrase
+                .iter()
+                .map(|note| seconds(note).1)
+                .fold(start, f64::max);
+            Segment {
+                track: track.name.clone(),
+                start,
+                end,
+                notes: phrase
+                    .iter()
+                    .map(|note| {
+                        let (note_start, note_end) = seconds(note);
+                        SegmentNote {
+                            start: note_start - start,
+                            end: note_end - start,
+                            key: note.key,
+                            lyric: note.lyric.clone(),
+                            phonemes: options.phoneme_set.phonemes(note),
+                        }
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Writes the HTK label of the phrase.
+fn lab(segment: &Segment) -> String {
+    let to_units = |seconds: f64| (seconds * 10_000_000.0).round() as i64;
+    let mut lab = String::new();
+    let mut position = 0.0;
+    for note in &segment.notes {
+        if note.start > position {
+            lab.push_str(&format!(
+                "{} {} {}\n",
+                to_units(position),
+                to_units(note.start),
+                REST_PHONEME
+            ));
+        }
+        let length = (note.end - note.start) / note.phonemes.len() as f64;
+        for (i, phoneme) in note.phonemes.iter().enumerate() {
+            lab.push_str(&format!(
+                "{} {} {}\n",
+                to_units(note.start + length * i as f64),
+                to_units(note.start + length * (i + 1) as f64),
+                phoneme
+            ));
+        }
+        position = note.end;
+    }
+    lab
+}
+
+/// Writes the F0 curve of the phrase.
+fn f0_csv(
+    tempo_map: &TempoMap,
+    track: &Track,
+    segment: &Segment,
+    options: &DatasetOptions,
+) -> String {
+    let pitch = track.pitch.as_ref().filter(|_| options.use_pitch);
+    let mut csv = "time,f0\n".to_string();
+    let frames = ((segment.end - segment.start) / options.hop_size).ceil() as usize;
+    for frame in 0..frames {
+        let time = frame as f64 * options.hop_size;
+        let f0 = segment
+            .notes
+            .iter()
+            .find(|note| note.start <= time && time < note.end)
+            .map_or(0.0, |note| {
+                let tick = Tick::ZERO
+                    .saturating_add(tempo_map.seconds_to_tick(segment.start + time).round() as i64);
+                let key = pitch
+                    .and_then(|pitch| pitch.key_at(tick, note.key))
+                    .unwrap_or(f64::from(note.key.get()));
+                440.0 * 2f64.powf((key - 69.0) / 12.0)
+            });
+        csv.push_str(&format!("{:.3},{:.3}\n", time, f0));
+    }
+    csv
+}
+
+impl Project {
+    /// Writes the dataset of the project to the directory, creating it if it doesn't exist.
+    /// See [`files`].
+    pub fn export_dataset(
+        &self,
+        directory: impl AsRef<Path>,
+        options: &DatasetOptions,
+    ) -> Result<()> {
+        let project = self.data.project();
+        if project.tracks.iter().all(|track| track.notes.is_empty()) {
+            return Err(ErrorKind::EmptyProject.into());
+        }
+        let directory = directory.as_ref();
+        std::fs::create_dir_all(directory)
+            .map_err(|e| anyhow!("Failed to create {}: {}", directory.display(), e))?;
+        for file in files(project, options)? {
+            let path = directory.join(&file.name);
+            std::fs::write(&path, file.content)
+                .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+}
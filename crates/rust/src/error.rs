@@ -1,15 +1,34 @@
+use crate::model::Format;
+use serde::Serialize;
 use strum::{AsRefStr, EnumString};
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-#[derive(Debug, Clone, Error, AsRefStr)]
+#[derive(Debug, Clone, Error, Serialize)]
+#[error("{kind}")]
 /// Represents an error that can occur during the conversion process.
-pub enum Error {
+///
+/// Use [`Error::kind`] to find out what went wrong, and [`Error::details`] to get the context
+/// of the error, which is useful for reporting broken files.
+///
+/// This is serialized as `{"kind": "illegalFile", "detail": {...}, ...}`, where `detail` is
+/// omitted for kinds without any detail, followed by the fields of [`ErrorDetails`].
+pub struct Error {
+    #[serde(flatten)]
+    kind: ErrorKind,
+    #[serde(flatten)]
+    details: Box<ErrorDetails>,
+}
+
+#[derive(Debug, Clone, Error, AsRefStr, Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "camelCase")]
+/// Represents the kind of [`Error`].
+pub enum ErrorKind {
     #[error("The project is empty.")]
     /// The project is empty.
     EmptyProject,
-    #[error("The file is illegal.")]
+    #[error("The file is illegal: {0}")]
     /// The file is illegal.
     IllegalFile(IllegalFile),
     #[error("The note position is illegal.")]
@@ -18,11 +37,48 @@ pub enum Error {
     #[error("Notes are overlapping.")]
     /// Notes are overlapping.
     NotesOverlapping,
+    #[error("The time signature is illegal.")]
+    /// The time signature is illegal, or doesn't start at a measure boundary.
+    IllegalTimeSignature,
+    #[error("The tempo is illegal.")]
+    /// The tempo is illegal.
+    IllegalTempo,
+    #[error("The index is out of range.")]
+    /// The index of a track or a note is out of range.
+    IndexOutOfRange,
+    #[error("The key {key} of note {note} in track {track} is out of the range of the format.")]
+    /// The key of a note is out of the range of the target format (see
+    /// [`Format::key_range`]).
+    KeyOutOfRange {
+        /// The index of the track.
+        track: usize,
+        /// The index of the note in the track.
+        note: usize,
+        /// The key of the note.
+        key: i32,
+    },
+    #[error("The lyrics don't match the notes.")]
+    /// The number of phrases or lyrics doesn't match the notes of the track.
+    LyricsMismatch,
+    #[error("The type of the Japanese lyrics couldn't be detected.")]
+    /// The type of the Japanese lyrics couldn't be detected, see
+    /// [`crate::UndetectedLyricsPolicy`].
+    LyricsTypeUndetected,
+    #[error("The request timed out.")]
+    /// The request to the JS thread didn't complete within the timeout set by
+    /// [`crate::base::UtaFormatixBuilder::timeout`].
+    Timeout,
+    #[cfg(feature = "multi-thread")]
+    #[error("The JS thread panicked: {}", .0.message)]
+    /// The JS thread panicked while processing the request, or while creating its JS engine.
+    /// The thread restarts its JS engine and keeps processing the other requests.
+    RunnerPanicked(Box<crate::CrashReport>),
     #[error("Unsupported file format.")]
     /// Unsupported file format.
     UnsupportedFileFormat,
     #[error("Unsupported legacy ppsf file format.")]
-    /// Unsupported legacy ppsf file format.
+    /// Unsupported legacy ppsf file format. See [`crate::Ppsf::inspect`] for what can be read
+    /// from it.
     UnsupportedLegacyPpsf,
 
     #[error("Unexpected error: {0}")]
@@ -30,7 +86,8 @@ pub enum Error {
     Unexpected(String),
 }
 
-#[derive(Debug, Clone, Error, EnumString)]
+#[derive(Debug, Clone, PartialEq, Eq, Error, EnumString, AsRefStr, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
 /// Represents an error that can occur when the file is illegal.
 pub enum IllegalFile {
     #[error("Unknown vsq version.")]
@@ -39,25 +96,148 @@ pub enum IllegalFile {
     #[error("Failed to find root of XML.")]
     /// Failed to find root of XML.
     XmlRootNotFound,
-    #[error("Failed to find element in XML.")]
+    #[error("Failed to find element `{name}` in XML.")]
     /// Failed to find element in XML.
-    XmlElementNotFound { name: String },
-    #[error("The value of XML element is illegal.")]
+    XmlElementNotFound {
+        /// The name of the element.
+        name: String,
+    },
+    #[error("The value of XML element `{name}` is illegal.")]
     /// The value of XML element is illegal.
-    IllegalXmlValue { name: String },
-    #[error("The attribute of XML element is illegal.")]
+    IllegalXmlValue {
+        /// The name of the element.
+        name: String,
+    },
+    #[error("The attribute `{attribute}` of XML element `{name}` is illegal.")]
     /// The attribute of XML element is illegal.
-    IllegalXmlAttribute { name: String, attribute: String },
+    IllegalXmlAttribute {
+        /// The name of the element.
+        name: String,
+        /// The name of the attribute.
+        attribute: String,
+    },
     #[error("Illegal MIDI file.")]
     /// Illegal MIDI file.
     IllegalMidiFile,
     #[error("Illegal tssln file.")]
     /// Illegal tssln file.
     IllegalTsslnFile,
+    #[error("Illegal UtaFormatix data file.")]
+    /// Illegal UtaFormatix data file.
+    IllegalUfDataFile,
+    #[error("Illegal simple JSON file.")]
+    /// Illegal simple JSON file, see [`crate::SimpleProject`].
+    IllegalSimpleJsonFile,
+}
+
+impl IllegalFile {
+    /// Returns the name of the XML element, if the error is about an XML element.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::XmlElementNotFound { name }
+            | Self::IllegalXmlValue { name }
+            | Self::IllegalXmlAttribute { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the XML attribute, if the error is about an XML attribute.
+    pub fn attribute(&self) -> Option<&str> {
+        match self {
+            Self::IllegalXmlAttribute { attribute, .. } => Some(attribute),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+/// Represents the context of an [`Error`].
+pub struct ErrorDetails {
+    /// The format which was being parsed or generated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<Format>,
+    /// The message of the JS exception, if the error was thrown by the JS thread.
+    #[serde(rename = "jsMessage", skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// The stack trace of the JS exception, if the error was thrown by the JS thread.
+    #[serde(rename = "jsStack", skip_serializing_if = "Option::is_none")]
+    pub stack: Option<String>,
+    /// The path of the XML element which caused the error, e.g. `note/lyric` or
+    /// `note/@attribute`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub element_path: Option<String>,
+}
+
+impl Error {
+    /// Creates a new error with empty details.
+    pub fn new(kind: ErrorKind) -> Self {
+        let element_path = match &kind {
+            ErrorKind::IllegalFile(kind) => match (kind.name(), kind.attribute()) {
+                (Some(name), Some(attribute)) if !name.is_empty() => {
+                    Some(format!("{}/@{}", name, attribute))
+                }
+                (Some(name), None) if !name.is_empty() => Some(name.to_string()),
+                _ => None,
+            },
+            _ => None,
+        };
+        Self {
+            kind,
+            details: Box::new(ErrorDetails {
+                element_path,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Returns the kind of the error.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// Consumes the error and returns its kind.
+    pub fn into_kind(self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the context of the error.
+    pub fn details(&self) -> &ErrorDetails {
+        &self.details
+    }
+
+    /// Sets the format, unless it is already set.
+    pub(crate) fn with_format(mut self, format: Format) -> Self {
+        self.details.format.get_or_insert(format);
+        self
+    }
+
+    /// Sets the message and the stack trace of the JS exception.
+    pub(crate) fn with_js_exception(
+        mut self,
+        message: Option<String>,
+        stack: Option<String>,
+    ) -> Self {
+        self.details.message = message;
+        self.details.stack = stack;
+        self
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Self::new(kind)
+    }
+}
+
+impl From<IllegalFile> for Error {
+    fn from(kind: IllegalFile) -> Self {
+        Self::new(ErrorKind::IllegalFile(kind))
+    }
 }
 
 impl From<anyhow::Error> for Error {
     fn from(e: anyhow::Error) -> Self {
-        Self::Unexpected(e.to_string())
+        Self::new(ErrorKind::Unexpected(e.to_string()))
     }
 }
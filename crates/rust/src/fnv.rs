@@ -0,0 +1,30 @@
+//! Hashing of inputs of the JS threads, for names of crash dumps.
+
+/// 128-bit FNV-1a, which is stable across Rust versions unlike `DefaultHasher`, so names of
+/// crash dumps stay valid.
+pub(crate) struct Fnv128(u128);
+
+impl Default for Fnv128 {
+    fn default() -> Self {
+        Self(0x6c62272e07bb014262b821756295c58d)
+    }
+}
+
+impl Fnv128 {
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u128::from(byte);
+            self.0 = self.0.wrapping_mul(0x0000000001000000000000000000013b);
+        }
+    }
+
+    /// Writes the bytes with their length, so adjacent fields can't be confused.
+    pub(crate) fn write_field(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+
+    pub(crate) fn finish(&self) -> u128 {
+        self.0
+    }
+}
@@ -3,75 +3,169 @@ use boa_engine::{
     object::builtins::{JsArray, JsTypedArray},
     Context, JsArgs, JsNativeError, JsResult, JsString, JsValue,
 };
+use encoding_rs::Encoding;
 use std::future::Future;
-use tracing::info;
+use tracing::{info, warn};
 
 pub fn sleep(
     _this: &JsValue,
     args: &[JsValue],
     context: &mut Context,
 ) -> impl Future<Output = JsResult<JsValue>> {
-    let delay = args.get_or_undefined(0).to_u32(context).unwrap();
+    let delay = args.get_or_undefined(0).to_u32(context);
     async move {
+        let delay = delay?;
         tokio::time::sleep(std::time::Duration::from_millis(u64::from(delay))).await;
         Ok(JsValue::undefined())
     }
 }
 
-pub fn encode(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+/// Returns the encoding of the label at `index` of the arguments, UTF-8 if it's omitted.
+///
+/// `allowed` is the canonical names of the encodings which the bundle may use, or `None` for
+/// any encoding. UTF-8 is always allowed.
+fn encoding_arg(
+    args: &[JsValue],
+    index: usize,
+    allowed: &Option<Vec<String>>,
+) -> JsResult<&'static Encoding> {
+    let label = args.get_or_undefined(index);
+    if label.is_undefined() {
+        return Ok(encoding_rs::UTF_8);
+    }
+    let label = label
+        .as_string()
+        .ok_or_else(|| {
+            JsNativeError::typ().with_message(format!("Invalid encoding: {}", label.display()))
+        })?
+        .to_std_string_escaped();
+    let encoding = Encoding::for_label(label.trim().as_bytes()).ok_or_else(|| {
+        JsNativeError::range().with_message(format!("Unknown encoding: {}", label))
+    })?;
+    match allowed {
+        Some(allowed)
+            if encoding != encoding_rs::UTF_8
+                && !allowed.iter().any(|name| name == encoding.name()) =>
+        {
+            Err(JsNativeError::range()
+                .with_message(format!("Encoding is not allowed: {}", encoding.name()))
+                .into())
+        }
+        _ => Ok(encoding),
+    }
+}
+
+/// Encodes a string, to an array of bytes in the encoding of the optional second argument.
+pub fn encode(
+    _this: &JsValue,
+    args: &[JsValue],
+    allowed: &Option<Vec<String>>,
+    context: &mut Context,
+) -> JsResult<JsValue> {
     let string = args
         .get_or_undefined(0)
         .as_string()
-        .ok_or_else(|| JsNativeError::error().with_message("Invalid string"))?
+        .ok_or_else(|| JsNativeError::typ().with_message("Invalid string"))?
         .to_std_string()
-        .map_err(|_| JsNativeError::error().with_message("Invalid string"))?;
-    let bytes = string.as_bytes();
-    let array = JsArray::from_iter(
-        bytes.iter().map(|byte| JsValue::new(byte.to_owned())),
-        context,
-    );
+        .map_err(|_| JsNativeError::typ().with_message("String has a lone surrogate"))?;
+    let encoding = encoding_arg(args, 1, allowed)?;
+
+    // encoding_rs encodes to UTF-8 for UTF-16, as the Encoding Standard does.
+    let bytes = if encoding == encoding_rs::UTF_16LE {
+        string.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    } else if encoding == encoding_rs::UTF_16BE {
+        string.encode_utf16().flat_map(u16::to_be_bytes).collect()
+    } else {
+        let (bytes, _, had_errors) = encoding.encode(&string);
+        if had_errors {
+            return Err(JsNativeError::typ()
+                .with_message(format!(
+                    "String has characters which can't be encoded in {}",
+                    encoding.name()
+                ))
+                .into());
+        }
+        bytes.into_owned()
+    };
+    let array = JsArray::from_iter(bytes.into_iter().map(JsValue::new), context);
 
     Ok(JsValue::new(array))
 }
 
-pub fn decode(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+/// Decodes an array of bytes in the encoding of the optional second argument to a string.
+///
+/// Malformed sequences are replaced with U+FFFD, with a warning.
+pub fn decode(
+    _this: &JsValue,
+    args: &[JsValue],
+    allowed: &Option<Vec<String>>,
+    context: &mut Context,
+) -> JsResult<JsValue> {
     let array = args.get_or_undefined(0).to_owned();
-    let encoding = args
-        .get_or_undefined(1)
-        .as_string()
-        .ok_or_else(|| {
-            JsNativeError::error().with_message(format!("Invalid encoding: {:?}", args[1]))
-        })?
-        .to_std_string()
-        .expect("Failed to get string");
+    let encoding = encoding_arg(args, 1, allowed)?;
 
     let uint8array = context
         .global_object()
-        .get(js_string!("Uint8Array"), context)
-        .expect("Failed to get Uint8Array");
+        .get(js_string!("Uint8Array"), context)?;
 
     let array = uint8array
         .as_constructor()
-        .expect("Failed to get constructor")
+        .ok_or_else(|| JsNativeError::typ().with_message("Uint8Array is not a constructor"))?
         .construct(&[array], None, context)?;
 
     let array = JsTypedArray::from_object(array.to_owned())
-        .map_err(|_| JsNativeError::error().with_message("Invalid array"))?;
+        .map_err(|_| JsNativeError::typ().with_message("Invalid array"))?;
 
-    let length = array.length(context).expect("Failed to get length");
+    let length = array.length(context)?;
     let mut data = Vec::with_capacity(length);
     for i in 0..length {
-        let value = array.get(i, context).expect("Failed to get value");
-        data.push(value.as_number().expect("Failed to get number") as u8);
+        let value = array.get(i, context)?;
+        let byte = value.as_number().ok_or_else(|| {
+            JsNativeError::typ().with_message(format!("Invalid byte: {:?}", value))
+        })?;
+        data.push(byte as u8);
     }
 
-    info!("Decoding data with encoding: {}", encoding);
-    let encoding =
-        encoding_rs::Encoding::for_label(encoding.as_bytes()).expect("Failed to get encoding");
+    info!("Decoding data with encoding: {}", encoding.name());
+    let (decoded, used, had_errors) = encoding.decode(&data);
+    if had_errors {
+        warn!(
+            "Malformed {} data was replaced with U+FFFD while decoding",
+            used.name()
+        );
+    }
+
+    Ok(JsValue::String(JsString::from(decoded.as_ref())))
+}
 
-    let (decoded, _, _) = encoding.decode(&data);
+/// Joins the arguments of a `console` method with spaces, like browsers do.
+fn console_message(args: &[JsValue]) -> String {
+    args.iter()
+        .map(|arg| match arg.as_string() {
+            Some(string) => string.to_std_string_escaped(),
+            None => arg.display().to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Defines a `console` method which logs to `tracing` with the `utaformatix::js` target.
+macro_rules! console_method {
+    ($name:ident, $level:ident) => {
+        pub fn $name(_this: &JsValue, args: &[JsValue], _context: &mut Context) -> JsResult<JsValue> {
+            tracing::$level!(target: "utaformatix::js", "{}", console_message(args));
+            Ok(JsValue::undefined())
+        }
+    };
+}
 
-    let result_string = JsValue::String(JsString::from(decoded.to_string()));
+console_method!(console_debug, debug);
+console_method!(console_log, info);
+console_method!(console_warn, warn);
+console_method!(console_error, error);
 
-    Ok(result_string)
+/// Panics with the message of the first argument, so that tests can crash the runner.
+#[cfg(debug_assertions)]
+pub fn panic(_this: &JsValue, args: &[JsValue], _context: &mut Context) -> JsResult<JsValue> {
+    panic!("{}", console_message(&args[..args.len().min(1)]));
 }
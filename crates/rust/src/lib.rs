@@ -1,14 +1,61 @@
 //! utaformatix is a library that allows you to use UtaFormatix in Rust.
+//!
+//! Conversions of most formats run utaformatix-ts on a JS engine, which is behind the `engine`
+//! feature (enabled by default through `multi-thread`). Without default features, this crate
+//! only contains the model of UtaFormatix data, the formats implemented natively (e.g.
+//! [`UfData::from_bytes`]) and the editing methods of [`Project`], which compiles much faster.
+//!
+//! With the `compact-debug` feature, the `Debug` output of [`UfData`] shows only the first
+//! items of lists, e.g. for logs. See [`UfData::debug_full`] for the full output.
+// The model has helpers which only the engine uses, e.g. to apply the format options.
+#![cfg_attr(not(feature = "engine"), allow(dead_code))]
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impls;
+#[cfg(feature = "engine")]
 pub mod base;
+#[cfg(feature = "cache")]
+mod cache;
+#[cfg(feature = "multi-thread")]
+mod crash;
+#[cfg(feature = "ml")]
+pub mod dataset;
 mod error;
+#[cfg(feature = "multi-thread")]
+mod fnv;
+#[cfg(feature = "engine")]
 mod job_queue;
+#[cfg(feature = "engine")]
 mod js_impls;
+#[cfg(feature = "mmap")]
+mod mmap;
 mod model;
+mod native;
+#[cfg(feature = "playback")]
+mod playback;
+#[cfg(feature = "preview")]
+pub mod preview;
+#[cfg(feature = "engine")]
 mod process;
 mod project;
+#[cfg(feature = "midly")]
+mod smf;
 
+pub use bytes::Bytes;
+#[cfg(feature = "cache")]
+pub use cache::{CacheConfig, CacheStats, CacheStorage};
+#[cfg(feature = "multi-thread")]
+pub use crash::{CrashReport, CrashedRequest};
 pub use error::*;
 pub use model::{
-    ConvertJapaneseLyricsOptions, GenerateOptions, JapaneseLyricsType, ParseOptions, UfData,
+    BreathPolicy, CcsOptions, CompatibilityIssue, CompatibilityReport,
+    ConvertJapaneseLyricsOptions, DefaultLyric, DynamicsMapping, Format, FormatMetadata,
+    FormatOptions, GenerateOptions, JapaneseLyricsType, Key, KeyRangePolicy, KeySignature,
+    LongVowelMark, LyricMarkers, LyricNormalization, LyricsTypeAnalysis, LyricsTypeCandidate, Mode,
+    MusicXmlOptions, MusicXmlParseOptions, NTransition, Note, NoteIndex, Oto, ParameterPoint,
+    ParseOptions, PhonemeSegment, Pitch, Ppsf, PpsfInfo, PrefixMap, Priority, RomajiCase,
+    RomajiOptions, RomajiStyle, ScaleDegreeOffset, StandardMidOptions, StandardMidParseOptions,
+    SvpOptions, Tempo, Tick, TimeSignature, Track, TrackColor, TrackLyricsTypeAnalysis,
+    TrackParameters, TrackVoice, TsslnOptions, UfData, UfProject, UndetectedLyricsPolicy,
+    UnicodeForm, UnmatchedLyric, UstOptions, UstPitchMode, VsqxOptions,
 };
 pub use project::*;
@@ -0,0 +1,63 @@
+//! Parsing files by memory-mapping them, instead of reading them into memory.
+use crate::{base::UtaFormatix, error::Result, model::ParseOptions, UfData};
+use bytes::Bytes;
+use duplicate::duplicate_item;
+use std::path::Path;
+
+/// Memory-maps the file, so that it is paged in by the OS instead of copied to the heap.
+fn map_file(path: &Path) -> Result<Bytes> {
+    let file = std::fs::File::open(path).map_err(anyhow::Error::from)?;
+    // SAFETY: The map is read-only. Modifying the file while it is mapped is undefined behavior,
+    // which is documented on the callers.
+    let mmap = unsafe { memmap2::Mmap::map(&file) }.map_err(anyhow::Error::from)?;
+    Ok(Bytes::from_owner(mmap))
+}
+
+impl UtaFormatix {
+    #[duplicate_item(
+        fn_name                        original_fn_name     kind;
+        [parse_standard_mid_from_path] [parse_standard_mid] ["Standard MIDI"];
+        [parse_music_xml_from_path]    [parse_music_xml]    ["MusicXML"];
+        [parse_ccs_from_path]          [parse_ccs]          ["CeVIO's project"];
+        [parse_dv_from_path]           [parse_dv]           ["DeepVocal's project"];
+        [parse_ustx_from_path]         [parse_ustx]         ["OpenUtau's project"];
+        [parse_ppsf_from_path]         [parse_ppsf]         ["Piapro Studio's project"];
+        [parse_s5p_from_path]          [parse_s5p]          ["Old Synthesizer V's project"];
+        [parse_svp_from_path]          [parse_svp]          ["Synthesizer V's project"];
+        [parse_tssln_from_path]        [parse_tssln]        ["VoiSona's project"];
+        [parse_uf_data_from_path]      [parse_uf_data]      ["UtaFormatix data"];
+        [parse_vocaloid_mid_from_path] [parse_vocaloid_mid] ["VOCALOID 1's project"];
+        [parse_vsq_from_path]          [parse_vsq]          ["VOCALOID 2's project"];
+        [parse_vsqx_from_path]         [parse_vsqx]         ["VOCALOID 3/4's project"];
+        [parse_vpr_from_path]          [parse_vpr]          ["VOCALOID 5's project"];
+    )]
+    #[doc = "Parses a "]
+    #[doc = kind]
+    #[doc = " file, by memory-mapping it."]
+    ///
+    /// The file must not be modified while it is being parsed.
+    pub async fn fn_name(
+        &self,
+        path: impl AsRef<Path>,
+        options: impl Into<Option<ParseOptions>>,
+    ) -> Result<UfData> {
+        let data = map_file(path.as_ref())?;
+        self.original_fn_name(data, options).await
+    }
+
+    /// Parses UTAU's project files, by memory-mapping them.
+    /// Each file will be parsed as a track.
+    ///
+    /// The files must not be modified while they are being parsed.
+    pub async fn parse_ust_from_path(
+        &self,
+        paths: impl IntoIterator<Item = impl AsRef<Path>>,
+        options: impl Into<Option<ParseOptions>>,
+    ) -> Result<UfData> {
+        let data = paths
+            .into_iter()
+            .map(|path| map_file(path.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+        self.parse_ust(data, options).await
+    }
+}
@@ -1,8 +1,120 @@
+mod breath;
+mod canonical_json;
+mod compatibility;
+mod debug;
+mod detect;
+mod fallback_timing;
+mod format_options;
+mod japanese_lyrics;
+mod key;
+mod key_range;
+mod lyrics;
+mod lyrics_type;
+mod markers;
+mod music_xml;
+mod note;
+mod note_index;
+mod oto;
+mod phoneme_segments;
+mod pitch;
+mod ppsf;
+mod prefix_map;
+mod romaji;
+#[cfg(feature = "engine")]
+mod s5p;
+mod standard_mid;
+mod svp;
+#[cfg(feature = "engine")]
+mod svp_mixer;
+mod tick;
+mod track;
+mod track_color;
+mod track_parameters;
+mod track_voice;
+mod ust;
+mod ust_pitch;
+#[cfg(feature = "engine")]
+mod ustx;
+#[cfg(feature = "engine")]
+mod vpr;
+mod xml;
+
+pub use breath::BreathPolicy;
+pub use compatibility::{CompatibilityIssue, CompatibilityReport};
+pub use format_options::{
+    CcsOptions, FormatOptions, MusicXmlOptions, StandardMidOptions, SvpOptions, TsslnOptions,
+    UstOptions, VsqxOptions,
+};
+pub(crate) use japanese_lyrics::{collapse_vowel_extensions, expand_vowel_extensions};
+pub use japanese_lyrics::{LongVowelMark, NTransition};
+pub use key::{Key, KeySignature, Mode, ScaleDegreeOffset};
+pub use key_range::KeyRangePolicy;
+pub use lyrics::{LyricNormalization, UnicodeForm};
+#[cfg(feature = "engine")]
+pub(crate) use lyrics_type::candidates as lyrics_type_candidates;
+pub use lyrics_type::{LyricsTypeAnalysis, LyricsTypeCandidate, TrackLyricsTypeAnalysis};
+#[cfg(feature = "engine")]
+pub(crate) use markers::add_music_xml_directions;
+pub use markers::LyricMarkers;
+pub use music_xml::MusicXmlParseOptions;
+#[cfg(feature = "engine")]
+pub(crate) use music_xml::{
+    read_key_signatures as read_music_xml_key_signatures,
+    write_key_signatures as write_music_xml_key_signatures,
+};
+pub(crate) use note::{key_name, parse_key_name};
+pub use note_index::NoteIndex;
+pub use oto::{Oto, UnmatchedLyric};
+pub use phoneme_segments::PhonemeSegment;
+#[cfg(feature = "engine")]
+pub(crate) use phoneme_segments::{read_svp_phoneme_segments, write_svp_phoneme_segments};
+pub use ppsf::{Ppsf, PpsfInfo};
+pub use prefix_map::PrefixMap;
+pub use romaji::{RomajiCase, RomajiOptions, RomajiStyle};
+#[cfg(feature = "engine")]
+pub(crate) use s5p::copy_database_names as copy_s5p_database_names;
+pub use standard_mid::StandardMidParseOptions;
+#[cfg(feature = "engine")]
+pub(crate) use svp::{edit_svp, has_svp_extensions, svp_json};
+#[cfg(feature = "engine")]
+pub(crate) use svp_mixer::{
+    read_track_states as read_svp_track_states, write_track_states as write_svp_track_states,
+};
+pub use tick::Tick;
+pub use track_color::TrackColor;
+#[cfg(feature = "engine")]
+pub(crate) use track_color::{
+    read_svp_track_colors, read_track_colors, write_svp_track_colors, write_track_colors,
+};
+#[cfg(feature = "engine")]
+pub(crate) use track_parameters::{read_track_parameters, write_track_parameters};
+pub use track_parameters::{DynamicsMapping, ParameterPoint, TrackParameters};
+pub use track_voice::TrackVoice;
+#[cfg(feature = "engine")]
+pub(crate) use track_voice::{read_track_voices, write_track_voices};
+#[cfg(feature = "engine")]
+pub(crate) use ust_pitch::write_mode1_pitch;
+pub use ust_pitch::UstPitchMode;
+#[cfg(feature = "engine")]
+pub(crate) use vpr::{
+    read_track_states as read_vpr_track_states, write_track_states as write_vpr_track_states,
+};
+
+use crate::error::Result;
 use serde::{Deserialize, Serialize};
-use strum::{Display, EnumString};
+use std::{borrow::Cow, ops::RangeInclusive};
+use strum::{Display, EnumIter, EnumString, IntoEnumIterator};
+
+/// Ticks per beat (quarter note) used by UtaFormatix data.
+pub(crate) const TICKS_PER_BEAT: i64 = 480;
 
 /// Represents the format of the data.
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+///
+/// This can be converted from and to the name of the variant, e.g. `"StandardMid"`.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, EnumIter, EnumString, Display,
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum Format {
     /// Standard MIDI file. (`.mid`)
     StandardMid,
@@ -57,6 +169,103 @@ impl Format {
         }
     }
 
+    /// Returns all formats.
+    pub fn all() -> impl Iterator<Item = Format> {
+        Self::iter()
+    }
+
+    /// Returns the information of the format, e.g. for format pickers of GUIs.
+    pub fn metadata(&self) -> FormatMetadata {
+        let (name, editor) = match self {
+            Self::StandardMid => ("Standard MIDI", None),
+            Self::MusicXml => ("MusicXML", None),
+            Self::Ccs => ("CeVIO", Some("CeVIO Creative Studio")),
+            Self::Dv => ("DeepVocal", Some("DeepVocal")),
+            Self::Ustx => ("OpenUtau", Some("OpenUtau")),
+            Self::Ppsf => ("Piapro Studio", Some("Piapro Studio")),
+            Self::S5p => ("Synthesizer V (old)", Some("Synthesizer V")),
+            Self::Svp => ("Synthesizer V", Some("Synthesizer V Studio")),
+            Self::Tssln => ("VoiSona", Some("VoiSona")),
+            Self::UfData => ("UtaFormatix data", Some("UtaFormatix")),
+            Self::Ust => ("UTAU", Some("UTAU")),
+            Self::VocaloidMid => ("VOCALOID 1", Some("VOCALOID 1")),
+            Self::Vsq => ("VOCALOID 2", Some("VOCALOID 2")),
+            Self::Vsqx => ("VOCALOID 3/4", Some("VOCALOID 3/4")),
+            Self::Vpr => ("VOCALOID 5", Some("VOCALOID 5")),
+        };
+        let extensions: &[&str] = match self {
+            Self::StandardMid => &["mid", "midi"],
+            Self::MusicXml => &["musicxml", "xml"],
+            Self::Ccs => &["ccs"],
+            Self::Dv => &["dv"],
+            Self::Ustx => &["ustx"],
+            Self::Ppsf => &["ppsf"],
+            Self::S5p => &["s5p"],
+            Self::Svp => &["svp"],
+            Self::Tssln => &["tssln"],
+            Self::UfData => &["ufdata"],
+            Self::Ust => &["ust"],
+            Self::VocaloidMid => &["mid"],
+            Self::Vsq => &["vsq"],
+            Self::Vsqx => &["vsqx"],
+            Self::Vpr => &["vpr"],
+        };
+
+        FormatMetadata {
+            format: *self,
+            name,
+            editor,
+            extensions,
+            pitch: !matches!(self, Self::MusicXml | Self::Ppsf),
+            phonemes: matches!(
+                self,
+                Self::Svp | Self::UfData | Self::Vsq | Self::Vsqx | Self::Vpr
+            ),
+            phoneme_timing: matches!(self, Self::Svp | Self::UfData),
+            multi_track: !matches!(self, Self::MusicXml | Self::Ust),
+            can_generate: *self != Self::Ppsf,
+        }
+    }
+
+    /// Returns the lyric which continues the vowel of the previous note (melisma) in the
+    /// format.
+    pub fn continuation_lyric(&self) -> &'static str {
+        match self {
+            Self::Ust | Self::Ustx => "+",
+            Self::Ccs | Self::Tssln => "ー",
+            _ => "-",
+        }
+    }
+
+    /// Returns the lyric of a breath note in the format, or `None` if the format doesn't have
+    /// one.
+    pub fn breath_lyric(&self) -> Option<&'static str> {
+        match self {
+            Self::Ust | Self::Ustx => Some("息"),
+            Self::Svp | Self::S5p | Self::UfData => Some("br"),
+            _ => None,
+        }
+    }
+
+    /// Returns the range of note keys which the editor of the format supports.
+    pub fn key_range(&self) -> RangeInclusive<i32> {
+        match self {
+            // C1 to B7, the range of the piano roll.
+            Self::Ust | Self::Dv | Self::S5p | Self::Svp => 24..=107,
+            _ => 0..=127,
+        }
+    }
+
+    /// Returns whether the format can store the text, i.e. whether it's in Shift_JIS for UST and
+    /// VOCALOID 1/2.
+    pub(crate) fn can_encode(&self, text: &str) -> bool {
+        if !matches!(self, Self::Ust | Self::VocaloidMid | Self::Vsq) {
+            return true;
+        }
+        let (_, _, had_errors) = encoding_rs::SHIFT_JIS.encode(text);
+        !had_errors
+    }
+
     /// Converts the format to a string for suffix.
     pub(crate) fn suffix(&self) -> &'static str {
         match self {
@@ -79,34 +288,236 @@ impl Format {
     }
 }
 
+/// Represents the information of a format, returned by [`Format::metadata`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatMetadata {
+    /// The format.
+    pub format: Format,
+    /// Display name of the format.
+    pub name: &'static str,
+    /// Name of the editor which uses the format, if any.
+    pub editor: Option<&'static str>,
+    /// Typical file extensions, the preferred one first.
+    pub extensions: &'static [&'static str],
+    /// Whether pitch data is supported.
+    pub pitch: bool,
+    /// Whether phonemes of notes are supported.
+    pub phonemes: bool,
+    /// Whether the timing of phonemes of notes ([`Note::phoneme_segments`]) is supported.
+    pub phoneme_timing: bool,
+    /// Whether a file can contain multiple tracks. Otherwise, each track is a separate file.
+    pub multi_track: bool,
+    /// Whether the format can be generated, as well as parsed.
+    pub can_generate: bool,
+}
+
+/// Represents the priority of a request to the JS thread.
+///
+/// Requests waiting in the queue are processed in the order of priority, and then in the order
+/// they were sent. A running request is never interrupted.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum Priority {
+    /// For background work, e.g. batch conversions.
+    Low,
+    /// The default priority.
+    #[default]
+    Normal,
+    /// For interactive work, e.g. previews requested by a user.
+    High,
+}
+
 /// Represents the options for parsing data.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct ParseOptions {
     /// Whether to parse the pitch data.
     pub pitch: bool,
-    /// The default lyric to use when the note's lyric is empty.
-    pub default_lyric: String,
+    /// What to do with notes whose lyric is empty.
+    pub default_lyric: DefaultLyric,
+    /// How to clean up lyrics. This is applied before [`ParseOptions::default_lyric`].
+    pub lyric_normalization: LyricNormalization,
+    /// Prefix map to strip prefixes and suffixes of UTAU lyrics with. This is applied before
+    /// [`ParseOptions::lyric_normalization`].
+    #[serde(skip)]
+    pub prefix_map: Option<PrefixMap>,
+    /// How to handle ties and melismas when parsing MusicXML files. This is applied before
+    /// cleaning up lyrics.
+    #[serde(default)]
+    pub music_xml: MusicXmlParseOptions,
+    /// Which tracks and channels to parse from Standard MIDI files, and how to name them.
+    /// This is only applied by the native parser.
+    #[serde(default)]
+    pub standard_mid: StandardMidParseOptions,
+    /// Tempo in BPM at the start of files which don't specify one, instead of 120, e.g. to
+    /// match the session which the file is imported into.
+    ///
+    /// This applies to UST files without any `Tempo` entry, and to MIDI-based formats without
+    /// a tempo event at the start.
+    #[serde(default)]
+    pub fallback_tempo: Option<i32>,
+    /// Time signature at the start of files which don't specify one, instead of 4/4. Its
+    /// measure position is ignored.
+    ///
+    /// This applies to UST files, which have no time signatures, and to MIDI-based formats
+    /// without a time signature event at the start.
+    #[serde(default)]
+    pub fallback_time_signature: Option<TimeSignature>,
+    /// Representation of the pitch bends to read from UST files. The pitch curves are read from
+    /// the Mode1 pitch bends instead of the Mode2 ones if this is [`UstPitchMode::Mode1`], unless
+    /// the file has no Mode1 pitch bends.
+    #[serde(default)]
+    pub ust_pitch_mode: UstPitchMode,
+    /// Whether to read the voices of the tracks of CeVIO's projects (`CastId` and `Language`)
+    /// into [`Track::voice`], so that they are written back when generating CeVIO's projects.
+    ///
+    /// Voices of VoiSona's projects aren't read yet, since utaformatix-ts doesn't expose them.
+    #[serde(default)]
+    pub ccs_voices: bool,
+    /// Whether to read the curves of the parameters of tracks into [`Track::parameters`], so
+    /// that they are written back when generating the same format. This applies to OpenUtau's
+    /// projects (`curves` of the voice parts) and Synthesizer V's projects (`parameters` of the
+    /// main groups).
+    #[serde(default)]
+    pub track_parameters: bool,
+    /// Priority of the request, if it uses the JS thread.
+    #[serde(skip)]
+    pub priority: Priority,
 }
 impl Default for ParseOptions {
     fn default() -> Self {
         Self {
             pitch: true,
-            default_lyric: "あ".to_string(),
+            default_lyric: DefaultLyric::default(),
+            lyric_normalization: LyricNormalization::default(),
+            prefix_map: None,
+            music_xml: MusicXmlParseOptions::default(),
+            standard_mid: StandardMidParseOptions::default(),
+            fallback_tempo: None,
+            fallback_time_signature: None,
+            ust_pitch_mode: UstPitchMode::default(),
+            ccs_voices: false,
+            track_parameters: false,
+            priority: Priority::default(),
+        }
+    }
+}
+
+/// Represents what to do with notes whose lyric is empty, used by [`ParseOptions`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", tag = "policy", content = "lyric")]
+pub enum DefaultLyric {
+    /// Keeps the lyric empty.
+    KeepEmpty,
+    /// Fills the lyric with the string.
+    Fill(String),
+    /// Copies the lyric of the previous note. The lyric of the first note is kept empty.
+    CopyPrevious,
+    /// Removes the note.
+    DropNote,
+}
+impl Default for DefaultLyric {
+    fn default() -> Self {
+        Self::Fill("あ".to_string())
+    }
+}
+impl DefaultLyric {
+    /// Applies the policy to the notes, which must be sorted by position.
+    pub fn apply(&self, notes: &mut Vec<Note>) {
+        match self {
+            Self::KeepEmpty => {}
+            Self::Fill(lyric) => {
+                for note in notes.iter_mut().filter(|note| note.lyric.is_empty()) {
+                    note.lyric.clone_from(lyric);
+                }
+            }
+            Self::CopyPrevious => {
+                for i in 1..notes.len() {
+                    if notes[i].lyric.is_empty() {
+                        notes[i].lyric = notes[i - 1].lyric.clone();
+                    }
+                }
+            }
+            Self::DropNote => notes.retain(|note| !note.lyric.is_empty()),
         }
     }
 }
 
 /// Represents the options for generating data.
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct GenerateOptions {
     /// Whether to generate the pitch data.
     pub pitch: bool,
+    /// Prefix map to add prefixes and suffixes to lyrics with, when generating UST files.
+    #[serde(skip)]
+    pub prefix_map: Option<PrefixMap>,
+    /// Options which only apply to a specific format.
+    pub format_options: Option<FormatOptions>,
+    /// What to do with breath notes.
+    #[serde(default)]
+    pub breath: BreathPolicy,
+    /// What to do with notes whose key is out of the range of the target format.
+    #[serde(default)]
+    pub key_range: KeyRangePolicy,
+    /// Syntax of markers of performance hints in lyrics, e.g. `[br]`, which are translated to
+    /// the target format. Lyrics are generated as they are if this is `None`.
+    #[serde(default)]
+    pub markers: Option<LyricMarkers>,
+    /// Mapping of the loudness and tension read from Synthesizer V's projects to the dynamics of
+    /// the target format. The curves are only written to svp files if this is `None`.
+    #[serde(default)]
+    pub dynamics: Option<DynamicsMapping>,
+    /// Priority of the request, if it uses the JS thread.
+    #[serde(skip)]
+    pub priority: Priority,
+}
+
+impl GenerateOptions {
+    /// Checks that [`GenerateOptions::prepare`] doesn't fail for the data, without changing it,
+    /// so that errors have the indices of the tracks and the notes in `data`.
+    pub(crate) fn validate(&self, format: Format, data: &UfData) -> Result<()> {
+        self.key_range.check(format, data)
+    }
+
+    /// Applies the options which change the data before generating it as `format`, cloning it
+    /// only if it changes.
+    pub(crate) fn prepare(&self, format: Format, data: &mut Cow<UfData>) -> Result<()> {
+        if let Some(markers) = &self.markers {
+            let has_markers = data.project().tracks.iter().any(|track| {
+                track
+                    .notes
+                    .iter()
+                    .any(|note| note.lyric.contains(&markers.open))
+            });
+            if has_markers {
+                for track in &mut data.to_mut().project_mut().tracks {
+                    markers.apply(format, &mut track.notes);
+                }
+            }
+        }
+        if self.breath != BreathPolicy::Keep {
+            for track in &mut data.to_mut().project_mut().tracks {
+                self.breath.apply(format, &mut track.notes);
+            }
+        }
+        if let Some(dynamics) = &self.dynamics {
+            dynamics.apply(format, data);
+        }
+        self.key_range.apply(format, data)
+    }
 }
 
 /// Represents the type of lyrics.
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, EnumString, Display)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, EnumString, Display)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub enum JapaneseLyricsType {
     /// Hiragana, CV (あ, い, う)
@@ -119,18 +530,47 @@ pub enum JapaneseLyricsType {
     RomajiVcv,
 }
 
+/// Represents what [`crate::Project::convert_japanese_lyrics`] does if the type of the lyrics
+/// can't be detected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum UndetectedLyricsPolicy {
+    /// Returns the project as it is, logging a warning.
+    #[default]
+    PassThrough,
+    /// Fails with [`ErrorKind::LyricsTypeUndetected`](crate::ErrorKind::LyricsTypeUndetected).
+    Error,
+    /// Converts the lyrics as the type.
+    Assume(JapaneseLyricsType),
+}
+
 /// Represents the options for converting Japanese lyrics.
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", default)]
 pub struct ConvertJapaneseLyricsOptions {
     /// Whether to convert vowel connections. (e.g. "あー" -> "ああ")
     pub convert_vowel_connections: bool,
+    /// What to do with notes whose lyric is a long vowel mark ("ー").
+    pub long_vowel_mark: LongVowelMark,
+    /// Whether to merge notes of a single small kana into the previous note
+    /// (e.g. "き", "ゃ" -> "きゃ"). Only applied to kana lyrics.
+    pub merge_small_kana: bool,
+    /// How VCV lyrics after "ん" start. Only applied to VCV lyrics.
+    pub n_transition: NTransition,
+    /// What to do if the source type isn't given and can't be detected.
+    pub on_undetected: UndetectedLyricsPolicy,
+    /// Priority of the request.
+    #[serde(skip)]
+    pub priority: Priority,
 }
 
 /// Represents the root document object of UtaFormatix data.
 ///
 /// See: <https://github.com/sdercolin/utaformatix-data?tab=readme-ov-file#root-document-object>
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct UfData {
     /// Format version of the data.
@@ -139,29 +579,193 @@ pub struct UfData {
     /// Project object.
     project: UfProject,
 }
+impl UfData {
+    /// The format version of UtaFormatix data that this crate reads and writes.
+    pub const FORMAT_VERSION: i32 = 1;
+
+    /// Creates a new UtaFormatix data from a project object.
+    pub fn new(project: UfProject) -> Self {
+        Self {
+            format_version: Self::FORMAT_VERSION,
+            project,
+        }
+    }
+
+    /// Returns the format version of the data.
+    pub fn format_version(&self) -> i32 {
+        self.format_version
+    }
+
+    /// Returns the project object.
+    pub fn project(&self) -> &UfProject {
+        &self.project
+    }
+
+    /// Returns the mutable project object.
+    pub fn project_mut(&mut self) -> &mut UfProject {
+        &mut self.project
+    }
+
+    /// Returns a copy of the data for each track, with only that track.
+    pub(crate) fn track_projects(&self) -> impl Iterator<Item = UfData> + '_ {
+        let project = &self.project;
+        project.tracks.iter().map(|track| {
+            UfData::new(UfProject {
+                name: project.name.clone(),
+                tracks: vec![track.clone()],
+                time_signatures: project.time_signatures.clone(),
+                tempos: project.tempos.clone(),
+                measure_prefix: project.measure_prefix,
+                key_signatures: project.key_signatures.clone(),
+            })
+        })
+    }
+
+    /// Parses a UtaFormatix data file synchronously.
+    ///
+    /// This is the same as [`crate::base::UtaFormatix::parse_uf_data`], but doesn't need an
+    /// instance of `UtaFormatix`.
+    pub fn from_bytes(data: &[u8], options: ParseOptions) -> crate::Result<Self> {
+        crate::native::uf_data::parse(data, options).map_err(|e| e.with_format(Format::UfData))
+    }
+
+    /// Generates a UtaFormatix data file synchronously.
+    ///
+    /// This is the same as [`crate::base::UtaFormatix::generate_uf_data`], but doesn't need an
+    /// instance of `UtaFormatix`.
+    pub fn to_bytes(&self, options: GenerateOptions) -> crate::Result<Vec<u8>> {
+        crate::native::uf_data::generate(self, options).map_err(|e| e.with_format(Format::UfData))
+    }
+
+    /// Serializes the data to MessagePack.
+    ///
+    /// This is more compact than JSON, especially for pitch data.
+    #[cfg(feature = "msgpack")]
+    pub fn to_msgpack(&self) -> crate::Result<Vec<u8>> {
+        Ok(rmp_serde::to_vec_named(self).map_err(anyhow::Error::from)?)
+    }
+
+    /// Deserializes the data from MessagePack, which was serialized by [`UfData::to_msgpack`].
+    #[cfg(feature = "msgpack")]
+    pub fn from_msgpack(data: &[u8]) -> crate::Result<Self> {
+        let data: Self = rmp_serde::from_slice(data)
+            .map_err(|_| crate::Error::from(crate::IllegalFile::IllegalUfDataFile))?;
+        data.check_format_version()
+    }
+
+    /// Serializes the data to CBOR.
+    ///
+    /// This is more compact than JSON, especially for pitch data.
+    #[cfg(feature = "cbor")]
+    pub fn to_cbor(&self) -> crate::Result<Vec<u8>> {
+        let mut buffer = vec![];
+        ciborium::into_writer(self, &mut buffer).map_err(anyhow::Error::from)?;
+        Ok(buffer)
+    }
+
+    /// Deserializes the data from CBOR, which was serialized by [`UfData::to_cbor`].
+    #[cfg(feature = "cbor")]
+    pub fn from_cbor(data: &[u8]) -> crate::Result<Self> {
+        let data: Self = ciborium::from_reader(data)
+            .map_err(|_| crate::Error::from(crate::IllegalFile::IllegalUfDataFile))?;
+        data.check_format_version()
+    }
+
+    #[cfg(any(feature = "msgpack", feature = "cbor"))]
+    fn check_format_version(self) -> crate::Result<Self> {
+        if self.format_version != Self::FORMAT_VERSION {
+            return Err(crate::ErrorKind::UnsupportedFileFormat.into());
+        }
+        Ok(self)
+    }
+}
 
 /// Represents the project object of UtaFormatix data v1.
 ///
 /// See: <https://github.com/sdercolin/utaformatix-data?tab=readme-ov-file#project-object>
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct UfProject {
     /// Project name.
     pub name: String,
     /// Track list.
     pub tracks: Vec<Track>,
-    /// Time signatures.
+    /// Time signatures, sorted by measure position.
     pub time_signatures: Vec<TimeSignature>,
     /// Tempo changes.
     pub tempos: Vec<Tempo>,
     /// Count of measure prefixes (measures that cannot contain notes, restricted by some editors).
     pub measure_prefix: i32,
+    /// Key signatures, sorted by measure position. This is an extension of UtaFormatix data,
+    /// which is omitted when it's empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub key_signatures: Vec<KeySignature>,
+}
+impl UfProject {
+    /// Converts a measure position to a tick position, using the time signatures. Measures
+    /// before the start are at the start, and positions too far to be a tick saturate.
+    pub fn measure_to_tick(&self, measure: i32) -> Tick {
+        Tick::ZERO.saturating_add(measure_to_tick(&self.time_signatures, measure))
+    }
+
+    /// Converts a tick position to a measure position, using the time signatures.
+    ///
+    /// Returns the measure containing the tick, saturating at the range of `i32`.
+    pub fn tick_to_measure(&self, tick: Tick) -> i32 {
+        tick_to_measure(&self.time_signatures, tick.get())
+    }
+}
+
+/// Converts a measure position to a tick position, saturating at the range of `i64`.
+///
+/// `time_signatures` must be sorted by measure position.
+pub(crate) fn measure_to_tick(time_signatures: &[TimeSignature], measure: i32) -> i64 {
+    let mut tick: i64 = 0;
+    let mut current_measure = 0;
+    let mut ticks_per_measure = TimeSignature::default().ticks_per_measure();
+    for time_signature in time_signatures {
+        if time_signature.measure_position >= measure {
+            break;
+        }
+        let measures = i64::from(time_signature.measure_position) - i64::from(current_measure);
+        tick = tick.saturating_add(measures.saturating_mul(ticks_per_measure));
+        current_measure = time_signature.measure_position;
+        ticks_per_measure = time_signature.ticks_per_measure();
+    }
+    let measures = i64::from(measure) - i64::from(current_measure);
+    tick.saturating_add(measures.saturating_mul(ticks_per_measure))
+}
+
+/// Converts a tick position to the measure containing it, saturating at the range of `i32`.
+///
+/// `time_signatures` must be sorted by measure position.
+pub(crate) fn tick_to_measure(time_signatures: &[TimeSignature], tick: i64) -> i32 {
+    let mut current_tick: i64 = 0;
+    let mut current_measure = 0;
+    let mut ticks_per_measure = TimeSignature::default().ticks_per_measure();
+    for time_signature in time_signatures {
+        let measures = i64::from(time_signature.measure_position) - i64::from(current_measure);
+        let next_tick = current_tick.saturating_add(measures.saturating_mul(ticks_per_measure));
+        if next_tick > tick {
+            break;
+        }
+        current_tick = next_tick;
+        current_measure = time_signature.measure_position;
+        ticks_per_measure = time_signature.ticks_per_measure();
+    }
+    let measures = tick
+        .saturating_sub(current_tick)
+        .div_euclid(ticks_per_measure);
+    let measure = i64::from(current_measure).saturating_add(measures);
+    measure.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32
 }
 
 /// Represents a track object of UtaFormatix data v1.
 ///
 /// See: <https://github.com/sdercolin/utaformatix-data?tab=readme-ov-file#track-object>
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Track {
     /// Track name.
@@ -170,30 +774,110 @@ pub struct Track {
     pub notes: Vec<Note>,
     /// Pitch data.
     pub pitch: Option<Pitch>,
+    /// Whether the track is muted, if the format has it.
+    ///
+    /// This is an extension to UtaFormatix data, read from and written to Synthesizer V's projects
+    /// (`mixer.mute`) and VOCALOID 5's projects (`isMuted`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub muted: Option<bool>,
+    /// Whether the track is soloed, if the format has it.
+    ///
+    /// This is an extension to UtaFormatix data, read from and written to Synthesizer V's projects
+    /// (`mixer.solo`) and VOCALOID 5's projects (`isSoloMode`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub solo: Option<bool>,
+    /// Position of the track in the track list shown by the editor, if the format has it. This
+    /// can differ from the position in [`UfProject::tracks`].
+    ///
+    /// This is an extension to UtaFormatix data, read from and written to Synthesizer V's projects
+    /// (`dispOrder`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<i32>,
+    /// Color of the track, if the format has it.
+    ///
+    /// This is an extension to UtaFormatix data, read from and written to Synthesizer V's and
+    /// OpenUtau's projects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<TrackColor>,
+    /// Voice which sings the track, if the format has it and
+    /// [`ParseOptions::ccs_voices`] is enabled.
+    ///
+    /// This is an extension to UtaFormatix data, read from and written to CeVIO's projects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voice: Option<TrackVoice>,
+    /// Curves of the parameters of the track, if the format has them and
+    /// [`ParseOptions::track_parameters`] is enabled.
+    ///
+    /// This is an extension to UtaFormatix data, read from and written to OpenUtau's and
+    /// Synthesizer V's projects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<TrackParameters>,
 }
 
 /// Represents a note object of UtaFormatix data v1.
 ///
 /// See: <https://github.com/sdercolin/utaformatix-data?tab=readme-ov-file#note-object>
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Note {
     /// Semitone value of the note's key (Center C = 60).
-    pub key: i32,
+    pub key: Key,
     /// Tick position of the note's start.
-    pub tick_on: i64,
+    pub tick_on: Tick,
     /// Tick position of the note's end.
-    pub tick_off: i64,
+    pub tick_off: Tick,
     /// Lyric.
     pub lyric: String,
     /// Phoneme (if available).
     pub phoneme: Option<String>,
+    /// Phonemes with their timing (if available). This is an extension of UtaFormatix data,
+    /// which is omitted when it's `None`.
+    ///
+    /// This is only read from and written to Synthesizer V's projects (`phonemes` and
+    /// `attributes.dur` of notes). CeVIO's projects keep the timing of phonemes as absolute
+    /// times in a separate curve (`Timing`), which can't be turned into the scales of
+    /// [`PhonemeSegment::duration_scale`] without the default durations of the voice, so the
+    /// timing isn't carried over to or from them. [`UfData::check_conversion`] reports this as
+    /// [`CompatibilityIssue::PhonemeTimingDropped`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phoneme_segments: Option<Vec<PhonemeSegment>>,
+}
+
+/// Notes are ordered by their start and then by their key. The other fields only break ties, so
+/// that the order is consistent with [`PartialEq`].
+impl Ord for Note {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (
+            self.tick_on,
+            self.key,
+            self.tick_off,
+            &self.lyric,
+            &self.phoneme,
+            &self.phoneme_segments,
+        )
+            .cmp(&(
+                other.tick_on,
+                other.key,
+                other.tick_off,
+                &other.lyric,
+                &other.phoneme,
+                &other.phoneme_segments,
+            ))
+    }
+}
+
+impl PartialOrd for Note {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 /// Represents a pitch object of UtaFormatix data v1.
 ///
 /// See: <https://github.com/sdercolin/utaformatix-data?tab=readme-ov-file#pitch-object>
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Pitch {
     /// Tick positions of the data points.
@@ -208,7 +892,8 @@ pub struct Pitch {
 /// Represents a time signature object of UtaFormatix data v1.
 ///
 /// See: <https://github.com/sdercolin/utaformatix-data?tab=readme-ov-file#time-signature-object>
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct TimeSignature {
     /// Measure (bar) position of the time signature.
@@ -218,15 +903,58 @@ pub struct TimeSignature {
     /// Note value per beat.
     pub denominator: i32,
 }
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self {
+            measure_position: 0,
+            numerator: 4,
+            denominator: 4,
+        }
+    }
+}
+impl TimeSignature {
+    /// Returns the length of a measure in ticks.
+    ///
+    /// This is at least 1 tick, also for time signatures which aren't valid (see
+    /// [`TimeSignature::is_valid`]).
+    pub fn ticks_per_measure(&self) -> i64 {
+        (TICKS_PER_BEAT * 4 * i64::from(self.numerator))
+            .checked_div(i64::from(self.denominator))
+            .unwrap_or(0)
+            .max(1)
+    }
+
+    /// Returns whether the numerator is positive and the denominator is a power of two, with
+    /// which a measure is at least 1 tick long.
+    pub fn is_valid(&self) -> bool {
+        self.numerator > 0
+            && self.denominator > 0
+            && (self.denominator as u32).is_power_of_two()
+            && TICKS_PER_BEAT * 4 * i64::from(self.numerator) >= i64::from(self.denominator)
+    }
+}
 
 /// Represents a tempo object of UtaFormatix data v1.
 ///
 /// See: <https://github.com/sdercolin/utaformatix-data?tab=readme-ov-file#tempo-object>
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Tempo {
     /// Tick position of the tempo change.
-    pub tick_position: i64,
+    pub tick_position: Tick,
     /// Tempo in beats-per-minute
     pub bpm: i32,
 }
+
+impl Tempo {
+    /// Returns the length of a beat in microseconds as written to MIDI files, or `None` if it
+    /// doesn't fit in their 24 bits, i.e. if the tempo isn't positive or is slower than 4 BPM.
+    pub(crate) fn microseconds_per_beat(&self) -> Option<u32> {
+        if self.bpm <= 0 {
+            return None;
+        }
+        let microseconds = 60_000_000 / self.bpm.unsigned_abs();
+        (microseconds < 1 << 24).then_some(microseconds)
+    }
+}
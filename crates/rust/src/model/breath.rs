@@ -0,0 +1,53 @@
+//! Handling of breath notes on generating.
+use super::{Format, Note};
+use serde::{Deserialize, Serialize};
+
+/// Lyrics which are treated as breath markers, compared case-insensitively.
+const BREATH_LYRICS: [&str; 3] = ["br", "息", "breath"];
+
+/// Represents what to do with breath notes, i.e. notes whose lyric is a breath marker
+/// ("br", "息" or "breath"), used by
+/// [`GenerateOptions::breath`](super::GenerateOptions::breath).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum BreathPolicy {
+    /// Keeps breath notes as they are.
+    #[default]
+    Keep,
+    /// Removes breath notes.
+    Strip,
+    /// Replaces the lyric of breath notes with the breath marker of the target format (see
+    /// [`Format::breath_lyric`]), and removes them if the format has no breath marker.
+    Convert,
+}
+
+/// Returns whether the lyric is a breath marker.
+fn is_breath_lyric(lyric: &str) -> bool {
+    let lyric = lyric.trim();
+    BREATH_LYRICS
+        .iter()
+        .any(|breath| lyric.eq_ignore_ascii_case(breath))
+}
+
+impl BreathPolicy {
+    /// Applies the policy to notes which are generated as `format`.
+    pub(crate) fn apply(&self, format: Format, notes: &mut Vec<Note>) {
+        let replacement = match self {
+            Self::Keep => return,
+            Self::Strip => None,
+            Self::Convert => format.breath_lyric(),
+        };
+        match replacement {
+            Some(lyric) => {
+                for note in notes.iter_mut() {
+                    if is_breath_lyric(&note.lyric) && note.lyric != lyric {
+                        note.lyric = lyric.to_string();
+                        note.phoneme = None;
+                    }
+                }
+            }
+            None => notes.retain(|note| !is_breath_lyric(&note.lyric)),
+        }
+    }
+}
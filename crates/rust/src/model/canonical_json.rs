@@ -0,0 +1,53 @@
+//! Canonical JSON of UtaFormatix data, for golden-file tests.
+use super::UfData;
+use crate::error::Result;
+use serde_json::{Map, Number, Value};
+
+/// The number of decimal places which floats are rounded to.
+const FLOAT_DECIMALS: i32 = 6;
+
+impl UfData {
+    /// Serializes the data to JSON which is stable across platforms and versions of the JS
+    /// engine, e.g. for golden-file tests of conversions:
+    ///
+    /// - Keys of objects are sorted.
+    /// - Floats are rounded to 6 decimal places, and `-0.0` is written as `0.0`.
+    /// - Fields which are `null` are omitted, e.g. `phoneme`. `null` in arrays is kept.
+    ///
+    /// The JSON is indented by 2 spaces, ends with a newline and can be parsed by
+    /// [`UfData::from_bytes`].
+    pub fn to_canonical_json(&self) -> Result<String> {
+        let value = serde_json::to_value(self).map_err(anyhow::Error::from)?;
+        let mut json =
+            serde_json::to_string_pretty(&canonicalize(value)).map_err(anyhow::Error::from)?;
+        json.push('\n');
+        Ok(json)
+    }
+}
+
+fn canonicalize(value: Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let mut entries = object
+                .into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .collect::<Vec<_>>();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            // Maps keep the insertion order if `preserve_order` of serde_json is enabled.
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, canonicalize(value)))
+                    .collect::<Map<_, _>>(),
+            )
+        }
+        Value::Array(array) => Value::Array(array.into_iter().map(canonicalize).collect()),
+        Value::Number(number) if number.is_f64() => {
+            let scale = 10f64.powi(FLOAT_DECIMALS);
+            let float = (number.as_f64().unwrap_or_default() * scale).round() / scale;
+            // Adding 0.0 turns -0.0 into 0.0.
+            Number::from_f64(float + 0.0).map_or(Value::Null, Value::Number)
+        }
+        value => value,
+    }
+}
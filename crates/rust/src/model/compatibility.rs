@@ -0,0 +1,149 @@
+//! Checks of what a format can't represent, without generating it.
+use super::{Format, UfData};
+use serde::{Deserialize, Serialize};
+
+/// Represents a feature of a project which is lost or changed by generating it as a format.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum CompatibilityIssue {
+    /// The format can only be parsed, so generating it fails with
+    /// [`ErrorKind::UnsupportedFileFormat`](crate::ErrorKind::UnsupportedFileFormat).
+    NotGeneratable,
+    /// The format has a track per file, so the tracks are generated as separate files.
+    MultipleFiles {
+        /// The number of tracks.
+        tracks: usize,
+    },
+    /// The pitch data of a track is dropped.
+    PitchDropped {
+        /// The index of the track.
+        track: usize,
+    },
+    /// The phonemes of notes of a track are dropped.
+    PhonemesDropped {
+        /// The index of the track.
+        track: usize,
+        /// The number of notes with phonemes.
+        notes: usize,
+    },
+    /// The timing of phonemes of notes of a track ([`Note::phoneme_segments`](super::Note::phoneme_segments))
+    /// is dropped.
+    PhonemeTimingDropped {
+        /// The index of the track.
+        track: usize,
+        /// The number of notes with the timing of phonemes.
+        notes: usize,
+    },
+    /// Keys of notes of a track are out of the range of the format (see [`Format::key_range`]),
+    /// so they are handled by [`KeyRangePolicy`](super::KeyRangePolicy).
+    KeysOutOfRange {
+        /// The index of the track.
+        track: usize,
+        /// The indices of the notes in the track.
+        notes: Vec<usize>,
+    },
+    /// The name or lyrics of a track contain characters which the format can't store, e.g.
+    /// characters outside Shift_JIS for UST and VOCALOID 1/2.
+    UnencodableText {
+        /// The index of the track.
+        track: usize,
+    },
+}
+
+/// Represents the features of a project which a format can't represent, returned by
+/// [`UfData::check_conversion`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CompatibilityReport {
+    /// The format which was checked.
+    pub format: Format,
+    /// The issues, in the order of tracks.
+    pub issues: Vec<CompatibilityIssue>,
+}
+
+impl CompatibilityReport {
+    /// Returns whether the project is generated without losing anything.
+    pub fn is_compatible(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl UfData {
+    /// Lists the features of the project which `format` can't represent, e.g. for warnings
+    /// before converting. This doesn't generate anything.
+    ///
+    /// Generate options aren't considered, so e.g. issues are reported for notes which
+    /// [`GenerateOptions::breath`](super::GenerateOptions::breath) would remove.
+    pub fn check_conversion(&self, format: Format) -> CompatibilityReport {
+        let metadata = format.metadata();
+        let tracks = &self.project().tracks;
+        let mut issues = vec![];
+        if !metadata.can_generate {
+            issues.push(CompatibilityIssue::NotGeneratable);
+        }
+        if !metadata.multi_track && tracks.len() > 1 {
+            issues.push(CompatibilityIssue::MultipleFiles {
+                tracks: tracks.len(),
+            });
+        }
+
+        let range = format.key_range();
+        for (index, track) in tracks.iter().enumerate() {
+            if !metadata.pitch
+                && track
+                    .pitch
+                    .as_ref()
+                    .is_some_and(|pitch| !pitch.ticks.is_empty())
+            {
+                issues.push(CompatibilityIssue::PitchDropped { track: index });
+            }
+            let phonemes = track
+                .notes
+                .iter()
+                .filter(|note| note.phoneme.as_ref().is_some_and(|p| !p.is_empty()))
+                .count();
+            if !metadata.phonemes && phonemes > 0 {
+                issues.push(CompatibilityIssue::PhonemesDropped {
+                    track: index,
+                    notes: phonemes,
+                });
+            }
+            let timed = track
+                .notes
+                .iter()
+                .filter(|note| note.phoneme_segments.is_some())
+                .count();
+            if !metadata.phoneme_timing && timed > 0 {
+                issues.push(CompatibilityIssue::PhonemeTimingDropped {
+                    track: index,
+                    notes: timed,
+                });
+            }
+            let out_of_range = track
+                .notes
+                .iter()
+                .enumerate()
+                .filter(|(_, note)| !range.contains(&note.key.get()))
+                .map(|(note, _)| note)
+                .collect::<Vec<_>>();
+            if !out_of_range.is_empty() {
+                issues.push(CompatibilityIssue::KeysOutOfRange {
+                    track: index,
+                    notes: out_of_range,
+                });
+            }
+            let can_encode = format.can_encode(&track.name)
+                && track
+                    .notes
+                    .iter()
+                    .all(|note| format.can_encode(&note.lyric));
+            if !can_encode {
+                issues.push(CompatibilityIssue::UnencodableText { track: index });
+            }
+        }
+
+        CompatibilityReport { format, issues }
+    }
+}
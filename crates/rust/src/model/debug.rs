@@ -0,0 +1,201 @@
+//! `Debug` output of UtaFormatix data, which is compact with the `compact-debug` feature.
+//!
+//! The full output is the same as the derived one.
+use super::{Pitch, Track, UfData, UfProject};
+use std::fmt;
+
+/// The number of items of lists which the compact output shows.
+const COMPACT_ITEMS: usize = 3;
+
+/// Formats a list, showing only the first items and the number of the others if compact.
+struct List<'a, T> {
+    items: &'a [T],
+    full: bool,
+}
+
+impl<T: fmt::Debug> fmt::Debug for List<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.full || self.items.len() <= COMPACT_ITEMS {
+            return f.debug_list().entries(self.items).finish();
+        }
+        f.debug_list()
+            .entries(&self.items[..COMPACT_ITEMS])
+            .entry(&format_args!(
+                "... {} more",
+                self.items.len() - COMPACT_ITEMS
+            ))
+            .finish()
+    }
+}
+
+struct PitchDebug<'a> {
+    pitch: &'a Pitch,
+    full: bool,
+}
+
+impl fmt::Debug for PitchDebug<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pitch")
+            .field(
+                "ticks",
+                &List {
+                    items: &self.pitch.ticks,
+                    full: self.full,
+                },
+            )
+            .field(
+                "values",
+                &List {
+                    items: &self.pitch.values,
+                    full: self.full,
+                },
+            )
+            .field("is_absolute", &self.pitch.is_absolute)
+            .finish()
+    }
+}
+
+struct TrackDebug<'a> {
+    track: &'a Track,
+    full: bool,
+}
+
+impl fmt::Debug for TrackDebug<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Track")
+            .field("name", &self.track.name)
+            .field(
+                "notes",
+                &List {
+                    items: &self.track.notes,
+                    full: self.full,
+                },
+            )
+            .field(
+                "pitch",
+                &self.track.pitch.as_ref().map(|pitch| PitchDebug {
+                    pitch,
+                    full: self.full,
+                }),
+            )
+            .field("muted", &self.track.muted)
+            .field("solo", &self.track.solo)
+            .field("order", &self.track.order)
+            .field("color", &self.track.color)
+            .field("voice", &self.track.voice)
+            .field("parameters", &self.track.parameters)
+            .finish()
+    }
+}
+
+struct UfProjectDebug<'a> {
+    project: &'a UfProject,
+    full: bool,
+}
+
+impl fmt::Debug for UfProjectDebug<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tracks = self
+            .project
+            .tracks
+            .iter()
+            .map(|track| TrackDebug {
+                track,
+                full: self.full,
+            })
+            .collect::<Vec<_>>();
+        f.debug_struct("UfProject")
+            .field("name", &self.project.name)
+            .field(
+                "tracks",
+                &List {
+                    items: &tracks,
+                    full: self.full,
+                },
+            )
+            .field(
+                "time_signatures",
+                &List {
+                    items: &self.project.time_signatures,
+                    full: self.full,
+                },
+            )
+            .field(
+                "tempos",
+                &List {
+                    items: &self.project.tempos,
+                    full: self.full,
+                },
+            )
+            .field("measure_prefix", &self.project.measure_prefix)
+            .field(
+                "key_signatures",
+                &List {
+                    items: &self.project.key_signatures,
+                    full: self.full,
+                },
+            )
+            .finish()
+    }
+}
+
+struct UfDataDebug<'a> {
+    data: &'a UfData,
+    full: bool,
+}
+
+impl fmt::Debug for UfDataDebug<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UfData")
+            .field("format_version", &self.data.format_version)
+            .field(
+                "project",
+                &UfProjectDebug {
+                    project: &self.data.project,
+                    full: self.full,
+                },
+            )
+            .finish()
+    }
+}
+
+impl fmt::Debug for UfData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        UfDataDebug {
+            data: self,
+            full: !cfg!(feature = "compact-debug"),
+        }
+        .fmt(f)
+    }
+}
+
+impl fmt::Debug for UfProject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        UfProjectDebug {
+            project: self,
+            full: !cfg!(feature = "compact-debug"),
+        }
+        .fmt(f)
+    }
+}
+
+impl fmt::Debug for Track {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        TrackDebug {
+            track: self,
+            full: !cfg!(feature = "compact-debug"),
+        }
+        .fmt(f)
+    }
+}
+
+impl UfData {
+    /// Returns a value whose `Debug` output contains every note and pitch point, even with
+    /// the `compact-debug` feature, which shows only the first items of lists.
+    pub fn debug_full(&self) -> impl fmt::Debug + '_ {
+        UfDataDebug {
+            data: self,
+            full: true,
+        }
+    }
+}
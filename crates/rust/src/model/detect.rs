@@ -0,0 +1,57 @@
+//! Detection of formats from the content of files.
+use super::Format;
+
+/// The number of bytes at the start of a file which are searched for markers of text formats.
+const HEAD_SIZE: usize = 4096;
+
+impl Format {
+    /// Guesses the format of a file from its content, e.g. for files without a meaningful
+    /// extension. Returns `None` if the content isn't recognized.
+    ///
+    /// DeepVocal, Piapro Studio and VoiSona projects aren't detected. MIDI files with VOCALOID's
+    /// metadata are detected as [`Format::Vsq`], so VOCALOID 1 files must be passed as
+    /// [`Format::VocaloidMid`] explicitly.
+    pub fn detect(data: &[u8]) -> Option<Format> {
+        if data.starts_with(b"MThd") {
+            return Some(if contains(data, b"DM:") {
+                Format::Vsq
+            } else {
+                Format::StandardMid
+            });
+        }
+        if data.starts_with(b"PK\x03\x04") {
+            // The names of the entries of ZIP files are stored uncompressed.
+            return contains(data, b"Project/sequence.json").then_some(Format::Vpr);
+        }
+
+        let head = &data[..data.len().min(HEAD_SIZE)];
+        if contains(head, b"[#SETTING]") || contains(head, b"[#VERSION]") {
+            Some(Format::Ust)
+        } else if contains(head, b"ustx_version") {
+            Some(Format::Ustx)
+        } else if contains(head, b"<vsq3") || contains(head, b"<vsq4") {
+            Some(Format::Vsqx)
+        } else if contains(head, b"<score-partwise") || contains(head, b"<score-timewise") {
+            Some(Format::MusicXml)
+        } else if contains(head, b"<Scenario") {
+            Some(Format::Ccs)
+        } else if head.trim_ascii_start().starts_with(b"{") {
+            // Keys of JSON formats may be anywhere in the file.
+            if contains(data, b"\"formatVersion\"") {
+                Some(Format::UfData)
+            } else if contains(data, b"\"renderConfig\"") {
+                Some(Format::Svp)
+            } else if contains(data, b"\"instrumental\"") && contains(data, b"\"mixer\"") {
+                Some(Format::S5p)
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    }
+}
+
+fn contains(data: &[u8], needle: &[u8]) -> bool {
+    data.windows(needle.len()).any(|window| window == needle)
+}
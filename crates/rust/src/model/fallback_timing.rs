@@ -0,0 +1,104 @@
+//! Tempo and time signature at the start of parsed files which don't specify them.
+use super::{ust, Format, ParseOptions, Tempo, Tick, TimeSignature, UfData};
+use crate::{
+    error::{ErrorKind, Result},
+    native::smf,
+};
+
+impl ParseOptions {
+    /// Checks [`ParseOptions::fallback_tempo`] and [`ParseOptions::fallback_time_signature`].
+    pub(crate) fn validate_fallback_timing(&self) -> Result<()> {
+        if self.fallback_tempo.is_some_and(|bpm| bpm <= 0) {
+            return Err(ErrorKind::IllegalTempo.into());
+        }
+        if let Some(time_signature) = &self.fallback_time_signature {
+            if !time_signature.is_valid() {
+                return Err(ErrorKind::IllegalTimeSignature.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the tempo at the start of files which don't specify one.
+    pub(crate) fn start_tempo(&self) -> Tempo {
+        Tempo {
+            tick_position: Tick::ZERO,
+            bpm: self.fallback_tempo.unwrap_or(DEFAULT_BPM),
+        }
+    }
+
+    /// Returns the time signature at the start of files which don't specify one.
+    pub(crate) fn start_time_signature(&self) -> TimeSignature {
+        TimeSignature {
+            measure_position: 0,
+            ..self.fallback_time_signature.clone().unwrap_or_default()
+        }
+    }
+
+    /// Replaces the tempo and time signature which the JS parser assumed at the start of data
+    /// parsed from `sources`, if the files don't specify them.
+    pub(crate) fn apply_fallback_timing(
+        &self,
+        format: Format,
+        sources: &[&[u8]],
+        data: &mut UfData,
+    ) {
+        if self.fallback_tempo.is_none() && self.fallback_time_signature.is_none() {
+            return;
+        }
+        let (tempo, time_signature) = match format {
+            // UST files have no time signatures, and the tempo is optional.
+            Format::Ust => (
+                sources.iter().any(|source| declares_ust_tempo(source)),
+                false,
+            ),
+            Format::Vsq | Format::VocaloidMid => sources
+                .iter()
+                .map(|source| declares_smf_timing(source))
+                .fold((false, false), |(a, b), (c, d)| (a || c, b || d)),
+            _ => return,
+        };
+
+        let project = data.project_mut();
+        if !tempo && self.fallback_tempo.is_some() {
+            let start = self.start_tempo();
+            match project.tempos.first_mut() {
+                Some(first) if first.tick_position == Tick::ZERO => *first = start,
+                _ => project.tempos.insert(0, start),
+            }
+        }
+        if !time_signature && self.fallback_time_signature.is_some() {
+            let start = self.start_time_signature();
+            match project.time_signatures.first_mut() {
+                Some(first) if first.measure_position == 0 => *first = start,
+                _ => project.time_signatures.insert(0, start),
+            }
+        }
+    }
+}
+
+/// The tempo which parsers assume, in BPM.
+pub(crate) const DEFAULT_BPM: i32 = 120;
+
+/// Returns whether the UST file has a `Tempo` entry, in the settings or in a note.
+fn declares_ust_tempo(source: &[u8]) -> bool {
+    ust::lines(source).any(|line| line.value("Tempo").is_some())
+}
+
+/// Returns whether the MIDI file has a tempo and a time signature at the start. Files which
+/// can't be read are treated as having both, so that they are left as parsed.
+fn declares_smf_timing(source: &[u8]) -> (bool, bool) {
+    let Ok(smf) = smf::read(source) else {
+        return (true, true);
+    };
+    let declares = |meta: u8| {
+        smf.tracks.iter().flatten().any(|event| {
+            event.tick == 0
+                && matches!(&event.kind, smf::EventKind::Meta { kind, .. } if *kind == meta)
+        })
+    };
+    (
+        declares(smf::META_TEMPO),
+        declares(smf::META_TIME_SIGNATURE),
+    )
+}
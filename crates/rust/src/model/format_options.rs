@@ -0,0 +1,438 @@
+//! Options which only apply to a specific format, applied to the files generated by
+//! utaformatix-ts.
+use super::{
+    svp::{edit_svp, edit_svp_tracks},
+    ust, xml, Format, UstPitchMode,
+};
+use crate::error::Result;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Represents the options which only apply to a specific format, used by
+/// [`GenerateOptions::format_options`](super::GenerateOptions::format_options).
+///
+/// The options are ignored when generating other formats.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", tag = "format")]
+pub enum FormatOptions {
+    /// Options for Standard MIDI files.
+    StandardMid(StandardMidOptions),
+    /// Options for MusicXML files.
+    MusicXml(MusicXmlOptions),
+    /// Options for UTAU's project files.
+    Ust(UstOptions),
+    /// Options for VOCALOID 3/4's project files.
+    Vsqx(VsqxOptions),
+    /// Options for Synthesizer V's project files.
+    Svp(SvpOptions),
+    /// Options for CeVIO's project files.
+    Ccs(CcsOptions),
+    /// Options for VoiSona's project files.
+    Tssln(TsslnOptions),
+}
+
+/// Represents the options for Standard MIDI files.
+///
+/// These don't apply to [`Format::VocaloidMid`], which is generated by utaformatix-ts: it writes
+/// the pitch curves as VOCALOID's own pitch data (`PIT` and `PBS`) instead of pitch bend events,
+/// and the options are ignored with a warning.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", default)]
+pub struct StandardMidOptions {
+    /// MIDI channel of the notes, from 0 to 15.
+    pub channel: u8,
+    /// Pitch bend range in semitones, which is written as RPN 0 at the start of each track.
+    /// Nothing is written if this is `None`, and the pitch curves are rendered assuming the
+    /// default range of 2 semitones.
+    pub pitch_bend_range: Option<u8>,
+    /// Minimum interval in ticks between pitch bend events, rendered from the pitch curves when
+    /// [`GenerateOptions::pitch`](super::GenerateOptions::pitch) is enabled. 0 writes an event
+    /// for every data point.
+    pub pitch_bend_resolution: u32,
+}
+
+/// Represents the options for MusicXML files.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", default)]
+pub struct MusicXmlOptions {
+    /// Whether to write a single score with a part for each track, instead of a file for each
+    /// track.
+    pub single_score: bool,
+}
+
+/// Represents the options for UTAU's project files.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", default)]
+pub struct UstOptions {
+    /// Voicebank directory, written as `VoiceDir`.
+    pub voice_dir: Option<String>,
+    /// Flags of the project, written as `Flags`. (e.g. `g-5B50`)
+    pub flags: Option<String>,
+    /// Flags of every note, written as `Flags` of the notes which don't have them. (e.g. `g-5`)
+    pub note_flags: Option<String>,
+    /// Envelope of every note, written as `Envelope` of the notes which don't have one.
+    /// (e.g. `5,35,0,100,100,100,100`)
+    pub envelope: Option<String>,
+    /// Whether to write the pitch curves as Mode2 pitch bends. `Some(false)` removes the pitch
+    /// bends (`PBS`, `PBW`, `PBY` and `PBM`) and `Mode2` from the file, and `Some(true)` writes
+    /// `Mode2=True` so that UTAU shows them. The file is left as generated if this is `None`.
+    pub mode2_pitch: Option<bool>,
+    /// Representation of the pitch curves. [`UstPitchMode::Mode1`] writes them as `PitchBend`
+    /// of each note instead of the Mode2 pitch bends, which are removed.
+    pub pitch_mode: UstPitchMode,
+}
+
+/// Represents the options for VOCALOID 3/4's project files.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", default)]
+pub struct VsqxOptions {
+    /// Name of the singer, written as `vVoiceName`.
+    pub singer_name: Option<String>,
+}
+
+/// Represents the options for Synthesizer V's project files.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", default)]
+pub struct SvpOptions {
+    /// Name of the voice database of every track.
+    pub database_name: Option<String>,
+}
+
+/// Represents the options for CeVIO's project files.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", default)]
+pub struct CcsOptions {
+    /// Cast ID of every track. (e.g. `A` for Satou Sasara)
+    pub cast_id: Option<String>,
+    /// Whether [`Project::export`](crate::Project::export) writes a file for each track, since
+    /// CeVIO handles a project per cast better. See
+    /// [`UtaFormatix::generate_ccs_per_track`](crate::base::UtaFormatix::generate_ccs_per_track).
+    pub file_per_track: bool,
+}
+
+/// Represents the options for VoiSona's project files.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", default)]
+pub struct TsslnOptions {
+    /// Whether [`Project::export`](crate::Project::export) writes a file for each track, since
+    /// VoiSona handles a project per voice better. See
+    /// [`UtaFormatix::generate_tssln_per_track`](crate::base::UtaFormatix::generate_tssln_per_track).
+    pub file_per_track: bool,
+}
+
+impl FormatOptions {
+    /// Returns the format which the options apply to.
+    pub fn format(&self) -> Format {
+        match self {
+            Self::StandardMid(_) => Format::StandardMid,
+            Self::MusicXml(_) => Format::MusicXml,
+            Self::Ust(_) => Format::Ust,
+            Self::Vsqx(_) => Format::Vsqx,
+            Self::Svp(_) => Format::Svp,
+            Self::Ccs(_) => Format::Ccs,
+            Self::Tssln(_) => Format::Tssln,
+        }
+    }
+
+    /// Applies the options to a file generated by utaformatix-ts.
+    ///
+    /// Standard MIDI files are generated natively, which reads the options by itself.
+    pub(crate) fn apply(&self, format: Format, data: Vec<u8>) -> Result<Vec<u8>> {
+        if self.format() != format {
+            if let (Self::StandardMid(_), Format::VocaloidMid) = (self, format) {
+                warn!(
+                    "Standard MIDI options don't apply to VOCALOID 1's MIDI files, ignoring them"
+                );
+            }
+            return Ok(data);
+        }
+        match self {
+            Self::StandardMid(_) | Self::MusicXml(_) => Ok(data),
+            Self::Ust(options) => {
+                let data = set_ust_settings(
+                    &data,
+                    &[
+                        ("VoiceDir", options.voice_dir.as_deref()),
+                        ("Flags", options.flags.as_deref()),
+                        (
+                            "Mode2",
+                            (options.mode2_pitch == Some(true)
+                                && options.pitch_mode == UstPitchMode::Mode2)
+                                .then_some("True"),
+                        ),
+                    ],
+                );
+                let removed: &[&str] = if options.mode2_pitch == Some(false)
+                    || options.pitch_mode == UstPitchMode::Mode1
+                {
+                    &["PBS", "PBW", "PBY", "PBM", "Mode2"]
+                } else {
+                    &[]
+                };
+                Ok(set_ust_note_defaults(
+                    &data,
+                    &[
+                        ("Flags", options.note_flags.as_deref()),
+                        ("Envelope", options.envelope.as_deref()),
+                    ],
+                    removed,
+                ))
+            }
+            Self::Vsqx(options) => match &options.singer_name {
+                Some(name) => set_vsqx_voice_name(data, name),
+                None => Ok(data),
+            },
+            Self::Svp(options) => match &options.database_name {
+                Some(name) => set_svp_database_names(data, |_| Some(name)),
+                None => Ok(data),
+            },
+            Self::Ccs(options) => match &options.cast_id {
+                Some(cast_id) => set_ccs_cast_id(data, cast_id),
+                None => Ok(data),
+            },
+            Self::Tssln(_) => Ok(data),
+        }
+    }
+}
+
+impl FormatOptions {
+    /// Applies the options to the files of a multi-file format generated by utaformatix-ts,
+    /// which may merge them into one.
+    pub(crate) fn apply_multiple(
+        &self,
+        format: Format,
+        files: Vec<Vec<u8>>,
+    ) -> Result<Vec<Vec<u8>>> {
+        let files = files
+            .into_iter()
+            .map(|file| self.apply(format, file))
+            .collect::<Result<Vec<_>>>()?;
+        if self.merges(format) {
+            merge_music_xml(files).map(|file| vec![file])
+        } else {
+            Ok(files)
+        }
+    }
+
+    /// Returns whether [`FormatOptions::apply_multiple`] merges the files, which requires all of
+    /// them at once.
+    pub(crate) fn merges(&self, format: Format) -> bool {
+        matches!(self, Self::MusicXml(options) if format == Format::MusicXml && options.single_score)
+    }
+
+    /// Returns whether a single-file format is exported as a file per track.
+    pub(crate) fn file_per_track(&self, format: Format) -> bool {
+        match self {
+            Self::Ccs(options) => format == Format::Ccs && options.file_per_track,
+            Self::Tssln(options) => format == Format::Tssln && options.file_per_track,
+            _ => false,
+        }
+    }
+}
+
+/// Sets the entries of the `[#SETTING]` section, adding them at the end of the section if they
+/// don't exist.
+fn set_ust_settings(data: &[u8], entries: &[(&str, Option<&str>)]) -> Vec<u8> {
+    let mut remaining = entries
+        .iter()
+        .filter_map(|(key, value)| value.map(|value| (*key, value)))
+        .collect::<Vec<_>>();
+    if remaining.is_empty() {
+        return data.to_vec();
+    }
+    let writer = ust::Writer::new(data);
+
+    let mut result = Vec::with_capacity(data.len());
+    let mut in_setting = false;
+    for line in ust::lines(data) {
+        match line.kind {
+            ust::LineKind::Section(name) => {
+                if in_setting {
+                    for (key, value) in remaining.drain(..) {
+                        writer.entry(&mut result, key, value);
+                    }
+                }
+                in_setting = name == b"SETTING";
+            }
+            ust::LineKind::Entry(name, _) if in_setting => {
+                if let Some(position) = remaining.iter().position(|(key, _)| name == key.as_bytes())
+                {
+                    let (key, value) = remaining.remove(position);
+                    writer.entry(&mut result, key, value);
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        result.extend_from_slice(line.raw);
+    }
+    if in_setting && !remaining.is_empty() {
+        writer.end_line(&mut result);
+        for (key, value) in remaining {
+            writer.entry(&mut result, key, value);
+        }
+    }
+    result
+}
+
+/// Adds the entries to every note section (`[#0000]`, `[#0001]`, ...) which doesn't have them,
+/// and removes the lines of the keys in `removed` from every section.
+fn set_ust_note_defaults(
+    data: &[u8],
+    entries: &[(&str, Option<&str>)],
+    removed: &[&str],
+) -> Vec<u8> {
+    let entries = entries
+        .iter()
+        .filter_map(|(key, value)| value.map(|value| (*key, value)))
+        .collect::<Vec<_>>();
+    if entries.is_empty() && removed.is_empty() {
+        return data.to_vec();
+    }
+    let writer = ust::Writer::new(data);
+
+    let mut result = Vec::with_capacity(data.len());
+    let mut missing = Vec::new();
+    let flush = |result: &mut Vec<u8>, missing: &mut Vec<(&str, &str)>| {
+        if !missing.is_empty() {
+            writer.end_line(result);
+        }
+        for (key, value) in missing.drain(..) {
+            writer.entry(result, key, value);
+        }
+    };
+    for line in ust::lines(data) {
+        match line.kind {
+            ust::LineKind::Section(name) => {
+                flush(&mut result, &mut missing);
+                if ust::is_note_section(name) {
+                    missing.clone_from(&entries);
+                }
+            }
+            ust::LineKind::Entry(name, _) => {
+                if removed.iter().any(|key| name == key.as_bytes()) {
+                    continue;
+                }
+                missing.retain(|(key, _)| name != key.as_bytes());
+            }
+            ust::LineKind::Other => {}
+        }
+        result.extend_from_slice(line.raw);
+    }
+    flush(&mut result, &mut missing);
+    result
+}
+
+/// Replaces the content of every `vVoiceName` element.
+fn set_vsqx_voice_name(data: Vec<u8>, name: &str) -> Result<Vec<u8>> {
+    let text =
+        String::from_utf8(data).map_err(|e| anyhow!("Generated vsqx is not UTF-8: {:?}", e))?;
+    let name = format!("<![CDATA[{}]]>", name.replace("]]>", "]]]]><![CDATA[>"));
+    let replacements = xml::elements(&text)
+        .into_iter()
+        .filter(|element| element.name == "vVoiceName")
+        .map(|element| (element.content, name.clone()))
+        .collect();
+    Ok(xml::splice(&text, replacements).into_bytes())
+}
+
+/// Sets the name of the voice database of each track, returned by `name` from the index of the
+/// track. Tracks whose name is `None` keep their database.
+pub(super) fn set_svp_database_names<'a>(
+    data: Vec<u8>,
+    name: impl Fn(usize) -> Option<&'a str>,
+) -> Result<Vec<u8>> {
+    edit_svp(data, |project| {
+        edit_svp_tracks(project, |index, track| {
+            let Some(name) = name(index) else {
+                return;
+            };
+            if let Some(database) = track
+                .pointer_mut("/mainRef/database")
+                .and_then(serde_json::Value::as_object_mut)
+            {
+                database.insert("name".to_string(), name.into());
+            }
+        });
+    })
+}
+
+/// Replaces the value of every `CastId` attribute.
+fn set_ccs_cast_id(data: Vec<u8>, cast_id: &str) -> Result<Vec<u8>> {
+    let text =
+        String::from_utf8(data).map_err(|e| anyhow!("Generated ccs is not UTF-8: {:?}", e))?;
+    let replacements = xml::elements(&text)
+        .iter()
+        .filter(|element| element.attribute("CastId").is_some())
+        .map(|element| element.set_attributes(&text, &[("CastId", cast_id)]))
+        .collect();
+    Ok(xml::splice(&text, replacements).into_bytes())
+}
+
+/// Merges MusicXML files of a single part each into a score with all of the parts, numbering
+/// the parts from `P1`. The header of the first file is kept.
+fn merge_music_xml(files: Vec<Vec<u8>>) -> Result<Vec<u8>> {
+    let texts = files
+        .into_iter()
+        .map(String::from_utf8)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| anyhow!("Generated MusicXML is not UTF-8: {:?}", e))?;
+    let Some(first) = texts.first() else {
+        return Ok(vec![]);
+    };
+
+    let mut score_parts = String::new();
+    let mut parts = String::new();
+    let mut first_ranges = None;
+    for (i, text) in texts.iter().enumerate() {
+        let elements = xml::elements(text);
+        let find = |name: &str| elements.iter().position(|element| element.name == name);
+        let score_part =
+            find("score-part").ok_or_else(|| anyhow!("Generated MusicXML has no score-part"))?;
+        let part = find("part").ok_or_else(|| anyhow!("Generated MusicXML has no part"))?;
+        let id = elements[score_part]
+            .attribute("id")
+            .ok_or_else(|| anyhow!("Generated MusicXML has no part id"))?;
+        let new_id = format!("P{}", i + 1);
+        // Instruments have ids prefixed by the id of their part, e.g. `P1-I1`.
+        let rename = |index: usize| {
+            let range = elements[index].range.clone();
+            let replacements = std::iter::once((index, &elements[index]))
+                .chain(xml::descendants(&elements, index))
+                .filter_map(|(_, element)| {
+                    let old = element.attribute("id")?;
+                    let new = match old.strip_prefix(id) {
+                        Some("") => new_id.clone(),
+                        Some(suffix) if suffix.starts_with('-') => format!("{new_id}{suffix}"),
+                        _ => return None,
+                    };
+                    let (tag, replacement) = element.set_attributes(text, &[("id", &new)]);
+                    Some((tag.start - range.start..tag.end - range.start, replacement))
+                })
+                .collect();
+            xml::splice(&text[range.clone()], replacements)
+        };
+        score_parts.push_str(&rename(score_part));
+        parts.push_str(&rename(part));
+        if i == 0 {
+            first_ranges = Some((
+                elements[score_part].range.clone(),
+                elements[part].range.clone(),
+            ));
+        }
+    }
+
+    let Some((score_part, part)) = first_ranges else {
+        return Ok(vec![]);
+    };
+    Ok(xml::splice(first, vec![(score_part, score_parts), (part, parts)]).into_bytes())
+}
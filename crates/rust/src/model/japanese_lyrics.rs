@@ -0,0 +1,178 @@
+//! Adjustments of converted Japanese lyrics, applied after utaformatix-ts converts them.
+use super::{ConvertJapaneseLyricsOptions, JapaneseLyricsType, Note, UfData};
+use serde::{Deserialize, Serialize};
+
+const LONG_VOWEL_MARK: &str = "ー";
+const SMALL_KANA: &str = "ぁぃぅぇぉゃゅょゎァィゥェォャュョヮ";
+/// Hiragana of each vowel, in the order of [`VOWELS`].
+const VOWEL_ROWS: [&str; 6] = [
+    "あかさたなはまやらわがざだばぱぁゃゎ",
+    "いきしちにひみりぎじぢびぴぃ",
+    "うくすつぬふむゆるぐずづぶぷぅゅっゔ",
+    "えけせてねへめれげぜでべぺぇ",
+    "おこそとのほもよろをごぞどぼぽぉょ",
+    "ん",
+];
+const VOWELS: [char; 6] = ['a', 'i', 'u', 'e', 'o', 'n'];
+const VOWEL_KANA: [&str; 6] = ["あ", "い", "う", "え", "お", "ん"];
+
+/// Represents what to do with notes whose lyric is a long vowel mark ("ー").
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum LongVowelMark {
+    /// Keeps the mark.
+    #[default]
+    Keep,
+    /// Replaces the mark with the vowel of the previous note, e.g. "か", "ー" becomes "か",
+    /// "あ" (or "ka", "a" in romaji, and "a あ" in VCV).
+    PreviousVowel,
+}
+
+/// Represents how VCV lyrics after "ん" start.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum NTransition {
+    /// Uses the "n" transition, e.g. "n か".
+    #[default]
+    N,
+    /// Starts from a rest, e.g. "- か", for voicebanks without "n" transitions.
+    Rest,
+}
+
+impl ConvertJapaneseLyricsOptions {
+    /// Applies the options which utaformatix-ts doesn't know to the converted data.
+    pub(crate) fn adjust(&self, data: &mut UfData, target: JapaneseLyricsType) {
+        let is_kana = matches!(
+            target,
+            JapaneseLyricsType::KanaCv | JapaneseLyricsType::KanaVcv
+        );
+        let is_vcv = matches!(
+            target,
+            JapaneseLyricsType::KanaVcv | JapaneseLyricsType::RomajiVcv
+        );
+        for track in &mut data.project_mut().tracks {
+            track.notes.sort_by_key(|note| note.tick_on);
+            if self.merge_small_kana && is_kana {
+                merge_small_kana(&mut track.notes);
+            }
+            if self.long_vowel_mark == LongVowelMark::PreviousVowel {
+                expand_vowel_extensions(&mut track.notes);
+            }
+            if self.n_transition == NTransition::Rest && is_vcv {
+                for note in &mut track.notes {
+                    if let Some(body) = note.lyric.strip_prefix("n ") {
+                        note.lyric = format!("- {body}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Splits a VCV lyric into the leading vowel and the body.
+fn split_vcv(lyric: &str) -> (Option<&str>, &str) {
+    match lyric.split_once(' ') {
+        Some((vowel, body)) => (Some(vowel), body),
+        None => (None, lyric),
+    }
+}
+
+/// Merges notes of a single small kana (e.g. "き", "ゃ") into the previous note ("きゃ").
+fn merge_small_kana(notes: &mut Vec<Note>) {
+    let mut merged: Vec<Note> = Vec::with_capacity(notes.len());
+    for note in notes.drain(..) {
+        let (_, body) = split_vcv(&note.lyric);
+        let mut chars = body.chars();
+        let is_small_kana = matches!(
+            (chars.next(), chars.next()),
+            (Some(c), None) if SMALL_KANA.contains(c)
+        );
+        match merged.last_mut() {
+            Some(previous) if is_small_kana && previous.tick_off == note.tick_on => {
+                previous.lyric.push_str(body);
+                previous.tick_off = note.tick_off;
+            }
+            _ => merged.push(note),
+        }
+    }
+    *notes = merged;
+}
+
+/// Replaces the lyrics of notes which are a long vowel mark with the vowel of the previous
+/// note. `notes` must be sorted by position.
+///
+/// The vowel is written in kana or romaji, and in VCV or CV, like the previous lyric.
+pub(crate) fn expand_vowel_extensions(notes: &mut [Note]) {
+    for i in 1..notes.len() {
+        if split_vcv(&notes[i].lyric).1 != LONG_VOWEL_MARK {
+            continue;
+        }
+        let Some(vowel) = vowel_of(&notes[i - 1].lyric) else {
+            continue;
+        };
+        notes[i].lyric = vowel_lyric(vowel, &notes[i - 1].lyric);
+    }
+}
+
+/// Replaces the lyrics of notes which only repeat the vowel of the previous note (e.g. "か",
+/// "あ") with a long vowel mark ("か", "ー"). `notes` must be sorted by position.
+///
+/// Only notes which start right at the end of the previous note are replaced, since a rest
+/// before a vowel makes a new attack.
+pub(crate) fn collapse_vowel_extensions(notes: &mut [Note]) {
+    // Vowels of the original lyrics, as collapsed notes lose theirs.
+    let vowels = notes
+        .iter()
+        .map(|note| vowel_of(&note.lyric))
+        .collect::<Vec<_>>();
+    for i in 1..notes.len() {
+        let Some(vowel) = vowels[i - 1] else {
+            continue;
+        };
+        if notes[i - 1].tick_off != notes[i].tick_on {
+            continue;
+        }
+        let romaji = VOWELS[vowel].to_string();
+        let (prefix, body) = split_vcv(&notes[i].lyric);
+        let is_extension = (body == VOWEL_KANA[vowel] || body == romaji)
+            && prefix.is_none_or(|prefix| prefix == romaji);
+        if is_extension {
+            notes[i].lyric = LONG_VOWEL_MARK.to_string();
+        }
+    }
+}
+
+/// Returns the lyric of the vowel, in the style of the previous lyric.
+fn vowel_lyric(vowel: usize, previous: &str) -> String {
+    let is_kana = split_vcv(previous)
+        .1
+        .chars()
+        .last()
+        .is_some_and(|c| !c.is_ascii());
+    let body = if is_kana {
+        VOWEL_KANA[vowel].to_string()
+    } else {
+        VOWELS[vowel].to_string()
+    };
+    if split_vcv(previous).0.is_some() {
+        format!("{} {body}", VOWELS[vowel])
+    } else {
+        body
+    }
+}
+
+/// Returns the index of the vowel the lyric ends with.
+fn vowel_of(lyric: &str) -> Option<usize> {
+    let last = split_vcv(lyric).1.chars().last()?;
+    // Katakana are 0x60 after their hiragana.
+    let hiragana = match last {
+        '\u{30a1}'..='\u{30f6}' => char::from_u32(last as u32 - 0x60)?,
+        c => c,
+    };
+    VOWEL_ROWS
+        .iter()
+        .position(|row| row.contains(hiragana))
+        .or_else(|| VOWELS.iter().position(|vowel| *vowel == last))
+}
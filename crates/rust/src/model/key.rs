@@ -0,0 +1,334 @@
+//! Keys and scales.
+use super::{key_name, parse_key_name, Note, Track};
+use serde::{Deserialize, Serialize};
+use std::{fmt, ops::Sub, str::FromStr};
+
+/// Krumhansl-Kessler key profiles, starting from the tonic.
+const MAJOR_PROFILE: [f64; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+const MINOR_PROFILE: [f64; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+const MAJOR_SCALE: [i32; 7] = [0, 2, 4, 5, 7, 9, 11];
+const MINOR_SCALE: [i32; 7] = [0, 2, 3, 5, 7, 8, 10];
+
+/// Represents the key of a note in semitones, as a MIDI note number (Center C = 60).
+///
+/// This is distinct from plain integers, so that keys aren't mixed up with pitch classes or
+/// cents. Keys are in the range of MIDI (0 to 127), which deserializing checks too; see
+/// [`KeyRangePolicy`](super::KeyRangePolicy) for the range of each format. This is
+/// (de)serialized as the number.
+///
+/// Keys are moved with [`Key::checked_add`], so that keys out of the range are handled by the
+/// caller.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(try_from = "i32", into = "i32")]
+pub struct Key(i32);
+
+impl Key {
+    /// The lowest key of MIDI.
+    pub const MIN: Self = Self(0);
+    /// The highest key of MIDI.
+    pub const MAX: Self = Self(127);
+    /// Center C (C4).
+    pub const CENTER_C: Self = Self(60);
+
+    /// Returns the key, or `None` if it's out of the range of MIDI.
+    pub const fn new(key: i32) -> Option<Self> {
+        if key < Self::MIN.0 || key > Self::MAX.0 {
+            None
+        } else {
+            Some(Self(key))
+        }
+    }
+
+    /// Returns the semitone value.
+    pub const fn get(self) -> i32 {
+        self.0
+    }
+
+    /// Returns the pitch class (C = 0, C# = 1, ..., B = 11).
+    pub const fn pitch_class(self) -> i32 {
+        self.0.rem_euclid(12)
+    }
+
+    /// Returns the name of the key, e.g. `C4` for Center C.
+    pub fn name(self) -> String {
+        key_name(self.0)
+    }
+
+    /// Moves the key by semitones, which can be negative. Returns `None` if the result is out
+    /// of the range of MIDI.
+    pub fn checked_add(self, semitones: i32) -> Option<Self> {
+        self.0.checked_add(semitones).and_then(Self::new)
+    }
+}
+
+impl Default for Key {
+    fn default() -> Self {
+        Self::CENTER_C
+    }
+}
+
+/// Returns the interval in semitones from the other key, which is negative if it's higher.
+impl Sub for Key {
+    type Output = i32;
+
+    fn sub(self, other: Self) -> i32 {
+        self.0 - other.0
+    }
+}
+
+impl PartialEq<i32> for Key {
+    fn eq(&self, key: &i32) -> bool {
+        self.0 == *key
+    }
+}
+
+impl From<Key> for i32 {
+    fn from(key: Key) -> Self {
+        key.0
+    }
+}
+
+impl TryFrom<i32> for Key {
+    type Error = crate::Error;
+
+    fn try_from(key: i32) -> crate::error::Result<Self> {
+        Self::new(key)
+            .ok_or_else(|| anyhow::anyhow!("Key out of the range of MIDI: {}", key).into())
+    }
+}
+
+/// Parses a name like `C4` or `A#3`, as returned by [`Key::name`].
+impl FromStr for Key {
+    type Err = crate::Error;
+
+    fn from_str(name: &str) -> crate::error::Result<Self> {
+        parse_key_name(name)
+            .and_then(Self::new)
+            .ok_or_else(|| anyhow::anyhow!("Invalid key name: {}", name).into())
+    }
+}
+
+/// Formats as the number, like the serialized value.
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// Represents the mode of a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum Mode {
+    /// Major (ionian) mode.
+    Major,
+    /// Natural minor (aeolian) mode.
+    Minor,
+}
+
+/// Represents an offset in scale degrees, used by [`Track::harmonize`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ScaleDegreeOffset(pub i32);
+impl ScaleDegreeOffset {
+    /// A diatonic third above.
+    pub const THIRD_ABOVE: Self = Self(2);
+    /// A diatonic third below.
+    pub const THIRD_BELOW: Self = Self(-2);
+    /// A diatonic fifth above.
+    pub const FIFTH_ABOVE: Self = Self(4);
+    /// A diatonic fifth below.
+    pub const FIFTH_BELOW: Self = Self(-4);
+}
+
+/// Represents a key signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct KeySignature {
+    /// Measure (bar) position of the key signature in
+    /// [`UfProject::key_signatures`](super::UfProject::key_signatures). This is 0 for keys which
+    /// aren't placed in a project, e.g. detected ones.
+    #[serde(default)]
+    pub measure_position: i32,
+    /// Pitch class of the tonic (C = 0, C# = 1, ..., B = 11).
+    pub tonic: i32,
+    /// Mode of the key.
+    pub mode: Mode,
+}
+impl Default for KeySignature {
+    fn default() -> Self {
+        Self {
+            measure_position: 0,
+            tonic: 0,
+            mode: Mode::Major,
+        }
+    }
+}
+impl KeySignature {
+    /// Detects the key of the notes by correlating the histogram of pitch classes, weighted by
+    /// note length, with the Krumhansl-Kessler key profiles.
+    ///
+    /// Returns C major if there are no notes.
+    pub fn detect<'a>(notes: impl IntoIterator<Item = &'a Note>) -> Self {
+        let mut histogram = [0.0; 12];
+        for note in notes {
+            let length = (note.tick_off - note.tick_on).max(0) as f64;
+            histogram[note.key.pitch_class() as usize] += length;
+        }
+        if histogram.iter().all(|weight| *weight == 0.0) {
+            return Self::default();
+        }
+
+        let mut best = (f64::NEG_INFINITY, Self::default());
+        for (mode, profile) in [(Mode::Major, MAJOR_PROFILE), (Mode::Minor, MINOR_PROFILE)] {
+            for tonic in 0..12 {
+                let rotated: [f64; 12] = std::array::from_fn(|i| profile[(i + 12 - tonic) % 12]);
+                let score = correlation(&histogram, &rotated);
+                if score > best.0 {
+                    best = (
+                        score,
+                        Self {
+                            measure_position: 0,
+                            tonic: tonic as i32,
+                            mode,
+                        },
+                    );
+                }
+            }
+        }
+
+        best.1
+    }
+
+    /// Creates a key signature from the number of sharps (positive) or flats (negative), as
+    /// written in MusicXML and MIDI files.
+    pub fn from_fifths(fifths: i32, mode: Mode) -> Self {
+        let major_tonic = (fifths * 7).rem_euclid(12);
+        Self {
+            measure_position: 0,
+            tonic: match mode {
+                Mode::Major => major_tonic,
+                Mode::Minor => (major_tonic + 9) % 12,
+            },
+            mode,
+        }
+    }
+
+    /// Returns the number of sharps (positive) or flats (negative), from -5 to 6.
+    pub fn fifths(&self) -> i32 {
+        let major_tonic = match self.mode {
+            Mode::Major => self.tonic,
+            Mode::Minor => self.tonic + 3,
+        };
+        // A fifth is 7 semitones, and 7 * 7 = 1 (mod 12).
+        let fifths = (major_tonic * 7).rem_euclid(12);
+        if fifths > 6 {
+            fifths - 12
+        } else {
+            fifths
+        }
+    }
+
+    /// Returns the pitch classes of the scale, starting from the tonic.
+    pub fn pitch_classes(&self) -> [i32; 7] {
+        self.scale()
+            .map(|degree| (self.tonic + degree).rem_euclid(12))
+    }
+
+    /// Returns whether the key is in the scale.
+    pub fn contains(&self, key: Key) -> bool {
+        self.pitch_classes().contains(&key.pitch_class())
+    }
+
+    /// Moves the key by scale degrees. Returns `None` if the result is out of the range of MIDI.
+    ///
+    /// Keys out of the scale are moved by the same number of semitones as the nearest key in
+    /// the scale.
+    pub fn transpose_diatonic(&self, key: Key, offset: ScaleDegreeOffset) -> Option<Key> {
+        let scale = self.scale();
+        let degree_to_semitone =
+            |degree: i32| scale[degree.rem_euclid(7) as usize] + 12 * degree.div_euclid(7);
+        let pitch_class = (self.snap(key).get() - self.tonic).rem_euclid(12);
+        let degree = scale
+            .iter()
+            .position(|semitone| *semitone == pitch_class)
+            .unwrap_or(0) as i32;
+        key.checked_add(degree_to_semitone(degree + offset.0) - degree_to_semitone(degree))
+    }
+
+    fn scale(&self) -> [i32; 7] {
+        match self.mode {
+            Mode::Major => MAJOR_SCALE,
+            Mode::Minor => MINOR_SCALE,
+        }
+    }
+
+    /// Returns the nearest key in the scale, preferring the lower one on ties.
+    pub fn snap(&self, key: Key) -> Key {
+        [0, -1, 1, -2, 2]
+            .into_iter()
+            .filter_map(|offset| key.checked_add(offset))
+            .find(|candidate| self.contains(*candidate))
+            .unwrap_or(key)
+    }
+}
+
+fn correlation(x: &[f64; 12], y: &[f64; 12]) -> f64 {
+    let mean_x = x.iter().sum::<f64>() / 12.0;
+    let mean_y = y.iter().sum::<f64>() / 12.0;
+    let (mut covariance, mut variance_x, mut variance_y) = (0.0, 0.0, 0.0);
+    for (x, y) in x.iter().zip(y) {
+        covariance += (x - mean_x) * (y - mean_y);
+        variance_x += (x - mean_x).powi(2);
+        variance_y += (y - mean_y).powi(2);
+    }
+    covariance / (variance_x * variance_y).sqrt()
+}
+
+impl Track {
+    /// Moves notes which are out of the scale to the nearest key in it.
+    pub fn snap_to_scale(&mut self, scale: &KeySignature) {
+        for note in &mut self.notes {
+            note.key = scale.snap(note.key);
+        }
+    }
+
+    /// Creates a harmony track, moving every note by scale degrees in the key. Returns `None`
+    /// if a key would be out of the range of MIDI.
+    ///
+    /// Lyrics are duplicated, and relative pitch is kept since it follows the notes.
+    pub fn harmonize(&self, interval: ScaleDegreeOffset, key: &KeySignature) -> Option<Track> {
+        Some(Track {
+            name: format!("{} (harmony)", self.name),
+            notes: self
+                .notes
+                .iter()
+                .map(|note| {
+                    Some(Note {
+                        key: key.transpose_diatonic(note.key, interval)?,
+                        ..note.clone()
+                    })
+                })
+                .collect::<Option<_>>()?,
+            pitch: self.pitch.clone().filter(|pitch| !pitch.is_absolute),
+            muted: None,
+            solo: None,
+            order: None,
+            color: None,
+            voice: None,
+            parameters: None,
+        })
+    }
+}
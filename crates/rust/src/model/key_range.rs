@@ -0,0 +1,83 @@
+//! Validation of note keys against the range of the target format.
+use super::{Format, Key, UfData};
+use crate::error::{ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// Represents what to do with notes whose key is out of the range of the target format (see
+/// [`Format::key_range`]), used by
+/// [`GenerateOptions::key_range`](super::GenerateOptions::key_range).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum KeyRangePolicy {
+    /// Fails with [`ErrorKind::KeyOutOfRange`].
+    #[default]
+    Error,
+    /// Generates the notes as they are.
+    Allow,
+    /// Moves the notes by octaves until they are in the range.
+    FoldOctaves,
+}
+
+impl KeyRangePolicy {
+    /// Checks the data which is generated as `format`, failing with
+    /// [`ErrorKind::KeyOutOfRange`] with the indices of the note in `data` if the policy is
+    /// [`KeyRangePolicy::Error`].
+    pub(crate) fn check(&self, format: Format, data: &UfData) -> Result<()> {
+        if *self != Self::Error {
+            return Ok(());
+        }
+        match out_of_range(format, data) {
+            Some((track, note, key)) => Err(ErrorKind::KeyOutOfRange { track, note, key }.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Applies the policy to the data which is generated as `format`, cloning it only if a
+    /// note is folded.
+    pub(crate) fn apply(&self, format: Format, data: &mut Cow<UfData>) -> Result<()> {
+        if *self == Self::Allow || out_of_range(format, data).is_none() {
+            return Ok(());
+        }
+        self.check(format, data)?;
+
+        let range = format.key_range();
+        for note in data
+            .to_mut()
+            .project_mut()
+            .tracks
+            .iter_mut()
+            .flat_map(|track| &mut track.notes)
+        {
+            let mut key = note.key.get();
+            while key < *range.start() {
+                key += 12;
+            }
+            while key > *range.end() {
+                key -= 12;
+            }
+            if let Some(key) = Key::new(key) {
+                note.key = key;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the indices of the track and the note, and the key, of the first note whose key is
+/// out of the range of `format`.
+fn out_of_range(format: Format, data: &UfData) -> Option<(usize, usize, i32)> {
+    let range = format.key_range();
+    data.project()
+        .tracks
+        .iter()
+        .enumerate()
+        .find_map(|(index, track)| {
+            track
+                .notes
+                .iter()
+                .position(|note| !range.contains(&note.key.get()))
+                .map(|note| (index, note, track.notes[note].key.get()))
+        })
+}
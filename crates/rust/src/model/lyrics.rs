@@ -0,0 +1,111 @@
+//! Cleanup of lyrics on parsing.
+use super::{Note, ParseOptions};
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+/// Marks appended to UTAU lyrics by prefix maps and voicebank conventions.
+const UTAU_SUFFIX_MARKS: [char; 4] = ['↑', '↓', '↗', '↘'];
+
+/// Represents a Unicode normalization form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum UnicodeForm {
+    /// Canonical composition, e.g. "か" + "゛" becomes "が".
+    Nfc,
+    /// Compatibility composition, which also folds full-width and half-width variants.
+    Nfkc,
+}
+
+/// Represents the options for cleaning up lyrics, used by [`ParseOptions`].
+///
+/// Every option is off by default, so lyrics are kept as they are in the file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct LyricNormalization {
+    /// Unicode normalization form to apply.
+    pub unicode_form: Option<UnicodeForm>,
+    /// Whether to convert full-width ASCII characters (e.g. "ｌａ") to half-width ("la").
+    pub half_width_romaji: bool,
+    /// Whether to trim whitespace around lyrics.
+    pub trim_whitespace: bool,
+    /// Whether to strip UTAU suffixes, i.e. pitch names (e.g. "_B4", " C#4") and arrows
+    /// (e.g. "↑"). Lyrics which only consist of a suffix are kept.
+    pub strip_utau_suffixes: bool,
+}
+impl LyricNormalization {
+    /// Returns the normalized lyric.
+    pub fn normalize(&self, lyric: &str) -> String {
+        let mut lyric = match self.unicode_form {
+            Some(UnicodeForm::Nfc) => lyric.nfc().collect(),
+            Some(UnicodeForm::Nfkc) => lyric.nfkc().collect(),
+            None => lyric.to_string(),
+        };
+        if self.half_width_romaji {
+            lyric = lyric.chars().map(to_half_width).collect();
+        }
+        if self.trim_whitespace {
+            lyric = lyric.trim().to_string();
+        }
+        if self.strip_utau_suffixes {
+            let stripped = strip_utau_suffixes(&lyric);
+            if !stripped.is_empty() {
+                lyric = stripped.to_string();
+            }
+        }
+        lyric
+    }
+}
+
+fn to_half_width(c: char) -> char {
+    match c {
+        '\u{3000}' => ' ',
+        '\u{ff01}'..='\u{ff5e}' => char::from_u32(c as u32 - 0xff01 + 0x21).unwrap_or(c),
+        c => c,
+    }
+}
+
+/// Strips trailing arrows and pitch names, with an optional `_` or space before them.
+fn strip_utau_suffixes(lyric: &str) -> &str {
+    let mut lyric = lyric;
+    loop {
+        let stripped = lyric.trim_end_matches(UTAU_SUFFIX_MARKS);
+        let stripped = strip_pitch_name(stripped).unwrap_or(stripped);
+        let stripped = stripped.trim_end_matches(['_', ' ']);
+        if stripped.len() == lyric.len() {
+            return lyric;
+        }
+        lyric = stripped;
+    }
+}
+
+/// Strips a trailing pitch name like "C4", "F#3" or "Bb-1".
+fn strip_pitch_name(lyric: &str) -> Option<&str> {
+    let rest = lyric.trim_end_matches(|c: char| c.is_ascii_digit());
+    if rest.len() == lyric.len() {
+        return None;
+    }
+    let rest = rest.strip_suffix('-').unwrap_or(rest);
+    let rest = rest
+        .strip_suffix('#')
+        .or_else(|| rest.strip_suffix('b'))
+        .unwrap_or(rest);
+    rest.strip_suffix(['A', 'B', 'C', 'D', 'E', 'F', 'G'])
+}
+
+impl ParseOptions {
+    /// Strips prefix map affixes, normalizes lyrics and applies the default lyric policy. `notes` must be sorted by
+    /// position.
+    pub(crate) fn clean_lyrics(&self, notes: &mut Vec<Note>) {
+        if let Some(prefix_map) = &self.prefix_map {
+            prefix_map.strip_from_notes(notes);
+        }
+        if self.lyric_normalization != LyricNormalization::default() {
+            for note in notes.iter_mut() {
+                note.lyric = self.lyric_normalization.normalize(&note.lyric);
+            }
+        }
+        self.default_lyric.apply(notes);
+    }
+}
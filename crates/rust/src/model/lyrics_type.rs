@@ -0,0 +1,120 @@
+//! Detailed results of analyzing the type of Japanese lyrics.
+use super::{JapaneseLyricsType, Note};
+use serde::{Deserialize, Serialize};
+
+/// Ratio of lyrics the most likely type needs to cover, not to be ambiguous.
+const UNAMBIGUOUS_RATIO: f64 = 0.8;
+const VOWELS: [&str; 7] = ["a", "i", "u", "e", "o", "n", "-"];
+
+/// Represents a candidate type of lyrics.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct LyricsTypeCandidate {
+    /// The type of lyrics.
+    pub lyrics_type: JapaneseLyricsType,
+    /// Ratio of lyrics which look like this type, from 0.0 to 1.0. Rests and lyrics which
+    /// don't look like any type are not counted.
+    pub ratio: f64,
+}
+
+/// Represents the result of analyzing the lyrics of a track.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TrackLyricsTypeAnalysis {
+    /// The detected type, or `None` if it cannot be determined.
+    pub lyrics_type: Option<JapaneseLyricsType>,
+    /// Candidate types, the most likely first.
+    pub candidates: Vec<LyricsTypeCandidate>,
+}
+
+/// Represents the result of analyzing the lyrics of a project.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct LyricsTypeAnalysis {
+    /// The detected type, or `None` if it cannot be determined.
+    pub lyrics_type: Option<JapaneseLyricsType>,
+    /// Candidate types of all tracks, the most likely first.
+    pub candidates: Vec<LyricsTypeCandidate>,
+    /// Results of each track.
+    pub tracks: Vec<TrackLyricsTypeAnalysis>,
+}
+impl LyricsTypeAnalysis {
+    /// Returns whether the detection is unreliable, i.e. no type is detected, or the detected
+    /// type covers less than 80% of the lyrics, or tracks disagree.
+    ///
+    /// Callers should ask users for the type instead of converting with an ambiguous result.
+    pub fn is_ambiguous(&self) -> bool {
+        let Some(lyrics_type) = self.lyrics_type else {
+            return true;
+        };
+        let ratio = self
+            .candidates
+            .iter()
+            .find(|candidate| candidate.lyrics_type == lyrics_type)
+            .map_or(0.0, |candidate| candidate.ratio);
+        ratio < UNAMBIGUOUS_RATIO
+            || self.tracks.iter().any(|track| {
+                track
+                    .lyrics_type
+                    .is_some_and(|track_type| track_type != lyrics_type)
+            })
+    }
+}
+
+/// Counts the lyrics which look like each type, and returns the ratios.
+pub(crate) fn candidates<'a>(
+    notes: impl IntoIterator<Item = &'a Note>,
+) -> Vec<LyricsTypeCandidate> {
+    let types = [
+        JapaneseLyricsType::KanaCv,
+        JapaneseLyricsType::KanaVcv,
+        JapaneseLyricsType::RomajiCv,
+        JapaneseLyricsType::RomajiVcv,
+    ];
+    let mut counts = [0usize; 4];
+    for note in notes {
+        if let Some(lyrics_type) = classify(&note.lyric) {
+            counts[types.iter().position(|t| *t == lyrics_type).unwrap_or(0)] += 1;
+        }
+    }
+    let total = counts.iter().sum::<usize>();
+    if total == 0 {
+        return vec![];
+    }
+
+    let mut candidates = types
+        .into_iter()
+        .zip(counts)
+        .filter(|(_, count)| *count > 0)
+        .map(|(lyrics_type, count)| LyricsTypeCandidate {
+            lyrics_type,
+            ratio: count as f64 / total as f64,
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|a, b| b.ratio.total_cmp(&a.ratio));
+    candidates
+}
+
+/// Guesses the type of a lyric, e.g. `a か` is [`JapaneseLyricsType::KanaVcv`].
+fn classify(lyric: &str) -> Option<JapaneseLyricsType> {
+    let (vowel, body) = match lyric.split_once(' ') {
+        Some((vowel, body)) if VOWELS.contains(&vowel) => (Some(vowel), body),
+        _ => (None, lyric),
+    };
+    let is_kana = !body.is_empty() && body.chars().all(is_kana);
+    let is_romaji = !body.is_empty() && body.chars().all(|c| c.is_ascii_lowercase());
+    match (vowel.is_some(), is_kana, is_romaji) {
+        (false, true, _) => Some(JapaneseLyricsType::KanaCv),
+        (true, true, _) => Some(JapaneseLyricsType::KanaVcv),
+        (false, _, true) => Some(JapaneseLyricsType::RomajiCv),
+        (true, _, true) => Some(JapaneseLyricsType::RomajiVcv),
+        _ => None,
+    }
+}
+
+fn is_kana(c: char) -> bool {
+    matches!(c, '\u{3041}'..='\u{309f}' | '\u{30a0}'..='\u{30ff}')
+}
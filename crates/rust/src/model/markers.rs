@@ -0,0 +1,192 @@
+//! Markers of performance hints in lyrics, e.g. `[br]` or `[mf]`, translated on generating.
+use super::{
+    music_xml::{is_tie_stop, note_has},
+    xml, Format, Note, Tick,
+};
+use crate::error::Result;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+/// Dynamic marks, from the softest.
+const DYNAMICS: [&str; 8] = ["ppp", "pp", "p", "mp", "mf", "f", "ff", "fff"];
+
+/// Represents the syntax of markers in lyrics, used by
+/// [`GenerateOptions::markers`](super::GenerateOptions::markers).
+///
+/// These markers are recognized, case-insensitively:
+///
+/// - `br`, `breath`: A breath. A note whose lyric is only this marker becomes a breath note of
+///   the target format (see [`Format::breath_lyric`]), or is removed if the format has none.
+/// - `ppp` to `fff`: A dynamic mark.
+/// - `cresc`, `dim` (or `decresc`): A crescendo or a diminuendo.
+///
+/// Dynamics apply to the note whose lyric has them, or to the next note if they are the whole
+/// lyric of a note, which is removed. They are written as directions in MusicXML, and removed
+/// for other formats. Other text in the delimiters is kept as it is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", default)]
+pub struct LyricMarkers {
+    /// The text which starts a marker.
+    pub open: String,
+    /// The text which ends a marker.
+    pub close: String,
+}
+
+impl Default for LyricMarkers {
+    fn default() -> Self {
+        Self {
+            open: "[".to_string(),
+            close: "]".to_string(),
+        }
+    }
+}
+
+/// Represents a recognized marker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Marker {
+    Breath,
+    Dynamic(&'static str),
+    Crescendo,
+    Diminuendo,
+}
+
+impl Marker {
+    fn parse(name: &str) -> Option<Self> {
+        let name = name.trim().to_ascii_lowercase();
+        match name.as_str() {
+            "br" | "breath" => Some(Self::Breath),
+            "cresc" => Some(Self::Crescendo),
+            "dim" | "decresc" => Some(Self::Diminuendo),
+            _ => DYNAMICS
+                .iter()
+                .find(|dynamic| **dynamic == name)
+                .map(|dynamic| Self::Dynamic(dynamic)),
+        }
+    }
+
+    /// Returns the MusicXML `direction` element of the marker.
+    fn music_xml_direction(&self) -> Option<String> {
+        let direction_type = match self {
+            Self::Breath => return None,
+            Self::Dynamic(dynamic) => format!("<dynamics><{}/></dynamics>", dynamic),
+            Self::Crescendo => "<words>cresc.</words>".to_string(),
+            Self::Diminuendo => "<words>dim.</words>".to_string(),
+        };
+        Some(format!(
+            "<direction placement=\"below\"><direction-type>{}</direction-type></direction>",
+            direction_type
+        ))
+    }
+}
+
+impl LyricMarkers {
+    /// Removes the recognized markers from the lyric, returning the rest of it and the markers.
+    fn split(&self, lyric: &str) -> (String, Vec<Marker>) {
+        let mut markers = vec![];
+        if self.open.is_empty() || self.close.is_empty() {
+            return (lyric.to_string(), markers);
+        }
+        let mut rest = String::with_capacity(lyric.len());
+        let mut remaining = lyric;
+        while let Some(start) = remaining.find(&self.open) {
+            let name_start = start + self.open.len();
+            let Some(length) = remaining[name_start..].find(&self.close) else {
+                break;
+            };
+            let name = &remaining[name_start..name_start + length];
+            let end = name_start + length + self.close.len();
+            match Marker::parse(name) {
+                Some(marker) => {
+                    rest.push_str(&remaining[..start]);
+                    markers.push(marker);
+                }
+                None => rest.push_str(&remaining[..end]),
+            }
+            remaining = &remaining[end..];
+        }
+        rest.push_str(remaining);
+        (rest.trim().to_string(), markers)
+    }
+
+    /// Removes the markers from the lyrics of notes which are generated as `format`, turning
+    /// breath markers into breath notes of the format.
+    pub(crate) fn apply(&self, format: Format, notes: &mut Vec<Note>) {
+        notes.retain_mut(|note| {
+            let (lyric, markers) = self.split(&note.lyric);
+            if markers.is_empty() {
+                return true;
+            }
+            if !lyric.is_empty() {
+                note.lyric = lyric;
+                return true;
+            }
+            match format.breath_lyric() {
+                Some(breath) if markers.contains(&Marker::Breath) => {
+                    note.lyric = breath.to_string();
+                    note.phoneme = None;
+                    true
+                }
+                _ => false,
+            }
+        });
+    }
+
+    /// Returns the ticks of the dynamics in the lyrics, which apply to the first note starting
+    /// at or after them once the markers are removed.
+    pub(crate) fn dynamics(&self, notes: &[Note]) -> Vec<(Tick, Marker)> {
+        notes
+            .iter()
+            .flat_map(|note| {
+                let (_, markers) = self.split(&note.lyric);
+                markers
+                    .into_iter()
+                    .filter(|marker| *marker != Marker::Breath)
+                    .map(move |marker| (note.tick_on, marker))
+            })
+            .collect()
+    }
+}
+
+/// Adds the dynamics returned by [`LyricMarkers::dynamics`] to a MusicXML file of a single part,
+/// generated from `notes`.
+///
+/// The notes are matched with the `note` elements in order, skipping rests, chords and notes
+/// which continue a tie.
+pub(crate) fn add_music_xml_directions(
+    data: Vec<u8>,
+    notes: &[Note],
+    dynamics: &[(Tick, Marker)],
+) -> Result<Vec<u8>> {
+    if dynamics.is_empty() {
+        return Ok(data);
+    }
+    let mut directions = vec![String::new(); notes.len()];
+    for (tick, marker) in dynamics {
+        let Some(index) = notes.iter().position(|note| note.tick_on >= *tick) else {
+            continue;
+        };
+        if let Some(direction) = marker.music_xml_direction() {
+            directions[index].push_str(&direction);
+        }
+    }
+
+    let text =
+        String::from_utf8(data).map_err(|e| anyhow!("Generated MusicXML is not UTF-8: {:?}", e))?;
+    let elements = xml::elements(&text);
+    let notes = elements
+        .iter()
+        .enumerate()
+        .filter(|(_, element)| element.name == "note")
+        .filter(|(index, _)| {
+            !note_has(&elements, *index, "rest")
+                && !note_has(&elements, *index, "chord")
+                && !is_tie_stop(&elements, *index)
+        });
+    let replacements = notes
+        .zip(directions)
+        .filter(|(_, direction)| !direction.is_empty())
+        .map(|((_, note), direction)| (note.range.start..note.range.start, direction))
+        .collect();
+    Ok(xml::splice(&text, replacements).into_bytes())
+}
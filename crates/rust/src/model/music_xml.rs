@@ -0,0 +1,262 @@
+//! Handling of ties, slurs, melismas and key signatures of MusicXML files, which are not kept
+//! by utaformatix-ts.
+use super::{
+    xml::{self, Element},
+    Format, Note, UfData,
+};
+#[cfg(feature = "engine")]
+use super::{KeySignature, Mode};
+#[cfg(feature = "engine")]
+use crate::error::Result;
+#[cfg(feature = "engine")]
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Represents the options for parsing MusicXML files, used by
+/// [`ParseOptions::music_xml`](super::ParseOptions::music_xml).
+///
+/// Both options are off by default, which gives a note for each notehead.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", default)]
+pub struct MusicXmlParseOptions {
+    /// Whether to merge noteheads tied to the previous one (`<tie type="stop"/>`) into it.
+    pub merge_ties: bool,
+    /// Whether to mark notes without a lyric as a melisma of the previous lyric, using the
+    /// continuation lyric (see [`Format::continuation_lyric`]), if they are slurred to the
+    /// previous note, or the previous lyric has `<extend/>` or is the start or middle of a
+    /// word (`<syllabic>`).
+    pub melismas: bool,
+}
+
+/// Notations of a notehead, which are not kept by utaformatix-ts.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Notehead {
+    tie_stop: bool,
+    slurred: bool,
+    extended: bool,
+    has_lyric: bool,
+}
+
+impl MusicXmlParseOptions {
+    /// Applies the options to the data parsed from `source`.
+    ///
+    /// Parsed notes are matched with the noteheads of the first part in order, so nothing is
+    /// changed if their numbers differ.
+    pub(crate) fn apply(&self, source: &[u8], data: &mut UfData) {
+        if !self.merge_ties && !self.melismas {
+            return;
+        }
+        let Some(track) = data.project_mut().tracks.first_mut() else {
+            return;
+        };
+        let noteheads = noteheads(&String::from_utf8_lossy(source));
+        if noteheads.len() != track.notes.len() {
+            warn!(
+                "Found {} noteheads for {} notes, ignoring MusicXML parse options",
+                noteheads.len(),
+                track.notes.len()
+            );
+            return;
+        }
+        track.notes.sort_by_key(|note| note.tick_on);
+
+        let mut notes: Vec<Note> = Vec::with_capacity(track.notes.len());
+        for (mut note, notehead) in track.notes.drain(..).zip(noteheads) {
+            if let Some(previous) = notes.last_mut() {
+                if self.merge_ties
+                    && notehead.tie_stop
+                    && previous.key == note.key
+                    && previous.tick_off == note.tick_on
+                {
+                    previous.tick_off = note.tick_off;
+                    continue;
+                }
+                if self.melismas
+                    && !notehead.has_lyric
+                    && note.lyric.is_empty()
+                    && (notehead.slurred || notehead.extended)
+                {
+                    note.lyric = Format::MusicXml.continuation_lyric().to_string();
+                }
+            }
+            notes.push(note);
+        }
+        track.notes = notes;
+    }
+}
+
+/// Returns the index of the first part (`<part>`) of the elements of a MusicXML document.
+fn first_part(elements: &[Element]) -> Option<usize> {
+    elements.iter().position(|element| element.name == "part")
+}
+
+/// Returns the index of the `key` element in the measure at `index`.
+#[cfg(feature = "engine")]
+fn find_key(elements: &[Element], index: usize) -> Option<usize> {
+    xml::descendants(elements, index)
+        .find(|(_, element)| element.name == "key")
+        .map(|(key, _)| key)
+}
+
+/// Returns whether the `note` element at `index` has a child named `name`, e.g. `rest`.
+pub(super) fn note_has(elements: &[Element], index: usize, name: &str) -> bool {
+    xml::child(elements, index, name).is_some()
+}
+
+/// Returns whether the `note` element at `index` is tied to the previous one
+/// (`<tie type="stop"/>`).
+pub(super) fn is_tie_stop(elements: &[Element], index: usize) -> bool {
+    xml::children(elements, index)
+        .any(|(_, element)| element.name == "tie" && element.attribute("type") == Some("stop"))
+}
+
+/// Sets [`UfProject::key_signatures`](super::UfProject::key_signatures) of the data parsed from
+/// `source`, from the traditional key signatures (`<key><fifths>`) of the measures of the first
+/// part. Modes other than `minor` are read as major.
+#[cfg(feature = "engine")]
+pub(crate) fn read_key_signatures(source: &[u8], data: &mut UfData) {
+    let text = String::from_utf8_lossy(source);
+    let elements = xml::elements(&text);
+    let mut key_signatures: Vec<KeySignature> = vec![];
+    let measures = first_part(&elements)
+        .into_iter()
+        .flat_map(|part| xml::children(&elements, part))
+        .filter(|(_, element)| element.name == "measure");
+    for (measure_position, (measure, _)) in (0..).zip(measures) {
+        let Some(key) = find_key(&elements, measure) else {
+            continue;
+        };
+        let Some(fifths) =
+            xml::child(&elements, key, "fifths").and_then(|fifths| fifths.text.trim().parse().ok())
+        else {
+            continue;
+        };
+        let mode = match xml::child(&elements, key, "mode").map(|mode| mode.text.trim()) {
+            Some("minor") => Mode::Minor,
+            _ => Mode::Major,
+        };
+        let key_signature = KeySignature {
+            measure_position,
+            ..KeySignature::from_fifths(fifths, mode)
+        };
+        let unchanged = key_signatures.last().is_some_and(|last| {
+            (last.tonic, last.mode) == (key_signature.tonic, key_signature.mode)
+        });
+        if !unchanged {
+            key_signatures.push(key_signature);
+        }
+    }
+    data.project_mut().key_signatures = key_signatures;
+}
+
+/// Writes the key signatures to a MusicXML file of a single part, as `<key>` elements in the
+/// attributes of the measures where they start, replacing the existing ones there.
+#[cfg(feature = "engine")]
+pub(crate) fn write_key_signatures(
+    data: Vec<u8>,
+    key_signatures: &[KeySignature],
+) -> Result<Vec<u8>> {
+    if key_signatures.is_empty() {
+        return Ok(data);
+    }
+    let text =
+        String::from_utf8(data).map_err(|e| anyhow!("Generated MusicXML is not UTF-8: {:?}", e))?;
+    let elements = xml::elements(&text);
+    let measures = (0..).zip(
+        elements
+            .iter()
+            .enumerate()
+            .filter(|(_, element)| element.name == "measure"),
+    );
+    let mut replacements = vec![];
+    for (measure_position, (index, measure)) in measures {
+        let Some(key_signature) = key_signatures
+            .iter()
+            .find(|key_signature| key_signature.measure_position == measure_position)
+        else {
+            continue;
+        };
+        let key = format!(
+            "<key><fifths>{}</fifths><mode>{}</mode></key>",
+            key_signature.fifths(),
+            match key_signature.mode {
+                Mode::Major => "major",
+                Mode::Minor => "minor",
+            }
+        );
+        if let Some(existing) = find_key(&elements, index) {
+            replacements.push((elements[existing].range.clone(), key));
+            continue;
+        }
+        let Some(attributes) =
+            xml::children(&elements, index).find(|(_, element)| element.name == "attributes")
+        else {
+            let start = measure.content.start;
+            replacements.push((start..start, format!("<attributes>{key}</attributes>")));
+            continue;
+        };
+        // `key` comes after `divisions` and before `time` in `attributes`.
+        let position = xml::child(&elements, attributes.0, "divisions")
+            .map_or(attributes.1.content.start, |divisions| divisions.range.end);
+        replacements.push((position..position, key));
+    }
+    Ok(xml::splice(&text, replacements).into_bytes())
+}
+
+/// Returns the pitched noteheads of the first part, skipping rests, chords and grace notes.
+fn noteheads(text: &str) -> Vec<Notehead> {
+    let elements = xml::elements(text);
+    let Some(part) = first_part(&elements) else {
+        return vec![];
+    };
+
+    let mut noteheads = vec![];
+    let mut in_slur = false;
+    let mut in_extend = false;
+    let notes = xml::descendants(&elements, part).filter(|(_, element)| element.name == "note");
+    for (index, _) in notes {
+        if ["rest", "chord", "grace"]
+            .iter()
+            .any(|name| note_has(&elements, index, name))
+        {
+            continue;
+        }
+        let slurs = xml::descendants(&elements, index)
+            .map(|(_, element)| element)
+            .filter(|element| element.name == "slur")
+            .filter_map(|slur| slur.attribute("type"))
+            .collect::<Vec<_>>();
+        let lyrics = xml::children(&elements, index)
+            .filter(|(_, element)| element.name == "lyric")
+            .map(|(lyric, _)| lyric)
+            .collect::<Vec<_>>();
+        let has_lyric = !lyrics.is_empty();
+        let notehead = Notehead {
+            tie_stop: is_tie_stop(&elements, index),
+            slurred: in_slur,
+            extended: in_extend && !has_lyric,
+            has_lyric,
+        };
+        if slurs.contains(&"start") {
+            in_slur = true;
+        }
+        if slurs.contains(&"stop") {
+            in_slur = false;
+        }
+        if has_lyric {
+            // The syllable is held over the following noteheads if it's extended, or if it's
+            // not the end of a word.
+            in_extend = lyrics.iter().any(|&lyric| {
+                let extended = xml::child(&elements, lyric, "extend")
+                    .is_some_and(|extend| extend.attribute("type") != Some("stop"));
+                let syllabic = xml::child(&elements, lyric, "syllabic")
+                    .is_some_and(|syllabic| matches!(syllabic.text.trim(), "begin" | "middle"));
+                extended || syllabic
+            });
+        }
+        noteheads.push(notehead);
+    }
+    noteheads
+}
@@ -0,0 +1,53 @@
+//! Editing utilities of notes, and names of keys.
+use super::{Note, Tick};
+
+const PITCH_CLASS_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+impl Note {
+    /// Splits the note at the tick, and returns the second half with `second_lyric` as its
+    /// lyric.
+    ///
+    /// Returns `None` and leaves the note untouched if the tick is not inside the note.
+    pub fn split_at(&mut self, tick: Tick, second_lyric: &str) -> Option<Note> {
+        if tick <= self.tick_on || tick >= self.tick_off {
+            return None;
+        }
+        let second = Note {
+            key: self.key,
+            tick_on: tick,
+            tick_off: self.tick_off,
+            lyric: second_lyric.to_string(),
+            phoneme: None,
+            phoneme_segments: None,
+        };
+        self.tick_off = tick;
+
+        Some(second)
+    }
+}
+
+/// Returns the name of the key, e.g. `C4` for 60.
+pub(crate) fn key_name(key: i32) -> String {
+    format!(
+        "{}{}",
+        PITCH_CLASS_NAMES[key.rem_euclid(12) as usize],
+        key.div_euclid(12) - 1
+    )
+}
+
+/// Parses a key name like `C4` (60) or `A#3`. Returns `None` if the octave is too far out of
+/// range to be represented.
+pub(crate) fn parse_key_name(name: &str) -> Option<i32> {
+    let octave_start = name.find(|c: char| c.is_ascii_digit() || c == '-')?;
+    let (pitch_class, octave) = name.split_at(octave_start);
+    let pitch_class = PITCH_CLASS_NAMES
+        .iter()
+        .position(|candidate| *candidate == pitch_class)?;
+    let octave = octave.parse::<i32>().ok()?;
+    octave
+        .checked_add(1)?
+        .checked_mul(12)?
+        .checked_add(pitch_class as i32)
+}
@@ -0,0 +1,77 @@
+//! Interval-indexed lookups of notes.
+use super::{Note, Tick, Track};
+use std::ops::Range;
+
+/// An index of the notes of a track, which answers position queries in `O(log n + k)`.
+///
+/// Created by [`Track::index`]. Building it sorts the notes, so reuse it for repeated queries.
+#[derive(Debug, Clone)]
+pub struct NoteIndex<'a> {
+    /// Notes sorted by their start.
+    notes: Vec<&'a Note>,
+    /// The maximum end of the notes up to each position in `notes`.
+    max_tick_offs: Vec<Tick>,
+}
+
+impl<'a> NoteIndex<'a> {
+    /// Creates an index of the notes.
+    pub fn new(notes: &'a [Note]) -> Self {
+        let mut notes = notes.iter().collect::<Vec<_>>();
+        notes.sort_by_key(|note| note.tick_on);
+        let max_tick_offs = notes
+            .iter()
+            .scan(None, |max, note| {
+                *max = (*max).max(Some(note.tick_off));
+                *max
+            })
+            .collect();
+
+        Self {
+            notes,
+            max_tick_offs,
+        }
+    }
+
+    /// Returns the notes overlapping the tick range, sorted by their start.
+    pub fn notes_in_range(&self, range: Range<Tick>) -> Vec<&'a Note> {
+        let end = self.notes.partition_point(|note| note.tick_on < range.end);
+        let start = self.max_tick_offs[..end].partition_point(|max| *max <= range.start);
+        self.notes[start..end]
+            .iter()
+            .filter(|note| note.tick_off > range.start)
+            .copied()
+            .collect()
+    }
+
+    /// Returns the notes sounding at the tick, sorted by their start.
+    pub fn notes_at(&self, tick: Tick) -> Vec<&'a Note> {
+        self.notes_in_range(tick..tick.saturating_add(1))
+    }
+}
+
+impl Track {
+    /// Creates an index of the notes for repeated position queries.
+    pub fn index(&self) -> NoteIndex<'_> {
+        NoteIndex::new(&self.notes)
+    }
+
+    /// Returns the notes overlapping the tick range, sorted by their start.
+    ///
+    /// This scans every note; use [`Track::index`] for repeated queries.
+    pub fn notes_in_range(&self, range: Range<Tick>) -> Vec<&Note> {
+        let mut notes = self
+            .notes
+            .iter()
+            .filter(|note| note.tick_on < range.end && note.tick_off > range.start)
+            .collect::<Vec<_>>();
+        notes.sort_by_key(|note| note.tick_on);
+        notes
+    }
+
+    /// Returns the notes sounding at the tick, sorted by their start.
+    ///
+    /// This scans every note; use [`Track::index`] for repeated queries.
+    pub fn notes_at(&self, tick: Tick) -> Vec<&Note> {
+        self.notes_in_range(tick..tick.saturating_add(1))
+    }
+}
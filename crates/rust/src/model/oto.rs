@@ -0,0 +1,139 @@
+//! UTAU voicebank configurations (oto.ini), for checking lyrics before synthesis.
+use super::{PrefixMap, Track};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Lyrics which don't need a sample.
+const REST_LYRICS: [&str; 3] = ["", "R", "r"];
+/// Maximum number of suggestions of an unmatched lyric.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Represents the aliases defined by oto.ini files of an UTAU voicebank.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Oto {
+    aliases: BTreeSet<String>,
+}
+
+/// Represents a note whose lyric is not in the voicebank, reported by [`Oto::validate`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnmatchedLyric {
+    /// Index of the track.
+    pub track: usize,
+    /// Index of the note in the track.
+    pub note: usize,
+    /// The lyric of the note.
+    pub lyric: String,
+    /// Aliases similar to the lyric, the most similar first.
+    pub suggestions: Vec<String>,
+}
+
+impl Oto {
+    /// Parses an oto.ini file, whose lines look like `file.wav=alias,offset,...`. Shift_JIS
+    /// files are also accepted.
+    ///
+    /// Entries without an alias use the file name without the extension, as UTAU does.
+    pub fn from_bytes(data: &[u8]) -> Self {
+        Self::parse(&crate::native::standard_mid::decode_text(data))
+    }
+
+    /// Parses the content of an oto.ini file. See [`Oto::from_bytes`].
+    pub fn parse(content: &str) -> Self {
+        let aliases = content
+            .lines()
+            .filter_map(|line| {
+                let (file, parameters) = line.split_once('=')?;
+                let alias = parameters.split(',').next().unwrap_or_default();
+                if alias.is_empty() {
+                    let stem = file.rsplit_once('.').map_or(file, |(stem, _)| stem);
+                    Some(stem.rsplit(['/', '\\']).next().unwrap_or(stem).to_string())
+                } else {
+                    Some(alias.to_string())
+                }
+            })
+            .filter(|alias| !alias.is_empty())
+            .collect();
+
+        Self { aliases }
+    }
+
+    /// Adds the aliases of another oto.ini, e.g. of a subdirectory of the voicebank.
+    pub fn extend(&mut self, other: Oto) {
+        self.aliases.extend(other.aliases);
+    }
+
+    /// Returns whether the alias is defined.
+    pub fn contains(&self, alias: &str) -> bool {
+        self.aliases.contains(alias)
+    }
+
+    /// Returns the aliases.
+    pub fn aliases(&self) -> impl Iterator<Item = &str> {
+        self.aliases.iter().map(String::as_str)
+    }
+
+    /// Finds notes whose lyric is not an alias of the voicebank, with suggestions of similar
+    /// aliases. Rests (empty lyrics, `R` and `r`) are not reported.
+    ///
+    /// With a prefix map, the lyric with its prefix and suffix is looked up first, and then
+    /// the lyric itself, like UTAU does.
+    pub fn validate(
+        &self,
+        tracks: &[Track],
+        prefix_map: Option<&PrefixMap>,
+    ) -> Vec<UnmatchedLyric> {
+        let mut unmatched = vec![];
+        for (track_index, track) in tracks.iter().enumerate() {
+            for (note_index, note) in track.notes.iter().enumerate() {
+                if REST_LYRICS.contains(&note.lyric.as_str()) || self.contains(&note.lyric) {
+                    continue;
+                }
+                if prefix_map.is_some_and(|prefix_map| {
+                    self.contains(&prefix_map.apply(&note.lyric, note.key))
+                }) {
+                    continue;
+                }
+                unmatched.push(UnmatchedLyric {
+                    track: track_index,
+                    note: note_index,
+                    lyric: note.lyric.clone(),
+                    suggestions: self.suggest(&note.lyric),
+                });
+            }
+        }
+        unmatched
+    }
+
+    /// Returns aliases within an edit distance of half the lyric's length.
+    fn suggest(&self, lyric: &str) -> Vec<String> {
+        let max_distance = (lyric.chars().count() / 2).max(1);
+        let mut candidates = self
+            .aliases
+            .iter()
+            .map(|alias| (edit_distance(lyric, alias), alias))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .collect::<Vec<_>>();
+        candidates.sort();
+        candidates
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, alias)| alias.clone())
+            .collect()
+    }
+}
+
+/// Levenshtein distance in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a != *b);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
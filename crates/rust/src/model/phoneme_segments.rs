@@ -0,0 +1,173 @@
+//! Timing of the phonemes of notes, read from and written to Synthesizer V's projects, which
+//! utaformatix-ts doesn't carry over.
+#[cfg(feature = "engine")]
+use super::{
+    svp::{edit_svp_tracks, svp_tracks, BLICKS_PER_TICK},
+    Note, UfData,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+};
+
+/// Represents a phoneme of a note with its timing, used by
+/// [`Note::phoneme_segments`](super::Note::phoneme_segments).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct PhonemeSegment {
+    /// The phoneme. (e.g. `k`)
+    pub phoneme: String,
+    /// Duration of the phoneme relative to its default duration, which is 1.0.
+    pub duration_scale: f64,
+}
+
+/// Segments are compared by the bits of the scales, so that notes can be ordered and hashed.
+impl PartialEq for PhonemeSegment {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PhonemeSegment {}
+
+impl Ord for PhonemeSegment {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.phoneme
+            .cmp(&other.phoneme)
+            .then_with(|| self.duration_scale.total_cmp(&other.duration_scale))
+    }
+}
+
+impl PartialOrd for PhonemeSegment {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Hash for PhonemeSegment {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.phoneme.hash(state);
+        self.duration_scale.to_bits().hash(state);
+    }
+}
+
+/// Returns whether a note of an svp file is at the same position and key as `note`.
+#[cfg(feature = "engine")]
+fn matches_svp_note(note: &Note, svp_note: &serde_json::Value) -> bool {
+    let onset = svp_note.get("onset").and_then(serde_json::Value::as_i64);
+    let pitch = svp_note.get("pitch").and_then(serde_json::Value::as_i64);
+    onset == Some(note.tick_on.get() * BLICKS_PER_TICK) && pitch == Some(i64::from(note.key.get()))
+}
+
+/// Sets [`Note::phoneme_segments`] of the notes parsed from an svp project, from the phonemes
+/// and the duration scales (`attributes.dur`) of its notes.
+///
+/// Only the notes of the main group of each track are read, and notes without duration scales
+/// or phonemes are left as they are.
+#[cfg(feature = "engine")]
+pub(crate) fn read_svp_phoneme_segments(project: &serde_json::Value, data: &mut UfData) {
+    let svp_tracks = svp_tracks(project);
+    let tracks = &mut data.project_mut().tracks;
+    if svp_tracks.len() != tracks.len() {
+        return;
+    }
+    for (svp_track, track) in svp_tracks.iter().zip(tracks) {
+        let Some(svp_notes) = svp_track
+            .pointer("/mainGroup/notes")
+            .and_then(serde_json::Value::as_array)
+        else {
+            continue;
+        };
+        for svp_note in svp_notes {
+            let Some(scales) = svp_note
+                .pointer("/attributes/dur")
+                .and_then(serde_json::Value::as_array)
+                .filter(|scales| !scales.is_empty())
+            else {
+                continue;
+            };
+            let Some(note) = track
+                .notes
+                .iter_mut()
+                .find(|note| matches_svp_note(note, svp_note))
+            else {
+                continue;
+            };
+            let phonemes = svp_note
+                .get("phonemes")
+                .and_then(serde_json::Value::as_str)
+                .filter(|phonemes| !phonemes.trim().is_empty())
+                .or(note.phoneme.as_deref())
+                .unwrap_or_default();
+            let segments = phonemes
+                .split_whitespace()
+                .enumerate()
+                .map(|(i, phoneme)| PhonemeSegment {
+                    phoneme: phoneme.to_string(),
+                    duration_scale: scales
+                        .get(i)
+                        .and_then(serde_json::Value::as_f64)
+                        .unwrap_or(1.0),
+                })
+                .collect::<Vec<_>>();
+            if !segments.is_empty() {
+                note.phoneme_segments = Some(segments);
+            }
+        }
+    }
+}
+
+/// Writes [`Note::phoneme_segments`] to an svp project generated from `data`, as the phonemes
+/// and the duration scales (`attributes.dur`) of the notes of the main group of each track.
+#[cfg(feature = "engine")]
+pub(crate) fn write_svp_phoneme_segments(project: &mut serde_json::Value, data: &UfData) {
+    let tracks = &data.project().tracks;
+    let has_segments = tracks
+        .iter()
+        .flat_map(|track| &track.notes)
+        .any(|note| note.phoneme_segments.is_some());
+    if !has_segments {
+        return;
+    }
+    edit_svp_tracks(project, |index, svp_track| {
+        let (Some(track), Some(svp_notes)) = (
+            tracks.get(index),
+            svp_track
+                .pointer_mut("/mainGroup/notes")
+                .and_then(serde_json::Value::as_array_mut),
+        ) else {
+            return;
+        };
+        for svp_note in svp_notes {
+            let Some(segments) = track
+                .notes
+                .iter()
+                .find(|note| matches_svp_note(note, svp_note))
+                .and_then(|note| note.phoneme_segments.as_ref())
+            else {
+                continue;
+            };
+            let Some(svp_note) = svp_note.as_object_mut() else {
+                continue;
+            };
+            let phonemes = segments
+                .iter()
+                .map(|segment| segment.phoneme.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            svp_note.insert("phonemes".to_string(), phonemes.into());
+            let attributes = svp_note
+                .entry("attributes")
+                .or_insert_with(|| serde_json::json!({}));
+            if let Some(attributes) = attributes.as_object_mut() {
+                let scales = segments
+                    .iter()
+                    .map(|segment| segment.duration_scale)
+                    .collect::<Vec<_>>();
+                attributes.insert("dur".to_string(), scales.into());
+            }
+        }
+    });
+}
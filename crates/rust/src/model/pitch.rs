@@ -0,0 +1,19 @@
+use super::{Key, Pitch, Tick};
+
+impl Pitch {
+    /// Returns the pitch in semitones at the tick, holding the last data point, or `None` if the
+    /// curve has no value there. `key` is the key of the note at the tick, which relative values
+    /// are based on.
+    pub fn key_at(&self, tick: Tick, key: Key) -> Option<f64> {
+        let index = self
+            .ticks
+            .partition_point(|point| i64::from(*point) <= tick.get())
+            .checked_sub(1)?;
+        let value = (*self.values.get(index)?)?;
+        Some(if self.is_absolute {
+            value
+        } else {
+            f64::from(key.get()) + value
+        })
+    }
+}
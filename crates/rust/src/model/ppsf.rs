@@ -0,0 +1,64 @@
+//! Inspection of Piapro Studio's project files, including legacy ones which can't be parsed.
+use super::xml;
+use serde::{Deserialize, Serialize};
+
+/// Helpers for Piapro Studio's project files.
+///
+/// Piapro Studio NT writes ZIP archives, which are parsed by
+/// [`UtaFormatix::parse_ppsf`](crate::base::UtaFormatix::parse_ppsf). Files of older versions
+/// fail with [`ErrorKind::UnsupportedLegacyPpsf`](crate::ErrorKind::UnsupportedLegacyPpsf), and
+/// need to be saved by Piapro Studio NT first.
+#[derive(Debug, Clone, Copy)]
+pub struct Ppsf;
+
+/// Represents the metadata of a Piapro Studio project file, returned by [`Ppsf::inspect`].
+///
+/// The metadata is only read from legacy files, since other files can be parsed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct PpsfInfo {
+    /// Whether the file is in the legacy format, which isn't a ZIP archive.
+    pub legacy: bool,
+    /// Version written in the root element, if any.
+    pub version: Option<String>,
+    /// Name of the project written in the root element, if any.
+    pub project_name: Option<String>,
+    /// Number of `track` elements, if any.
+    pub track_count: Option<usize>,
+}
+
+impl Ppsf {
+    /// Reads whatever metadata is recoverable from a Piapro Studio project file, e.g. to tell
+    /// the user what a legacy file is instead of only failing to parse it.
+    ///
+    /// Legacy files are read as XML on a best-effort basis: the version and the project name are
+    /// taken from the attributes of the root element, and anything which can't be found is
+    /// `None`. Returns `None` if the data is neither a ZIP archive nor an XML document with a
+    /// root element, so it isn't a project file at all. This doesn't use the JS thread.
+    pub fn inspect(data: &[u8]) -> Option<PpsfInfo> {
+        if data.starts_with(b"PK\x03\x04") {
+            return Some(PpsfInfo::default());
+        }
+        let (text, _, _) = encoding_rs::UTF_8.decode(data);
+        let elements = xml::elements(&text);
+        let root = elements.first()?;
+        let attribute = |names: &[&str]| {
+            root.attributes
+                .iter()
+                .find(|(key, _)| names.iter().any(|name| key.eq_ignore_ascii_case(name)))
+                .map(|(_, value)| value.clone())
+                .filter(|value| !value.is_empty())
+        };
+        let track_count = elements
+            .iter()
+            .filter(|element| element.name.eq_ignore_ascii_case("track"))
+            .count();
+        Some(PpsfInfo {
+            legacy: true,
+            version: attribute(&["version", "ver"]),
+            project_name: attribute(&["name", "projectName", "title"]),
+            track_count: (track_count > 0).then_some(track_count),
+        })
+    }
+}
@@ -0,0 +1,96 @@
+//! UTAU prefix maps (prefix.map of voicebanks).
+use super::{parse_key_name, Key, Note};
+use std::collections::BTreeMap;
+
+/// Represents a prefix map of an UTAU voicebank, which selects samples by adding a prefix and
+/// a suffix to lyrics depending on the key.
+///
+/// Used by [`ParseOptions::prefix_map`](super::ParseOptions::prefix_map) to strip them, and
+/// [`GenerateOptions::prefix_map`](super::GenerateOptions::prefix_map) to add them on
+/// generating UST files.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PrefixMap {
+    /// Prefix and suffix of each key.
+    entries: BTreeMap<i32, (String, String)>,
+}
+
+impl PrefixMap {
+    /// Parses a prefix.map file, which consists of lines of a key name (e.g. `C#4`), a prefix
+    /// and a suffix separated by tabs. Shift_JIS files are also accepted.
+    ///
+    /// Lines with an unknown key name are ignored, as UTAU does.
+    pub fn from_bytes(data: &[u8]) -> Self {
+        Self::parse(&crate::native::standard_mid::decode_text(data))
+    }
+
+    /// Parses the content of a prefix.map file. See [`PrefixMap::from_bytes`].
+    pub fn parse(content: &str) -> Self {
+        let entries = content
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('\t');
+                let key = parse_key_name(fields.next()?.trim())?;
+                let prefix = fields.next().unwrap_or_default().to_string();
+                let suffix = fields.next().unwrap_or_default().trim_end().to_string();
+                Some((key, (prefix, suffix)))
+            })
+            .collect();
+
+        Self { entries }
+    }
+
+    /// Sets the prefix and suffix of the key.
+    pub fn insert(&mut self, key: Key, prefix: impl Into<String>, suffix: impl Into<String>) {
+        self.entries
+            .insert(key.get(), (prefix.into(), suffix.into()));
+    }
+
+    /// Returns the prefix and suffix of the key.
+    pub fn get(&self, key: Key) -> Option<(&str, &str)> {
+        self.entries
+            .get(&key.get())
+            .map(|(prefix, suffix)| (prefix.as_str(), suffix.as_str()))
+    }
+
+    /// Adds the prefix and suffix of the key to the lyric.
+    pub fn apply(&self, lyric: &str, key: Key) -> String {
+        match self.get(key) {
+            Some((prefix, suffix)) => format!("{prefix}{lyric}{suffix}"),
+            None => lyric.to_string(),
+        }
+    }
+
+    /// Removes the prefix and suffix of the key from the lyric, if the lyric has them.
+    pub fn strip<'a>(&self, lyric: &'a str, key: Key) -> &'a str {
+        let Some((prefix, suffix)) = self.get(key) else {
+            return lyric;
+        };
+        lyric
+            .strip_prefix(prefix)
+            .and_then(|lyric| lyric.strip_suffix(suffix))
+            .filter(|lyric| !lyric.is_empty())
+            .unwrap_or(lyric)
+    }
+
+    /// Adds the prefixes and suffixes to the lyrics of the notes.
+    pub(crate) fn apply_to_notes(&self, notes: &mut [Note]) {
+        for note in notes {
+            note.lyric = self.apply(&note.lyric, note.key);
+        }
+    }
+
+    /// Returns the keys with their prefixes and suffixes, in the order of the keys.
+    #[cfg(feature = "cache")]
+    pub(crate) fn entries(&self) -> impl Iterator<Item = (i32, &str, &str)> {
+        self.entries
+            .iter()
+            .map(|(key, (prefix, suffix))| (*key, prefix.as_str(), suffix.as_str()))
+    }
+
+    /// Removes the prefixes and suffixes from the lyrics of the notes.
+    pub(crate) fn strip_from_notes(&self, notes: &mut [Note]) {
+        for note in notes {
+            note.lyric = self.strip(&note.lyric, note.key).to_string();
+        }
+    }
+}
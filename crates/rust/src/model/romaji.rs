@@ -0,0 +1,191 @@
+//! Conversion between romanization styles of Japanese lyrics.
+use super::Track;
+use serde::{Deserialize, Serialize};
+
+/// Syllables which differ between the styles, in Hepburn, Kunrei-shiki and Nihon-shiki.
+///
+/// Syllables are read in this order, so "ji" is read as "じ" rather than "ぢ".
+const SYLLABLES: [[&str; 3]; 20] = [
+    ["shi", "si", "si"],
+    ["sha", "sya", "sya"],
+    ["shu", "syu", "syu"],
+    ["sho", "syo", "syo"],
+    ["chi", "ti", "ti"],
+    ["cha", "tya", "tya"],
+    ["chu", "tyu", "tyu"],
+    ["cho", "tyo", "tyo"],
+    ["tsu", "tu", "tu"],
+    ["fu", "hu", "hu"],
+    ["ji", "zi", "zi"],
+    ["ja", "zya", "zya"],
+    ["ju", "zyu", "zyu"],
+    ["jo", "zyo", "zyo"],
+    ["zu", "zu", "zu"],
+    // ぢ, ぢゃ, ぢゅ, ぢょ and づ, whose Hepburn can be replaced by `D_ROW_HEPBURN`.
+    ["ji", "zi", "di"],
+    ["ja", "zya", "dya"],
+    ["ju", "zyu", "dyu"],
+    ["jo", "zyo", "dyo"],
+    ["zu", "zu", "du"],
+];
+/// Index of the first syllable of the "d" row in [`SYLLABLES`].
+const D_ROW_START: usize = 15;
+/// Hepburn of the "d" row which distinguishes it from the "z" row.
+const D_ROW_HEPBURN: [&str; 5] = ["dji", "dja", "dju", "djo", "dzu"];
+/// Length of the longest syllable.
+const MAX_SYLLABLE_LENGTH: usize = 3;
+
+/// Represents a style of romanization.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum RomajiStyle {
+    /// Hepburn, e.g. "shi", "chi", "tsu", "fu", "ji".
+    #[default]
+    Hepburn,
+    /// Kunrei-shiki, e.g. "si", "ti", "tu", "hu", "zi".
+    Kunrei,
+    /// Nihon-shiki, which is Kunrei-shiki with "di" and "du" for "ぢ" and "づ".
+    NihonShiki,
+}
+
+impl RomajiStyle {
+    fn column(&self) -> usize {
+        match self {
+            Self::Hepburn => 0,
+            Self::Kunrei => 1,
+            Self::NihonShiki => 2,
+        }
+    }
+}
+
+/// Represents the case of converted romaji.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum RomajiCase {
+    /// Keeps the case of each syllable.
+    #[default]
+    Keep,
+    /// Converts to lowercase, e.g. "Shi" becomes "shi".
+    Lower,
+    /// Converts to uppercase, e.g. "shi" becomes "SHI".
+    Upper,
+}
+
+/// Represents the options for converting romaji lyrics between styles, e.g. to match the
+/// aliases of a CV voicebank.
+///
+/// Only ASCII letters are converted, so kana and suffixes like "↑" are kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", default)]
+pub struct RomajiOptions {
+    /// Style of the lyrics. Syllables are ambiguous between styles (e.g. "ti" is "ち" in
+    /// Kunrei-shiki but "ティ" in Hepburn), so this can't be detected.
+    pub from: RomajiStyle,
+    /// Style to convert to.
+    pub to: RomajiStyle,
+    /// Whether to write "ぢ" and "づ" as "dji" and "dzu" (and "dja" etc.) in Hepburn, which some
+    /// voicebanks use to distinguish them from "じ" and "ず". They are also read if this is set.
+    pub distinct_d_row: bool,
+    /// Case of the converted lyrics.
+    pub case: RomajiCase,
+}
+
+impl RomajiOptions {
+    /// Returns the lyric converted to [`RomajiOptions::to`].
+    pub fn convert(&self, lyric: &str) -> String {
+        let chars = lyric.chars().collect::<Vec<_>>();
+        let mut converted = String::with_capacity(lyric.len());
+        let mut i = 0;
+        while i < chars.len() {
+            let Some((length, syllable)) = self.read_syllable(&chars, i) else {
+                converted.push(chars[i]);
+                i += 1;
+                continue;
+            };
+            let mut target = self.syllable(syllable, self.to).to_string();
+            let source = &chars[i..i + length];
+            if source.iter().all(char::is_ascii_uppercase) {
+                target.make_ascii_uppercase();
+            } else if source[0].is_ascii_uppercase() {
+                target[..1].make_ascii_uppercase();
+            }
+            // Geminates take the consonant of the converted syllable, e.g. "ssi" to "sshi",
+            // except "ch" which takes "t", e.g. "tti" to "tchi".
+            if i > 0 && is_geminate(chars[i - 1], chars[i]) {
+                let consonant = if target
+                    .get(..2)
+                    .is_some_and(|head| head.eq_ignore_ascii_case("ch"))
+                {
+                    't'
+                } else {
+                    target.chars().next().unwrap_or(chars[i])
+                };
+                converted.pop();
+                converted.push(if chars[i - 1].is_ascii_uppercase() {
+                    consonant.to_ascii_uppercase()
+                } else {
+                    consonant.to_ascii_lowercase()
+                });
+            }
+            converted.push_str(&target);
+            i += length;
+        }
+
+        match self.case {
+            RomajiCase::Keep => converted,
+            RomajiCase::Lower => converted.to_ascii_lowercase(),
+            RomajiCase::Upper => converted.to_ascii_uppercase(),
+        }
+    }
+
+    /// Reads the syllable at `start`, returning its length and its index in [`SYLLABLES`].
+    fn read_syllable(&self, chars: &[char], start: usize) -> Option<(usize, usize)> {
+        // Syllables start after vowels, "n" and other non-consonants, or after a geminate.
+        if let Some(previous) = start.checked_sub(1).map(|i| chars[i].to_ascii_lowercase()) {
+            let is_boundary = !previous.is_ascii_alphabetic()
+                || "aiueon".contains(previous)
+                || is_geminate(previous, chars[start]);
+            if !is_boundary {
+                return None;
+            }
+        }
+        (1..=MAX_SYLLABLE_LENGTH).rev().find_map(|length| {
+            let text = chars.get(start..start + length)?;
+            if !text.iter().all(char::is_ascii_alphabetic) {
+                return None;
+            }
+            let text = text.iter().collect::<String>().to_ascii_lowercase();
+            (0..SYLLABLES.len())
+                .find(|&syllable| self.syllable(syllable, self.from) == text)
+                .map(|syllable| (length, syllable))
+        })
+    }
+
+    fn syllable(&self, syllable: usize, style: RomajiStyle) -> &'static str {
+        match syllable.checked_sub(D_ROW_START) {
+            Some(d_row) if self.distinct_d_row && style == RomajiStyle::Hepburn => D_ROW_HEPBURN
+                .get(d_row)
+                .copied()
+                .unwrap_or(SYLLABLES[syllable][0]),
+            _ => SYLLABLES[syllable][style.column()],
+        }
+    }
+}
+
+/// Returns whether `previous` doubles the consonant `c`, e.g. "s" of "sshi" or "t" of "tchi".
+fn is_geminate(previous: char, c: char) -> bool {
+    let (previous, c) = (previous.to_ascii_lowercase(), c.to_ascii_lowercase());
+    !"aiueon".contains(previous) && (previous == c || (previous, c) == ('t', 'c'))
+}
+
+impl Track {
+    /// Converts the romaji lyrics of the notes. See [`RomajiOptions`].
+    pub fn convert_romaji(&mut self, options: &RomajiOptions) {
+        for note in &mut self.notes {
+            note.lyric = options.convert(&note.lyric);
+        }
+    }
+}
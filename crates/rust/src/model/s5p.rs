@@ -0,0 +1,36 @@
+//! Parameters of old Synthesizer V projects which utaformatix-ts doesn't carry over to
+//! Synthesizer V Studio projects.
+use super::{
+    format_options::set_svp_database_names,
+    svp::{svp_json, svp_tracks},
+};
+use crate::error::Result;
+use tracing::warn;
+
+/// Returns the name of the voice database (`dbName`) of each track of an s5p file, in the order
+/// of the tracks.
+fn database_names(s5p: &[u8]) -> Vec<Option<String>> {
+    let Some(project) = svp_json(s5p) else {
+        warn!("Failed to read the voice databases of s5p: Not JSON");
+        return vec![];
+    };
+    svp_tracks(&project)
+        .iter()
+        .map(|track| {
+            track
+                .get("dbName")
+                .and_then(serde_json::Value::as_str)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+        })
+        .collect()
+}
+
+/// Writes the voice database of each track of an s5p file to an svp file generated from it.
+pub(crate) fn copy_database_names(svp: Vec<u8>, s5p: &[u8]) -> Result<Vec<u8>> {
+    let names = database_names(s5p);
+    if names.iter().all(Option::is_none) {
+        return Ok(svp);
+    }
+    set_svp_database_names(svp, |index| names.get(index)?.as_deref())
+}
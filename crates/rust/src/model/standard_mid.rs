@@ -0,0 +1,51 @@
+//! Selection of tracks and channels of Standard MIDI files on parsing.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// MIDI channel of the General MIDI percussion, 10 in one-based numbering.
+const DRUM_CHANNEL: u8 = 9;
+
+/// Represents the options for parsing Standard MIDI files, used by
+/// [`ParseOptions::standard_mid`](super::ParseOptions::standard_mid).
+///
+/// Every track with notes becomes a track by default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", default)]
+pub struct StandardMidParseOptions {
+    /// Indices of the track chunks to parse, starting from 0 (which is usually the conductor
+    /// track). Every track is parsed if this is `None`.
+    pub tracks: Option<Vec<usize>>,
+    /// MIDI channels of the notes to parse, from 0 to 15. Every channel is parsed if this is
+    /// `None`.
+    pub channels: Option<Vec<u8>>,
+    /// Whether to ignore notes on the percussion channel (channel 9, or 10 in one-based
+    /// numbering), which are never vocals.
+    pub skip_drums: bool,
+    /// Names of the tracks, keyed by the names of the track chunks. Tracks whose name is not
+    /// in the map keep it.
+    pub track_names: HashMap<String, String>,
+}
+
+impl StandardMidParseOptions {
+    /// Returns whether the track chunk at the index is parsed.
+    pub(crate) fn includes_track(&self, index: usize) -> bool {
+        self.tracks
+            .as_ref()
+            .is_none_or(|tracks| tracks.contains(&index))
+    }
+
+    /// Returns whether notes on the channel are parsed.
+    pub(crate) fn includes_channel(&self, channel: u8) -> bool {
+        !(self.skip_drums && channel == DRUM_CHANNEL)
+            && self
+                .channels
+                .as_ref()
+                .is_none_or(|channels| channels.contains(&channel))
+    }
+
+    /// Returns the name of the track which is parsed from a track chunk named `name`.
+    pub(crate) fn track_name(&self, name: String) -> String {
+        self.track_names.get(&name).cloned().unwrap_or(name)
+    }
+}
@@ -0,0 +1,78 @@
+//! JSON of Synthesizer V's projects, shared by what reads and writes the parts of them which
+//! utaformatix-ts doesn't carry over.
+use super::{Format, UfData};
+use crate::error::Result;
+use anyhow::anyhow;
+
+/// Synthesizer V's time unit per tick, with 705,600,000 blicks per beat.
+#[cfg(feature = "engine")]
+pub(super) const BLICKS_PER_TICK: i64 = 705_600_000 / super::TICKS_PER_BEAT;
+
+/// Returns the length of the JSON of an svp or s5p file, after which Synthesizer V writes null
+/// characters.
+fn json_length(data: &[u8]) -> usize {
+    data.iter()
+        .rposition(|&byte| byte != 0)
+        .map_or(0, |i| i + 1)
+}
+
+/// Parses an svp or s5p file, or returns `None` if it isn't JSON.
+pub(crate) fn svp_json(data: &[u8]) -> Option<serde_json::Value> {
+    serde_json::from_slice(&data[..json_length(data)]).ok()
+}
+
+/// Edits a generated svp file as JSON, keeping the null characters after it.
+pub(crate) fn edit_svp(
+    data: Vec<u8>,
+    edit: impl FnOnce(&mut serde_json::Value),
+) -> Result<Vec<u8>> {
+    let length = json_length(&data);
+    let mut project: serde_json::Value = serde_json::from_slice(&data[..length])
+        .map_err(|e| anyhow!("Failed to parse generated svp: {:?}", e))?;
+    edit(&mut project);
+    let mut result =
+        serde_json::to_vec(&project).map_err(|e| anyhow!("Failed to write svp: {:?}", e))?;
+    result.extend_from_slice(&data[length..]);
+    Ok(result)
+}
+
+/// Returns whether `data` has anything which is written to svp files after generating them,
+/// i.e. what needs [`edit_svp`].
+pub(crate) fn has_svp_extensions(data: &UfData) -> bool {
+    data.project().tracks.iter().any(|track| {
+        track.muted.is_some()
+            || track.solo.is_some()
+            || track.order.is_some()
+            || track
+                .color
+                .as_ref()
+                .is_some_and(|color| color.format == Format::Svp)
+            || track
+                .notes
+                .iter()
+                .any(|note| note.phoneme_segments.is_some())
+    })
+}
+
+/// Returns the tracks (`tracks`) of an svp or s5p project.
+pub(super) fn svp_tracks(project: &serde_json::Value) -> &[serde_json::Value] {
+    project
+        .get("tracks")
+        .and_then(serde_json::Value::as_array)
+        .map_or(&[], Vec::as_slice)
+}
+
+/// Edits each track of an svp project, with the index of the track.
+pub(super) fn edit_svp_tracks(
+    project: &mut serde_json::Value,
+    mut edit: impl FnMut(usize, &mut serde_json::Value),
+) {
+    if let Some(tracks) = project
+        .get_mut("tracks")
+        .and_then(serde_json::Value::as_array_mut)
+    {
+        for (index, track) in tracks.iter_mut().enumerate() {
+            edit(index, track);
+        }
+    }
+}
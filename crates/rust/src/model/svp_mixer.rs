@@ -0,0 +1,64 @@
+//! Mute, solo and display order of the tracks of Synthesizer V's projects, which utaformatix-ts
+//! doesn't carry over.
+use super::{
+    svp::{edit_svp_tracks, svp_tracks},
+    UfData,
+};
+
+/// Sets [`Track::muted`](super::Track::muted), [`Track::solo`](super::Track::solo) and
+/// [`Track::order`](super::Track::order) of the tracks parsed from an svp project, from the
+/// mixers (`mixer.mute` and `mixer.solo`) and the display orders (`dispOrder`) of its tracks.
+pub(crate) fn read_track_states(project: &serde_json::Value, data: &mut UfData) {
+    let svp_tracks = svp_tracks(project);
+    let tracks = &mut data.project_mut().tracks;
+    if svp_tracks.len() != tracks.len() {
+        return;
+    }
+    for (svp_track, track) in svp_tracks.iter().zip(tracks) {
+        track.muted = svp_track
+            .pointer("/mixer/mute")
+            .and_then(serde_json::Value::as_bool);
+        track.solo = svp_track
+            .pointer("/mixer/solo")
+            .and_then(serde_json::Value::as_bool);
+        track.order = svp_track
+            .get("dispOrder")
+            .and_then(serde_json::Value::as_i64)
+            .and_then(|order| i32::try_from(order).ok());
+    }
+}
+
+/// Writes [`Track::muted`](super::Track::muted), [`Track::solo`](super::Track::solo) and
+/// [`Track::order`](super::Track::order) to an svp project generated from `data`. Values which
+/// are `None` are left as generated.
+pub(crate) fn write_track_states(project: &mut serde_json::Value, data: &UfData) {
+    let tracks = &data.project().tracks;
+    let has_states = tracks
+        .iter()
+        .any(|track| track.muted.is_some() || track.solo.is_some() || track.order.is_some());
+    if !has_states {
+        return;
+    }
+    edit_svp_tracks(project, |index, svp_track| {
+        let (Some(track), Some(svp_track)) = (tracks.get(index), svp_track.as_object_mut()) else {
+            return;
+        };
+        if let Some(order) = track.order {
+            svp_track.insert("dispOrder".to_string(), order.into());
+        }
+        if track.muted.is_none() && track.solo.is_none() {
+            return;
+        }
+        let mixer = svp_track
+            .entry("mixer")
+            .or_insert_with(|| serde_json::json!({}));
+        if let Some(mixer) = mixer.as_object_mut() {
+            if let Some(muted) = track.muted {
+                mixer.insert("mute".to_string(), muted.into());
+            }
+            if let Some(solo) = track.solo {
+                mixer.insert("solo".to_string(), solo.into());
+            }
+        }
+    });
+}
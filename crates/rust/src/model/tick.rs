@@ -0,0 +1,103 @@
+//! Positions in ticks.
+use super::TICKS_PER_BEAT;
+use serde::{Deserialize, Serialize};
+use std::{fmt, ops::Sub};
+
+/// Represents a position in ticks, of which a quarter note has 480, e.g. the start of a note.
+///
+/// This is distinct from plain integers, so that positions aren't mixed up with lengths in
+/// seconds or milliseconds. Positions are never negative, which deserializing checks too. This
+/// is (de)serialized as the number.
+///
+/// Positions are moved with [`Tick::checked_add`] or [`Tick::saturating_add`], so that moving
+/// before the start is handled by the caller.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(try_from = "i64", into = "i64")]
+pub struct Tick(i64);
+
+impl Tick {
+    /// The start of the project.
+    pub const ZERO: Self = Self(0);
+
+    /// Returns the position, or `None` if `ticks` is negative.
+    pub const fn new(ticks: i64) -> Option<Self> {
+        if ticks < 0 {
+            None
+        } else {
+            Some(Self(ticks))
+        }
+    }
+
+    /// Returns the position of the beat (quarter note), or `None` if it's negative.
+    pub const fn from_beats(beats: i64) -> Option<Self> {
+        match beats.checked_mul(TICKS_PER_BEAT) {
+            Some(ticks) => Self::new(ticks),
+            None => None,
+        }
+    }
+
+    /// Applies a calculation to the number of ticks, e.g. scaling, stopping at the start.
+    pub(crate) fn map(self, f: impl FnOnce(i64) -> i64) -> Self {
+        Self(f(self.0).max(0))
+    }
+
+    /// Returns the number of ticks.
+    pub const fn get(self) -> i64 {
+        self.0
+    }
+
+    /// Moves the position by `ticks`, which can be negative. Returns `None` if the result is
+    /// negative or overflows.
+    pub fn checked_add(self, ticks: i64) -> Option<Self> {
+        self.0.checked_add(ticks).and_then(Self::new)
+    }
+
+    /// Moves the position by `ticks`, which can be negative, stopping at the start.
+    pub fn saturating_add(self, ticks: i64) -> Self {
+        Self(self.0.saturating_add(ticks).max(0))
+    }
+}
+
+/// Returns the number of ticks from the other position, which is negative if it's later. This
+/// saturates instead of overflowing.
+impl Sub for Tick {
+    type Output = i64;
+
+    fn sub(self, other: Self) -> i64 {
+        self.0.saturating_sub(other.0)
+    }
+}
+
+impl PartialEq<i64> for Tick {
+    fn eq(&self, ticks: &i64) -> bool {
+        self.0 == *ticks
+    }
+}
+
+impl From<Tick> for i64 {
+    fn from(tick: Tick) -> Self {
+        tick.0
+    }
+}
+
+impl TryFrom<i64> for Tick {
+    type Error = crate::Error;
+
+    fn try_from(ticks: i64) -> crate::error::Result<Self> {
+        Self::new(ticks).ok_or_else(|| anyhow::anyhow!("Negative tick position: {}", ticks).into())
+    }
+}
+
+/// Formats as the number, like the serialized value.
+impl fmt::Debug for Tick {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for Tick {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
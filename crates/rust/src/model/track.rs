@@ -0,0 +1,152 @@
+//! Editing utilities of tracks.
+use super::{Format, Note, Tick, Track};
+use crate::error::{ErrorKind, Result};
+use std::{cmp::Reverse, collections::HashMap, ops::Range};
+
+impl Track {
+    /// Extends notes to the start of the next note, if the gap between them is at most
+    /// `max_ticks`, which makes the line legato.
+    pub fn remove_micro_gaps(&mut self, max_ticks: i64) {
+        self.notes.sort_by_key(|note| note.tick_on);
+        for i in 1..self.notes.len() {
+            let next_tick_on = self.notes[i].tick_on;
+            let previous = &mut self.notes[i - 1];
+            let gap = next_tick_on - previous.tick_off;
+            if gap > 0 && gap <= max_ticks {
+                previous.tick_off = next_tick_on;
+            }
+        }
+    }
+
+    /// Fills every gap between notes, including the one before the first note, with a rest
+    /// note which has `lyric` as its lyric (e.g. `"R"` for UTAU).
+    ///
+    /// Rest notes take the key of the previous note, or the next note for the first gap.
+    pub fn insert_rest_lyrics(&mut self, lyric: &str) {
+        self.notes.sort_by_key(|note| note.tick_on);
+        let mut notes = Vec::with_capacity(self.notes.len() * 2);
+        let mut previous: Option<&Note> = None;
+        for note in &self.notes {
+            let (tick_on, key) = match previous {
+                Some(previous) => (previous.tick_off, previous.key),
+                None => (Tick::ZERO, note.key),
+            };
+            if note.tick_on > tick_on {
+                notes.push(Note {
+                    key,
+                    tick_on,
+                    tick_off: note.tick_on,
+                    lyric: lyric.to_string(),
+                    phoneme: None,
+                    phoneme_segments: None,
+                });
+            }
+            notes.push(note.clone());
+            previous = Some(note);
+        }
+        self.notes = notes;
+    }
+
+    /// Removes notes whose lyric is one of `lyrics`, e.g. `&["R", "r"]` for UTAU.
+    pub fn remove_rest_notes(&mut self, lyrics: &[&str]) {
+        self.notes
+            .retain(|note| !lyrics.contains(&note.lyric.as_str()));
+    }
+
+    /// Ties the notes in the range (indices of the notes sorted by position) into the first
+    /// one.
+    ///
+    /// Notes with the same key as the previous one are merged into it, and the others become
+    /// a melisma, using the continuation lyric of the target format
+    /// (see [`Format::continuation_lyric`]).
+    pub fn tie_notes(&mut self, range: Range<usize>, format: Format) {
+        self.notes.sort_by_key(|note| note.tick_on);
+        let end = range.end.min(self.notes.len());
+        if range.start >= end {
+            return;
+        }
+        let tied = self.notes.drain(range.start..end).collect::<Vec<_>>();
+        let mut merged: Vec<Note> = Vec::with_capacity(tied.len());
+        for note in tied {
+            match merged.last_mut() {
+                Some(previous) if previous.key == note.key && previous.tick_off >= note.tick_on => {
+                    previous.tick_off = previous.tick_off.max(note.tick_off);
+                }
+                Some(_) => merged.push(Note {
+                    lyric: format.continuation_lyric().to_string(),
+                    phoneme: None,
+                    ..note
+                }),
+                None => merged.push(note),
+            }
+        }
+        self.notes.splice(range.start..range.start, merged);
+    }
+
+    /// Moves notes and pitch points by ticks, which can be negative. Pitch points moved before
+    /// the start are removed.
+    ///
+    /// Returns [`ErrorKind::IllegalNotePosition`] if a note would start or end before the start;
+    /// the track is left untouched in that case.
+    pub fn shift(&mut self, ticks: i64) -> Result<()> {
+        let positions = self
+            .notes
+            .iter()
+            .map(|note| {
+                Some((
+                    note.tick_on.checked_add(ticks)?,
+                    note.tick_off.checked_add(ticks)?,
+                ))
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or(ErrorKind::IllegalNotePosition)?;
+        for (note, (tick_on, tick_off)) in self.notes.iter_mut().zip(positions) {
+            note.tick_on = tick_on;
+            note.tick_off = tick_off;
+        }
+        if let Some(pitch) = &mut self.pitch {
+            let (ticks, values) = pitch
+                .ticks
+                .iter()
+                .zip(&pitch.values)
+                .map(|(tick, value)| (i64::from(*tick) + ticks, *value))
+                .filter(|(tick, _)| *tick >= 0)
+                .map(|(tick, value)| (tick as i32, value))
+                .unzip();
+            pitch.ticks = ticks;
+            pitch.values = values;
+        }
+
+        Ok(())
+    }
+
+    /// Removes notes which are artifacts of MIDI exports of DAWs, and returns the number of
+    /// removed notes:
+    ///
+    /// - Notes which don't have a positive length.
+    /// - Notes which are entirely within another note with the same lyric, including exact
+    ///   duplicates. The longer note is kept.
+    ///
+    /// Notes are sorted by their start.
+    pub fn cleanup(&mut self) -> usize {
+        let count = self.notes.len();
+        self.notes.retain(|note| note.tick_off > note.tick_on);
+        self.notes
+            .sort_by_key(|note| (note.tick_on, Reverse(note.tick_off)));
+        // Notes start at or after the kept ones, so a note is within one with the same lyric
+        // if any of them ends at or after it.
+        let mut ends = HashMap::<String, Tick>::new();
+        let notes = std::mem::take(&mut self.notes);
+        for note in notes {
+            if ends
+                .get(&note.lyric)
+                .is_some_and(|end| *end >= note.tick_off)
+            {
+                continue;
+            }
+            ends.insert(note.lyric.clone(), note.tick_off);
+            self.notes.push(note);
+        }
+        count - self.notes.len()
+    }
+}
@@ -0,0 +1,147 @@
+//! Colors of tracks, which utaformatix-ts doesn't carry over.
+use super::Format;
+#[cfg(feature = "engine")]
+use super::{
+    svp::{edit_svp_tracks, svp_tracks},
+    ustx::{edit_ustx, edit_ustx_list, ustx_list, ustx_string, ustx_yaml},
+    UfData,
+};
+#[cfg(feature = "engine")]
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+
+/// Represents the color of a track as written by an editor, used by
+/// [`Track::color`](super::Track::color).
+///
+/// Editors represent colors differently, e.g. `ff7db235` in Synthesizer V and `Blue` in
+/// OpenUtau, so colors are only written to files of the format they were read from.
+///
+/// Colors are read from Synthesizer V's and OpenUtau's projects. Colors of VOCALOID 5's
+/// projects and groups of tracks aren't kept.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TrackColor {
+    /// Format which the color was read from.
+    pub format: Format,
+    /// The color as written in the file, e.g. `dispColor` of Synthesizer V's projects and
+    /// `track_color` of OpenUtau's projects.
+    pub value: String,
+}
+
+/// Key of the colors of tracks in OpenUtau's projects.
+#[cfg(feature = "engine")]
+const USTX_TRACK_COLOR: &str = "track_color";
+
+/// Sets [`Track::color`](super::Track::color) of the tracks parsed from `source`, if the format
+/// has colors of tracks. Colors of svp files are read by [`read_svp_track_colors`] instead.
+#[cfg(feature = "engine")]
+pub(crate) fn read_track_colors(format: Format, source: &[u8], data: &mut UfData) {
+    let colors = match format {
+        Format::Ustx => ustx_colors(source),
+        _ => return,
+    };
+    set_colors(format, colors, data);
+}
+
+/// Sets [`Track::color`](super::Track::color) of the tracks parsed from an svp project, from
+/// the colors (`dispColor`) of its tracks.
+#[cfg(feature = "engine")]
+pub(crate) fn read_svp_track_colors(project: &serde_json::Value, data: &mut UfData) {
+    let colors = svp_tracks(project)
+        .iter()
+        .map(|track| {
+            track
+                .get("dispColor")
+                .and_then(serde_json::Value::as_str)
+                .map(str::to_string)
+        })
+        .collect();
+    set_colors(Format::Svp, colors, data);
+}
+
+/// Sets the colors of the tracks, if there is one for each track.
+#[cfg(feature = "engine")]
+fn set_colors(format: Format, colors: Vec<Option<String>>, data: &mut UfData) {
+    let tracks = &mut data.project_mut().tracks;
+    if colors.len() != tracks.len() {
+        return;
+    }
+    for (track, color) in tracks.iter_mut().zip(colors) {
+        track.color = color.map(|value| TrackColor { format, value });
+    }
+}
+
+/// Returns the colors of the tracks of `data` which were read from the format.
+#[cfg(feature = "engine")]
+fn colors(format: Format, data: &UfData) -> Vec<Option<&str>> {
+    data.project()
+        .tracks
+        .iter()
+        .map(|track| {
+            track
+                .color
+                .as_ref()
+                .filter(|color| color.format == format)
+                .map(|color| color.value.as_str())
+        })
+        .collect()
+}
+
+/// Writes [`Track::color`](super::Track::color) to a file generated from `data`, for the colors
+/// which were read from the same format. Colors of svp files are written by
+/// [`write_svp_track_colors`] instead.
+#[cfg(feature = "engine")]
+pub(crate) fn write_track_colors(format: Format, file: Vec<u8>, data: &UfData) -> Result<Vec<u8>> {
+    let colors = colors(format, data);
+    if colors.iter().all(Option::is_none) {
+        return Ok(file);
+    }
+    match format {
+        Format::Ustx => edit_ustx(file, |project| set_ustx_colors(project, &colors)),
+        _ => Ok(file),
+    }
+}
+
+/// Writes [`Track::color`](super::Track::color) to an svp project generated from `data`, as the
+/// colors (`dispColor`) of its tracks, for the colors which were read from svp files.
+#[cfg(feature = "engine")]
+pub(crate) fn write_svp_track_colors(project: &mut serde_json::Value, data: &UfData) {
+    let colors = colors(Format::Svp, data);
+    edit_svp_tracks(project, |index, svp_track| {
+        let (Some(Some(color)), Some(svp_track)) = (colors.get(index), svp_track.as_object_mut())
+        else {
+            return;
+        };
+        svp_track.insert("dispColor".to_string(), (*color).into());
+    });
+}
+
+/// Returns the color (`track_color`) of each track of an OpenUtau project, in the order of the
+/// tracks.
+#[cfg(feature = "engine")]
+fn ustx_colors(source: &[u8]) -> Vec<Option<String>> {
+    let Some(project) = ustx_yaml(source) else {
+        return vec![];
+    };
+    ustx_list(&project, "tracks")
+        .iter()
+        .map(|track| {
+            track
+                .get(USTX_TRACK_COLOR)
+                .and_then(ustx_string)
+                .filter(|color| !color.is_empty())
+        })
+        .collect()
+}
+
+/// Sets the colors (`track_color`) of the tracks of an OpenUtau project, replacing the existing
+/// ones of the tracks which have colors.
+#[cfg(feature = "engine")]
+fn set_ustx_colors(project: &mut serde_yaml::Mapping, colors: &[Option<&str>]) {
+    edit_ustx_list(project, "tracks", |index, track| {
+        if let Some(color) = colors.get(index).copied().flatten() {
+            track.insert(USTX_TRACK_COLOR.into(), color.into());
+        }
+    });
+}
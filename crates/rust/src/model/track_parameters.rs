@@ -0,0 +1,420 @@
+//! Curves of parameters of tracks, which utaformatix-ts doesn't carry over.
+#[cfg(feature = "engine")]
+use super::{
+    svp::{edit_svp, edit_svp_tracks, svp_json, svp_tracks, BLICKS_PER_TICK},
+    ustx::{edit_ustx, edit_ustx_list, ustx_list, ustx_string, ustx_yaml},
+};
+use super::{Format, Tick, Track, UfData};
+#[cfg(feature = "engine")]
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, collections::BTreeMap};
+
+/// Represents the curves of the parameters of a track as written by an editor, used by
+/// [`Track::parameters`](super::Track::parameters).
+///
+/// Editors have different parameters with different ranges, so the curves are only written to
+/// files of the format they were read from, unless they are mapped by
+/// [`GenerateOptions::dynamics`](super::GenerateOptions::dynamics).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TrackParameters {
+    /// Format which the curves were read from.
+    pub format: Format,
+    /// Curves by the names of the parameters in the format, e.g. `dyn` and `pitd` of
+    /// OpenUtau's expressions, or `loudness` and `tension` of Synthesizer V's parameters.
+    pub curves: BTreeMap<String, Vec<ParameterPoint>>,
+}
+
+/// Represents a point of a curve of [`TrackParameters`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ParameterPoint {
+    /// Tick position of the point.
+    pub tick: Tick,
+    /// Value of the parameter, in the unit of the format. Values of Synthesizer V's parameters
+    /// are in thousandths, e.g. thousandths of decibels for `loudness`.
+    pub value: i32,
+}
+
+/// Represents how the loudness and tension of Synthesizer V's projects are mapped to the
+/// dynamics of other editors, used by
+/// [`GenerateOptions::dynamics`](super::GenerateOptions::dynamics).
+///
+/// The curves are read by [`ParseOptions::track_parameters`](super::ParseOptions::track_parameters),
+/// and mapped to OpenUtau's dynamics (`dyn`), which is clamped to its range of -24 to 12
+/// decibels. Other formats, including CeVIO's projects, are generated without them.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", default)]
+pub struct DynamicsMapping {
+    /// Decibels of the dynamics per decibel of the loudness (`loudness`).
+    pub loudness_scale: f64,
+    /// Decibels of the dynamics at the highest tension (`tension` of 1), and the opposite at
+    /// the lowest. Tension is ignored if this is 0.
+    pub tension_scale: f64,
+}
+
+impl Default for DynamicsMapping {
+    fn default() -> Self {
+        Self {
+            loudness_scale: 1.0,
+            tension_scale: 0.0,
+        }
+    }
+}
+
+/// Name of OpenUtau's dynamics, in tenths of decibels.
+const USTX_DYNAMICS: &str = "dyn";
+/// Range of [`USTX_DYNAMICS`].
+const USTX_DYNAMICS_RANGE: std::ops::RangeInclusive<f64> = -240.0..=120.0;
+/// Scale of the values of Synthesizer V's parameters in [`ParameterPoint::value`].
+const SVP_VALUE_SCALE: f64 = 1000.0;
+
+impl DynamicsMapping {
+    /// Replaces the curves of the tracks read from svp files by the dynamics of `format`,
+    /// cloning the data only if a track has them.
+    pub(crate) fn apply(&self, format: Format, data: &mut Cow<UfData>) {
+        if format != Format::Ustx {
+            return;
+        }
+        let dynamics = |track: &Track| {
+            track
+                .parameters
+                .as_ref()
+                .filter(|parameters| parameters.format == Format::Svp)
+                .and_then(|parameters| self.dynamics(parameters))
+        };
+        if data
+            .project()
+            .tracks
+            .iter()
+            .all(|track| dynamics(track).is_none())
+        {
+            return;
+        }
+        for track in &mut data.to_mut().project_mut().tracks {
+            let Some(points) = dynamics(track) else {
+                continue;
+            };
+            let points = points
+                .into_iter()
+                .map(|(tick, decibels)| ParameterPoint {
+                    tick,
+                    value: (decibels * 10.0)
+                        .round()
+                        .clamp(*USTX_DYNAMICS_RANGE.start(), *USTX_DYNAMICS_RANGE.end())
+                        as i32,
+                })
+                .collect();
+            track.parameters = Some(TrackParameters {
+                format,
+                curves: BTreeMap::from([(USTX_DYNAMICS.to_string(), points)]),
+            });
+        }
+    }
+
+    /// Returns the dynamics in decibels at the points of the loudness and tension curves of
+    /// Synthesizer V, or `None` if they are missing. The curves are interpolated linearly.
+    fn dynamics(&self, parameters: &TrackParameters) -> Option<Vec<(Tick, f64)>> {
+        let curves = [
+            ("loudness", self.loudness_scale),
+            ("tension", self.tension_scale),
+        ]
+        .into_iter()
+        .filter(|(_, scale)| *scale != 0.0)
+        .filter_map(|(name, scale)| {
+            let curve = parameters.curves.get(name)?;
+            (!curve.is_empty()).then_some((curve.as_slice(), scale))
+        })
+        .collect::<Vec<_>>();
+        if curves.is_empty() {
+            return None;
+        }
+        let mut ticks = curves
+            .iter()
+            .flat_map(|(curve, _)| curve.iter().map(|point| point.tick))
+            .collect::<Vec<_>>();
+        ticks.sort();
+        ticks.dedup();
+        let points = ticks
+            .into_iter()
+            .map(|tick| {
+                let decibels = curves
+                    .iter()
+                    .map(|(curve, scale)| value_at(curve, tick) / SVP_VALUE_SCALE * scale)
+                    .sum();
+                (tick, decibels)
+            })
+            .collect();
+        Some(points)
+    }
+}
+
+/// Returns the value of a curve at `tick`, interpolated linearly between its points and held
+/// before the first and after the last one.
+fn value_at(curve: &[ParameterPoint], tick: Tick) -> f64 {
+    let index = curve.partition_point(|point| point.tick <= tick);
+    match (
+        index.checked_sub(1).map(|i| curve[i]),
+        curve.get(index).copied(),
+    ) {
+        (Some(before), Some(after)) => {
+            let ratio = (tick - before.tick) as f64 / (after.tick - before.tick) as f64;
+            f64::from(before.value) + f64::from(after.value - before.value) * ratio
+        }
+        (Some(point), None) | (None, Some(point)) => f64::from(point.value),
+        (None, None) => 0.0,
+    }
+}
+
+/// Sets [`Track::parameters`](super::Track::parameters) of the tracks parsed from `source`, if
+/// the format has curves of parameters.
+#[cfg(feature = "engine")]
+pub(crate) fn read_track_parameters(format: Format, source: &[u8], data: &mut UfData) {
+    match format {
+        Format::Ustx => {
+            if let Some(project) = ustx_yaml(source) {
+                read_ustx_parameters(&project, data);
+            }
+        }
+        Format::Svp => {
+            if let Some(project) = svp_json(source) {
+                read_svp_parameters(&project, data);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Reads the curves (`curves`) of the voice parts of an OpenUtau project.
+#[cfg(feature = "engine")]
+fn read_ustx_parameters(project: &serde_yaml::Mapping, data: &mut UfData) {
+    let tracks = &mut data.project_mut().tracks;
+    if ustx_list(project, "tracks").len() != tracks.len() {
+        return;
+    }
+    for part in ustx_voice_parts(project) {
+        let Some(track) = tracks.get_mut(part.track_no) else {
+            continue;
+        };
+        let parameters = track.parameters.get_or_insert_with(|| TrackParameters {
+            format: Format::Ustx,
+            curves: BTreeMap::new(),
+        });
+        for (name, points) in part.curves {
+            let curve = parameters.curves.entry(name).or_default();
+            curve.extend(points.into_iter().filter_map(|(x, value)| {
+                let tick = Tick::new(part.position.checked_add(x)?)?;
+                Some(ParameterPoint { tick, value })
+            }));
+            curve.sort_by_key(|point| point.tick);
+        }
+    }
+}
+
+/// Reads the parameters (`parameters`) of the main groups of the tracks of an svp project, with
+/// the positions rounded to ticks. Curves of the other groups are skipped.
+#[cfg(feature = "engine")]
+fn read_svp_parameters(project: &serde_json::Value, data: &mut UfData) {
+    let svp_tracks = svp_tracks(project);
+    let tracks = &mut data.project_mut().tracks;
+    if svp_tracks.len() != tracks.len() {
+        return;
+    }
+    for (svp_track, track) in svp_tracks.iter().zip(tracks) {
+        let Some(svp_parameters) = svp_track
+            .pointer("/mainGroup/parameters")
+            .and_then(serde_json::Value::as_object)
+        else {
+            continue;
+        };
+        let curves = svp_parameters
+            .iter()
+            .filter_map(|(name, curve)| {
+                let points = curve
+                    .get("points")?
+                    .as_array()?
+                    .chunks_exact(2)
+                    .map(|point| {
+                        let blicks = point[0].as_f64()?;
+                        Some(ParameterPoint {
+                            tick: Tick::new((blicks / BLICKS_PER_TICK as f64).round() as i64)?,
+                            value: (point[1].as_f64()? * SVP_VALUE_SCALE).round() as i32,
+                        })
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                (!points.is_empty()).then(|| (name.clone(), points))
+            })
+            .collect::<BTreeMap<_, _>>();
+        if !curves.is_empty() {
+            track.parameters = Some(TrackParameters {
+                format: Format::Svp,
+                curves,
+            });
+        }
+    }
+}
+
+/// Writes [`Track::parameters`](super::Track::parameters) to a file generated from `data`, for
+/// the curves which were read from the same format.
+#[cfg(feature = "engine")]
+pub(crate) fn write_track_parameters(
+    format: Format,
+    file: Vec<u8>,
+    data: &UfData,
+) -> Result<Vec<u8>> {
+    let parameters = data
+        .project()
+        .tracks
+        .iter()
+        .map(|track| {
+            track
+                .parameters
+                .as_ref()
+                .filter(|parameters| parameters.format == format)
+        })
+        .collect::<Vec<_>>();
+    if parameters.iter().all(Option::is_none) {
+        return Ok(file);
+    }
+    match format {
+        Format::Ustx => edit_ustx(file, |project| set_ustx_curves(project, &parameters)),
+        Format::Svp => edit_svp(file, |project| set_svp_parameters(project, &parameters)),
+        _ => Ok(file),
+    }
+}
+
+/// Represents a voice part of an OpenUtau project, with the positions of its curves relative
+/// to the part.
+#[cfg(feature = "engine")]
+#[derive(Debug, Default)]
+struct UstxVoicePart {
+    track_no: usize,
+    position: i64,
+    curves: Vec<(String, Vec<(i64, i32)>)>,
+}
+
+/// Returns the voice parts (`voice_parts`) of an OpenUtau project with their curves
+/// (`curves`), in order. Parts whose track or position can't be read are skipped.
+#[cfg(feature = "engine")]
+fn ustx_voice_parts(project: &serde_yaml::Mapping) -> Vec<UstxVoicePart> {
+    ustx_list(project, "voice_parts")
+        .iter()
+        .filter_map(|part| {
+            Some(UstxVoicePart {
+                track_no: match part.get("track_no") {
+                    Some(track_no) => usize::try_from(track_no.as_u64()?).ok()?,
+                    None => 0,
+                },
+                position: match part.get("position") {
+                    Some(position) => position.as_i64()?,
+                    None => 0,
+                },
+                curves: part.get("curves").map(ustx_curves).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// Parses the curves (`curves`) of a voice part. Curves which can't be read are skipped.
+#[cfg(feature = "engine")]
+fn ustx_curves(curves: &serde_yaml::Value) -> Vec<(String, Vec<(i64, i32)>)> {
+    let integers = |curve: &serde_yaml::Value, key: &str| {
+        curve
+            .get(key)?
+            .as_sequence()?
+            .iter()
+            .map(serde_yaml::Value::as_i64)
+            .collect::<Option<Vec<_>>>()
+    };
+    curves
+        .as_sequence()
+        .map_or(&[][..], Vec::as_slice)
+        .iter()
+        .filter_map(|curve| {
+            let xs = integers(curve, "xs")?;
+            let ys = integers(curve, "ys")?
+                .into_iter()
+                .map(|y| i32::try_from(y).ok())
+                .collect::<Option<Vec<_>>>()?;
+            let abbr = ustx_string(curve.get("abbr")?)?;
+            (xs.len() == ys.len()).then(|| (abbr, xs.into_iter().zip(ys).collect()))
+        })
+        .collect()
+}
+
+/// Sets the curves (`curves`) of the first voice part of each track of an OpenUtau project,
+/// replacing the existing ones of the tracks which have curves.
+#[cfg(feature = "engine")]
+fn set_ustx_curves(project: &mut serde_yaml::Mapping, parameters: &[Option<&TrackParameters>]) {
+    let mut written = vec![false; parameters.len()];
+    edit_ustx_list(project, "voice_parts", |_, part| {
+        let track_no = part
+            .get("track_no")
+            .and_then(serde_yaml::Value::as_u64)
+            .and_then(|track_no| usize::try_from(track_no).ok());
+        let position = part
+            .get("position")
+            .and_then(serde_yaml::Value::as_i64)
+            .unwrap_or(0);
+        // Only the first part of each track gets the curves of the track.
+        let curves = track_no
+            .and_then(|track_no| Some((track_no, (*parameters.get(track_no)?)?)))
+            .filter(|(track_no, _)| !std::mem::replace(&mut written[*track_no], true));
+        let Some((_, parameters)) = curves else {
+            return;
+        };
+
+        let curves = parameters
+            .curves
+            .iter()
+            .map(|(name, points)| {
+                let mut curve = serde_yaml::Mapping::new();
+                let xs = points.iter().map(|point| point.tick.get() - position);
+                let ys = points.iter().map(|point| point.value);
+                curve.insert("xs".into(), xs.collect());
+                curve.insert("ys".into(), ys.collect());
+                curve.insert("abbr".into(), name.as_str().into());
+                serde_yaml::Value::Mapping(curve)
+            })
+            .collect();
+        part.insert("curves".into(), curves);
+    });
+}
+
+/// Sets the parameters (`parameters`) of the main group of each track of an svp project,
+/// replacing the points of the curves which the tracks have.
+#[cfg(feature = "engine")]
+fn set_svp_parameters(project: &mut serde_json::Value, parameters: &[Option<&TrackParameters>]) {
+    edit_svp_tracks(project, |index, svp_track| {
+        let Some(Some(parameters)) = parameters.get(index) else {
+            return;
+        };
+        let Some(svp_parameters) = svp_track
+            .pointer_mut("/mainGroup/parameters")
+            .and_then(serde_json::Value::as_object_mut)
+        else {
+            return;
+        };
+        for (name, points) in &parameters.curves {
+            let points = points
+                .iter()
+                .flat_map(|point| {
+                    [
+                        serde_json::Value::from(point.tick.get() * BLICKS_PER_TICK),
+                        serde_json::Value::from(f64::from(point.value) / SVP_VALUE_SCALE),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            let curve = svp_parameters
+                .entry(name.clone())
+                .or_insert_with(|| serde_json::json!({ "mode": "cubic" }));
+            if let Some(curve) = curve.as_object_mut() {
+                curve.insert("points".to_string(), points.into());
+            }
+        }
+    });
+}
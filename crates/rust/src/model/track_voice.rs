@@ -0,0 +1,150 @@
+//! Voices of tracks, which utaformatix-ts doesn't carry over.
+use super::Format;
+#[cfg(feature = "engine")]
+use super::{
+    xml::{self, Element},
+    UfData,
+};
+#[cfg(feature = "engine")]
+use crate::error::Result;
+#[cfg(feature = "engine")]
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+/// Represents the voice which sings a track, as written by an editor, used by
+/// [`Track::voice`](super::Track::voice).
+///
+/// Editors identify voices differently, so voices are only written to files of the format they
+/// were read from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TrackVoice {
+    /// Format which the voice was read from.
+    pub format: Format,
+    /// Identifier of the voice, e.g. `CastId` of CeVIO's projects.
+    pub cast: String,
+    /// Language which the voice sings in, e.g. `Language` of CeVIO's projects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+/// Sets [`Track::voice`](super::Track::voice) of the tracks parsed from `source`, if the format
+/// has voices of tracks.
+#[cfg(feature = "engine")]
+pub(crate) fn read_track_voices(format: Format, source: &[u8], data: &mut UfData) {
+    let voices = match format {
+        Format::Ccs => ccs_voices(&String::from_utf8_lossy(source)),
+        _ => return,
+    };
+    let tracks = &mut data.project_mut().tracks;
+    if voices.len() != tracks.len() {
+        return;
+    }
+    for (track, voice) in tracks.iter_mut().zip(voices) {
+        track.voice = voice.map(|(cast, language)| TrackVoice {
+            format,
+            cast,
+            language,
+        });
+    }
+}
+
+/// Writes [`Track::voice`](super::Track::voice) to a file generated from `data`, for the voices
+/// which were read from the same format.
+#[cfg(feature = "engine")]
+pub(crate) fn write_track_voices(format: Format, file: Vec<u8>, data: &UfData) -> Result<Vec<u8>> {
+    let voices = data
+        .project()
+        .tracks
+        .iter()
+        .map(|track| track.voice.as_ref().filter(|voice| voice.format == format))
+        .collect::<Vec<_>>();
+    if voices.iter().all(Option::is_none) {
+        return Ok(file);
+    }
+    match format {
+        Format::Ccs => {
+            let text = String::from_utf8(file)
+                .map_err(|e| anyhow!("Generated ccs is not UTF-8: {:?}", e))?;
+            Ok(set_ccs_voices(&text, &voices).into_bytes())
+        }
+        _ => Ok(file),
+    }
+}
+
+/// Category of the units and the groups of songs in CeVIO's projects.
+#[cfg(feature = "engine")]
+const CCS_SONG: &str = "SingerSong";
+
+/// Returns the cast (`CastId`) and the language (`Language`) of each group of songs of a CeVIO
+/// project, which are the tracks, in order. The cast of the first unit of the group is used if
+/// the group doesn't have one.
+#[cfg(feature = "engine")]
+fn ccs_voices(text: &str) -> Vec<Option<(String, Option<String>)>> {
+    let elements = xml::elements(text);
+    let songs = ccs_songs(&elements);
+    let voice = |element: &Element| {
+        (
+            element.attribute("CastId").map(str::to_string),
+            element.attribute("Language").map(str::to_string),
+        )
+    };
+    songs
+        .iter()
+        .filter(|element| element.name == "Group")
+        .map(|group| {
+            let id = group.attribute("Id");
+            let unit = songs
+                .iter()
+                .find(|unit| unit.name == "Unit" && id.is_some() && unit.attribute("Group") == id)
+                .map(|unit| voice(unit));
+            let (cast, language) = voice(group);
+            let cast = cast.or_else(|| unit.clone().and_then(|(cast, _)| cast))?;
+            let language = language.or_else(|| unit.and_then(|(_, language)| language));
+            Some((cast, language))
+        })
+        .collect()
+}
+
+/// Returns the units and the groups of songs of a CeVIO project, in order.
+#[cfg(feature = "engine")]
+fn ccs_songs(elements: &[Element]) -> Vec<&Element> {
+    elements
+        .iter()
+        .filter(|element| matches!(element.name.as_str(), "Unit" | "Group"))
+        .filter(|element| element.attribute("Category") == Some(CCS_SONG))
+        .collect()
+}
+
+/// Sets the casts (`CastId`) and the languages (`Language`) of the groups of songs of a CeVIO
+/// project and of their units, for the groups which have voices.
+#[cfg(feature = "engine")]
+fn set_ccs_voices(text: &str, voices: &[Option<&TrackVoice>]) -> String {
+    let elements = xml::elements(text);
+    let songs = ccs_songs(&elements);
+    // Units refer to their groups, which are written after them.
+    let group_voices = songs
+        .iter()
+        .filter(|element| element.name == "Group")
+        .zip(voices)
+        .filter_map(|(group, voice)| Some((group.attribute("Id")?, (*voice)?)))
+        .collect::<Vec<_>>();
+
+    let replacements = songs
+        .iter()
+        .filter_map(|element| {
+            let reference = match element.name.as_str() {
+                "Unit" => element.attribute("Group")?,
+                _ => element.attribute("Id")?,
+            };
+            let (_, voice) = group_voices.iter().find(|(id, _)| *id == reference)?;
+            let mut values = vec![("CastId", voice.cast.as_str())];
+            if let Some(language) = &voice.language {
+                values.push(("Language", language));
+            }
+            Some(element.set_attributes(text, &values))
+        })
+        .collect();
+    xml::splice(text, replacements)
+}
@@ -0,0 +1,102 @@
+//! Reading and editing UST files line by line, for what utaformatix-ts doesn't carry over.
+//!
+//! Files are edited by copying their lines, so that everything which isn't changed is kept as it
+//! was written, including the encoding and the newlines.
+
+/// Represents a line of a UST file, returned by [`lines`].
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Line<'a> {
+    /// The whole line, including the newline.
+    pub(super) raw: &'a [u8],
+    pub(super) kind: LineKind<'a>,
+}
+
+/// Represents what a line of a UST file is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum LineKind<'a> {
+    /// Header of a section, e.g. `SETTING` for `[#SETTING]` and `0000` for `[#0000]`.
+    Section(&'a [u8]),
+    /// Entry of a section, as its key and its value.
+    Entry(&'a [u8], &'a [u8]),
+    /// Any other line, including empty ones.
+    Other,
+}
+
+impl<'a> Line<'a> {
+    /// Returns the value of the entry if the line is an entry of `key`.
+    pub(super) fn value(&self, key: &str) -> Option<&'a [u8]> {
+        match self.kind {
+            LineKind::Entry(name, value) if name == key.as_bytes() => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the lines of a UST file, in order.
+pub(super) fn lines(data: &[u8]) -> impl Iterator<Item = Line<'_>> {
+    data.split_inclusive(|&byte| byte == b'\n').map(|raw| {
+        let content = raw.strip_suffix(b"\n").unwrap_or(raw);
+        let content = content.strip_suffix(b"\r").unwrap_or(content);
+        let kind = if let Some(name) = content
+            .strip_prefix(b"[#")
+            .and_then(|name| name.strip_suffix(b"]"))
+        {
+            LineKind::Section(name)
+        } else if let Some(position) = content.iter().position(|&byte| byte == b'=') {
+            LineKind::Entry(&content[..position], &content[position + 1..])
+        } else {
+            LineKind::Other
+        };
+        Line { raw, kind }
+    })
+}
+
+/// Returns whether a section is a note (`[#0000]`, `[#0001]`, ...), including rests.
+pub(super) fn is_note_section(name: &[u8]) -> bool {
+    !name.is_empty() && name.iter().all(u8::is_ascii_digit)
+}
+
+/// Returns the newline of a UST file.
+fn newline(data: &[u8]) -> &'static [u8] {
+    if data.windows(2).any(|window| window == b"\r\n") {
+        b"\r\n"
+    } else {
+        b"\n"
+    }
+}
+
+/// Represents how entries are written to a UST file.
+pub(super) struct Writer {
+    newline: &'static [u8],
+    is_utf8: bool,
+}
+
+impl Writer {
+    /// Returns the writer for the newline and the encoding of a UST file, which is Shift_JIS
+    /// unless the file declares `Charset=UTF-8`.
+    pub(super) fn new(data: &[u8]) -> Self {
+        Self {
+            newline: newline(data),
+            is_utf8: lines(data).any(|line| line.value("Charset") == Some(b"UTF-8")),
+        }
+    }
+
+    /// Writes an entry, with a newline.
+    pub(super) fn entry(&self, result: &mut Vec<u8>, key: &str, value: &str) {
+        result.extend_from_slice(key.as_bytes());
+        result.push(b'=');
+        if self.is_utf8 {
+            result.extend_from_slice(value.as_bytes());
+        } else {
+            result.extend_from_slice(&encoding_rs::SHIFT_JIS.encode(value).0);
+        }
+        result.extend_from_slice(self.newline);
+    }
+
+    /// Writes a newline if the last line of `result` doesn't end with one.
+    pub(super) fn end_line(&self, result: &mut Vec<u8>) {
+        if !result.is_empty() && !result.ends_with(b"\n") {
+            result.extend_from_slice(self.newline);
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Mode1 pitch bends of UST files.
+//!
+//! UST files have two incompatible representations of pitch: Mode1 writes the offsets from the
+//! key of each note at regular intervals (`PitchBend`), and Mode2 writes the control points of
+//! curves (`PBS`, `PBW`, `PBY` and `PBM`). utaformatix-ts only reads and writes Mode2, so Mode1 is
+//! read into and written from the pitch curves here, which also converts between the two modes.
+use super::{
+    fallback_timing::DEFAULT_BPM,
+    ust::{self, LineKind},
+    Format, ParseOptions, Pitch, Tempo, Track, UfData, TICKS_PER_BEAT,
+};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Represents the representation of pitch bends in UST files, used by
+/// [`ParseOptions::ust_pitch_mode`] and [`UstOptions::pitch_mode`](super::UstOptions::pitch_mode).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum UstPitchMode {
+    /// Offsets from the key of each note in cents at every 5 ticks, written as `PitchBend`.
+    Mode1,
+    /// Control points of curves, written as `PBS`, `PBW`, `PBY` and `PBM`.
+    #[default]
+    Mode2,
+}
+
+/// Interval of Mode1 pitch bends in ticks.
+const MODE1_INTERVAL: i64 = 5;
+
+impl ParseOptions {
+    /// Replaces the pitch curves parsed by the JS parser with the Mode1 pitch bends of `sources`,
+    /// if [`ParseOptions::ust_pitch_mode`] is [`UstPitchMode::Mode1`]. Files without any Mode1
+    /// pitch bend keep the parsed curves.
+    pub(crate) fn apply_ust_pitch(&self, format: Format, sources: &[&[u8]], data: &mut UfData) {
+        if format != Format::Ust || !self.pitch || self.ust_pitch_mode != UstPitchMode::Mode1 {
+            return;
+        }
+        let project = data.project_mut();
+        if project.tracks.len() != sources.len() {
+            warn!(
+                "Mode1 pitch bends are ignored: {} files were parsed into {} tracks",
+                sources.len(),
+                project.tracks.len()
+            );
+            return;
+        }
+        let pitches = sources
+            .iter()
+            .map(|source| read_mode1_pitch(source, &project.tempos))
+            .collect::<Vec<_>>();
+        for (track, pitch) in project.tracks.iter_mut().zip(pitches) {
+            if let Some(pitch) = pitch {
+                track.pitch = Some(pitch);
+            }
+        }
+    }
+}
+
+/// Represents a note section (`[#0000]`, `[#0001]`, ...) of a UST file, including rests.
+struct UstNote<'a> {
+    /// Tick position of the start of the note.
+    start: i64,
+    length: i64,
+    /// Offset of the first pitch bend from the start of the note, in milliseconds.
+    pitch_bend_start: Option<f64>,
+    pitch_bend: Option<&'a [u8]>,
+}
+
+/// Returns the note sections of a UST file, in order.
+fn read_notes(source: &[u8]) -> Vec<UstNote<'_>> {
+    let mut notes: Vec<UstNote> = vec![];
+    let mut in_note = false;
+    for line in ust::lines(source) {
+        match line.kind {
+            LineKind::Section(name) => {
+                in_note = ust::is_note_section(name);
+                if in_note {
+                    let start = notes.last().map_or(0, |note| note.start + note.length);
+                    notes.push(UstNote {
+                        start,
+                        length: 0,
+                        pitch_bend_start: None,
+                        pitch_bend: None,
+                    });
+                }
+            }
+            LineKind::Entry(key, value) => {
+                let (Some(note), true) = (notes.last_mut(), in_note) else {
+                    continue;
+                };
+                match key {
+                    b"Length" => note.length = parse_number(value).unwrap_or(0.0) as i64,
+                    b"PBStart" => note.pitch_bend_start = parse_number(value),
+                    b"PitchBend" => note.pitch_bend = Some(value),
+                    _ => {}
+                }
+            }
+            LineKind::Other => {}
+        }
+    }
+    notes
+}
+
+fn parse_number(value: &[u8]) -> Option<f64> {
+    std::str::from_utf8(value).ok()?.trim().parse().ok()
+}
+
+/// Reads the Mode1 pitch bends of a UST file as a relative pitch curve, or returns `None` if the
+/// file has none.
+///
+/// Pitch bends before the start of their note are dropped, since the curve is relative to the
+/// key of the note at each tick.
+fn read_mode1_pitch(source: &[u8], tempos: &[Tempo]) -> Option<Pitch> {
+    let notes = read_notes(source);
+    if notes.iter().all(|note| note.pitch_bend.is_none()) {
+        return None;
+    }
+    let mut ticks = vec![];
+    let mut values = vec![];
+    for note in &notes {
+        let mut push = |tick: i64, value: f64| {
+            if ticks.last().is_some_and(|&last| i64::from(last) >= tick) {
+                return;
+            }
+            ticks.push(tick as i32);
+            values.push(Some(value));
+        };
+        let Some(pitch_bend) = note.pitch_bend else {
+            push(note.start, 0.0);
+            continue;
+        };
+        let bpm = tempos
+            .iter()
+            .take_while(|tempo| tempo.tick_position.get() <= note.start)
+            .last()
+            .map_or(DEFAULT_BPM, |tempo| tempo.bpm);
+        let offset = note.pitch_bend_start.map_or(0, |milliseconds| {
+            (milliseconds * f64::from(bpm) * TICKS_PER_BEAT as f64 / 60_000.0).round() as i64
+        });
+        for (i, cents) in pitch_bend.split(|&byte| byte == b',').enumerate() {
+            let tick = note.start + offset + i as i64 * MODE1_INTERVAL;
+            if tick >= note.start + note.length {
+                break;
+            }
+            if tick >= note.start {
+                push(tick, parse_number(cents).unwrap_or(0.0) / 100.0);
+            }
+        }
+    }
+    Some(Pitch {
+        ticks,
+        values,
+        is_absolute: false,
+    })
+}
+
+/// Writes the pitch curve of `track` as Mode1 pitch bends of a UST file generated from it,
+/// replacing the existing ones. Notes whose pitch doesn't bend are left without them.
+///
+/// The note sections are matched with the notes by their position, so that rests are skipped.
+pub(crate) fn write_mode1_pitch(data: Vec<u8>, track: &Track) -> Vec<u8> {
+    let Some(pitch) = &track.pitch else {
+        return data;
+    };
+    let writer = ust::Writer::new(&data);
+    let mut notes = track.notes.iter().collect::<Vec<_>>();
+    notes.sort_by_key(|note| note.tick_on);
+    let pitch_bend = |start: i64| {
+        let index = notes.partition_point(|note| note.tick_on.get() < start);
+        let note = notes
+            .get(index)
+            .filter(|note| note.tick_on.get() == start)?;
+        let key = f64::from(note.key.get());
+        let cents = (0..note.tick_off - note.tick_on)
+            .step_by(MODE1_INTERVAL as usize)
+            .map(|offset| {
+                let value = pitch.key_at(note.tick_on.saturating_add(offset), note.key);
+                ((value.unwrap_or(key) - key) * 100.0).round() as i64
+            })
+            .collect::<Vec<_>>();
+        if cents.iter().all(|&cents| cents == 0) {
+            return None;
+        }
+        let cents = cents.iter().map(i64::to_string).collect::<Vec<_>>();
+        Some(cents.join(","))
+    };
+
+    let mut result = Vec::with_capacity(data.len());
+    let mut section: Option<(i64, i64)> = None;
+    let mut position = 0;
+    let flush = |result: &mut Vec<u8>, section: Option<(i64, i64)>| {
+        if let Some(cents) = section.and_then(|(start, _)| pitch_bend(start)) {
+            writer.end_line(result);
+            writer.entry(result, "PBType", &MODE1_INTERVAL.to_string());
+            writer.entry(result, "PBStart", "0");
+            writer.entry(result, "PitchBend", &cents);
+        }
+    };
+    for line in ust::lines(&data) {
+        match line.kind {
+            LineKind::Section(name) => {
+                flush(&mut result, section);
+                if let Some((_, length)) = section.take() {
+                    position += length;
+                }
+                if ust::is_note_section(name) {
+                    section = Some((position, 0));
+                }
+            }
+            LineKind::Entry(key, value) => {
+                if let Some((_, length)) = &mut section {
+                    if key == b"Length" {
+                        *length = parse_number(value).unwrap_or(0.0) as i64;
+                    }
+                    if [&b"PBType"[..], b"PBStart", b"PitchBend"].contains(&key) {
+                        continue;
+                    }
+                }
+            }
+            LineKind::Other => {}
+        }
+        result.extend_from_slice(line.raw);
+    }
+    flush(&mut result, section);
+    result
+}
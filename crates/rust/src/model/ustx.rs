@@ -0,0 +1,55 @@
+//! YAML of OpenUtau's projects, shared by what reads and writes the parts of them which
+//! utaformatix-ts doesn't carry over.
+use crate::error::Result;
+use anyhow::anyhow;
+use serde_yaml::{Mapping, Value};
+
+/// Parses an ustx file, or returns `None` if it isn't a YAML mapping.
+pub(super) fn ustx_yaml(data: &[u8]) -> Option<Mapping> {
+    serde_yaml::from_slice(data).ok()
+}
+
+/// Edits a generated ustx file as YAML. The order of the keys is kept, but the whole file is
+/// written back by serde_yaml.
+pub(super) fn edit_ustx(data: Vec<u8>, edit: impl FnOnce(&mut Mapping)) -> Result<Vec<u8>> {
+    let mut project: Mapping = serde_yaml::from_slice(&data)
+        .map_err(|e| anyhow!("Failed to parse generated ustx: {:?}", e))?;
+    edit(&mut project);
+    let result =
+        serde_yaml::to_string(&project).map_err(|e| anyhow!("Failed to write ustx: {:?}", e))?;
+    Ok(result.into_bytes())
+}
+
+/// Returns the items of the top-level list `key` of an ustx project, e.g. `tracks`.
+pub(super) fn ustx_list<'a>(project: &'a Mapping, key: &str) -> &'a [Value] {
+    project
+        .get(key)
+        .and_then(Value::as_sequence)
+        .map_or(&[], Vec::as_slice)
+}
+
+/// Edits each item of the top-level list `key` of an ustx project which is a mapping, with the
+/// index of the item.
+pub(super) fn edit_ustx_list(
+    project: &mut Mapping,
+    key: &str,
+    mut edit: impl FnMut(usize, &mut Mapping),
+) {
+    if let Some(items) = project.get_mut(key).and_then(Value::as_sequence_mut) {
+        for (index, item) in items.iter_mut().enumerate() {
+            if let Some(item) = item.as_mapping_mut() {
+                edit(index, item);
+            }
+        }
+    }
+}
+
+/// Reads a scalar as a string, as OpenUtau does for names, e.g. `123` as `"123"`.
+pub(super) fn ustx_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
@@ -0,0 +1,111 @@
+//! Mute and solo of the tracks of VOCALOID 5's projects, which utaformatix-ts doesn't carry
+//! over. The projects are zip archives with the sequence in `Project/sequence.json`.
+use super::UfData;
+use crate::error::Result;
+use anyhow::anyhow;
+use std::io::{Cursor, Read, Write};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+const SEQUENCE_PATH: &str = "Project/sequence.json";
+
+/// Reads the sequence of a vpr project, or returns `None` if it isn't a vpr project.
+fn read_sequence(data: &[u8]) -> Option<serde_json::Value> {
+    let mut archive = ZipArchive::new(Cursor::new(data)).ok()?;
+    let mut file = archive.by_name(SEQUENCE_PATH).ok()?;
+    let mut sequence = vec![];
+    file.read_to_end(&mut sequence).ok()?;
+    serde_json::from_slice(&sequence).ok()
+}
+
+/// Returns whether a track of a sequence is a singing track (`type` 0), which are the tracks
+/// utaformatix-ts parses. Audio tracks are skipped.
+fn is_singing_track(track: &serde_json::Value) -> bool {
+    track
+        .get("type")
+        .and_then(serde_json::Value::as_i64)
+        .is_none_or(|track_type| track_type == 0)
+}
+
+/// Sets [`Track::muted`](super::Track::muted) and [`Track::solo`](super::Track::solo) of the
+/// tracks parsed from a vpr project, from `isMuted` and `isSoloMode` of its singing tracks.
+pub(crate) fn read_track_states(source: &[u8], data: &mut UfData) {
+    let Some(sequence) = read_sequence(source) else {
+        return;
+    };
+    let vpr_tracks = sequence
+        .get("tracks")
+        .and_then(serde_json::Value::as_array)
+        .map_or(&[][..], Vec::as_slice)
+        .iter()
+        .filter(|track| is_singing_track(track))
+        .collect::<Vec<_>>();
+    let tracks = &mut data.project_mut().tracks;
+    if vpr_tracks.len() != tracks.len() {
+        return;
+    }
+    for (vpr_track, track) in vpr_tracks.into_iter().zip(tracks) {
+        track.muted = vpr_track
+            .get("isMuted")
+            .and_then(serde_json::Value::as_bool);
+        track.solo = vpr_track
+            .get("isSoloMode")
+            .and_then(serde_json::Value::as_bool);
+    }
+}
+
+/// Writes [`Track::muted`](super::Track::muted) and [`Track::solo`](super::Track::solo) to a
+/// vpr project generated from `data`. Values which are `None` are left as generated, and the
+/// other files of the archive are copied as they are.
+pub(crate) fn write_track_states(result: Vec<u8>, data: &UfData) -> Result<Vec<u8>> {
+    let tracks = &data.project().tracks;
+    if !tracks
+        .iter()
+        .any(|track| track.muted.is_some() || track.solo.is_some())
+    {
+        return Ok(result);
+    }
+    let mut sequence = read_sequence(&result)
+        .ok_or_else(|| anyhow!("Failed to read the sequence of generated vpr"))?;
+    if let Some(vpr_tracks) = sequence
+        .get_mut("tracks")
+        .and_then(serde_json::Value::as_array_mut)
+    {
+        let vpr_tracks = vpr_tracks
+            .iter_mut()
+            .filter(|track| is_singing_track(track));
+        for (vpr_track, track) in vpr_tracks.zip(tracks) {
+            let Some(vpr_track) = vpr_track.as_object_mut() else {
+                continue;
+            };
+            if let Some(muted) = track.muted {
+                vpr_track.insert("isMuted".to_string(), muted.into());
+            }
+            if let Some(solo) = track.solo {
+                vpr_track.insert("isSoloMode".to_string(), solo.into());
+            }
+        }
+    }
+    let sequence =
+        serde_json::to_vec(&sequence).map_err(|e| anyhow!("Failed to write vpr: {:?}", e))?;
+    rewrite_archive(&result, &sequence).map_err(|e| anyhow!("Failed to write vpr: {:?}", e).into())
+}
+
+/// Copies the archive of a vpr project, replacing its sequence.
+fn rewrite_archive(data: &[u8], sequence: &[u8]) -> zip::result::ZipResult<Vec<u8>> {
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+    let mut writer = ZipWriter::new(Cursor::new(Vec::with_capacity(data.len())));
+    for index in 0..archive.len() {
+        let file = archive.by_index_raw(index)?;
+        if file.name() != SEQUENCE_PATH {
+            writer.raw_copy_file(file)?;
+            continue;
+        }
+        drop(file);
+        writer.start_file(
+            SEQUENCE_PATH,
+            SimpleFileOptions::default().compression_method(CompressionMethod::Deflated),
+        )?;
+        writer.write_all(sequence)?;
+    }
+    Ok(writer.finish()?.into_inner())
+}
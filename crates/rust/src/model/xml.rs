@@ -0,0 +1,197 @@
+//! Reading and editing XML documents, for what utaformatix-ts doesn't carry over in the formats
+//! which are XML.
+//!
+//! Documents are read with quick-xml, and edited by replacing the ranges of elements in the
+//! original text, so that everything else is kept as it was written.
+use quick_xml::{
+    escape::escape,
+    events::{BytesStart, Event},
+    Reader,
+};
+use std::ops::Range;
+
+/// Represents an element of an XML document, returned by [`elements`].
+#[derive(Debug, Clone)]
+pub(super) struct Element {
+    /// Name of the element, including its prefix.
+    pub(super) name: String,
+    /// Attributes of the start tag, in order, with their values unescaped.
+    pub(super) attributes: Vec<(String, String)>,
+    /// Range of the start tag, which is the whole element if it's empty (`<name/>`).
+    pub(super) start_tag: Range<usize>,
+    /// Range of the content between the tags, which is empty at the end of the start tag if the
+    /// element is empty.
+    pub(super) content: Range<usize>,
+    /// Range of the whole element, including the tags.
+    pub(super) range: Range<usize>,
+    /// Index of the parent element, if any.
+    pub(super) parent: Option<usize>,
+    /// Text directly in the element, including CDATA sections, with references unescaped.
+    pub(super) text: String,
+}
+
+impl Element {
+    /// Returns the unescaped value of an attribute.
+    pub(super) fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the replacement of the start tag in `text` which sets attributes, adding the ones
+    /// which don't exist at the end. The end of the tag (e.g. ` />`) is kept as written.
+    pub(super) fn set_attributes(
+        &self,
+        text: &str,
+        values: &[(&str, &str)],
+    ) -> (Range<usize>, String) {
+        let mut attributes = self.attributes.clone();
+        for (name, value) in values {
+            match attributes.iter_mut().find(|(key, _)| key == name) {
+                Some((_, old)) => *old = value.to_string(),
+                None => attributes.push((name.to_string(), value.to_string())),
+            }
+        }
+        let original = &text[self.start_tag.clone()];
+        let body = original
+            .strip_suffix("/>")
+            .or_else(|| original.strip_suffix('>'))
+            .unwrap_or(original)
+            .trim_end();
+        let mut tag = format!("<{}", self.name);
+        for (key, value) in &attributes {
+            tag.push_str(&format!(" {}=\"{}\"", key, escape(value.as_str())));
+        }
+        tag.push_str(&original[body.len()..]);
+        (self.start_tag.clone(), tag)
+    }
+}
+
+/// Reads the elements of an XML document, in document order, skipping declarations, comments
+/// and processing instructions.
+///
+/// Reading stops at malformed markup, and the elements which are open there end at the end of
+/// the text, so that what comes before it is still read.
+pub(super) fn elements(text: &str) -> Vec<Element> {
+    let mut reader = Reader::from_str(text);
+    let mut elements: Vec<Element> = vec![];
+    let mut open: Vec<usize> = vec![];
+    loop {
+        let start = reader.buffer_position() as usize;
+        let Ok(event) = reader.read_event() else {
+            break;
+        };
+        let end = reader.buffer_position() as usize;
+        match event {
+            Event::Start(tag) => {
+                elements.push(element(&tag, start..end, open.last().copied()));
+                open.push(elements.len() - 1);
+            }
+            Event::Empty(tag) => {
+                elements.push(element(&tag, start..end, open.last().copied()));
+            }
+            Event::End(_) => {
+                if let Some(element) = open.pop().map(|index| &mut elements[index]) {
+                    element.content.end = start;
+                    element.range.end = end;
+                }
+            }
+            Event::Text(content) => {
+                if let Some(&index) = open.last() {
+                    match content.unescape() {
+                        Ok(content) => elements[index].text.push_str(&content),
+                        Err(_) => elements[index]
+                            .text
+                            .push_str(&String::from_utf8_lossy(&content)),
+                    }
+                }
+            }
+            Event::CData(content) => {
+                if let Some(&index) = open.last() {
+                    elements[index]
+                        .text
+                        .push_str(&String::from_utf8_lossy(&content));
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    for index in open {
+        elements[index].content.end = text.len();
+        elements[index].range.end = text.len();
+    }
+    elements
+}
+
+/// Returns an element read from a start tag, which ends at the end of the tag until its end tag
+/// is read.
+fn element(tag: &BytesStart, range: Range<usize>, parent: Option<usize>) -> Element {
+    let attributes = tag
+        .attributes()
+        .with_checks(false)
+        .flatten()
+        .map(|attribute| {
+            let value = match attribute.unescape_value() {
+                Ok(value) => value.into_owned(),
+                Err(_) => String::from_utf8_lossy(&attribute.value).into_owned(),
+            };
+            (
+                String::from_utf8_lossy(attribute.key.as_ref()).into_owned(),
+                value,
+            )
+        })
+        .collect();
+    Element {
+        name: String::from_utf8_lossy(tag.name().as_ref()).into_owned(),
+        attributes,
+        start_tag: range.clone(),
+        content: range.end..range.end,
+        range,
+        parent,
+        text: String::new(),
+    }
+}
+
+/// Returns the descendants of the element at `index` with their indices, in document order.
+pub(super) fn descendants(
+    elements: &[Element],
+    index: usize,
+) -> impl Iterator<Item = (usize, &Element)> {
+    let end = elements[index].range.end;
+    elements
+        .iter()
+        .enumerate()
+        .skip(index + 1)
+        .take_while(move |(_, element)| element.range.start < end)
+}
+
+/// Returns the children of the element at `index` with their indices, in document order.
+pub(super) fn children(
+    elements: &[Element],
+    index: usize,
+) -> impl Iterator<Item = (usize, &Element)> {
+    descendants(elements, index).filter(move |(_, element)| element.parent == Some(index))
+}
+
+/// Returns the first child named `name` of the element at `index`.
+pub(super) fn child<'a>(elements: &'a [Element], index: usize, name: &str) -> Option<&'a Element> {
+    children(elements, index)
+        .map(|(_, element)| element)
+        .find(|element| element.name == name)
+}
+
+/// Replaces ranges of `text`, which must not overlap. Empty ranges insert the replacements.
+pub(super) fn splice(text: &str, mut replacements: Vec<(Range<usize>, String)>) -> String {
+    replacements.sort_by_key(|(range, _)| range.start);
+    let mut result = String::with_capacity(text.len());
+    let mut position = 0;
+    for (range, replacement) in replacements {
+        result.push_str(&text[position..range.start]);
+        result.push_str(&replacement);
+        position = range.end;
+    }
+    result.push_str(&text[position..]);
+    result
+}
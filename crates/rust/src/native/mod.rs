@@ -0,0 +1,4 @@
+//! Native implementations of formats, which don't need the JS thread.
+pub(crate) mod smf;
+pub(crate) mod standard_mid;
+pub(crate) mod uf_data;
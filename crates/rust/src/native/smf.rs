@@ -0,0 +1,326 @@
+//! A minimal Standard MIDI File reader/writer.
+use crate::error::{Error, ErrorKind, IllegalFile, Result};
+use std::borrow::Cow;
+
+/// Meta event type of track names.
+pub(crate) const META_TRACK_NAME: u8 = 0x03;
+/// Meta event type of lyrics.
+pub(crate) const META_LYRIC: u8 = 0x05;
+/// Meta event type of the end of a track.
+pub(crate) const META_END_OF_TRACK: u8 = 0x2f;
+/// Meta event type of tempo changes.
+pub(crate) const META_TEMPO: u8 = 0x51;
+/// Meta event type of time signatures.
+pub(crate) const META_TIME_SIGNATURE: u8 = 0x58;
+/// Meta event type of key signatures.
+pub(crate) const META_KEY_SIGNATURE: u8 = 0x59;
+/// Maximum delta time of an event, which is a 28-bit variable-length quantity.
+const MAX_DELTA: u64 = (1 << 28) - 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Smf<'a> {
+    pub(crate) format: u16,
+    /// Ticks per quarter note.
+    pub(crate) division: u16,
+    pub(crate) tracks: Vec<Vec<Event<'a>>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Event<'a> {
+    /// Absolute tick position of the event.
+    pub(crate) tick: u64,
+    pub(crate) kind: EventKind<'a>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum EventKind<'a> {
+    NoteOn {
+        channel: u8,
+        key: u8,
+        velocity: u8,
+    },
+    NoteOff {
+        channel: u8,
+        key: u8,
+        velocity: u8,
+    },
+    PitchBend {
+        channel: u8,
+        value: u16,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+    /// Meta events, whose data is borrowed from the file or the project when possible.
+    Meta {
+        kind: u8,
+        data: Cow<'a, [u8]>,
+    },
+    /// Other channel messages and system exclusive messages, which are kept as raw bytes.
+    Other(Vec<u8>),
+}
+
+fn illegal() -> Error {
+    IllegalFile::IllegalMidiFile.into()
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8]> {
+        let end = self.position.checked_add(length).ok_or_else(illegal)?;
+        let bytes = self.data.get(self.position..end).ok_or_else(illegal)?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    /// Reads a data byte of a channel message, which must not have the top bit set.
+    fn data_byte(&mut self) -> Result<u8> {
+        let byte = self.u8()?;
+        if byte & 0x80 != 0 {
+            return Err(illegal());
+        }
+        Ok(byte)
+    }
+
+    fn peek(&self) -> Result<u8> {
+        self.data.get(self.position).copied().ok_or_else(illegal)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn variable_length(&mut self) -> Result<u32> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let byte = self.u8()?;
+            value = (value << 7) | u32::from(byte & 0x7f);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(illegal())
+    }
+}
+
+/// Reads a Standard MIDI File.
+pub(crate) fn read(data: &[u8]) -> Result<Smf<'_>> {
+    let mut reader = Reader::new(data);
+    if reader.bytes(4)? != b"MThd" {
+        return Err(illegal());
+    }
+    let header_length = reader.u32()? as usize;
+    if header_length < 6 {
+        return Err(illegal());
+    }
+    let format = reader.u16()?;
+    let track_count = reader.u16()?;
+    let division = reader.u16()?;
+    if division & 0x8000 != 0 || division == 0 {
+        // SMPTE time division is not supported.
+        return Err(illegal());
+    }
+    reader.bytes(header_length - 6)?;
+
+    let mut tracks = Vec::with_capacity(track_count as usize);
+    while !reader.is_empty() && tracks.len() < track_count as usize {
+        let chunk_type = reader.bytes(4)?;
+        let length = reader.u32()? as usize;
+        let chunk = reader.bytes(length)?;
+        if chunk_type == b"MTrk" {
+            tracks.push(read_track(chunk)?);
+        }
+    }
+
+    Ok(Smf {
+        format,
+        division,
+        tracks,
+    })
+}
+
+fn read_track(data: &[u8]) -> Result<Vec<Event<'_>>> {
+    let mut reader = Reader::new(data);
+    let mut events = vec![];
+    let mut tick = 0u64;
+    let mut running_status = None;
+    while !reader.is_empty() {
+        tick += u64::from(reader.variable_length()?);
+        let status = if reader.peek()? & 0x80 != 0 {
+            reader.u8()?
+        } else {
+            running_status.ok_or_else(illegal)?
+        };
+        let kind = match status {
+            // Meta and system exclusive events cancel the running status.
+            0xff => {
+                running_status = None;
+                let kind = reader.u8()?;
+                let length = reader.variable_length()? as usize;
+                let data = Cow::Borrowed(reader.bytes(length)?);
+                if kind == META_END_OF_TRACK {
+                    break;
+                }
+                EventKind::Meta { kind, data }
+            }
+            0xf0 | 0xf7 => {
+                running_status = None;
+                let length = reader.variable_length()? as usize;
+                let mut raw = vec![status];
+                raw.extend_from_slice(reader.bytes(length)?);
+                EventKind::Other(raw)
+            }
+            0x80..=0xef => {
+                running_status = Some(status);
+                let channel = status & 0x0f;
+                match status & 0xf0 {
+                    0x80 => EventKind::NoteOff {
+                        channel,
+                        key: reader.data_byte()?,
+                        velocity: reader.data_byte()?,
+                    },
+                    0x90 => {
+                        let key = reader.data_byte()?;
+                        let velocity = reader.data_byte()?;
+                        if velocity == 0 {
+                            EventKind::NoteOff {
+                                channel,
+                                key,
+                                velocity,
+                            }
+                        } else {
+                            EventKind::NoteOn {
+                                channel,
+                                key,
+                                velocity,
+                            }
+                        }
+                    }
+                    0xb0 => EventKind::ControlChange {
+                        channel,
+                        controller: reader.data_byte()?,
+                        value: reader.data_byte()?,
+                    },
+                    0xe0 => {
+                        let lsb = reader.data_byte()?;
+                        let msb = reader.data_byte()?;
+                        EventKind::PitchBend {
+                            channel,
+                            value: (u16::from(msb) << 7) | u16::from(lsb),
+                        }
+                    }
+                    0xc0 | 0xd0 => EventKind::Other(vec![status, reader.data_byte()?]),
+                    _ => EventKind::Other(vec![status, reader.data_byte()?, reader.data_byte()?]),
+                }
+            }
+            _ => return Err(illegal()),
+        };
+        events.push(Event { tick, kind });
+    }
+
+    Ok(events)
+}
+
+fn write_variable_length(buffer: &mut Vec<u8>, value: u32) {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    let mut value = value >> 7;
+    while value > 0 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.reverse();
+    buffer.extend(bytes);
+}
+
+/// Writes a Standard MIDI File.
+///
+/// Events in each track must be sorted by tick. End of track events are added automatically.
+/// Fails if two consecutive events are too far apart to be stored.
+pub(crate) fn write(smf: &Smf) -> Result<Vec<u8>> {
+    let mut buffer = vec![];
+    buffer.extend_from_slice(b"MThd");
+    buffer.extend_from_slice(&6u32.to_be_bytes());
+    buffer.extend_from_slice(&smf.format.to_be_bytes());
+    buffer.extend_from_slice(&(smf.tracks.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(&smf.division.to_be_bytes());
+
+    for track in &smf.tracks {
+        let mut chunk = vec![];
+        let mut tick = 0u64;
+        for event in track {
+            let delta = event.tick.saturating_sub(tick);
+            if delta > MAX_DELTA {
+                return Err(ErrorKind::IllegalNotePosition.into());
+            }
+            write_variable_length(&mut chunk, delta as u32);
+            tick = tick.max(event.tick);
+            match &event.kind {
+                EventKind::NoteOn {
+                    channel,
+                    key,
+                    velocity,
+                } => chunk.extend([0x90 | channel, *key, *velocity]),
+                EventKind::NoteOff {
+                    channel,
+                    key,
+                    velocity,
+                } => chunk.extend([0x80 | channel, *key, *velocity]),
+                EventKind::PitchBend { channel, value } => chunk.extend([
+                    0xe0 | channel,
+                    (value & 0x7f) as u8,
+                    ((value >> 7) & 0x7f) as u8,
+                ]),
+                EventKind::ControlChange {
+                    channel,
+                    controller,
+                    value,
+                } => chunk.extend([0xb0 | channel, *controller, *value]),
+                EventKind::Meta { kind, data } => {
+                    chunk.extend([0xff, *kind]);
+                    write_variable_length(&mut chunk, data.len() as u32);
+                    chunk.extend_from_slice(data);
+                }
+                EventKind::Other(raw) => {
+                    if let [status @ (0xf0 | 0xf7), rest @ ..] = raw.as_slice() {
+                        chunk.push(*status);
+                        write_variable_length(&mut chunk, rest.len() as u32);
+                        chunk.extend_from_slice(rest);
+                    } else {
+                        chunk.extend_from_slice(raw);
+                    }
+                }
+            }
+        }
+        chunk.extend([0x00, 0xff, META_END_OF_TRACK, 0x00]);
+
+        buffer.extend_from_slice(b"MTrk");
+        buffer.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+        buffer.extend(chunk);
+    }
+
+    Ok(buffer)
+}
@@ -0,0 +1,471 @@
+//! Native implementation of Standard MIDI files.
+use super::smf::{self, Event, EventKind, Smf};
+use crate::{
+    error::{ErrorKind, Result},
+    model::{
+        tick_to_measure, Format, FormatOptions, GenerateOptions, Key, KeySignature, Mode, Note,
+        ParseOptions, Pitch, StandardMidOptions, Tempo, Tick, TimeSignature, Track, UfData,
+        UfProject, TICKS_PER_BEAT,
+    },
+};
+use std::{borrow::Cow, collections::HashMap};
+
+/// Project name used for parsed files, matching the JS implementation.
+const PROJECT_NAME: &str = "data.mid";
+const NOTE_VELOCITY: u8 = 100;
+/// Pitch bend range assumed by General MIDI, in semitones.
+const DEFAULT_PITCH_BEND_RANGE: u8 = 2;
+const PITCH_BEND_CENTER: i64 = 8192;
+
+/// Decodes a text meta event, falling back to Shift_JIS for legacy files.
+pub(crate) fn decode_text(data: &[u8]) -> String {
+    match std::str::from_utf8(data) {
+        Ok(text) => text.to_string(),
+        Err(_) => encoding_rs::SHIFT_JIS.decode(data).0.into_owned(),
+    }
+}
+
+fn convert_tick(tick: u64, division: u16) -> Tick {
+    Tick::ZERO.saturating_add((tick as i64 * TICKS_PER_BEAT) / i64::from(division))
+}
+
+/// Parses a Standard MIDI file.
+pub(crate) fn parse(data: &[u8], options: ParseOptions) -> Result<UfData> {
+    options.validate_fallback_timing()?;
+    let smf = smf::read(data)?;
+    from_smf(&smf, PROJECT_NAME.to_string(), &options)
+}
+
+/// Reads the project from the events of a Standard MIDI file, which is shared with the
+/// conversion from `midly::Smf`. The fallback timing of the options must be validated first.
+pub(crate) fn from_smf(smf: &Smf, name: String, options: &ParseOptions) -> Result<UfData> {
+    let division = smf.division;
+    let (tempos, time_signatures) = timing(smf, options);
+    let key_signatures = key_signatures(smf, &time_signatures);
+
+    let mut project = UfProject {
+        name,
+        tracks: vec![],
+        time_signatures,
+        tempos,
+        measure_prefix: 0,
+        key_signatures,
+    };
+    for (index, events) in smf.tracks.iter().enumerate() {
+        if !options.standard_mid.includes_track(index) {
+            continue;
+        }
+        let Some((name, notes)) = parse_track(events, division, options) else {
+            continue;
+        };
+        let name = name.unwrap_or_else(|| format!("Track {}", project.tracks.len() + 1));
+        project.tracks.push(Track {
+            name: options.standard_mid.track_name(name),
+            notes,
+            pitch: None,
+            muted: None,
+            solo: None,
+            order: None,
+            color: None,
+            voice: None,
+            parameters: None,
+        });
+    }
+
+    if project.tracks.is_empty() {
+        return Err(ErrorKind::EmptyProject.into());
+    }
+
+    Ok(UfData::new(project))
+}
+
+/// Reads the tempo map, time signatures and key signatures of a Standard MIDI file.
+pub(crate) fn read_timing(
+    data: &[u8],
+) -> Result<(Vec<Tempo>, Vec<TimeSignature>, Vec<KeySignature>)> {
+    let smf = smf::read(data)?;
+    let (tempos, time_signatures) = timing(&smf, &ParseOptions::default());
+    let key_signatures = key_signatures(&smf, &time_signatures);
+    Ok((tempos, time_signatures, key_signatures))
+}
+
+/// Collects the tempo changes and time signatures from every track, falling back to the
+/// ones of the options at the start.
+fn timing(smf: &Smf, options: &ParseOptions) -> (Vec<Tempo>, Vec<TimeSignature>) {
+    let division = smf.division;
+    let mut tempos = vec![];
+    let mut time_signature_ticks = vec![];
+    for event in smf.tracks.iter().flatten() {
+        let EventKind::Meta { kind, data } = &event.kind else {
+            continue;
+        };
+        let tick = convert_tick(event.tick, division);
+        match (*kind, &**data) {
+            (smf::META_TEMPO, [a, b, c]) => {
+                let microseconds = u32::from_be_bytes([0, *a, *b, *c]);
+                if microseconds == 0 {
+                    continue;
+                }
+                tempos.push(Tempo {
+                    tick_position: tick,
+                    bpm: (60_000_000.0 / f64::from(microseconds)).round() as i32,
+                });
+            }
+            (smf::META_TIME_SIGNATURE, [numerator, denominator, ..]) if *denominator < 8 => {
+                time_signature_ticks.push((tick, i32::from(*numerator), 1 << denominator));
+            }
+            _ => {}
+        }
+    }
+
+    tempos.sort_by_key(|tempo| tempo.tick_position);
+    tempos.dedup_by(|next, previous| {
+        if next.tick_position == previous.tick_position {
+            previous.bpm = next.bpm;
+            true
+        } else {
+            false
+        }
+    });
+    if tempos.first().map(|tempo| tempo.tick_position) != Some(Tick::ZERO) {
+        tempos.insert(0, options.start_tempo());
+    }
+
+    let mut time_signatures: Vec<TimeSignature> = vec![];
+    time_signature_ticks.sort_by_key(|(tick, _, _)| *tick);
+    for (tick, numerator, denominator) in time_signature_ticks {
+        if numerator == 0 {
+            continue;
+        }
+        let measure_position = tick_to_measure(&time_signatures, tick.get());
+        time_signatures
+            .retain(|time_signature| time_signature.measure_position != measure_position);
+        time_signatures.push(TimeSignature {
+            measure_position,
+            numerator,
+            denominator,
+        });
+    }
+    if time_signatures
+        .first()
+        .map(|time_signature| time_signature.measure_position)
+        != Some(0)
+    {
+        time_signatures.insert(0, options.start_time_signature());
+    }
+
+    (tempos, time_signatures)
+}
+
+/// Collects the key signatures from every track. Later ones win when several are in the same
+/// measure.
+fn key_signatures(smf: &Smf, time_signatures: &[TimeSignature]) -> Vec<KeySignature> {
+    let mut key_signature_ticks = vec![];
+    for event in smf.tracks.iter().flatten() {
+        let EventKind::Meta {
+            kind: smf::META_KEY_SIGNATURE,
+            data,
+        } = &event.kind
+        else {
+            continue;
+        };
+        let &[fifths, mode] = &**data else {
+            continue;
+        };
+        let mode = if mode == 0 { Mode::Major } else { Mode::Minor };
+        key_signature_ticks.push((
+            convert_tick(event.tick, smf.division),
+            i32::from(fifths as i8),
+            mode,
+        ));
+    }
+
+    let mut key_signatures: Vec<KeySignature> = vec![];
+    key_signature_ticks.sort_by_key(|(tick, _, _)| *tick);
+    for (tick, fifths, mode) in key_signature_ticks {
+        let measure_position = tick_to_measure(time_signatures, tick.get());
+        key_signatures.retain(|key_signature| key_signature.measure_position != measure_position);
+        key_signatures.push(KeySignature {
+            measure_position,
+            ..KeySignature::from_fifths(fifths, mode)
+        });
+    }
+    key_signatures
+}
+
+fn parse_track(
+    events: &[Event],
+    division: u16,
+    options: &ParseOptions,
+) -> Option<(Option<String>, Vec<Note>)> {
+    let mut name = None;
+    let mut notes: Vec<Note> = vec![];
+    let mut pending_lyric: Option<String> = None;
+    let mut open_notes: HashMap<(u8, u8), usize> = HashMap::new();
+
+    for event in events {
+        let tick = convert_tick(event.tick, division);
+        match &event.kind {
+            EventKind::Meta {
+                kind: smf::META_TRACK_NAME,
+                data,
+            } if name.is_none() => {
+                name = Some(decode_text(data));
+            }
+            EventKind::Meta {
+                kind: smf::META_LYRIC,
+                data,
+            } => {
+                let lyric = decode_text(data);
+                // Some files put the lyric event right after the note on event.
+                match notes.last_mut() {
+                    Some(note) if note.tick_on == tick && note.lyric.is_empty() => {
+                        note.lyric = lyric;
+                    }
+                    _ => pending_lyric = Some(lyric),
+                }
+            }
+            EventKind::NoteOn { channel, .. } | EventKind::NoteOff { channel, .. }
+                if !options.standard_mid.includes_channel(*channel) => {}
+            EventKind::NoteOn { channel, key, .. } => {
+                if let Some(index) = open_notes.remove(&(*channel, *key)) {
+                    notes[index].tick_off = tick;
+                }
+                let Some(note_key) = Key::new(i32::from(*key)) else {
+                    continue;
+                };
+                open_notes.insert((*channel, *key), notes.len());
+                notes.push(Note {
+                    key: note_key,
+                    tick_on: tick,
+                    tick_off: tick,
+                    lyric: pending_lyric.take().unwrap_or_default(),
+                    phoneme: None,
+                    phoneme_segments: None,
+                });
+            }
+            EventKind::NoteOff { channel, key, .. } => {
+                if let Some(index) = open_notes.remove(&(*channel, *key)) {
+                    notes[index].tick_off = tick;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if notes.is_empty() {
+        return None;
+    }
+
+    notes.sort_by_key(|note| (note.tick_on, note.key));
+    for i in 1..notes.len() {
+        let next_tick_on = notes[i].tick_on;
+        let previous = &mut notes[i - 1];
+        if previous.tick_off > next_tick_on {
+            previous.tick_off = next_tick_on;
+        }
+    }
+    notes.retain(|note| note.tick_off > note.tick_on);
+    options.clean_lyrics(&mut notes);
+
+    Some((name, notes))
+}
+
+/// Generates a Standard MIDI file.
+pub(crate) fn generate(data: &UfData, options: GenerateOptions) -> Result<Vec<u8>> {
+    let mut data = Cow::Borrowed(data);
+    options.prepare(Format::StandardMid, &mut data)?;
+    smf::write(&to_smf(&data, &options)?)
+}
+
+/// Builds the events of a Standard MIDI file from prepared data, which is shared with the
+/// conversion to `midly::Smf`. Names and lyrics are borrowed from the data.
+pub(crate) fn to_smf<'a>(data: &'a UfData, options: &GenerateOptions) -> Result<Smf<'a>> {
+    let project = data.project();
+    let midi_options = match &options.format_options {
+        Some(FormatOptions::StandardMid(midi_options)) => midi_options.clone(),
+        _ => StandardMidOptions::default(),
+    };
+    let channel = midi_options.channel.min(15);
+    if project.tracks.iter().all(|track| track.notes.is_empty()) {
+        return Err(ErrorKind::EmptyProject.into());
+    }
+
+    let mut conductor = vec![Event {
+        tick: 0,
+        kind: EventKind::Meta {
+            kind: smf::META_TRACK_NAME,
+            data: Cow::Borrowed(project.name.as_bytes()),
+        },
+    }];
+    for time_signature in &project.time_signatures {
+        let numerator = u8::try_from(time_signature.numerator).unwrap_or(0);
+        if !time_signature.is_valid() || numerator == 0 {
+            return Err(ErrorKind::IllegalTimeSignature.into());
+        }
+        conductor.push(Event {
+            tick: project
+                .measure_to_tick(time_signature.measure_position)
+                .get()
+                .max(0) as u64,
+            kind: EventKind::Meta {
+                kind: smf::META_TIME_SIGNATURE,
+                data: Cow::Owned(vec![
+                    numerator,
+                    time_signature.denominator.trailing_zeros() as u8,
+                    24,
+                    8,
+                ]),
+            },
+        });
+    }
+    for key_signature in &project.key_signatures {
+        conductor.push(Event {
+            tick: project
+                .measure_to_tick(key_signature.measure_position)
+                .get()
+                .max(0) as u64,
+            kind: EventKind::Meta {
+                kind: smf::META_KEY_SIGNATURE,
+                data: Cow::Owned(vec![
+                    key_signature.fifths() as i8 as u8,
+                    u8::from(key_signature.mode == Mode::Minor),
+                ]),
+            },
+        });
+    }
+    for tempo in &project.tempos {
+        let microseconds = tempo
+            .microseconds_per_beat()
+            .ok_or(ErrorKind::IllegalTempo)?;
+        let bytes = microseconds.to_be_bytes();
+        conductor.push(Event {
+            tick: tempo.tick_position.get().max(0) as u64,
+            kind: EventKind::Meta {
+                kind: smf::META_TEMPO,
+                data: Cow::Owned(bytes[1..].to_vec()),
+            },
+        });
+    }
+    conductor.sort_by_key(|event| event.tick);
+
+    let mut tracks = vec![conductor];
+    for track in &project.tracks {
+        let mut events = vec![Event {
+            tick: 0,
+            kind: EventKind::Meta {
+                kind: smf::META_TRACK_NAME,
+                data: Cow::Borrowed(track.name.as_bytes()),
+            },
+        }];
+        if let Some(range) = midi_options.pitch_bend_range {
+            // RPN 0 (pitch bend sensitivity): the range in semitones, then in cents.
+            for (controller, value) in [(101, 0), (100, 0), (6, range.min(127)), (38, 0)] {
+                events.push(Event {
+                    tick: 0,
+                    kind: EventKind::ControlChange {
+                        channel,
+                        controller,
+                        value,
+                    },
+                });
+            }
+        }
+        let pitch = track.pitch.as_ref().filter(|_| options.pitch);
+        let mut bend = PITCH_BEND_CENTER as u16;
+        for note in &track.notes {
+            let key = note.key.get().clamp(0, 127) as u8;
+            let tick_on = note.tick_on.get().max(0) as u64;
+            let tick_off = note.tick_off.get().max(0) as u64;
+            events.push(Event {
+                tick: tick_on,
+                kind: EventKind::Meta {
+                    kind: smf::META_LYRIC,
+                    data: Cow::Borrowed(note.lyric.as_bytes()),
+                },
+            });
+            let bends = match pitch {
+                Some(pitch) => pitch_bends(pitch, note, &midi_options),
+                None => vec![],
+            };
+            let mut bends = bends.into_iter().peekable();
+            // The bend at the start of the note has to be sent before the note on.
+            if let Some((_, value)) = bends.next_if(|(tick, _)| *tick == note.tick_on) {
+                if value != bend {
+                    events.push(Event {
+                        tick: tick_on,
+                        kind: EventKind::PitchBend { channel, value },
+                    });
+                    bend = value;
+                }
+            }
+            events.push(Event {
+                tick: tick_on,
+                kind: EventKind::NoteOn {
+                    channel,
+                    key,
+                    velocity: NOTE_VELOCITY,
+                },
+            });
+            for (tick, value) in bends {
+                if value != bend {
+                    events.push(Event {
+                        tick: tick.get().max(0) as u64,
+                        kind: EventKind::PitchBend { channel, value },
+                    });
+                    bend = value;
+                }
+            }
+            events.push(Event {
+                tick: tick_off,
+                kind: EventKind::NoteOff {
+                    channel,
+                    key,
+                    velocity: 0,
+                },
+            });
+        }
+        // Note offs must come before note ons at the same tick.
+        events.sort_by_key(|event| (event.tick, !matches!(event.kind, EventKind::NoteOff { .. })));
+        tracks.push(events);
+    }
+
+    Ok(Smf {
+        format: 1,
+        division: TICKS_PER_BEAT as u16,
+        tracks,
+    })
+}
+
+/// Renders the pitch curve during the note as pitch bend values, in pairs of tick and value.
+fn pitch_bends(pitch: &Pitch, note: &Note, options: &StandardMidOptions) -> Vec<(Tick, u16)> {
+    let range = f64::from(
+        options
+            .pitch_bend_range
+            .unwrap_or(DEFAULT_PITCH_BEND_RANGE)
+            .max(1),
+    );
+    let value_at = |tick: Tick| {
+        let offset = pitch
+            .key_at(tick, note.key)
+            .map_or(0.0, |key| key - f64::from(note.key.get()));
+        let value = PITCH_BEND_CENTER + (offset / range * PITCH_BEND_CENTER as f64).round() as i64;
+        value.clamp(0, PITCH_BEND_CENTER * 2 - 1) as u16
+    };
+
+    let mut bends = vec![(note.tick_on, value_at(note.tick_on))];
+    let start = pitch
+        .ticks
+        .partition_point(|tick| i64::from(*tick) <= note.tick_on.get());
+    for tick in pitch.ticks[start..]
+        .iter()
+        .filter_map(|tick| Tick::new(i64::from(*tick)))
+        .take_while(|tick| *tick < note.tick_off)
+    {
+        let (last_tick, _) = bends[bends.len() - 1];
+        if tick - last_tick >= i64::from(options.pitch_bend_resolution) {
+            bends.push((tick, value_at(tick)));
+        }
+    }
+    bends
+}
@@ -0,0 +1,58 @@
+//! Native implementation of UtaFormatix data.
+use crate::{
+    error::{Error, ErrorKind, IllegalFile, Result},
+    model::{Format, GenerateOptions, ParseOptions, TimeSignature, UfData},
+};
+use std::borrow::Cow;
+
+/// Parses a UtaFormatix data file.
+pub(crate) fn parse(data: &[u8], options: ParseOptions) -> Result<UfData> {
+    let value: serde_json::Value =
+        serde_json::from_slice(data).map_err(|_| Error::from(IllegalFile::IllegalUfDataFile))?;
+    let format_version = value
+        .get("formatVersion")
+        .and_then(serde_json::Value::as_i64)
+        .ok_or_else(|| Error::from(IllegalFile::IllegalUfDataFile))?;
+    if format_version != i64::from(UfData::FORMAT_VERSION) {
+        return Err(ErrorKind::UnsupportedFileFormat.into());
+    }
+    let mut data: UfData =
+        serde_json::from_value(value).map_err(|_| Error::from(IllegalFile::IllegalUfDataFile))?;
+    let project = data.project_mut();
+    if !project.time_signatures.iter().all(TimeSignature::is_valid) {
+        return Err(ErrorKind::IllegalTimeSignature.into());
+    }
+    // Conversions between measures and ticks expect the signatures to be sorted.
+    project
+        .time_signatures
+        .sort_by_key(|time_signature| time_signature.measure_position);
+    project
+        .key_signatures
+        .sort_by_key(|key_signature| key_signature.measure_position);
+
+    for track in &mut data.project_mut().tracks {
+        if !options.pitch {
+            track.pitch = None;
+        }
+        options.clean_lyrics(&mut track.notes);
+    }
+
+    Ok(data)
+}
+
+/// Generates a UtaFormatix data file.
+pub(crate) fn generate(data: &UfData, options: GenerateOptions) -> Result<Vec<u8>> {
+    let mut data = Cow::Borrowed(data);
+    options.prepare(Format::UfData, &mut data)?;
+    let result = if options.pitch {
+        serde_json::to_vec(&data)
+    } else {
+        let mut data = data.into_owned();
+        for track in &mut data.project_mut().tracks {
+            track.pitch = None;
+        }
+        serde_json::to_vec(&data)
+    };
+
+    Ok(result.map_err(anyhow::Error::from)?)
+}
@@ -0,0 +1,52 @@
+//! Timed MIDI events for live playback, e.g. through `midir` output ports.
+use crate::project::{Project, TempoMap};
+use midly::{num::u7, MidiMessage};
+use std::time::Duration;
+
+/// Velocity of played notes.
+const VELOCITY: u8 = 100;
+
+impl Project {
+    /// Returns note on/off messages of all tracks, with their times from the start of the
+    /// project, sorted by time.
+    ///
+    /// On the same time, note offs come first so adjoining notes of the same key are not cut.
+    /// Notes with keys out of the MIDI range are skipped. Send them on a channel of your choice,
+    /// e.g. with [`midly::live::LiveEvent::Midi`].
+    pub fn playback_events(&self) -> Vec<(Duration, MidiMessage)> {
+        let project = self.data.project();
+        let tempo_map = TempoMap::new(&project.tempos);
+        let to_duration =
+            |tick: i64| Duration::from_secs_f64(tempo_map.tick_to_seconds(tick).max(0.0));
+
+        let mut events = vec![];
+        for note in project.tracks.iter().flat_map(|track| &track.notes) {
+            let Some(key) = u8::try_from(note.key.get()).ok().filter(|key| *key <= 127) else {
+                continue;
+            };
+            let key = u7::new(key);
+            events.push((
+                note.tick_on.get(),
+                1,
+                MidiMessage::NoteOn {
+                    key,
+                    vel: u7::new(VELOCITY),
+                },
+            ));
+            events.push((
+                note.tick_off.get(),
+                0,
+                MidiMessage::NoteOff {
+                    key,
+                    vel: u7::new(0),
+                },
+            ));
+        }
+        events.sort_by_key(|(tick, order, _)| (*tick, *order));
+
+        events
+            .into_iter()
+            .map(|(tick, _, message)| (to_duration(tick), message))
+            .collect()
+    }
+}
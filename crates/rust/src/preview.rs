@@ -0,0 +1,142 @@
+//! Simple audio previews of tracks, for checking conversions by ear.
+//!
+//! This is not a singing synthesizer: every note is rendered as a plain oscillator tone.
+use crate::{
+    error::{ErrorKind, Result},
+    model::{Tick, Track, UfProject},
+    project::{Project, TempoMap},
+};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::TAU;
+
+/// Length of the fade in and out of each note, in seconds, which avoids clicks.
+const FADE_SECONDS: f64 = 0.005;
+
+/// Represents the waveform of the oscillator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Waveform {
+    /// Sine wave, which is soft but easy to follow.
+    Sine,
+    /// Sawtooth wave, which is harsh but easier to hear on small speakers.
+    Saw,
+}
+
+/// Represents the options for [`render_wav`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewOptions {
+    /// Sample rate of the output, in Hz.
+    pub sample_rate: u32,
+    /// Waveform of the oscillator.
+    pub waveform: Waveform,
+    /// Amplitude of a note, from 0.0 to 1.0. Overlapping notes are summed and clipped.
+    pub volume: f64,
+    /// Whether to follow the pitch curve of the track, if any.
+    pub use_pitch: bool,
+}
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        Self {
+            sample_rate: 44100,
+            waveform: Waveform::Sine,
+            volume: 0.5,
+            use_pitch: true,
+        }
+    }
+}
+
+/// Renders the notes of the track to mono samples, using the tempos of the project.
+///
+/// Inside a note, ticks are mapped to samples linearly, so tempo changes in the middle of a
+/// note only affect where the note starts and ends.
+pub fn render_samples(project: &UfProject, track: &Track, options: &PreviewOptions) -> Vec<f32> {
+    let sample_rate = f64::from(options.sample_rate.max(1));
+    let tempo_map = TempoMap::new(&project.tempos);
+    let to_sample =
+        |tick: Tick| (tempo_map.tick_to_seconds(tick.get()) * sample_rate).round() as usize;
+    let pitch = track.pitch.as_ref().filter(|_| options.use_pitch);
+
+    let length = track
+        .notes
+        .iter()
+        .map(|note| to_sample(note.tick_off))
+        .max()
+        .unwrap_or(0);
+    let mut samples = vec![0.0; length];
+    for note in &track.notes {
+        let (start, end) = (to_sample(note.tick_on), to_sample(note.tick_off));
+        if end <= start {
+            continue;
+        }
+        let ticks_per_sample = (note.tick_off - note.tick_on) as f64 / (end - start) as f64;
+        let fade = (FADE_SECONDS * sample_rate)
+            .min((end - start) as f64 / 2.0)
+            .max(1.0);
+        let mut phase = 0.0;
+        for (i, sample) in samples[start..end].iter_mut().enumerate() {
+            let tick = note
+                .tick_on
+                .saturating_add((i as f64 * ticks_per_sample) as i64);
+            let key = pitch
+                .and_then(|pitch| pitch.key_at(tick, note.key))
+                .unwrap_or(f64::from(note.key.get()));
+            phase = (phase + key_to_frequency(key) / sample_rate).fract();
+            let envelope = (i as f64 / fade)
+                .min((end - start - i) as f64 / fade)
+                .min(1.0);
+            let value = match options.waveform {
+                Waveform::Sine => (phase * TAU).sin(),
+                Waveform::Saw => phase * 2.0 - 1.0,
+            };
+            *sample += (value * envelope * options.volume) as f32;
+        }
+    }
+    for sample in &mut samples {
+        *sample = sample.clamp(-1.0, 1.0);
+    }
+
+    samples
+}
+
+/// Renders the notes of the track to a 16-bit mono PCM WAV file.
+pub fn render_wav(project: &UfProject, track: &Track, options: &PreviewOptions) -> Vec<u8> {
+    let samples = render_samples(project, track, options);
+    let data_length = (samples.len() * 2) as u32;
+    let mut wav = Vec::with_capacity(44 + samples.len() * 2);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_length).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, 1 channel.
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&options.sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(options.sample_rate * 2).to_le_bytes());
+    // Block align and bits per sample.
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_length.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&((sample * f32::from(i16::MAX)) as i16).to_le_bytes());
+    }
+
+    wav
+}
+
+fn key_to_frequency(key: f64) -> f64 {
+    440.0 * 2f64.powf((key - 69.0) / 12.0)
+}
+
+impl Project {
+    /// Renders the track at the index to a WAV file. See [`render_wav`].
+    pub fn render_preview(&self, track: usize, options: &PreviewOptions) -> Result<Vec<u8>> {
+        let project = self.data.project();
+        let track = project
+            .tracks
+            .get(track)
+            .ok_or(ErrorKind::IndexOutOfRange)?;
+        Ok(render_wav(project, track, options))
+    }
+}
@@ -1,9 +1,26 @@
 use crate::{
-    error::{Error, Result},
-    model::{Format, GenerateOptions, JapaneseLyricsType, ParseOptions, UfData},
+    error::{Error, ErrorKind, Result},
+    model::{
+        add_music_xml_directions, edit_svp, has_svp_extensions, lyrics_type_candidates,
+        read_music_xml_key_signatures, read_svp_phoneme_segments, read_svp_track_colors,
+        read_svp_track_states, read_track_colors, read_track_parameters, read_track_voices,
+        read_vpr_track_states, svp_json, write_mode1_pitch, write_music_xml_key_signatures,
+        write_svp_phoneme_segments, write_svp_track_colors, write_svp_track_states,
+        write_track_colors, write_track_parameters, write_track_voices, write_vpr_track_states,
+        Format, FormatOptions, GenerateOptions, JapaneseLyricsType, Key, LyricsTypeAnalysis, Note,
+        ParseOptions, Priority, Tempo, Tick, TimeSignature, Track, TrackLyricsTypeAnalysis, UfData,
+        UfProject, UstPitchMode,
+    },
     ConvertJapaneseLyricsOptions, IllegalFile,
 };
-use std::{cell::OnceCell, str::FromStr};
+use std::{
+    borrow::Cow,
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    str::FromStr,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
 
 use anyhow::anyhow;
 use boa_engine::{
@@ -11,13 +28,28 @@ use boa_engine::{
     object::builtins::{JsArray, JsTypedArray},
     JsResult, JsString, JsValue, NativeFunction,
 };
+use bytes::Bytes;
 use educe::Educe;
-use tracing::info;
+use tracing::{error, info, warn, Level};
 use uuid::Uuid;
 
+/// Logs a message from the runner, at the level set by [`RunnerConfig::log_level`].
+macro_rules! runner_log {
+    ($level:expr, $($arg:tt)+) => {
+        match $level {
+            Level::ERROR => tracing::error!($($arg)+),
+            Level::WARN => tracing::warn!($($arg)+),
+            Level::INFO => tracing::info!($($arg)+),
+            Level::DEBUG => tracing::debug!($($arg)+),
+            _ => tracing::trace!($($arg)+),
+        }
+    };
+}
+
 pub(crate) struct Message<T> {
     pub(crate) message: T,
     pub(crate) nonce: Uuid,
+    pub(crate) priority: Priority,
 }
 
 impl<T> Message<T> {
@@ -25,6 +57,18 @@ impl<T> Message<T> {
         Self {
             message,
             nonce: Uuid::new_v4(),
+            priority: Priority::default(),
+        }
+    }
+}
+
+impl Message<RequestMessageData> {
+    /// Creates a request message, with the priority taken from its options.
+    pub(crate) fn request(message: RequestMessageData) -> Self {
+        let priority = message.priority();
+        Self {
+            priority,
+            ..Self::new(message)
         }
     }
 }
@@ -34,13 +78,13 @@ impl<T> Message<T> {
 pub(crate) enum RequestMessageData {
     ParseSingle {
         #[educe(Debug(ignore))]
-        data: Vec<u8>,
+        data: Bytes,
         options: ParseOptions,
         format: Format,
     },
     ParseMultiple {
         #[educe(Debug(ignore))]
-        data: Vec<Vec<u8>>,
+        data: Vec<Bytes>,
         options: ParseOptions,
         format: Format,
     },
@@ -56,6 +100,12 @@ pub(crate) enum RequestMessageData {
         options: GenerateOptions,
         format: Format,
     },
+    GenerateMultipleStream {
+        #[educe(Debug(ignore))]
+        data: UfData,
+        options: GenerateOptions,
+        format: Format,
+    },
     AnalyzeJapaneseLyricsType {
         #[educe(Debug(ignore))]
         data: UfData,
@@ -67,23 +117,392 @@ pub(crate) enum RequestMessageData {
         target_type: JapaneseLyricsType,
         options: ConvertJapaneseLyricsOptions,
     },
+    /// Runs a tiny conversion, and then waits for the other runners at the gate so that every
+    /// runner takes one of these requests.
+    WarmUp { gate: Arc<WarmUpGate> },
+}
+
+/// Holds the runners which warmed up until every runner did, used by
+/// [`RequestMessageData::WarmUp`].
+///
+/// Runners stop waiting after [`WarmUpGate::TIMEOUT`], e.g. when another runner is busy with a
+/// long request, so that they can take other requests. A runner may warm up twice then.
+#[derive(Debug)]
+pub(crate) struct WarmUpGate {
+    remaining: Mutex<usize>,
+    arrived: std::sync::Condvar,
+}
+
+impl WarmUpGate {
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    pub(crate) fn new(runners: usize) -> Self {
+        Self {
+            remaining: Mutex::new(runners),
+            arrived: std::sync::Condvar::new(),
+        }
+    }
+
+    fn wait(&self) {
+        let mut remaining = self
+            .remaining
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *remaining = remaining.saturating_sub(1);
+        if *remaining == 0 {
+            self.arrived.notify_all();
+            return;
+        }
+        let (_remaining, result) = self
+            .arrived
+            .wait_timeout_while(remaining, Self::TIMEOUT, |remaining| *remaining > 0)
+            .unwrap_or_else(PoisonError::into_inner);
+        if result.timed_out() {
+            warn!("Stopped waiting for the other runners to warm up");
+        }
+    }
+}
+
+impl RequestMessageData {
+    fn priority(&self) -> Priority {
+        match self {
+            Self::ParseSingle { options, .. } | Self::ParseMultiple { options, .. } => {
+                options.priority
+            }
+            Self::GenerateSingle { options, .. }
+            | Self::GenerateMultiple { options, .. }
+            | Self::GenerateMultipleStream { options, .. } => options.priority,
+            Self::AnalyzeJapaneseLyricsType { .. } => Priority::default(),
+            Self::ConvertJapaneseLyrics { options, .. } => options.priority,
+            Self::WarmUp { .. } => Priority::default(),
+        }
+    }
+
+    /// Returns the name of the request in logs and crash reports.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Self::ParseSingle { .. } => "parse",
+            Self::ParseMultiple { .. } => "parse_multiple",
+            Self::GenerateSingle { .. } => "generate",
+            Self::GenerateMultiple { .. } => "generate_multiple",
+            Self::GenerateMultipleStream { .. } => "generate_multiple_stream",
+            Self::AnalyzeJapaneseLyricsType { .. } => "analyze_japanese_lyrics_type",
+            Self::ConvertJapaneseLyrics { .. } => "convert_japanese_lyrics",
+            Self::WarmUp { .. } => "warm_up",
+        }
+    }
+
+    /// Returns the format which is parsed or generated, if any.
+    pub(crate) fn format(&self) -> Option<Format> {
+        match self {
+            Self::ParseSingle { format, .. }
+            | Self::ParseMultiple { format, .. }
+            | Self::GenerateSingle { format, .. }
+            | Self::GenerateMultiple { format, .. }
+            | Self::GenerateMultipleStream { format, .. } => Some(*format),
+            Self::AnalyzeJapaneseLyricsType { .. }
+            | Self::ConvertJapaneseLyrics { .. }
+            | Self::WarmUp { .. } => None,
+        }
+    }
+}
+
+/// Describes a request for [`RunnerConfig::slow_call_threshold`], taken before it is processed.
+struct CallSummary {
+    kind: &'static str,
+    format: Option<Format>,
+    /// Total size of the files to parse.
+    input_bytes: Option<usize>,
+    /// Number of notes of the data to convert.
+    input_notes: Option<usize>,
+}
+
+impl CallSummary {
+    fn new(message: &RequestMessageData) -> Self {
+        let (input_bytes, data) = match message {
+            RequestMessageData::ParseSingle { data, .. } => (Some(data.len()), None),
+            RequestMessageData::ParseMultiple { data, .. } => {
+                (Some(data.iter().map(Bytes::len).sum()), None)
+            }
+            RequestMessageData::GenerateSingle { data, .. }
+            | RequestMessageData::GenerateMultiple { data, .. }
+            | RequestMessageData::GenerateMultipleStream { data, .. }
+            | RequestMessageData::AnalyzeJapaneseLyricsType { data }
+            | RequestMessageData::ConvertJapaneseLyrics { data, .. } => (None, Some(data)),
+            RequestMessageData::WarmUp { .. } => (None, None),
+        };
+        Self {
+            kind: message.kind(),
+            format: message.format(),
+            input_bytes,
+            input_notes: data.map(|data| {
+                data.project()
+                    .tracks
+                    .iter()
+                    .map(|track| track.notes.len())
+                    .sum()
+            }),
+        }
+    }
+}
+
+/// Requests which are received but not processed yet, ordered by priority and then by arrival.
+struct RequestQueue {
+    pending: BinaryHeap<QueuedRequest>,
+    sequence: u64,
+    /// Number of requests which are taken from the channel to be ordered, so that they count
+    /// towards [`RunnerConfig::queue_bound`].
+    limit: usize,
+}
+
+struct QueuedRequest {
+    request: Request,
+    sequence: u64,
+}
+
+impl QueuedRequest {
+    fn key(&self) -> (Priority, Reverse<u64>) {
+        (self.request.message.priority, Reverse(self.sequence))
+    }
+}
+
+impl PartialEq for QueuedRequest {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+impl Eq for QueuedRequest {}
+impl PartialOrd for QueuedRequest {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for QueuedRequest {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl RequestQueue {
+    /// Creates a queue for the channel created by [`SyncThread::new`], which holds one request
+    /// if the queue is bounded, so that the rest of the bound is used for ordering.
+    fn new(bound: Option<usize>) -> Self {
+        Self {
+            pending: BinaryHeap::new(),
+            sequence: 0,
+            limit: bound.map_or(usize::MAX, |bound| bound.max(1)),
+        }
+    }
+
+    fn push(&mut self, request: Request) {
+        self.pending.push(QueuedRequest {
+            request,
+            sequence: self.sequence,
+        });
+        self.sequence += 1;
+    }
+
+    /// Takes the requests sent so far from the channel, up to the bound of the queue, and returns
+    /// the request to process next.
+    /// Blocks if there is nothing to process, and returns `None` once the channel is closed.
+    ///
+    /// The queue is shared by all workers, so the lock is not held while blocking.
+    #[cfg(feature = "multi-thread")]
+    fn next(queue: &Mutex<Self>, receiver: &async_channel::Receiver<Request>) -> Option<Request> {
+        loop {
+            if let Some(request) = Self::try_next(queue, receiver) {
+                return Some(request);
+            }
+            let request = receiver.recv_blocking().ok()?;
+            queue
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(request);
+        }
+    }
+
+    /// Same as [`RequestQueue::next`], but returns `None` instead of blocking if there is nothing
+    /// to process.
+    fn try_next(
+        queue: &Mutex<Self>,
+        receiver: &async_channel::Receiver<Request>,
+    ) -> Option<Request> {
+        let mut queue = queue.lock().unwrap_or_else(PoisonError::into_inner);
+        while queue.pending.len() < queue.limit {
+            let Ok(request) = receiver.try_recv() else {
+                break;
+            };
+            queue.push(request);
+        }
+        queue.pending.pop().map(|queued| queued.request)
+    }
 }
 
 #[derive(Educe, Clone)]
 #[educe(Debug)]
 pub(crate) enum ResponseMessageData {
-    Panic,
     Parse(Result<UfData>),
     GenerateSingle(Result<Vec<u8>>),
     GenerateMultiple(Result<Vec<Vec<u8>>>),
-    AnalyzeJapaneseLyricsType(Result<Option<JapaneseLyricsType>>),
+    /// A file of [`RequestMessageData::GenerateMultipleStream`], sent before the response.
+    GenerateFile(#[educe(Debug(ignore))] Vec<u8>),
+    GenerateMultipleStream(Result<()>),
+    AnalyzeJapaneseLyricsType(Result<LyricsTypeAnalysis>),
     ConvertJapaneseLyrics(Result<UfData>),
+    WarmUp(Result<()>),
+}
+
+/// A request to the JS thread, with the channel to send its response to.
+pub(crate) struct Request {
+    pub(crate) message: Message<RequestMessageData>,
+    pub(crate) response_sender: async_channel::Sender<Message<ResponseMessageData>>,
+    /// When the request was created, to measure the time spent in the queue.
+    pub(crate) sent_at: Instant,
+}
+
+impl Request {
+    /// Responds with `error` instead of processing the request.
+    fn fail(self, error: Error) {
+        self.responder().fail(error);
+    }
+
+    /// Returns what can respond to the request with an error, even after the request is taken
+    /// by the runner.
+    fn responder(&self) -> Responder {
+        let kind = match &self.message.message {
+            RequestMessageData::ParseSingle { .. } | RequestMessageData::ParseMultiple { .. } => {
+                ResponseKind::Parse
+            }
+            RequestMessageData::GenerateSingle { .. } => ResponseKind::GenerateSingle,
+            RequestMessageData::GenerateMultiple { .. } => ResponseKind::GenerateMultiple,
+            RequestMessageData::GenerateMultipleStream { .. } => {
+                ResponseKind::GenerateMultipleStream
+            }
+            RequestMessageData::AnalyzeJapaneseLyricsType { .. } => {
+                ResponseKind::AnalyzeJapaneseLyricsType
+            }
+            RequestMessageData::ConvertJapaneseLyrics { .. } => ResponseKind::ConvertJapaneseLyrics,
+            RequestMessageData::WarmUp { gate } => ResponseKind::WarmUp(gate.clone()),
+        };
+        Responder {
+            kind,
+            nonce: self.message.nonce,
+            priority: self.message.priority,
+            response_sender: self.response_sender.clone(),
+        }
+    }
+
+    pub(crate) fn new(
+        message: Message<RequestMessageData>,
+        response_sender: async_channel::Sender<Message<ResponseMessageData>>,
+    ) -> Self {
+        Self {
+            message,
+            response_sender,
+            sent_at: Instant::now(),
+        }
+    }
+}
+
+/// Responds to a request with an error, e.g. when the runner panicked while processing it.
+struct Responder {
+    kind: ResponseKind,
+    nonce: Uuid,
+    priority: Priority,
+    response_sender: async_channel::Sender<Message<ResponseMessageData>>,
+}
+
+/// The kind of [`ResponseMessageData`] which answers a request.
+enum ResponseKind {
+    Parse,
+    GenerateSingle,
+    GenerateMultiple,
+    GenerateMultipleStream,
+    AnalyzeJapaneseLyricsType,
+    ConvertJapaneseLyrics,
+    WarmUp(Arc<WarmUpGate>),
+}
+
+impl Responder {
+    fn fail(self, error: Error) {
+        let response = match self.kind {
+            ResponseKind::Parse => ResponseMessageData::Parse(Err(error)),
+            ResponseKind::GenerateSingle => ResponseMessageData::GenerateSingle(Err(error)),
+            ResponseKind::GenerateMultiple => ResponseMessageData::GenerateMultiple(Err(error)),
+            ResponseKind::GenerateMultipleStream => {
+                ResponseMessageData::GenerateMultipleStream(Err(error))
+            }
+            ResponseKind::AnalyzeJapaneseLyricsType => {
+                ResponseMessageData::AnalyzeJapaneseLyricsType(Err(error))
+            }
+            ResponseKind::ConvertJapaneseLyrics => {
+                ResponseMessageData::ConvertJapaneseLyrics(Err(error))
+            }
+            ResponseKind::WarmUp(gate) => {
+                gate.wait();
+                ResponseMessageData::WarmUp(Err(error))
+            }
+        };
+        // The caller may have been cancelled, in which case no one waits for the response.
+        let _ = self.response_sender.send_blocking(Message {
+            nonce: self.nonce,
+            priority: self.priority,
+            message: response,
+        });
+    }
+}
+
+/// Configuration of the JS threads, set by [`crate::base::UtaFormatixBuilder`].
+#[derive(Debug, Clone)]
+pub(crate) struct RunnerConfig {
+    pub(crate) workers: usize,
+    pub(crate) queue_bound: Option<usize>,
+    pub(crate) bundle: Option<Arc<str>>,
+    pub(crate) loop_iteration_limit: Option<u64>,
+    pub(crate) recursion_limit: Option<usize>,
+    pub(crate) stack_size_limit: Option<usize>,
+    pub(crate) log_level: Level,
+    /// Number of requests after which a runner is recreated.
+    pub(crate) recycle_after: Option<u64>,
+    /// Growth of the resident memory of the process, in bytes, after which a runner is
+    /// recreated.
+    pub(crate) recycle_memory_growth: Option<u64>,
+    /// Processing time after which a request is logged as a warning.
+    pub(crate) slow_call_threshold: Option<Duration>,
+    /// Canonical names of the encodings which the bundle may use, or `None` for any encoding.
+    pub(crate) encodings: Option<Vec<String>>,
+    #[cfg(feature = "multi-thread")]
+    pub(crate) crash: crate::crash::CrashConfig,
+}
+
+impl Default for RunnerConfig {
+    fn default() -> Self {
+        Self {
+            workers: 1,
+            queue_bound: None,
+            bundle: None,
+            loop_iteration_limit: None,
+            recursion_limit: None,
+            stack_size_limit: None,
+            log_level: Level::INFO,
+            recycle_after: None,
+            recycle_memory_growth: None,
+            slow_call_threshold: None,
+            encodings: None,
+            #[cfg(feature = "multi-thread")]
+            crash: Default::default(),
+        }
+    }
 }
 
 pub(crate) struct SyncThread {
-    pub(crate) handle: OnceCell<std::thread::JoinHandle<()>>,
-    pub(crate) request_sender: async_channel::Sender<Message<RequestMessageData>>,
-    pub(crate) response_receiver: async_channel::Receiver<Message<ResponseMessageData>>,
+    #[cfg(feature = "multi-thread")]
+    pub(crate) handles: Vec<std::thread::JoinHandle<()>>,
+    pub(crate) request_sender: async_channel::Sender<Request>,
+    /// Runs the requests on the thread which waits for them, without the `multi-thread`
+    /// feature.
+    #[cfg(not(feature = "multi-thread"))]
+    local: LocalRunner,
 }
 
 impl Drop for SyncThread {
@@ -91,33 +510,92 @@ impl Drop for SyncThread {
         info!("Dropping SyncThread");
         self.request_sender.close();
         info!("Closed request sender");
-        self.handle
-            .take()
-            .expect("Failed to get handle")
-            .join()
-            .expect("Failed to join thread");
+        // A panic in the runner has already been reported by closing the response channels.
+        #[cfg(feature = "multi-thread")]
+        for handle in std::mem::take(&mut self.handles) {
+            if handle.join().is_err() {
+                info!("JS runner thread panicked");
+            }
+        }
     }
 }
 
 impl SyncThread {
-    pub(crate) fn new() -> Self {
-        let (request_sender, request_receiver) = async_channel::unbounded();
-        let (response_sender, response_receiver) = async_channel::unbounded();
-        let handle = std::thread::spawn(move || {
-            runner_entry(request_receiver, response_sender);
-        });
-        let handle_cell = OnceCell::new();
-        handle_cell.set(handle).expect("Failed to set handle");
+    pub(crate) fn new(config: RunnerConfig) -> Self {
+        // The other requests of a bounded queue wait in `RequestQueue`, which takes them from the
+        // channel only while it has room.
+        let (request_sender, request_receiver) = match config.queue_bound {
+            Some(_) => async_channel::bounded(1),
+            None => async_channel::unbounded(),
+        };
+        let queue = Arc::new(Mutex::new(RequestQueue::new(config.queue_bound)));
+        #[cfg(feature = "multi-thread")]
+        let handles = (0..config.workers.max(1))
+            .map(|_| {
+                let receiver = request_receiver.clone();
+                let queue = queue.clone();
+                let config = config.clone();
+                std::thread::spawn(move || {
+                    runner_entry(receiver, queue, config);
+                })
+            })
+            .collect();
         Self {
-            handle: handle_cell,
+            #[cfg(feature = "multi-thread")]
+            handles,
             request_sender,
-            response_receiver,
+            #[cfg(not(feature = "multi-thread"))]
+            local: LocalRunner {
+                receiver: request_receiver,
+                queue,
+                config,
+                runner: std::cell::RefCell::new(None),
+            },
         }
     }
+
+    /// Processes the requests sent so far, if they are not processed by the JS threads.
+    ///
+    /// This must be awaited after sending a request and before waiting for its response.
+    #[cfg(feature = "multi-thread")]
+    pub(crate) async fn run_pending(&self) {}
+
+    /// Processes the requests sent so far on the current thread, including the ones sent while
+    /// processing. Returns immediately if another call on this thread is already processing
+    /// them, since that call processes the new requests too.
+    #[cfg(not(feature = "multi-thread"))]
+    #[allow(clippy::await_holding_refcell_ref)]
+    pub(crate) async fn run_pending(&self) {
+        let local = &self.local;
+        let Ok(mut runner) = local.runner.try_borrow_mut() else {
+            return;
+        };
+        while let Some(request) = RequestQueue::try_next(&local.queue, &local.receiver) {
+            let current = runner.get_or_insert_with(|| Runner::new(&local.config));
+            if Runner::handle_or_fail(current, request, &local.config).await {
+                runner_log!(local.config.log_level, "Recycling runner");
+                // The next request creates a new runner.
+                *runner = None;
+            }
+        }
+    }
+}
+
+/// Runner which lives on the threads of the callers, used without the `multi-thread` feature.
+#[cfg(not(feature = "multi-thread"))]
+struct LocalRunner {
+    receiver: async_channel::Receiver<Request>,
+    queue: Arc<Mutex<RequestQueue>>,
+    config: RunnerConfig,
+    /// Started by the first request.
+    runner: std::cell::RefCell<Option<Result<Runner>>>,
 }
+
+#[cfg(feature = "multi-thread")]
 fn runner_entry(
-    receiver: async_channel::Receiver<Message<RequestMessageData>>,
-    sender: async_channel::Sender<Message<ResponseMessageData>>,
+    receiver: async_channel::Receiver<Request>,
+    queue: Arc<Mutex<RequestQueue>>,
+    config: RunnerConfig,
 ) {
     info!("JS runner thread started");
     let rt = tokio::runtime::Builder::new_multi_thread()
@@ -125,148 +603,433 @@ fn runner_entry(
         .build()
         .expect("Failed to create runtime");
 
-    let main = std::panic::catch_unwind(|| {
-        let sender = sender.clone();
-        rt.block_on(runner_entry_inner(receiver, sender));
-    });
-    if main.is_err() {
-        sender
-            .send_blocking(Message {
-                nonce: Uuid::new_v4(),
-                message: ResponseMessageData::Panic,
-            })
-            .expect("Failed to send panic message");
+    // The config is only read, so it is still valid after a panic.
+    loop {
+        let runner =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| Runner::new(&config)));
+        let runner = match runner {
+            Ok(runner) => runner,
+            Err(payload) => {
+                let report = crate::crash::report(&config.crash, payload.as_ref());
+                // Creating another runner would panic in the same way, so the waiting requests
+                // fail with the report, and the requests sent later fail to be sent.
+                receiver.close();
+                let mut requests = vec![];
+                while let Ok(request) = receiver.try_recv() {
+                    requests.push(request);
+                }
+                requests.extend(
+                    queue
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .pending
+                        .drain()
+                        .map(|queued| queued.request),
+                );
+                for request in requests {
+                    request.fail(ErrorKind::RunnerPanicked(Box::new(report.clone())).into());
+                }
+                return;
+            }
+        };
+        let in_flight = std::cell::Cell::new(None);
+        let main = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            rt.block_on(runner_entry_inner(
+                runner,
+                receiver.clone(),
+                &queue,
+                &config,
+                &in_flight,
+            ));
+        }));
+        let Err(payload) = main else {
+            return;
+        };
+        // Only the request being processed has failed, so the other requests are processed by a
+        // new runner.
+        let report = crate::crash::report(&config.crash, payload.as_ref());
+        if let Some(responder) = in_flight.take() {
+            responder.fail(ErrorKind::RunnerPanicked(Box::new(report)).into());
+        }
+        runner_log!(config.log_level, "Restarting runner after a panic");
     }
 }
+/// Processes the requests until the channel is closed. `in_flight` holds the request being
+/// processed, so that it can fail if the runner panics.
+#[cfg(feature = "multi-thread")]
 async fn runner_entry_inner(
-    receiver: async_channel::Receiver<Message<RequestMessageData>>,
-    sender: async_channel::Sender<Message<ResponseMessageData>>,
+    mut runner: Result<Runner>,
+    receiver: async_channel::Receiver<Request>,
+    queue: &Mutex<RequestQueue>,
+    config: &RunnerConfig,
+    in_flight: &std::cell::Cell<Option<Responder>>,
 ) {
-    info!("Loading utaformatix");
-    let source = boa_engine::Source::from_bytes(include_str!("./utaformatix.js"));
-    let queue = std::rc::Rc::new(crate::job_queue::TokioJobQueue::default());
-    let mut context = boa_engine::Context::builder()
-        .job_queue(queue)
-        .build()
-        .unwrap();
-
-    context
-        .register_global_builtin_callable(
-            js_string!("__sleep"),
-            2,
-            NativeFunction::from_async_fn(crate::js_impls::sleep),
-        )
-        .expect("Failed to register sleep function");
-    context
-        .register_global_builtin_callable(
-            js_string!("__encode"),
-            1,
-            NativeFunction::from_fn_ptr(crate::js_impls::encode),
-        )
-        .expect("Failed to register encode function");
-    context
-        .register_global_builtin_callable(
-            js_string!("__decode"),
-            1,
-            NativeFunction::from_fn_ptr(crate::js_impls::decode),
-        )
-        .expect("Failed to register decode function");
-    context.eval(source).expect("Failed to evaluate script");
-
-    let mut utaformatix = match context
-        .global_object()
-        .get(js_string!("utaformatix"), &mut context)
-    {
-        Ok(boa_engine::JsValue::Object(val)) => val,
-        Ok(_) => panic!("Failed to initialize utaformatix: Unexpected return value"),
-        Err(error) => {
-            let value = error.to_opaque(&mut context);
-            panic!(
-                "Failed to initialize utaformatix: {:?}",
-                value.to_json(&mut context)
-            );
+    loop {
+        runner_log!(config.log_level, "Waiting for message");
+        let Some(request) = RequestQueue::next(queue, &receiver) else {
+            runner_log!(config.log_level, "Runner channel closed");
+            break;
+        };
+        in_flight.set(Some(request.responder()));
+        let recycle = Runner::handle_or_fail(&mut runner, request, config).await;
+        in_flight.set(None);
+        if recycle {
+            runner_log!(config.log_level, "Recycling runner");
+            // The old context is dropped first, so that both aren't in memory at once.
+            drop(runner);
+            runner = Runner::new(config);
         }
-    };
+    }
+}
 
-    info!("Loaded utaformatix");
+/// JS engine with utaformatix loaded, which processes requests one at a time.
+struct Runner {
+    context: boa_engine::Context,
+    utaformatix: boa_engine::JsObject,
+    log_level: Level,
+    slow_call_threshold: Option<Duration>,
+    #[cfg(feature = "multi-thread")]
+    crash: crate::crash::CrashConfig,
+    /// Number of requests processed by this runner.
+    handled: u64,
+    /// Resident memory of the process when this runner was created.
+    initial_memory: Option<u64>,
+}
 
-    loop {
-        info!("Waiting for message");
-        let Ok(Message { message, nonce }) = receiver.recv_blocking() else {
-            info!("Runner channel closed");
-            break;
+impl Runner {
+    /// Creates a runner, evaluating the bundle. Fails if the bundle throws or doesn't define
+    /// `utaformatix`.
+    fn new(config: &RunnerConfig) -> Result<Self> {
+        runner_log!(config.log_level, "Loading utaformatix");
+        let source = boa_engine::Source::from_bytes(
+            config
+                .bundle
+                .as_deref()
+                .unwrap_or(include_str!("./utaformatix.js")),
+        );
+        let job_queue = std::rc::Rc::new(crate::job_queue::TokioJobQueue::default());
+        let mut context = boa_engine::Context::builder()
+            .job_queue(job_queue)
+            .build()
+            .unwrap();
+        let limits = context.runtime_limits_mut();
+        if let Some(limit) = config.loop_iteration_limit {
+            limits.set_loop_iteration_limit(limit);
+        }
+        if let Some(limit) = config.recursion_limit {
+            limits.set_recursion_limit(limit);
+        }
+        if let Some(limit) = config.stack_size_limit {
+            limits.set_stack_size_limit(limit);
+        }
+
+        context
+            .register_global_builtin_callable(
+                js_string!("__sleep"),
+                2,
+                NativeFunction::from_async_fn(crate::js_impls::sleep),
+            )
+            .expect("Failed to register sleep function");
+        context
+            .register_global_builtin_callable(
+                js_string!("__encode"),
+                2,
+                NativeFunction::from_copy_closure_with_captures(
+                    crate::js_impls::encode,
+                    config.encodings.clone(),
+                ),
+            )
+            .expect("Failed to register encode function");
+        context
+            .register_global_builtin_callable(
+                js_string!("__decode"),
+                2,
+                NativeFunction::from_copy_closure_with_captures(
+                    crate::js_impls::decode,
+                    config.encodings.clone(),
+                ),
+            )
+            .expect("Failed to register decode function");
+        // Only debug builds can be crashed from the bundle, to test the crash handling.
+        #[cfg(debug_assertions)]
+        context
+            .register_global_builtin_callable(
+                js_string!("__panic"),
+                1,
+                NativeFunction::from_fn_ptr(crate::js_impls::panic),
+            )
+            .expect("Failed to register panic function");
+        // Messages from utaformatix-ts, e.g. warnings of the Kotlin code, are logged to
+        // `tracing` instead of being discarded.
+        let console = boa_engine::object::ObjectInitializer::new(&mut context)
+            .function(
+                NativeFunction::from_fn_ptr(crate::js_impls::console_debug),
+                js_string!("debug"),
+                0,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(crate::js_impls::console_log),
+                js_string!("log"),
+                0,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(crate::js_impls::console_log),
+                js_string!("info"),
+                0,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(crate::js_impls::console_warn),
+                js_string!("warn"),
+                0,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(crate::js_impls::console_error),
+                js_string!("error"),
+                0,
+            )
+            .build();
+        context
+            .register_global_property(
+                js_string!("console"),
+                console,
+                boa_engine::property::Attribute::all(),
+            )
+            .expect("Failed to register console");
+        let utaformatix = match context.eval(source).and_then(|_| {
+            context
+                .global_object()
+                .get(js_string!("utaformatix"), &mut context)
+        }) {
+            Ok(boa_engine::JsValue::Object(val)) => val,
+            Ok(_) => {
+                error!("Failed to initialize utaformatix: Unexpected value");
+                return Err(anyhow!("Failed to initialize utaformatix: Unexpected value").into());
+            }
+            Err(exception) => {
+                let value = exception.to_opaque(&mut context);
+                let message = format!(
+                    "Failed to evaluate script: {:?}",
+                    value.to_json(&mut context)
+                );
+                error!("{}", message);
+                return Err(anyhow!(message).into());
+            }
+        };
+
+        runner_log!(config.log_level, "Loaded utaformatix");
+
+        Ok(Self {
+            context,
+            utaformatix,
+            log_level: config.log_level,
+            slow_call_threshold: config.slow_call_threshold,
+            #[cfg(feature = "multi-thread")]
+            crash: config.crash.clone(),
+            handled: 0,
+            initial_memory: resident_memory(),
+        })
+    }
+
+    /// Processes the request, or responds with the error if the runner couldn't be created.
+    /// Returns whether the runner should be recreated afterwards.
+    async fn handle_or_fail(
+        runner: &mut Result<Self>,
+        request: Request,
+        config: &RunnerConfig,
+    ) -> bool {
+        match runner {
+            Ok(runner) => {
+                runner.handle(request).await;
+                runner.should_recycle(config)
+            }
+            Err(error) => {
+                request.fail(error.clone());
+                false
+            }
+        }
+    }
+
+    /// Returns whether the runner should be recreated to release the memory of its context,
+    /// as set by [`RunnerConfig::recycle_after`] and [`RunnerConfig::recycle_memory_growth`].
+    fn should_recycle(&self, config: &RunnerConfig) -> bool {
+        if config
+            .recycle_after
+            .is_some_and(|requests| self.handled >= requests)
+        {
+            return true;
+        }
+        let Some(growth) = config.recycle_memory_growth else {
+            return false;
         };
-        info!("Received message: {:?}", message);
-        match message {
+        match (self.initial_memory, resident_memory()) {
+            (Some(initial), Some(memory)) => memory.saturating_sub(initial) > growth,
+            _ => false,
+        }
+    }
+
+    async fn handle(&mut self, request: Request) {
+        self.handled += 1;
+        let Request {
+            message:
+                Message {
+                    message,
+                    nonce,
+                    priority,
+                },
+            response_sender,
+            sent_at,
+        } = request;
+        runner_log!(
+            self.log_level,
+            "Received message ({:?}): {:?}",
+            priority,
+            message
+        );
+        let started_at = Instant::now();
+        let summary = self.slow_call_threshold.map(|_| CallSummary::new(&message));
+        // The request is shared with the crash report instead of copied, and the handlers only
+        // borrow it.
+        let message = Arc::new(message);
+        #[cfg(feature = "multi-thread")]
+        let _in_flight = crate::crash::InFlight::new(&self.crash, &message);
+        let response = match &*message {
             RequestMessageData::ParseSingle {
                 data,
                 options,
                 format,
             } => {
-                let result =
-                    parse_single(&mut utaformatix, &mut context, format, data, options).await;
-                info!("Completed parsing");
-                sender
-                    .send_blocking(Message {
-                        nonce,
-                        message: ResponseMessageData::Parse(result),
-                    })
-                    .expect("Failed to send response");
+                let result = parse_single(
+                    &mut self.utaformatix,
+                    &mut self.context,
+                    *format,
+                    data,
+                    options,
+                )
+                .await
+                .map_err(|e| e.with_format(*format));
+                runner_log!(self.log_level, "Completed parsing");
+                ResponseMessageData::Parse(result)
             }
             RequestMessageData::ParseMultiple {
                 data,
                 options,
                 format,
             } => {
-                let result =
-                    parse_multiple(&mut utaformatix, &mut context, format, data, options).await;
-                info!("Completed parsing multiple");
-                sender
-                    .send_blocking(Message {
-                        nonce,
-                        message: ResponseMessageData::Parse(result),
-                    })
-                    .expect("Failed to send response");
+                let result = parse_multiple(
+                    &mut self.utaformatix,
+                    &mut self.context,
+                    *format,
+                    data,
+                    options,
+                )
+                .await
+                .map_err(|e| e.with_format(*format));
+                runner_log!(self.log_level, "Completed parsing multiple");
+                ResponseMessageData::Parse(result)
             }
             RequestMessageData::GenerateSingle {
                 data,
                 options,
                 format,
             } => {
-                let result =
-                    generate_single(&mut utaformatix, &mut context, format, data, options).await;
-                info!("Completed generating");
-                sender
-                    .send_blocking(Message {
-                        nonce,
-                        message: ResponseMessageData::GenerateSingle(result),
-                    })
-                    .expect("Failed to send response");
+                let result = generate_single(
+                    &mut self.utaformatix,
+                    &mut self.context,
+                    *format,
+                    data,
+                    options,
+                )
+                .await
+                .map_err(|e| e.with_format(*format));
+                runner_log!(self.log_level, "Completed generating");
+                ResponseMessageData::GenerateSingle(result)
             }
             RequestMessageData::GenerateMultiple {
                 data,
                 options,
                 format,
             } => {
-                let result =
-                    generate_multiple(&mut utaformatix, &mut context, format, data, options).await;
-                info!("Completed generating multiple");
-                sender
-                    .send_blocking(Message {
-                        nonce,
-                        message: ResponseMessageData::GenerateMultiple(result),
-                    })
-                    .expect("Failed to send response");
+                let mut files = vec![];
+                let result = generate_each(
+                    &mut self.utaformatix,
+                    &mut self.context,
+                    *format,
+                    data,
+                    options,
+                    |file| {
+                        files.push(file);
+                        Ok(())
+                    },
+                )
+                .await
+                .map(|()| files)
+                .map_err(|e| e.with_format(*format));
+                runner_log!(self.log_level, "Completed generating multiple");
+                ResponseMessageData::GenerateMultiple(result)
+            }
+            RequestMessageData::GenerateMultipleStream {
+                data,
+                options,
+                format,
+            } => {
+                // The channel holds a single message with the JS threads, so this waits until
+                // the caller receives the previous file.
+                let mut send = |file| {
+                    response_sender
+                        .send_blocking(Message {
+                            nonce,
+                            priority,
+                            message: ResponseMessageData::GenerateFile(file),
+                        })
+                        .map_err(|_| anyhow!("Response channel closed").into())
+                };
+                // Each track is generated by itself, so that the JS engine only holds one of
+                // them at a time, unless the format options merge the files.
+                let format = *format;
+                let merges = options
+                    .format_options
+                    .as_ref()
+                    .is_some_and(|format_options| format_options.merges(format));
+                let result = async {
+                    if merges {
+                        return generate_each(
+                            &mut self.utaformatix,
+                            &mut self.context,
+                            format,
+                            data,
+                            options,
+                            &mut send,
+                        )
+                        .await;
+                    }
+                    options.validate(format, data)?;
+                    for track in data.track_projects() {
+                        generate_each(
+                            &mut self.utaformatix,
+                            &mut self.context,
+                            format,
+                            &track,
+                            options,
+                            &mut send,
+                        )
+                        .await?;
+                    }
+                    Ok(())
+                }
+                .await
+                .map_err(|e| e.with_format(format));
+                runner_log!(self.log_level, "Completed streaming generation");
+                ResponseMessageData::GenerateMultipleStream(result)
             }
             RequestMessageData::AnalyzeJapaneseLyricsType { data } => {
-                let result = analyze_japanese_lyrics_type(&mut utaformatix, &mut context, data);
-                info!("Completed analyzing Japanese lyrics type: {:?}", result);
-                sender
-                    .send_blocking(Message {
-                        nonce,
-                        message: ResponseMessageData::AnalyzeJapaneseLyricsType(result),
-                    })
-                    .expect("Failed to send response");
+                let result =
+                    analyze_japanese_lyrics_type(&mut self.utaformatix, &mut self.context, data);
+                runner_log!(
+                    self.log_level,
+                    "Completed analyzing Japanese lyrics type: {:?}",
+                    result
+                );
+                ResponseMessageData::AnalyzeJapaneseLyricsType(result)
             }
             RequestMessageData::ConvertJapaneseLyrics {
                 data,
@@ -275,23 +1038,50 @@ async fn runner_entry_inner(
                 options,
             } => {
                 let result = convert_japanese_lyrics(
-                    &mut utaformatix,
-                    &mut context,
+                    &mut self.utaformatix,
+                    &mut self.context,
                     data,
-                    source_type,
-                    target_type,
+                    *source_type,
+                    *target_type,
                     options,
                 );
-                info!("Completed converting Japanese lyrics");
-                sender
-                    .send_blocking(Message {
-                        nonce,
-                        message: ResponseMessageData::ConvertJapaneseLyrics(result),
-                    })
-                    .expect("Failed to send response");
+                runner_log!(self.log_level, "Completed converting Japanese lyrics");
+                ResponseMessageData::ConvertJapaneseLyrics(result)
+            }
+            RequestMessageData::WarmUp { gate } => {
+                let result = warm_up(&mut self.utaformatix, &mut self.context).await;
+                runner_log!(self.log_level, "Completed warming up");
+                gate.wait();
+                ResponseMessageData::WarmUp(result)
+            }
+        };
+        if let (Some(threshold), Some(summary)) = (self.slow_call_threshold, summary) {
+            let run = started_at.elapsed();
+            if run >= threshold {
+                warn!(
+                    kind = summary.kind,
+                    format = ?summary.format,
+                    input_bytes = ?summary.input_bytes,
+                    input_notes = ?summary.input_notes,
+                    queued = ?started_at.duration_since(sent_at),
+                    run = ?run,
+                    "Slow call"
+                );
             }
         }
-        info!("Sent response");
+        // The caller may have been cancelled, in which case no one waits for the response.
+        if response_sender
+            .send_blocking(Message {
+                nonce,
+                priority,
+                message: response,
+            })
+            .is_err()
+        {
+            runner_log!(self.log_level, "Response channel closed");
+            return;
+        }
+        runner_log!(self.log_level, "Sent response");
     }
 }
 
@@ -302,350 +1092,616 @@ fn wrap_error(
 ) -> Result<boa_engine::JsValue> {
     let result = result.map_err(|e| {
         let value = e.to_opaque(context);
-        for (error, name) in [
-            (Error::EmptyProject, js_string!("EmptyProjectException")),
-            (
-                Error::IllegalNotePosition,
-                js_string!("IllegalNotePositionException"),
-            ),
-            (
-                Error::NotesOverlapping,
-                js_string!("NotesOverlappingException"),
-            ),
-            (
-                Error::UnsupportedFileFormat,
-                js_string!("UnsupportedFileFormatError"),
-            ),
-            (
-                Error::UnsupportedLegacyPpsf,
-                js_string!("UnsupportedLegacyPpsfError"),
-            ),
-        ] {
-            let exception = utaformatix
-                .get(name.to_owned(), context)
-                .expect("Failed to get exception");
-            if value
-                .instance_of(&exception, context)
-                .expect("Failed to check instance")
-            {
-                return error;
-            }
-        }
-        let illegal_file_exception = utaformatix
-            .get(js_string!("IllegalFileException"), context)
-            .expect("Failed to get exception");
-        if value
-            .instance_of(&illegal_file_exception, context)
-            .expect("Failed to check instance")
-        {
-            let value = value.as_object().expect("Failed to convert to object");
-            let name = value
-                .get(js_string!("constructor"), context)
-                .expect("Failed to get constructor")
-                .as_object()
-                .expect("Failed to convert to object")
-                .get(js_string!("name"), context)
-                .expect("Failed to get name")
-                .as_string()
-                .expect("Failed to convert to string")
-                .to_std_string()
-                .expect("Failed to convert to string");
-            let kind = IllegalFile::from_str(&name).expect("Failed to convert to IllegalFile");
-            return Error::IllegalFile(kind);
-        }
-
-        let value = value.to_string(context).map_or_else(
-            |_| "Unknown error".to_owned(),
-            |v| v.to_std_string_escaped(),
-        );
-        Error::Unexpected(value)
+        let kind = error_kind(&value, utaformatix, context);
+        let message = string_property(&value, js_string!("message"), context);
+        let stack = string_property(&value, js_string!("stack"), context);
+        Error::new(kind).with_js_exception(message, stack)
     })?;
 
     Ok(result)
 }
 
-async fn parse_single(
-    utaformatix: &mut boa_engine::JsObject,
+fn error_kind(
+    value: &JsValue,
+    utaformatix: &boa_engine::JsObject,
     context: &mut boa_engine::Context,
-    format: Format,
-    data: Vec<u8>,
-    options: ParseOptions,
-) -> Result<UfData> {
-    let data = boa_engine::object::builtins::JsUint8Array::from_iter(data, context)
-        .map_err(|e| anyhow!("Failed to create Uint8Array: {:?}", e))?;
-    let function_name = format!("parse{}", format.suffix());
-    let boa_engine::JsValue::Object(parser) = utaformatix
-        .get(JsString::from(function_name), context)
-        .expect("Failed to get parse function")
+) -> ErrorKind {
+    for (kind, name) in [
+        (ErrorKind::EmptyProject, js_string!("EmptyProjectException")),
+        (
+            ErrorKind::IllegalNotePosition,
+            js_string!("IllegalNotePositionException"),
+        ),
+        (
+            ErrorKind::NotesOverlapping,
+            js_string!("NotesOverlappingException"),
+        ),
+        (
+            ErrorKind::UnsupportedFileFormat,
+            js_string!("UnsupportedFileFormatError"),
+        ),
+        (
+            ErrorKind::UnsupportedLegacyPpsf,
+            js_string!("UnsupportedLegacyPpsfError"),
+        ),
+    ] {
+        if is_instance_of(value, utaformatix, name, context) {
+            return kind;
+        }
+    }
+    if is_instance_of(
+        value,
+        utaformatix,
+        js_string!("IllegalFileException"),
+        context,
+    ) {
+        if let Some(mut kind) =
+            constructor_name(value, context).and_then(|name| IllegalFile::from_str(&name).ok())
+        {
+            match &mut kind {
+                IllegalFile::XmlElementNotFound { name }
+                | IllegalFile::IllegalXmlValue { name } => {
+                    *name =
+                        own_string_property(value, js_string!("name"), context).unwrap_or_default();
+                }
+                IllegalFile::IllegalXmlAttribute { name, attribute } => {
+                    *name =
+                        own_string_property(value, js_string!("name"), context).unwrap_or_default();
+                    *attribute = own_string_property(value, js_string!("attribute"), context)
+                        .unwrap_or_default();
+                }
+                _ => {}
+            }
+            return ErrorKind::IllegalFile(kind);
+        }
+    }
+
+    let value = value.to_string(context).map_or_else(
+        |_| "Unknown error".to_owned(),
+        |v| v.to_std_string_escaped(),
+    );
+    ErrorKind::Unexpected(value)
+}
+
+fn string_property(
+    value: &JsValue,
+    name: JsString,
+    context: &mut boa_engine::Context,
+) -> Option<String> {
+    value
+        .as_object()?
+        .get(name, context)
+        .ok()?
+        .as_string()?
+        .to_std_string()
+        .ok()
+}
+
+fn is_instance_of(
+    value: &JsValue,
+    utaformatix: &boa_engine::JsObject,
+    name: JsString,
+    context: &mut boa_engine::Context,
+) -> bool {
+    let Ok(exception) = utaformatix.get(name, context) else {
+        return false;
+    };
+    value.instance_of(&exception, context).unwrap_or(false)
+}
+
+/// Same as [`string_property`], but ignores inherited properties like `Error.prototype.name`.
+fn own_string_property(
+    value: &JsValue,
+    name: JsString,
+    context: &mut boa_engine::Context,
+) -> Option<String> {
+    let object = value.as_object()?;
+    if !object.has_own_property(name.clone(), context).ok()? {
+        return None;
+    }
+    string_property(value, name, context)
+}
+
+fn constructor_name(value: &JsValue, context: &mut boa_engine::Context) -> Option<String> {
+    let constructor = value
+        .as_object()?
+        .get(js_string!("constructor"), context)
+        .ok()?;
+    string_property(&constructor, js_string!("name"), context)
+}
+
+fn get_function(
+    utaformatix: &boa_engine::JsObject,
+    name: JsString,
+    context: &mut boa_engine::Context,
+) -> Result<boa_engine::JsObject> {
+    match utaformatix.get(name.clone(), context) {
+        Ok(boa_engine::JsValue::Object(function)) if function.is_callable() => Ok(function),
+        _ => Err(anyhow!("Failed to get function: {}", name.to_std_string_escaped()).into()),
+    }
+}
+
+/// [`ParseOptions`] for utaformatix-ts, which only knows a lyric to fill empty lyrics with.
+///
+/// Empty lyrics are kept, and lyrics are cleaned up after parsing.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsParseOptions {
+    pitch: bool,
+    default_lyric: String,
+}
+impl From<&ParseOptions> for JsParseOptions {
+    fn from(options: &ParseOptions) -> Self {
+        Self {
+            pitch: options.pitch,
+            default_lyric: String::new(),
+        }
+    }
+}
+
+/// [`ConvertJapaneseLyricsOptions`] for utaformatix-ts. The other options are applied after
+/// converting.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsConvertJapaneseLyricsOptions {
+    convert_vowel_connections: bool,
+}
+impl From<&ConvertJapaneseLyricsOptions> for JsConvertJapaneseLyricsOptions {
+    fn from(options: &ConvertJapaneseLyricsOptions) -> Self {
+        Self {
+            convert_vowel_connections: options.convert_vowel_connections,
+        }
+    }
+}
+
+/// Generate options which utaformatix-ts knows.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsGenerateOptions {
+    pitch: bool,
+}
+impl From<&GenerateOptions> for JsGenerateOptions {
+    fn from(options: &GenerateOptions) -> Self {
+        Self {
+            pitch: options.pitch,
+        }
+    }
+}
+
+fn clean_lyrics(mut data: UfData, options: &ParseOptions) -> UfData {
+    for track in &mut data.project_mut().tracks {
+        options.clean_lyrics(&mut track.notes);
+    }
+    data
+}
+
+fn to_js_value<T: serde::Serialize>(
+    value: &T,
+    context: &mut boa_engine::Context,
+) -> Result<boa_engine::JsValue> {
+    let json =
+        serde_json::to_value(value).map_err(|e| anyhow!("Failed to convert to JSON: {:?}", e))?;
+    Ok(boa_engine::JsValue::from_json(&json, context)
+        .map_err(|e| anyhow!("Failed to convert to JsValue: {:?}", e))?)
+}
+
+/// Converts data returned by utaformatix-ts. Notes whose key is out of the range of MIDI fail
+/// with [`ErrorKind::KeyOutOfRange`], and notes before the start, e.g. in the measure prefix, are
+/// clamped to it with a warning, instead of failing to deserialize.
+fn from_js_value(value: &boa_engine::JsValue, context: &mut boa_engine::Context) -> Result<UfData> {
+    let mut json = value
+        .to_json(context)
+        .map_err(|e| anyhow!("Failed to convert to JSON: {:?}", e))?;
+    check_js_notes(&mut json)?;
+    Ok(serde_json::from_value(json).map_err(|e| anyhow!("Failed to parse JSON: {:?}", e))?)
+}
+
+/// Checks the keys of the notes of data returned by utaformatix-ts, and clamps their positions.
+fn check_js_notes(json: &mut serde_json::Value) -> Result<()> {
+    let Some(tracks) = json
+        .pointer_mut("/project/tracks")
+        .and_then(serde_json::Value::as_array_mut)
     else {
-        panic!("Failed to get parse function: Unexpected return value");
+        return Ok(());
     };
-    if !parser.is_callable() {
-        panic!("Failed to get parse function: Unexpected return value");
-    }
-    let result_promise = parser
-        .call(
-            &boa_engine::JsValue::undefined(),
-            &[
-                data.into(),
-                boa_engine::JsValue::from_json(
-                    &serde_json::to_value(options).expect("Failed to convert to JSON"),
-                    context,
-                )
-                .expect("Failed to convert to JsValue"),
-            ],
-            context,
-        )
-        .map_err(|e| anyhow!("Failed to call parse function: {:?}", e))?;
+    let number =
+        |note: &serde_json::Value, name: &str| note.get(name).and_then(serde_json::Value::as_f64);
+    for (track, value) in tracks.iter_mut().enumerate() {
+        let Some(notes) = value
+            .get_mut("notes")
+            .and_then(serde_json::Value::as_array_mut)
+        else {
+            continue;
+        };
+        for (note, value) in notes.iter().enumerate() {
+            if let Some(key) = number(value, "key").filter(|&key| Key::new(key as i32).is_none()) {
+                let key = key as i32;
+                return Err(ErrorKind::KeyOutOfRange { track, note, key }.into());
+            }
+        }
+        let length = notes.len();
+        notes.retain(|note| number(note, "tickOff").is_none_or(|tick| tick > 0.0));
+        let mut clamped = length - notes.len();
+        for note in notes.iter_mut() {
+            if number(note, "tickOn").is_some_and(|tick| tick < 0.0) {
+                note["tickOn"] = 0.into();
+                clamped += 1;
+            }
+        }
+        if clamped > 0 {
+            warn!(
+                "Clamped {} notes of track {} before the start of the project",
+                clamped, track
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Calls the function, and waits for the returned promise while running the job queue.
+async fn call_async(
+    function: &boa_engine::JsObject,
+    args: &[boa_engine::JsValue],
+    context: &mut boa_engine::Context,
+) -> Result<JsResult<boa_engine::JsValue>> {
+    let result_promise = function
+        .call(&boa_engine::JsValue::undefined(), args, context)
+        .map_err(|e| anyhow!("Failed to call function: {:?}", e))?;
     let boa_engine::JsValue::Object(result_promise) = result_promise else {
-        panic!("Failed to call parse function: Unexpected return value");
+        return Err(anyhow!("Failed to call function: Unexpected return value").into());
     };
     let result_promise = boa_engine::object::builtins::JsPromise::from_object(result_promise)
-        .expect("Failed to convert to JsPromise");
+        .map_err(|e| anyhow!("Failed to convert to JsPromise: {:?}", e))?;
     let future = result_promise.into_js_future(context);
 
     let runner = async { context.run_jobs_async().await };
 
     let (_, result) = tokio::join!(runner, future);
 
+    Ok(result)
+}
+
+fn typed_array_to_bytes(
+    value: &boa_engine::JsValue,
+    context: &mut boa_engine::Context,
+) -> Result<Vec<u8>> {
+    let object = value
+        .as_object()
+        .ok_or_else(|| anyhow!("Failed to convert to object: {:?}", value))?
+        .to_owned();
+    let array = JsTypedArray::from_object(object)
+        .map_err(|e| anyhow!("Failed to convert to JsTypedArray: {:?}", e))?;
+    let length = array
+        .length(context)
+        .map_err(|e| anyhow!("Failed to get length: {:?}", e))?;
+    let mut data = Vec::with_capacity(length);
+    for i in 0..length {
+        let value = array
+            .get(i, context)
+            .map_err(|e| anyhow!("Failed to get value: {:?}", e))?;
+        let byte = value
+            .as_number()
+            .ok_or_else(|| anyhow!("Unexpected value in typed array: {:?}", value))?;
+        data.push(byte as u8);
+    }
+
+    Ok(data)
+}
+
+async fn parse_single(
+    utaformatix: &mut boa_engine::JsObject,
+    context: &mut boa_engine::Context,
+    format: Format,
+    data: &Bytes,
+    options: &ParseOptions,
+) -> Result<UfData> {
+    options.validate_fallback_timing()?;
+    let source: &[u8] = data;
+    let data = boa_engine::object::builtins::JsUint8Array::from_iter(data.iter().copied(), context)
+        .map_err(|e| anyhow!("Failed to create Uint8Array: {:?}", e))?;
+    let function_name = format!("parse{}", format.suffix());
+    let parser = get_function(utaformatix, JsString::from(function_name), context)?;
+    let js_options = to_js_value(&JsParseOptions::from(options), context)?;
+    let result = call_async(&parser, &[data.into(), js_options], context).await?;
+
     let result = wrap_error(result, utaformatix, context)?;
     if !result.is_object() {
         return Err(anyhow!("Failed to parse: Unexpected return value: {:?}", result).into());
     }
-    Ok(serde_json::from_value(
-        result
-            .to_json(context)
-            .map_err(|e| anyhow!("Failed to convert to JSON: {:?}", e))?,
-    )
-    .map_err(|e| anyhow!("Failed to parse JSON: {:?}", e))?)
+    let mut result = from_js_value(&result, context)?;
+    if format == Format::MusicXml {
+        options.music_xml.apply(source, &mut result);
+        read_music_xml_key_signatures(source, &mut result);
+    }
+    if format == Format::Svp {
+        if let Some(svp) = svp_json(source) {
+            read_svp_phoneme_segments(&svp, &mut result);
+            read_svp_track_states(&svp, &mut result);
+            read_svp_track_colors(&svp, &mut result);
+        }
+    }
+    if format == Format::Vpr {
+        read_vpr_track_states(source, &mut result);
+    }
+    read_track_colors(format, source, &mut result);
+    if options.ccs_voices {
+        read_track_voices(format, source, &mut result);
+    }
+    if options.track_parameters {
+        read_track_parameters(format, source, &mut result);
+    }
+    options.apply_fallback_timing(format, &[source], &mut result);
+    options.apply_ust_pitch(format, &[source], &mut result);
+    Ok(clean_lyrics(result, options))
 }
 
 async fn parse_multiple(
     utaformatix: &mut boa_engine::JsObject,
     context: &mut boa_engine::Context,
     format: Format,
-    data: Vec<Vec<u8>>,
-    options: ParseOptions,
+    data: &[Bytes],
+    options: &ParseOptions,
 ) -> Result<UfData> {
-    let data = data
-        .into_iter()
-        .map(|data| boa_engine::object::builtins::JsUint8Array::from_iter(data, context))
+    options.validate_fallback_timing()?;
+    let sources = data;
+    let data = sources
+        .iter()
+        .map(|data| {
+            boa_engine::object::builtins::JsUint8Array::from_iter(data.iter().copied(), context)
+        })
         .collect::<std::result::Result<Vec<_>, _>>()
-        .expect("Failed to create Uint8Array")
+        .map_err(|e| anyhow!("Failed to create Uint8Array: {:?}", e))?
         .into_iter()
         .map(JsValue::from)
         .collect::<Vec<JsValue>>();
 
     let function_name = format!("parse{}", format.suffix());
-    let boa_engine::JsValue::Object(parser) = utaformatix
-        .get(JsString::from(function_name), context)
-        .expect("Failed to get parse function")
-    else {
-        panic!("Failed to get parse function: Unexpected return value");
-    };
-    if !parser.is_callable() {
-        panic!("Failed to get parse function: Unexpected return value");
-    }
-    let result_promise = parser
-        .call(
-            &boa_engine::JsValue::undefined(),
-            &[
-                boa_engine::object::builtins::JsArray::from_iter(data, context).into(),
-                boa_engine::JsValue::from_json(
-                    &serde_json::to_value(options).expect("Failed to convert to JSON"),
-                    context,
-                )
-                .expect("Failed to convert to JsValue"),
-            ],
-            context,
-        )
-        .map_err(|e| anyhow!("Failed to call parse function: {:?}", e))?;
-    let boa_engine::JsValue::Object(result_promise) = result_promise else {
-        panic!("Failed to call parse function: Unexpected return value");
-    };
-    let result_promise = boa_engine::object::builtins::JsPromise::from_object(result_promise)
-        .expect("Failed to convert to JsPromise");
-    let future = result_promise.into_js_future(context);
-
-    let runner = async { context.run_jobs_async().await };
-
-    let (_, result) = tokio::join!(runner, future);
+    let parser = get_function(utaformatix, JsString::from(function_name), context)?;
+    let data = boa_engine::object::builtins::JsArray::from_iter(data, context).into();
+    let js_options = to_js_value(&JsParseOptions::from(options), context)?;
+    let result = call_async(&parser, &[data, js_options], context).await?;
 
     let result = wrap_error(result, utaformatix, context)?;
     if !result.is_object() {
         return Err(anyhow!("Failed to parse: Unexpected return value: {:?}", result).into());
     }
-    Ok(serde_json::from_value(
-        result
-            .to_json(context)
-            .map_err(|e| anyhow!("Failed to convert to JSON: {:?}", e))?,
+    let mut result = from_js_value(&result, context)?;
+    let sources = sources.iter().map(|source| &source[..]).collect::<Vec<_>>();
+    options.apply_fallback_timing(format, &sources, &mut result);
+    options.apply_ust_pitch(format, &sources, &mut result);
+    Ok(clean_lyrics(result, options))
+}
+
+/// Returns the resident memory of the process in bytes, or `None` if it's unknown. This is only
+/// supported on Linux.
+fn resident_memory() -> Option<u64> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes = line
+        .trim_start_matches("VmRSS:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Generates a project with a single note, which initializes what the first conversion does.
+async fn warm_up(
+    utaformatix: &mut boa_engine::JsObject,
+    context: &mut boa_engine::Context,
+) -> Result<()> {
+    let data = UfData::new(UfProject {
+        name: "Warm-up".to_string(),
+        tracks: vec![Track {
+            name: "Track".to_string(),
+            notes: vec![Note {
+                key: Key::CENTER_C,
+                tick_on: Tick::ZERO,
+                tick_off: Tick::ZERO.saturating_add(480),
+                lyric: "あ".to_string(),
+                phoneme: None,
+                phoneme_segments: None,
+            }],
+            pitch: None,
+            muted: None,
+            solo: None,
+            order: None,
+            color: None,
+            voice: None,
+            parameters: None,
+        }],
+        time_signatures: vec![TimeSignature::default()],
+        tempos: vec![Tempo {
+            tick_position: Tick::ZERO,
+            bpm: 120,
+        }],
+        measure_prefix: 0,
+        key_signatures: vec![],
+    });
+    generate_single(
+        utaformatix,
+        context,
+        Format::Ustx,
+        &data,
+        &GenerateOptions::default(),
     )
-    .map_err(|e| anyhow!("Failed to parse JSON: {:?}", e))?)
+    .await
+    .map(|_| ())
 }
 
 async fn generate_single(
     utaformatix: &mut boa_engine::JsObject,
     context: &mut boa_engine::Context,
     format: Format,
-    data: UfData,
-    options: GenerateOptions,
+    data: &UfData,
+    options: &GenerateOptions,
 ) -> Result<Vec<u8>> {
+    let mut data = Cow::Borrowed(data);
+    options.prepare(format, &mut data)?;
     let function_name = format!("generate{}", format.suffix());
-    let boa_engine::JsValue::Object(parser) = utaformatix
-        .get(JsString::from(function_name), context)
-        .expect("Failed to get parse function")
-    else {
-        panic!("Failed to get parse function: Unexpected return value");
-    };
-    if !parser.is_callable() {
-        panic!("Failed to get parse function: Unexpected return value");
-    }
-    let result_promise = parser
-        .call(
-            &boa_engine::JsValue::undefined(),
-            &[
-                boa_engine::JsValue::from_json(
-                    &serde_json::to_value(data).expect("Failed to convert to JSON"),
-                    context,
-                )
-                .expect("Failed to convert to JsValue"),
-                boa_engine::JsValue::from_json(
-                    &serde_json::to_value(options).expect("Failed to convert to JSON"),
-                    context,
-                )
-                .expect("Failed to convert to JsValue"),
-            ],
-            context,
-        )
-        .map_err(|e| anyhow!("Failed to call parse function: {:?}", e))?;
-    let boa_engine::JsValue::Object(result_promise) = result_promise else {
-        panic!("Failed to call parse function: Unexpected return value");
-    };
-    let result_promise = boa_engine::object::builtins::JsPromise::from_object(result_promise)
-        .expect("Failed to convert to JsPromise");
-    let future = result_promise.into_js_future(context);
-
-    let runner = async { context.run_jobs_async().await };
+    let generator = get_function(utaformatix, JsString::from(function_name), context)?;
+    let js_data = to_js_value(&data, context)?;
+    let js_options = to_js_value(&JsGenerateOptions::from(options), context)?;
+    let result = call_async(&generator, &[js_data, js_options], context).await?;
 
-    let (_, result) = tokio::join!(runner, future);
-
-    let result = wrap_error(result, utaformatix, context)?
-        .as_object()
-        .expect("Failed to convert to object")
-        .to_owned();
-    let array = JsTypedArray::from_object(result).expect("Failed to convert to JsTypedArray");
-    let length = array.length(context).expect("Failed to get length");
-    let mut data = Vec::with_capacity(length as usize);
-    for i in 0..length {
-        let value = array.get(i, context).expect("Failed to get value");
-        data.push(value.as_number().expect("Failed to get number") as u8);
+    let result = wrap_error(result, utaformatix, context)?;
+    let mut result = typed_array_to_bytes(&result, context)?;
+    if format == Format::Svp && has_svp_extensions(&data) {
+        result = edit_svp(result, |svp| {
+            write_svp_phoneme_segments(svp, &data);
+            write_svp_track_states(svp, &data);
+            write_svp_track_colors(svp, &data);
+        })?;
+    }
+    if format == Format::Vpr {
+        result = write_vpr_track_states(result, &data)?;
+    }
+    let result = write_track_colors(format, result, &data)?;
+    // Format options are applied afterwards, so that they override the voices.
+    let result = write_track_voices(format, result, &data)?;
+    let result = write_track_parameters(format, result, &data)?;
+    match &options.format_options {
+        Some(format_options) => format_options.apply(format, result),
+        None => Ok(result),
     }
-
-    Ok(data)
 }
 
-async fn generate_multiple(
+/// Generates a multi-file format, passing each file to `on_file` as soon as it is converted from
+/// JS, unless the format options merge the files.
+async fn generate_each(
     utaformatix: &mut boa_engine::JsObject,
     context: &mut boa_engine::Context,
     format: Format,
-    data: UfData,
-    options: GenerateOptions,
-) -> Result<Vec<Vec<u8>>> {
-    let function_name = format!("generate{}", format.suffix());
-    let boa_engine::JsValue::Object(parser) = utaformatix
-        .get(JsString::from(function_name), context)
-        .expect("Failed to get parse function")
-    else {
-        panic!("Failed to get parse function: Unexpected return value");
+    data: &UfData,
+    options: &GenerateOptions,
+    mut on_file: impl FnMut(Vec<u8>) -> Result<()>,
+) -> Result<()> {
+    // Dynamics are taken before the markers are removed, and written as MusicXML directions.
+    let dynamics = match (&options.markers, format) {
+        (Some(markers), Format::MusicXml) => data
+            .project()
+            .tracks
+            .iter()
+            .map(|track| markers.dynamics(&track.notes))
+            .collect(),
+        _ => vec![],
     };
-    if !parser.is_callable() {
-        panic!("Failed to get parse function: Unexpected return value");
-    }
-    let result_promise = parser
-        .call(
-            &boa_engine::JsValue::undefined(),
-            &[
-                boa_engine::JsValue::from_json(
-                    &serde_json::to_value(data).expect("Failed to convert to JSON"),
-                    context,
-                )
-                .expect("Failed to convert to JsValue"),
-                boa_engine::JsValue::from_json(
-                    &serde_json::to_value(options).expect("Failed to convert to JSON"),
-                    context,
-                )
-                .expect("Failed to convert to JsValue"),
-            ],
-            context,
-        )
-        .map_err(|e| anyhow!("Failed to call parse function: {:?}", e))?;
-    let boa_engine::JsValue::Object(result_promise) = result_promise else {
-        panic!("Failed to call parse function: Unexpected return value");
-    };
-    let result_promise = boa_engine::object::builtins::JsPromise::from_object(result_promise)
-        .expect("Failed to convert to JsPromise");
-    let future = result_promise.into_js_future(context);
-
-    let runner = async { context.run_jobs_async().await };
-
-    let (_, result) = tokio::join!(runner, future);
+    let mut data = Cow::Borrowed(data);
+    options.prepare(format, &mut data)?;
+    if let (Format::Ust, Some(prefix_map)) = (format, &options.prefix_map) {
+        for track in &mut data.to_mut().project_mut().tracks {
+            prefix_map.apply_to_notes(&mut track.notes);
+        }
+    }
+    let writes_mode1_pitch = format == Format::Ust
+        && options.pitch
+        && matches!(
+            &options.format_options,
+            Some(FormatOptions::Ust(ust_options)) if ust_options.pitch_mode == UstPitchMode::Mode1
+        );
+    let function_name = format!("generate{}", format.suffix());
+    let generator = get_function(utaformatix, JsString::from(function_name), context)?;
+    let js_data = to_js_value(&data, context)?;
+    let js_options = to_js_value(&JsGenerateOptions::from(options), context)?;
+    let result = call_async(&generator, &[js_data, js_options], context).await?;
 
-    let result = wrap_error(result, utaformatix, context)?
+    let result = wrap_error(result, utaformatix, context)?;
+    let result = result
         .as_object()
-        .expect("Failed to convert to object")
+        .ok_or_else(|| anyhow!("Failed to convert to object: {:?}", result))?
         .to_owned();
-    let result = JsArray::from_object(result).expect("Failed to convert to JsArray");
-    let length = result.length(context).expect("Failed to get length");
+    let result = JsArray::from_object(result)
+        .map_err(|e| anyhow!("Failed to convert to JsArray: {:?}", e))?;
+    let length = result
+        .length(context)
+        .map_err(|e| anyhow!("Failed to get length: {:?}", e))?;
+    let merging = options
+        .format_options
+        .as_ref()
+        .filter(|format_options| format_options.merges(format));
     let mut files = vec![];
     for i in 0..length {
-        let value = result.get(i, context).expect("Failed to get value");
-        let array = JsTypedArray::from_object(
-            value
-                .as_object()
-                .expect("Failed to convert to JsObject")
-                .to_owned(),
-        )
-        .expect("Failed to convert to JsTypedArray");
-        let length = array.length(context).expect("Failed to get length");
-        let mut data = Vec::with_capacity(length as usize);
-        for i in 0..length {
-            let value = array.get(i, context).expect("Failed to get value");
-            data.push(value.as_number().expect("Failed to get number") as u8);
+        let value = result
+            .get(i, context)
+            .map_err(|e| anyhow!("Failed to get value: {:?}", e))?;
+        let mut file = typed_array_to_bytes(&value, context)?;
+        let track = data.project().tracks.get(i as usize);
+        if let (Some(dynamics), Some(track)) = (dynamics.get(i as usize), track) {
+            file = add_music_xml_directions(file, &track.notes, dynamics)?;
+        }
+        if format == Format::MusicXml {
+            file = write_music_xml_key_signatures(file, &data.project().key_signatures)?;
+        }
+        if let (Some(track), true) = (track, writes_mode1_pitch) {
+            file = write_mode1_pitch(file, track);
+        }
+        if merging.is_some() {
+            files.push(file);
+            continue;
+        }
+        match &options.format_options {
+            Some(format_options) => on_file(format_options.apply(format, file)?)?,
+            None => on_file(file)?,
         }
-        files.push(data);
     }
 
-    Ok(files)
+    if let Some(format_options) = merging {
+        for file in format_options.apply_multiple(format, files)? {
+            on_file(file)?;
+        }
+    }
+    Ok(())
 }
 
 fn analyze_japanese_lyrics_type(
     utaformatix: &mut boa_engine::JsObject,
     context: &mut boa_engine::Context,
-    data: UfData,
-) -> Result<Option<JapaneseLyricsType>> {
-    let boa_engine::JsValue::Object(parser) = utaformatix
-        .get(js_string!("analyzeJapaneseLyricsType"), context)
-        .expect("Failed to get parse function")
-    else {
-        panic!("Failed to get parse function: Unexpected return value");
-    };
-    if !parser.is_callable() {
-        panic!("Failed to get parse function: Unexpected return value");
+    data: &UfData,
+) -> Result<LyricsTypeAnalysis> {
+    let lyrics_type = detect_japanese_lyrics_type(utaformatix, context, data)?;
+    let project = data.project();
+    let mut tracks = vec![];
+    for track in &project.tracks {
+        let track_data = UfData::new(UfProject {
+            tracks: vec![track.clone()],
+            ..project.clone()
+        });
+        tracks.push(TrackLyricsTypeAnalysis {
+            lyrics_type: detect_japanese_lyrics_type(utaformatix, context, &track_data)?,
+            candidates: lyrics_type_candidates(&track.notes),
+        });
     }
-    let result = parser.call(
-        &boa_engine::JsValue::undefined(),
-        &[boa_engine::JsValue::from_json(
-            &serde_json::to_value(data).expect("Failed to convert to JSON"),
-            context,
-        )
-        .expect("Failed to convert to JsValue")],
+
+    Ok(LyricsTypeAnalysis {
+        lyrics_type,
+        candidates: lyrics_type_candidates(project.tracks.iter().flat_map(|track| &track.notes)),
+        tracks,
+    })
+}
+
+fn detect_japanese_lyrics_type(
+    utaformatix: &mut boa_engine::JsObject,
+    context: &mut boa_engine::Context,
+    data: &UfData,
+) -> Result<Option<JapaneseLyricsType>> {
+    let analyzer = get_function(
+        utaformatix,
+        js_string!("analyzeJapaneseLyricsType"),
         context,
-    );
-    let result = wrap_error(result, utaformatix, context)?
+    )?;
+    let data = to_js_value(data, context)?;
+    let result = analyzer.call(&boa_engine::JsValue::undefined(), &[data], context);
+    let result = wrap_error(result, utaformatix, context)?;
+    let result = result
         .as_string()
-        .expect("Failed to convert to string")
-        .to_owned();
-    let result = result.to_std_string().expect("Failed to convert to string");
+        .ok_or_else(|| anyhow!("Failed to convert to string: {:?}", result))?
+        .to_std_string()
+        .map_err(|e| anyhow!("Failed to convert to string: {:?}", e))?;
     let result = JapaneseLyricsType::from_str(&result).ok();
 
     Ok(result)
@@ -654,44 +1710,27 @@ fn analyze_japanese_lyrics_type(
 fn convert_japanese_lyrics(
     utaformatix: &mut boa_engine::JsObject,
     context: &mut boa_engine::Context,
-    data: UfData,
+    data: &UfData,
     source: JapaneseLyricsType,
     to: JapaneseLyricsType,
-    options: ConvertJapaneseLyricsOptions,
+    options: &ConvertJapaneseLyricsOptions,
 ) -> Result<UfData> {
-    let boa_engine::JsValue::Object(parser) = utaformatix
-        .get(js_string!("convertJapaneseLyrics"), context)
-        .expect("Failed to get parse function")
-    else {
-        panic!("Failed to get parse function: Unexpected return value");
-    };
-    if !parser.is_callable() {
-        panic!("Failed to get parse function: Unexpected return value");
-    }
-    let result = parser.call(
+    let converter = get_function(utaformatix, js_string!("convertJapaneseLyrics"), context)?;
+    let data = to_js_value(data, context)?;
+    let js_options = to_js_value(&JsConvertJapaneseLyricsOptions::from(options), context)?;
+    let result = converter.call(
         &boa_engine::JsValue::undefined(),
         &[
-            boa_engine::JsValue::from_json(
-                &serde_json::to_value(data).expect("Failed to convert to JSON"),
-                context,
-            )
-            .expect("Failed to convert to JsValue"),
+            data,
             JsString::from(source.to_string()).into(),
             JsString::from(to.to_string()).into(),
-            boa_engine::JsValue::from_json(
-                &serde_json::to_value(options).expect("Failed to convert to JSON"),
-                context,
-            )
-            .expect("Failed to convert to JsValue"),
+            js_options,
         ],
         context,
     );
     let result = wrap_error(result, utaformatix, context)?;
 
-    Ok(serde_json::from_value(
-        result
-            .to_json(context)
-            .map_err(|e| anyhow!("Failed to convert to JSON: {:?}", e))?,
-    )
-    .map_err(|e| anyhow!("Failed to parse JSON: {:?}", e))?)
+    let mut data = from_js_value(&result, context)?;
+    options.adjust(&mut data, to);
+    Ok(data)
 }
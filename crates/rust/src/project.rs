@@ -1,14 +1,51 @@
+mod click;
+mod edit_session;
+mod export;
+mod humanize;
+mod key;
+mod lyrics_txt;
+mod measure_prefix;
+mod shift;
+mod simple_json;
+mod summary;
+mod tempo;
+mod time_signature;
+mod track_name;
+mod vowel_extension;
+
+pub use edit_session::EditSession;
+#[cfg(feature = "ml")]
+pub(crate) use export::sanitize_file_name;
+pub use export::{CollisionPolicy, NamingTemplate};
+pub use humanize::HumanizeOptions;
+pub use shift::{AlignOptions, ShiftOptions};
+pub use simple_json::{SimpleNote, SimpleProject, SimpleTrack};
+pub use summary::ProjectSummary;
+#[cfg(any(feature = "ml", feature = "playback", feature = "preview"))]
+pub(crate) use tempo::TempoMap;
+pub use track_name::TrackNameOptions;
+
+use crate::model::UfData;
+#[cfg(feature = "engine")]
 use crate::{
-    base::UtaFormatix,
-    error::Result,
+    base::{GeneratedFiles, UtaFormatix},
+    error::{ErrorKind, Result},
     model::{
-        ConvertJapaneseLyricsOptions, GenerateOptions, JapaneseLyricsType, ParseOptions, UfData,
+        ConvertJapaneseLyricsOptions, Format, GenerateOptions, JapaneseLyricsType,
+        LyricsTypeAnalysis, ParseOptions, Tempo, Tick, TimeSignature, Track, UfProject,
+        UndetectedLyricsPolicy,
     },
 };
+#[cfg(feature = "engine")]
+use bytes::Bytes;
+#[cfg(feature = "engine")]
 use duplicate::duplicate_item;
+#[cfg(feature = "multi-thread")]
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-use tokio::sync::Mutex;
+#[cfg(feature = "multi-thread")]
+use tokio::sync::{Mutex, MutexGuard};
+#[cfg(feature = "engine")]
 use tracing::warn;
 
 #[derive(Debug, Clone)]
@@ -38,14 +75,34 @@ impl<'de> Deserialize<'de> for Project {
     }
 }
 
+#[cfg(feature = "multi-thread")]
 static UTAFORMATIX: Lazy<Mutex<UtaFormatix>> = Lazy::new(|| Mutex::new(UtaFormatix::new()));
 
+/// Returns the instance shared by the projects.
+#[cfg(feature = "multi-thread")]
+async fn utaformatix() -> MutexGuard<'static, UtaFormatix> {
+    UTAFORMATIX.lock().await
+}
+
+/// Returns the instance shared by the projects on this thread, since it can't be sent to other
+/// threads without the `multi-thread` feature.
+#[cfg(all(feature = "engine", not(feature = "multi-thread")))]
+async fn utaformatix() -> &'static UtaFormatix {
+    thread_local! {
+        static UTAFORMATIX: &'static UtaFormatix = Box::leak(Box::new(UtaFormatix::new()));
+    }
+    UTAFORMATIX.with(|utaformatix| *utaformatix)
+}
+
 impl Project {
     /// Creates a new instance of `Project`.
     pub fn new(data: UfData) -> Self {
         Self { data }
     }
+}
 
+#[cfg(feature = "engine")]
+impl Project {
     #[duplicate_item(
         fn_name              format_enum           kind;
         [parse_standard_mid] [Format::StandardMid] ["Standard MIDI"];
@@ -66,8 +123,11 @@ impl Project {
     #[doc = "Parses a "]
     #[doc = kind]
     #[doc = " file."]
-    pub async fn fn_name(data: &[u8], options: ParseOptions) -> Result<Self> {
-        let utaformatix = UTAFORMATIX.lock().await;
+    pub async fn fn_name(
+        data: impl Into<Bytes>,
+        options: impl Into<Option<ParseOptions>>,
+    ) -> Result<Self> {
+        let utaformatix = utaformatix().await;
         utaformatix.fn_name(data, options).await.map(Self::new)
     }
 
@@ -78,9 +138,12 @@ impl Project {
     #[doc = "Parses a "]
     #[doc = kind]
     #[doc = " file."]
-    pub async fn fn_name(data: &[u8], options: ParseOptions) -> Result<Self> {
-        let utaformatix = UTAFORMATIX.lock().await;
-        utaformatix.fn_name(&[data], options).await.map(Self::new)
+    pub async fn fn_name(
+        data: impl Into<Bytes>,
+        options: impl Into<Option<ParseOptions>>,
+    ) -> Result<Self> {
+        let utaformatix = utaformatix().await;
+        utaformatix.fn_name([data], options).await.map(Self::new)
     }
 
     #[duplicate_item(
@@ -91,14 +154,27 @@ impl Project {
     #[doc = kind]
     #[doc = " file."]
     /// You can pass multiple files to parse at once, each file will be parsed as a track.
-    pub async fn fn_name(data: &[&[u8]], options: ParseOptions) -> Result<Self> {
-        let utaformatix = UTAFORMATIX.lock().await;
+    pub async fn fn_name(
+        data: impl IntoIterator<Item = impl Into<Bytes>>,
+        options: impl Into<Option<ParseOptions>>,
+    ) -> Result<Self> {
+        let utaformatix = utaformatix().await;
         utaformatix
             .original_fn_name(data, options)
             .await
             .map(Self::new)
     }
 
+    /// Parses files of different formats into a single project.
+    /// See [`UtaFormatix::parse_mixed`].
+    pub async fn parse_mixed(
+        files: impl IntoIterator<Item = (Format, impl Into<Bytes>)>,
+        options: impl Into<Option<ParseOptions>>,
+    ) -> Result<Self> {
+        let utaformatix = utaformatix().await;
+        utaformatix.parse_mixed(files, options).await.map(Self::new)
+    }
+
     #[duplicate_item(
         fn_name                  format_enum          kind;
         [generate_standard_mid] [Format::StandardMid] ["Standard MIDI"];
@@ -117,8 +193,8 @@ impl Project {
     #[doc = "Generates a "]
     #[doc = kind]
     #[doc = " file."]
-    pub async fn fn_name(&self, options: GenerateOptions) -> Result<Vec<u8>> {
-        let utaformatix = UTAFORMATIX.lock().await;
+    pub async fn fn_name(&self, options: impl Into<Option<GenerateOptions>>) -> Result<Vec<u8>> {
+        let utaformatix = utaformatix().await;
         utaformatix.fn_name(&self.data, options).await
     }
 
@@ -131,45 +207,142 @@ impl Project {
     #[doc = kind]
     #[doc = " file."]
     /// Returns the bytes of the generated file, each representing a track.
-    pub async fn fn_name(&self, options: GenerateOptions) -> Result<Vec<Vec<u8>>> {
-        let utaformatix = UTAFORMATIX.lock().await;
+    pub async fn fn_name(
+        &self,
+        options: impl Into<Option<GenerateOptions>>,
+    ) -> Result<Vec<Vec<u8>>> {
+        let utaformatix = utaformatix().await;
         utaformatix.fn_name(&self.data, options).await
     }
 
-    /// Analyzes the type of Japanese lyrics.
-    /// Returns `None` if the lyrics type cannot be determined.
-    pub async fn analyze_japanese_lyrics_type(&self) -> Result<Option<JapaneseLyricsType>> {
-        let utaformatix = UTAFORMATIX.lock().await;
+    #[duplicate_item(
+        fn_name                    format_enum     kind;
+        [generate_ccs_per_track]   [Format::Ccs]   ["CeVIO's project"];
+        [generate_tssln_per_track] [Format::Tssln] ["VoiSona's project"];
+    )]
+    #[doc = "Generates a "]
+    #[doc = kind]
+    #[doc = " file for each track."]
+    /// See [`UtaFormatix::generate_ccs_per_track`].
+    pub async fn fn_name(
+        &self,
+        options: impl Into<Option<GenerateOptions>>,
+    ) -> Result<Vec<(String, Vec<u8>)>> {
+        let utaformatix = utaformatix().await;
+        utaformatix.fn_name(&self.data, options).await
+    }
+
+    #[duplicate_item(
+        fn_name                        format_enum          kind;
+        [generate_music_xml_stream]    [Format::MusicXml]    ["MusicXML"];
+        [generate_ust_stream]          [Format::Ust]         ["UTAU's project"];
+    )]
+    #[doc = "Generates a "]
+    #[doc = kind]
+    #[doc = " file, receiving the files one at a time."]
+    /// See [`UtaFormatix::generate_music_xml_stream`].
+    pub async fn fn_name(
+        &self,
+        options: impl Into<Option<GenerateOptions>>,
+    ) -> Result<GeneratedFiles> {
+        let utaformatix = utaformatix().await;
+        utaformatix.fn_name(&self.data, options).await
+    }
+
+    /// Analyzes the type of Japanese lyrics, of the whole project and each track.
+    ///
+    /// See [`LyricsTypeAnalysis::is_ambiguous`] to check whether the result is reliable.
+    pub async fn analyze_japanese_lyrics_type(&self) -> Result<LyricsTypeAnalysis> {
+        let utaformatix = utaformatix().await;
         utaformatix
             .analyze_japanese_lyrics_type(self.data.clone())
             .await
     }
 
     /// Converts Japanese lyrics.
+    ///
+    /// The source type is detected if it's `None`. See
+    /// [`ConvertJapaneseLyricsOptions::on_undetected`] for what happens if it can't be detected.
     pub async fn convert_japanese_lyrics(
         &self,
         source_type: Option<JapaneseLyricsType>,
         target_type: JapaneseLyricsType,
         options: ConvertJapaneseLyricsOptions,
     ) -> Result<Self> {
-        let source_type = if let Some(source_type) = source_type {
-            Some(source_type)
-        } else {
-            self.analyze_japanese_lyrics_type().await?
-        };
-        if source_type.is_none() {
-            warn!("Failed to determine the source type of the Japanese lyrics");
+        let Some(source_type) = source_lyrics_type(&self.data, source_type, &options).await? else {
             return Ok(Self::new(self.data.clone()));
-        }
-        let utaformatix = UTAFORMATIX.lock().await;
+        };
+        let utaformatix = utaformatix().await;
         utaformatix
-            .convert_japanese_lyrics(
-                self.data.clone(),
-                source_type.unwrap(),
-                target_type,
-                options,
-            )
+            .convert_japanese_lyrics(self.data.clone(), source_type, target_type, options)
             .await
             .map(Self::new)
     }
 }
+
+#[cfg(feature = "engine")]
+impl Track {
+    /// Converts the Japanese lyrics of the track, without the rest of the project, e.g. for
+    /// editors with a language setting per track.
+    ///
+    /// The source type is detected from this track if it's `None`. See
+    /// [`ConvertJapaneseLyricsOptions::on_undetected`] for what happens if it can't be detected,
+    /// in which case the track is left as it is.
+    pub async fn convert_lyrics(
+        &mut self,
+        source_type: Option<JapaneseLyricsType>,
+        target_type: JapaneseLyricsType,
+        options: ConvertJapaneseLyricsOptions,
+    ) -> Result<()> {
+        let data = UfData::new(UfProject {
+            name: String::new(),
+            tracks: vec![self.clone()],
+            time_signatures: vec![TimeSignature::default()],
+            tempos: vec![Tempo {
+                tick_position: Tick::ZERO,
+                bpm: 120,
+            }],
+            measure_prefix: 0,
+            key_signatures: vec![],
+        });
+        let Some(source_type) = source_lyrics_type(&data, source_type, &options).await? else {
+            return Ok(());
+        };
+        let utaformatix = utaformatix().await;
+        let mut data = utaformatix
+            .convert_japanese_lyrics(data, source_type, target_type, options)
+            .await?;
+        if let Some(track) = data.project_mut().tracks.pop() {
+            *self = track;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the type of the Japanese lyrics to convert from, detecting it from `data` if
+/// `source_type` is `None`, or `None` if the lyrics should be left as they are.
+#[cfg(feature = "engine")]
+async fn source_lyrics_type(
+    data: &UfData,
+    source_type: Option<JapaneseLyricsType>,
+    options: &ConvertJapaneseLyricsOptions,
+) -> Result<Option<JapaneseLyricsType>> {
+    if let Some(source_type) = source_type {
+        return Ok(Some(source_type));
+    }
+    let analysis = utaformatix()
+        .await
+        .analyze_japanese_lyrics_type(data.clone())
+        .await?;
+    if let Some(source_type) = analysis.lyrics_type {
+        return Ok(Some(source_type));
+    }
+    match options.on_undetected {
+        UndetectedLyricsPolicy::PassThrough => {
+            warn!("Failed to determine the source type of the Japanese lyrics");
+            Ok(None)
+        }
+        UndetectedLyricsPolicy::Error => Err(ErrorKind::LyricsTypeUndetected.into()),
+        UndetectedLyricsPolicy::Assume(source_type) => Ok(Some(source_type)),
+    }
+}
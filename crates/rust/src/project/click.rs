@@ -0,0 +1,76 @@
+//! Click track (metronome) generation.
+use super::Project;
+use crate::model::{measure_to_tick, Key, Note, Tick, Track, TICKS_PER_BEAT};
+
+/// Key of the first beat of a measure (C6).
+const DOWNBEAT_KEY: Key = match Key::new(84) {
+    Some(key) => key,
+    None => panic!("C6 is out of the range of MIDI"),
+};
+/// Key of the other beats (G5).
+const BEAT_KEY: Key = match Key::new(79) {
+    Some(key) => key,
+    None => panic!("G5 is out of the range of MIDI"),
+};
+const CLICK_LYRIC: &str = "ta";
+
+impl Project {
+    /// Creates a track with a short note on every beat, accenting the first beat of each
+    /// measure with a higher key, so singers have a guide when recording.
+    ///
+    /// Beats follow the time signatures (a beat is a `1/denominator` note), and the tempo map
+    /// of the project applies as is since notes are placed in ticks. The track lasts until the
+    /// end of the measure containing the last note, or one measure if there are no notes.
+    pub fn generate_click_track(&self) -> Track {
+        let project = self.data.project();
+        let mut time_signatures = project.time_signatures.clone();
+        time_signatures.sort_by_key(|time_signature| time_signature.measure_position);
+        let end = project
+            .tracks
+            .iter()
+            .flat_map(|track| &track.notes)
+            .map(|note| note.tick_off.get())
+            .max()
+            .unwrap_or(1);
+
+        let mut notes = vec![];
+        let mut measure = 0;
+        loop {
+            let measure_tick = measure_to_tick(&time_signatures, measure);
+            if measure_tick >= end {
+                break;
+            }
+            let time_signature = time_signatures
+                .iter()
+                .rev()
+                .find(|time_signature| time_signature.measure_position <= measure)
+                .cloned()
+                .unwrap_or_default();
+            let ticks_per_beat = TICKS_PER_BEAT * 4 / i64::from(time_signature.denominator.max(1));
+            for beat in 0..i64::from(time_signature.numerator.max(1)) {
+                let tick_on = Tick::ZERO.saturating_add(measure_tick + beat * ticks_per_beat);
+                notes.push(Note {
+                    key: if beat == 0 { DOWNBEAT_KEY } else { BEAT_KEY },
+                    tick_on,
+                    tick_off: tick_on.saturating_add((ticks_per_beat / 4).max(1)),
+                    lyric: CLICK_LYRIC.to_string(),
+                    phoneme: None,
+                    phoneme_segments: None,
+                });
+            }
+            measure += 1;
+        }
+
+        Track {
+            name: "Click".to_string(),
+            notes,
+            pitch: None,
+            muted: None,
+            solo: None,
+            order: None,
+            color: None,
+            voice: None,
+            parameters: None,
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Editing sessions with undo/redo.
+use super::Project;
+use crate::{
+    error::{ErrorKind, Result},
+    model::{Note, Track, UfData, UfProject},
+};
+use std::{ops::Range, sync::Arc};
+
+/// A state of the project. Tracks are shared between states, so only edited tracks are copied.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    /// The project without tracks.
+    project: Arc<UfProject>,
+    tracks: Vec<Arc<Track>>,
+}
+
+impl Snapshot {
+    fn new(data: UfData) -> Self {
+        let mut project = data.project().clone();
+        let tracks = std::mem::take(&mut project.tracks)
+            .into_iter()
+            .map(Arc::new)
+            .collect();
+        Self {
+            project: Arc::new(project),
+            tracks,
+        }
+    }
+
+    fn to_data(&self) -> UfData {
+        UfData::new(UfProject {
+            tracks: self
+                .tracks
+                .iter()
+                .map(|track| track.as_ref().clone())
+                .collect(),
+            ..self.project.as_ref().clone()
+        })
+    }
+}
+
+/// An editing session of a [`Project`], with change tracking and undo/redo.
+///
+/// Cloning a session is cheap, since tracks are shared until they are edited.
+#[derive(Debug, Clone)]
+pub struct EditSession {
+    current: Snapshot,
+    saved: Snapshot,
+    undo_stack: Vec<Snapshot>,
+    redo_stack: Vec<Snapshot>,
+}
+
+impl EditSession {
+    /// Starts a session. The project is treated as saved.
+    pub fn new(project: Project) -> Self {
+        let snapshot = Snapshot::new(project.data);
+        Self {
+            current: snapshot.clone(),
+            saved: snapshot,
+            undo_stack: vec![],
+            redo_stack: vec![],
+        }
+    }
+
+    /// Returns the edited project.
+    pub fn project(&self) -> Project {
+        Project::new(self.current.to_data())
+    }
+
+    /// Returns the track at the index.
+    pub fn track(&self, index: usize) -> Option<&Track> {
+        self.current.tracks.get(index).map(Arc::as_ref)
+    }
+
+    /// Returns the tracks.
+    pub fn tracks(&self) -> impl Iterator<Item = &Track> {
+        self.current.tracks.iter().map(Arc::as_ref)
+    }
+
+    /// Transposes the notes in the range by semitones.
+    ///
+    /// Returns [`ErrorKind::KeyOutOfRange`] if a key would be out of the range of MIDI; nothing
+    /// is changed in that case.
+    pub fn transpose(&mut self, track: usize, notes: Range<usize>, semitones: i32) -> Result<()> {
+        self.edit_notes(track, notes, |index, note| {
+            note.key = note
+                .key
+                .checked_add(semitones)
+                .ok_or(ErrorKind::KeyOutOfRange {
+                    track,
+                    note: index,
+                    key: note.key.get().saturating_add(semitones),
+                })?;
+            Ok(())
+        })
+    }
+
+    /// Moves the notes in the range by ticks.
+    ///
+    /// Returns [`ErrorKind::IllegalNotePosition`] if a note would be moved before the start;
+    /// nothing is changed in that case.
+    pub fn move_notes(&mut self, track: usize, notes: Range<usize>, ticks: i64) -> Result<()> {
+        self.edit_notes(track, notes, |_, note| {
+            note.tick_on = note
+                .tick_on
+                .checked_add(ticks)
+                .ok_or(ErrorKind::IllegalNotePosition)?;
+            note.tick_off = note
+                .tick_off
+                .checked_add(ticks)
+                .ok_or(ErrorKind::IllegalNotePosition)?;
+            Ok(())
+        })
+    }
+
+    /// Changes the lyric of the note.
+    pub fn set_lyric(&mut self, track: usize, note: usize, lyric: impl Into<String>) -> Result<()> {
+        let lyric = lyric.into();
+        self.edit_notes(track, note..note + 1, |_, note| {
+            note.lyric.clone_from(&lyric);
+            note.phoneme = None;
+            Ok(())
+        })
+    }
+
+    /// Applies an arbitrary edit to the track, as an undoable step.
+    pub fn edit_track(&mut self, track: usize, edit: impl FnOnce(&mut Track)) -> Result<()> {
+        if track >= self.current.tracks.len() {
+            return Err(ErrorKind::IndexOutOfRange.into());
+        }
+        self.push_undo();
+        edit(Arc::make_mut(&mut self.current.tracks[track]));
+        Ok(())
+    }
+
+    /// Applies an arbitrary edit to the whole project, as an undoable step.
+    ///
+    /// This copies every track, so prefer [`EditSession::edit_track`] for edits of a track.
+    pub fn edit_project<T>(&mut self, edit: impl FnOnce(&mut Project) -> Result<T>) -> Result<T> {
+        let mut project = self.project();
+        let result = edit(&mut project)?;
+        self.push_undo();
+        self.current = Snapshot::new(project.data);
+        Ok(result)
+    }
+
+    /// Reverts the last edit. Returns `false` if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(snapshot) = self.undo_stack.pop() else {
+            return false;
+        };
+        self.redo_stack
+            .push(std::mem::replace(&mut self.current, snapshot));
+        true
+    }
+
+    /// Re-applies the last undone edit. Returns `false` if there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let Some(snapshot) = self.redo_stack.pop() else {
+            return false;
+        };
+        self.undo_stack
+            .push(std::mem::replace(&mut self.current, snapshot));
+        true
+    }
+
+    /// Returns whether there is an edit to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    /// Returns whether there is an edit to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Returns whether the project differs from the last saved state.
+    pub fn is_modified(&self) -> bool {
+        !Arc::ptr_eq(&self.current.project, &self.saved.project)
+            || !self.changed_tracks().is_empty()
+    }
+
+    /// Returns the indices of tracks which differ from the last saved state.
+    pub fn changed_tracks(&self) -> Vec<usize> {
+        let count = self.current.tracks.len().max(self.saved.tracks.len());
+        (0..count)
+            .filter(|index| {
+                match (
+                    self.current.tracks.get(*index),
+                    self.saved.tracks.get(*index),
+                ) {
+                    (Some(current), Some(saved)) => !Arc::ptr_eq(current, saved),
+                    _ => true,
+                }
+            })
+            .collect()
+    }
+
+    /// Marks the current state as saved.
+    pub fn mark_saved(&mut self) {
+        self.saved = self.current.clone();
+    }
+
+    /// Edits the notes in the range, with their indices, as an undoable step. Nothing is
+    /// changed if an edit fails.
+    fn edit_notes(
+        &mut self,
+        track: usize,
+        notes: Range<usize>,
+        mut edit: impl FnMut(usize, &mut Note) -> Result<()>,
+    ) -> Result<()> {
+        let current = &self
+            .current
+            .tracks
+            .get(track)
+            .ok_or(ErrorKind::IndexOutOfRange)?
+            .notes;
+        if notes.start > notes.end || notes.end > current.len() {
+            return Err(ErrorKind::IndexOutOfRange.into());
+        }
+        let mut edited = current[notes.clone()].to_vec();
+        for (index, note) in notes.clone().zip(&mut edited) {
+            edit(index, note)?;
+        }
+        self.edit_track(track, |track| {
+            track.notes.splice(notes, edited);
+        })
+    }
+
+    fn push_undo(&mut self) {
+        self.undo_stack.push(self.current.clone());
+        self.redo_stack.clear();
+    }
+}
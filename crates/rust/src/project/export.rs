@@ -0,0 +1,242 @@
+//! Generation of files into a directory, named by templates.
+#[cfg(feature = "engine")]
+use super::Project;
+#[cfg(feature = "engine")]
+use crate::{
+    error::{ErrorKind, Result},
+    model::{Format, GenerateOptions},
+};
+#[cfg(feature = "engine")]
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "engine")]
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// Names which Windows reserves for devices, with or without an extension.
+#[cfg(any(feature = "engine", feature = "ml"))]
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Represents what to do when a file to export already exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CollisionPolicy {
+    /// Adds a number to the name, e.g. `Project (2).svp`.
+    #[default]
+    Rename,
+    /// Overwrites the existing file.
+    Overwrite,
+    /// Fails without writing anything.
+    Error,
+}
+
+/// Represents how [`Project::export`] names files.
+///
+/// Templates can contain these placeholders:
+///
+/// - `{project}`: Name of the project.
+/// - `{track}`: Name of the track.
+/// - `{track_index}`: Index of the track, starting from 1.
+/// - `{ext}`: Preferred extension of the format, without the dot.
+///
+/// Other text in braces is kept as it is. Characters which can't be used in file names on
+/// Windows are replaced with `_`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NamingTemplate {
+    /// Template of formats which generate a single file, where the track placeholders are
+    /// empty.
+    pub single: String,
+    /// Template of formats which generate a file per track, e.g. UST.
+    pub per_track: String,
+    /// What to do when a file already exists. Files of the same export never overwrite each
+    /// other.
+    pub on_collision: CollisionPolicy,
+}
+
+impl Default for NamingTemplate {
+    fn default() -> Self {
+        Self {
+            single: "{project}.{ext}".to_string(),
+            per_track: "{project}_{track_index}_{track}.{ext}".to_string(),
+            on_collision: CollisionPolicy::default(),
+        }
+    }
+}
+
+#[cfg(feature = "engine")]
+impl NamingTemplate {
+    fn render(
+        &self,
+        template: &str,
+        project: &str,
+        track: Option<(usize, &str)>,
+        ext: &str,
+    ) -> String {
+        let (track_index, track) = match track {
+            Some((index, name)) => ((index + 1).to_string(), name),
+            None => (String::new(), ""),
+        };
+        let name = template
+            .replace("{project}", project)
+            .replace("{track_index}", &track_index)
+            .replace("{track}", track)
+            .replace("{ext}", ext);
+        sanitize_file_name(&name)
+    }
+}
+
+/// Makes the name usable as a file name on every platform, replacing characters which Windows
+/// doesn't allow with `_`.
+#[cfg(any(feature = "engine", feature = "ml"))]
+pub(crate) fn sanitize_file_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+    // Windows drops trailing dots and spaces.
+    let name = name.trim_end_matches(['.', ' ']);
+    if name.is_empty() {
+        return "_".to_string();
+    }
+    let stem = name.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem.trim_end()))
+    {
+        format!("_{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Adds ` (n)` before the extension of the name, e.g. `Project (2).svp`.
+#[cfg(feature = "engine")]
+fn numbered(name: &str, number: usize) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{} ({}).{}", stem, number, ext),
+        _ => format!("{} ({})", name, number),
+    }
+}
+
+#[cfg(feature = "engine")]
+impl Project {
+    /// Generates the project in the format and writes the files to the directory, creating it
+    /// if it doesn't exist. Returns the paths of the written files.
+    ///
+    /// Files are named by [`NamingTemplate::per_track`] if the format generates a file per
+    /// track, and by [`NamingTemplate::single`] otherwise. CeVIO and VoiSona projects are
+    /// split into a file per track by `file_per_track` of [`crate::CcsOptions`] and
+    /// [`crate::TsslnOptions`].
+    pub async fn export(
+        &self,
+        directory: impl AsRef<Path>,
+        format: Format,
+        options: GenerateOptions,
+        template: &NamingTemplate,
+    ) -> Result<Vec<PathBuf>> {
+        let files = self.generate(format, options.clone()).await?;
+        let directory = directory.as_ref();
+        let project = self.data.project();
+        // Files are merged into one in some cases, e.g. MusicXML with `single_score`.
+        let per_track = (!format.metadata().multi_track || file_per_track(format, &options))
+            && files.len() == project.tracks.len();
+        let ext = format.extension();
+
+        let mut used = HashSet::new();
+        let mut paths = vec![];
+        for (index, _) in files.iter().enumerate() {
+            let name = if per_track {
+                let track = project
+                    .tracks
+                    .get(index)
+                    .map_or("", |track| track.name.as_str());
+                template.render(
+                    &template.per_track,
+                    &project.name,
+                    Some((index, track)),
+                    ext,
+                )
+            } else {
+                template.render(&template.single, &project.name, None, ext)
+            };
+            if template.on_collision == CollisionPolicy::Error && directory.join(&name).exists() {
+                return Err(
+                    anyhow!("File already exists: {}", directory.join(name).display()).into(),
+                );
+            }
+            // File systems of Windows and macOS are case-insensitive.
+            let is_free = |name: &str| {
+                !used.contains(&name.to_lowercase())
+                    && (template.on_collision == CollisionPolicy::Overwrite
+                        || !directory.join(name).exists())
+            };
+            let name = if is_free(&name) {
+                name
+            } else {
+                (2..)
+                    .map(|number| numbered(&name, number))
+                    .find(|name| is_free(name))
+                    .expect("Ran out of numbers")
+            };
+            used.insert(name.to_lowercase());
+            paths.push(directory.join(name));
+        }
+
+        std::fs::create_dir_all(directory)
+            .map_err(|e| anyhow!("Failed to create {}: {}", directory.display(), e))?;
+        for (path, file) in paths.iter().zip(files) {
+            std::fs::write(path, file)
+                .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+        }
+        Ok(paths)
+    }
+
+    /// Generates the project in the format, returning a file per track for multi-file formats.
+    async fn generate(&self, format: Format, options: GenerateOptions) -> Result<Vec<Vec<u8>>> {
+        let file = match format {
+            Format::Ccs if file_per_track(format, &options) => {
+                let files = self.generate_ccs_per_track(options).await?;
+                return Ok(files.into_iter().map(|(_, file)| file).collect());
+            }
+            Format::Tssln if file_per_track(format, &options) => {
+                let files = self.generate_tssln_per_track(options).await?;
+                return Ok(files.into_iter().map(|(_, file)| file).collect());
+            }
+            Format::MusicXml => return self.generate_music_xml(options).await,
+            Format::Ust => return self.generate_ust(options).await,
+            Format::Ppsf => return Err(ErrorKind::UnsupportedFileFormat.into()),
+            Format::StandardMid => self.generate_standard_mid(options).await?,
+            Format::Ccs => self.generate_ccs(options).await?,
+            Format::Dv => self.generate_dv(options).await?,
+            Format::Ustx => self.generate_ustx(options).await?,
+            Format::S5p => self.generate_s5p(options).await?,
+            Format::Svp => self.generate_svp(options).await?,
+            Format::Tssln => self.generate_tssln(options).await?,
+            Format::UfData => self.generate_uf_data(options).await?,
+            Format::VocaloidMid => self.generate_vocaloid_mid(options).await?,
+            Format::Vsq => self.generate_vsq(options).await?,
+            Format::Vsqx => self.generate_vsqx(options).await?,
+            Format::Vpr => self.generate_vpr(options).await?,
+        };
+        Ok(vec![file])
+    }
+}
+
+/// Returns whether the format options export a single-file format as a file per track.
+#[cfg(feature = "engine")]
+fn file_per_track(format: Format, options: &GenerateOptions) -> bool {
+    options
+        .format_options
+        .as_ref()
+        .is_some_and(|format_options| format_options.file_per_track(format))
+}
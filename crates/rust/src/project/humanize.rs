@@ -0,0 +1,78 @@
+//! Humanization of timing and pitch.
+use super::Project;
+use crate::model::Tick;
+use serde::{Deserialize, Serialize};
+
+/// Represents the options for [`Project::humanize`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HumanizeOptions {
+    /// Seed of the random numbers. The same seed always gives the same result.
+    pub seed: u64,
+    /// Maximum offset of note onsets, in ticks.
+    pub timing: i64,
+    /// Maximum drift of pitch, in semitones.
+    pub pitch_drift: f64,
+}
+impl Default for HumanizeOptions {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            timing: 10,
+            pitch_drift: 0.05,
+        }
+    }
+}
+
+impl Project {
+    /// Applies bounded random jitter to note onsets and pitch.
+    ///
+    /// Onsets never move before the end of the previous note or past their own end, so notes
+    /// don't overlap. Pitch drift is only applied to tracks which already have pitch data.
+    pub fn humanize(&mut self, options: HumanizeOptions) {
+        let mut random = SplitMix64(options.seed);
+        for track in &mut self.data.project_mut().tracks {
+            track.notes.sort_by_key(|note| note.tick_on);
+            let mut previous_tick_off = Tick::ZERO;
+            for note in &mut track.notes {
+                let offset = (random.next_f64() * 2.0 - 1.0) * options.timing as f64;
+                let min = previous_tick_off.min(note.tick_on);
+                note.tick_on = note
+                    .tick_on
+                    .saturating_add(offset.round() as i64)
+                    .clamp(min, note.tick_off.saturating_add(-1).max(min));
+                previous_tick_off = note.tick_off;
+            }
+
+            let Some(pitch) = &mut track.pitch else {
+                continue;
+            };
+            // A bounded random walk, which sounds like a slow drift rather than noise.
+            let mut drift = 0.0;
+            let step = options.pitch_drift / 8.0;
+            for value in pitch.values.iter_mut().flatten() {
+                drift = (drift + (random.next_f64() * 2.0 - 1.0) * step)
+                    .clamp(-options.pitch_drift, options.pitch_drift);
+                *value += drift;
+            }
+        }
+    }
+}
+
+/// A small deterministic random number generator, see <https://prng.di.unimi.it/splitmix64.c>.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
@@ -0,0 +1,18 @@
+//! Key detection of projects.
+use super::Project;
+use crate::model::KeySignature;
+
+impl Project {
+    /// Detects the key of the project from the notes of all tracks.
+    ///
+    /// See [`KeySignature::detect`] for the details.
+    pub fn detect_key(&self) -> KeySignature {
+        KeySignature::detect(
+            self.data
+                .project()
+                .tracks
+                .iter()
+                .flat_map(|track| &track.notes),
+        )
+    }
+}
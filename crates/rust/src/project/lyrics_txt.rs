@@ -0,0 +1,85 @@
+//! Plain text export and import of lyrics.
+use super::Project;
+use crate::{
+    error::{ErrorKind, Result},
+    model::Note,
+};
+
+impl Project {
+    /// Writes the lyrics of the track as plain text, one line per phrase, with the lyrics of
+    /// the notes separated by spaces.
+    ///
+    /// A phrase ends where the rest between two notes is at least `phrase_break` ticks long.
+    pub fn generate_lyrics_txt(&self, track: usize, phrase_break: i64) -> Result<String> {
+        let track = self
+            .data
+            .project()
+            .tracks
+            .get(track)
+            .ok_or(ErrorKind::IndexOutOfRange)?;
+        let mut notes = track.notes.iter().collect::<Vec<_>>();
+        notes.sort_by_key(|note| note.tick_on);
+
+        let mut text = String::new();
+        for phrase in phrases(&notes, phrase_break) {
+            let lyrics = phrase
+                .iter()
+                .map(|note| note.lyric.as_str())
+                .collect::<Vec<_>>();
+            text.push_str(&lyrics.join(" "));
+            text.push('\n');
+        }
+        Ok(text)
+    }
+
+    /// Applies lyrics written by [`Project::generate_lyrics_txt`] back onto the notes of the
+    /// track, without touching their timing.
+    ///
+    /// `phrase_break` has to be the same as the one used to generate the text. Blank lines are
+    /// ignored, and the phonemes of the notes whose lyric changed are cleared. The track is
+    /// left untouched if the number of phrases or lyrics in a phrase doesn't match the notes.
+    pub fn parse_lyrics_txt(&mut self, track: usize, text: &str, phrase_break: i64) -> Result<()> {
+        let track = self
+            .data
+            .project_mut()
+            .tracks
+            .get_mut(track)
+            .ok_or(ErrorKind::IndexOutOfRange)?;
+        track.notes.sort_by_key(|note| note.tick_on);
+
+        let lines = text
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>())
+            .filter(|lyrics| !lyrics.is_empty())
+            .collect::<Vec<_>>();
+        let lengths = phrases(&track.notes, phrase_break)
+            .map(<[_]>::len)
+            .collect::<Vec<_>>();
+        if lines.len() != lengths.len()
+            || lines
+                .iter()
+                .zip(&lengths)
+                .any(|(lyrics, &length)| lyrics.len() != length)
+        {
+            return Err(ErrorKind::LyricsMismatch.into());
+        }
+
+        for (note, lyric) in track.notes.iter_mut().zip(lines.into_iter().flatten()) {
+            if note.lyric != lyric {
+                note.lyric = lyric.to_string();
+                note.phoneme = None;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits notes sorted by position into phrases.
+fn phrases<N: std::borrow::Borrow<Note>>(
+    notes: &[N],
+    phrase_break: i64,
+) -> impl Iterator<Item = &[N]> {
+    notes.chunk_by(move |previous, next| {
+        next.borrow().tick_on - previous.borrow().tick_off < phrase_break
+    })
+}
@@ -0,0 +1,99 @@
+//! Normalization of measure prefixes.
+use super::{tempo::shift_tempos, Project};
+use crate::{
+    error::{ErrorKind, Result},
+    model::{Format, KeySignature, TimeSignature},
+};
+
+impl Project {
+    /// Returns the measure prefix that the format expects.
+    ///
+    /// VOCALOID 1-4 projects start with a pre-measure, so they need one measure of prefix;
+    /// other formats don't need any.
+    pub fn required_measure_prefix(format: Format) -> i32 {
+        match format {
+            Format::VocaloidMid | Format::Vsq | Format::Vsqx => 1,
+            _ => 0,
+        }
+    }
+
+    /// Changes the measure prefix, shifting notes, pitch, tempos, time signatures and key
+    /// signatures so that they keep their positions relative to the first non-prefix measure.
+    ///
+    /// Added measures use the first time signature. Returns
+    /// [`ErrorKind::IllegalNotePosition`] if a note would be moved before the start of the
+    /// project; the project is left untouched in that case.
+    pub fn set_measure_prefix(&mut self, measure_prefix: i32) -> Result<()> {
+        if measure_prefix < 0 {
+            return Err(ErrorKind::IllegalNotePosition.into());
+        }
+        let project = self.data.project();
+        let shift = measure_prefix - project.measure_prefix;
+        if shift == 0 {
+            return Ok(());
+        }
+        let delta = if shift > 0 {
+            let first = project
+                .time_signatures
+                .first()
+                .filter(|time_signature| time_signature.measure_position == 0)
+                .cloned()
+                .unwrap_or_default();
+            i64::from(shift) * first.ticks_per_measure()
+        } else {
+            -project.measure_to_tick(-shift).get()
+        };
+        if project
+            .tracks
+            .iter()
+            .flat_map(|track| &track.notes)
+            .any(|note| note.tick_on.checked_add(delta).is_none())
+        {
+            return Err(ErrorKind::IllegalNotePosition.into());
+        }
+
+        let project = self.data.project_mut();
+        project.measure_prefix = measure_prefix;
+        for track in &mut project.tracks {
+            track.shift(delta)?;
+        }
+        // The last tempo and time signature before the new start are moved to the start.
+        shift_tempos(&mut project.tempos, delta);
+
+        let mut time_signatures: Vec<TimeSignature> = vec![];
+        for (index, time_signature) in project.time_signatures.iter().enumerate() {
+            let measure_position = if index == 0 && time_signature.measure_position == 0 {
+                0
+            } else {
+                (time_signature.measure_position + shift).max(0)
+            };
+            if measure_position == 0 {
+                time_signatures.clear();
+            }
+            time_signatures.push(TimeSignature {
+                measure_position,
+                ..time_signature.clone()
+            });
+        }
+        project.time_signatures = time_signatures;
+
+        let mut key_signatures: Vec<KeySignature> = vec![];
+        for (index, key_signature) in project.key_signatures.iter().enumerate() {
+            let measure_position = if index == 0 && key_signature.measure_position == 0 {
+                0
+            } else {
+                (key_signature.measure_position + shift).max(0)
+            };
+            if measure_position == 0 {
+                key_signatures.clear();
+            }
+            key_signatures.push(KeySignature {
+                measure_position,
+                ..*key_signature
+            });
+        }
+        project.key_signatures = key_signatures;
+
+        Ok(())
+    }
+}
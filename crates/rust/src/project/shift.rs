@@ -0,0 +1,123 @@
+//! Shifting the whole project in time.
+use super::{tempo::shift_tempos, Project};
+use crate::error::{ErrorKind, Result};
+use serde::{Deserialize, Serialize};
+
+/// Represents what [`Project::shift_all`] moves besides notes and pitch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ShiftOptions {
+    /// Whether to move tempo changes. The tempo at the start is kept.
+    pub tempos: bool,
+    /// Whether to move time and key signatures. The ones at the start are kept there, and the
+    /// others must land on measure boundaries.
+    pub time_signatures: bool,
+}
+
+/// Represents the options for [`Project::align_to_measure`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AlignOptions {
+    /// Whether to pad the leading silence up to the next measure, instead of trimming it to
+    /// the start of the measure of the first note.
+    pub pad: bool,
+    /// Measure prefix to set after aligning, e.g. [`Project::required_measure_prefix`] of the
+    /// target format.
+    pub measure_prefix: Option<i32>,
+}
+
+impl Project {
+    /// Moves every track by ticks, which can be negative, e.g. to align the vocal with an
+    /// instrumental which starts later. See [`Track::shift`](crate::Track::shift).
+    ///
+    /// Returns [`ErrorKind::IllegalNotePosition`] if a note would start before the start, or
+    /// [`ErrorKind::IllegalTimeSignature`] if a moved time or key signature would be off a
+    /// measure boundary; the project is left untouched in that case.
+    pub fn shift_all(&mut self, ticks: i64, options: ShiftOptions) -> Result<()> {
+        if self
+            .data
+            .project()
+            .tracks
+            .iter()
+            .flat_map(|track| &track.notes)
+            .any(|note| note.tick_on.checked_add(ticks).is_none())
+        {
+            return Err(ErrorKind::IllegalNotePosition.into());
+        }
+        if options.time_signatures {
+            let mut time_signatures = self.time_signature_ticks();
+            shift_signatures(&mut time_signatures, ticks);
+            let mut key_signatures = self.key_signature_ticks();
+            shift_signatures(&mut key_signatures, ticks);
+            self.rebuild_signatures(time_signatures, key_signatures)?;
+        }
+
+        let project = self.data.project_mut();
+        for track in &mut project.tracks {
+            track.shift(ticks)?;
+        }
+        if options.tempos {
+            shift_tempos(&mut project.tempos, ticks);
+        }
+
+        Ok(())
+    }
+
+    /// Moves every track so that the first note starts at a measure boundary, which VSQ-family
+    /// formats require, and returns the moved ticks. Tempo changes are moved with the notes.
+    ///
+    /// Projects without notes are left as they are, except for
+    /// [`AlignOptions::measure_prefix`].
+    pub fn align_to_measure(&mut self, options: AlignOptions) -> Result<i64> {
+        let project = self.data.project();
+        let first = project
+            .tracks
+            .iter()
+            .flat_map(|track| &track.notes)
+            .map(|note| note.tick_on)
+            .min();
+        let ticks = match first {
+            Some(first) => {
+                let measure = project.tick_to_measure(first);
+                let start = project.measure_to_tick(measure);
+                if start == first {
+                    0
+                } else if options.pad {
+                    project.measure_to_tick(measure + 1) - first
+                } else {
+                    start - first
+                }
+            }
+            None => 0,
+        };
+        if ticks != 0 {
+            self.shift_all(
+                ticks,
+                ShiftOptions {
+                    tempos: true,
+                    time_signatures: false,
+                },
+            )?;
+        }
+        if let Some(measure_prefix) = options.measure_prefix {
+            self.set_measure_prefix(measure_prefix)?;
+        }
+
+        Ok(ticks)
+    }
+}
+
+/// Moves time or key signatures by ticks, keeping the ones at the start. If they are moved
+/// earlier, the last one before the start is moved to the start.
+fn shift_signatures<T>(entries: &mut Vec<(i64, T)>, ticks: i64) {
+    for (tick, _) in entries.iter_mut() {
+        if *tick > 0 {
+            *tick = tick.saturating_add(ticks).max(0);
+        }
+    }
+    let start = entries
+        .iter()
+        .rposition(|(tick, _)| *tick == 0)
+        .unwrap_or(0);
+    entries.drain(..start);
+}
@@ -0,0 +1,179 @@
+//! Conversion to and from a flat JSON list of notes, timed in seconds.
+use super::{tempo::TempoMap, Project};
+use crate::{
+    error::{ErrorKind, IllegalFile, Result},
+    model::{
+        parse_key_name, Key, Note, Tempo, Tick, TimeSignature, Track, UfData, UfProject,
+        TICKS_PER_BEAT,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+/// Tempo of projects created from simple JSON, which doesn't have a tempo map.
+const SIMPLE_JSON_BPM: i32 = 120;
+
+/// Represents a project in simple JSON, used by [`Project::to_simple_json`] and
+/// [`Project::from_simple_json`].
+///
+/// ```json
+/// {
+///   "name": "Project",
+///   "tracks": [
+///     {
+///       "name": "Vocal",
+///       "notes": [{ "start": 0.5, "end": 1.0, "pitch": "C4", "lyric": "あ" }]
+///     }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct SimpleProject {
+    /// Name of the project.
+    #[serde(default)]
+    pub name: String,
+    /// Tracks of the project.
+    pub tracks: Vec<SimpleTrack>,
+}
+
+/// Represents a track in simple JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct SimpleTrack {
+    /// Name of the track.
+    #[serde(default)]
+    pub name: String,
+    /// Notes of the track, sorted by their start.
+    pub notes: Vec<SimpleNote>,
+}
+
+/// Represents a note in simple JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct SimpleNote {
+    /// Start of the note in seconds.
+    pub start: f64,
+    /// End of the note in seconds.
+    pub end: f64,
+    /// Name of the key, where `C4` is the middle C (60). Sharps are written as `#`.
+    pub pitch: String,
+    /// Lyric of the note.
+    pub lyric: String,
+}
+
+impl Project {
+    /// Converts the project to simple JSON, a flat list of notes with their times in seconds.
+    /// See [`SimpleProject`] for the schema.
+    ///
+    /// Tempos, time signatures, pitch and phonemes are not kept.
+    pub fn to_simple_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self.to_simple_project()).map_err(anyhow::Error::from)?)
+    }
+
+    /// Creates a project from simple JSON. See [`SimpleProject`] for the schema.
+    ///
+    /// The project is in 120 BPM and 4/4, with notes placed at the nearest ticks.
+    pub fn from_simple_json(json: &str) -> Result<Self> {
+        let simple: SimpleProject = serde_json::from_str(json)
+            .map_err(|_| crate::Error::from(IllegalFile::IllegalSimpleJsonFile))?;
+        Self::from_simple_project(simple)
+    }
+
+    /// Converts the project to [`SimpleProject`].
+    pub fn to_simple_project(&self) -> SimpleProject {
+        let project = self.data.project();
+        let tempo_map = TempoMap::new(&project.tempos);
+        let tracks = project
+            .tracks
+            .iter()
+            .map(|track| {
+                let mut notes = track.notes.iter().collect::<Vec<_>>();
+                notes.sort_by_key(|note| note.tick_on);
+                SimpleTrack {
+                    name: track.name.clone(),
+                    notes: notes
+                        .into_iter()
+                        .map(|note| SimpleNote {
+                            start: tempo_map.tick_to_seconds(note.tick_on.get()),
+                            end: tempo_map.tick_to_seconds(note.tick_off.get()),
+                            pitch: note.key.name(),
+                            lyric: note.lyric.clone(),
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        SimpleProject {
+            name: project.name.clone(),
+            tracks,
+        }
+    }
+
+    /// Creates a project from [`SimpleProject`]. See [`Project::from_simple_json`].
+    pub fn from_simple_project(simple: SimpleProject) -> Result<Self> {
+        let to_tick = |seconds: f64| {
+            (seconds * f64::from(SIMPLE_JSON_BPM) / 60.0 * TICKS_PER_BEAT as f64).round() as i64
+        };
+        let tracks = simple
+            .tracks
+            .into_iter()
+            .map(|track| {
+                let notes = track
+                    .notes
+                    .into_iter()
+                    .map(|note| {
+                        if !(note.start.is_finite() && note.end.is_finite() && note.start >= 0.0) {
+                            return Err(ErrorKind::IllegalNotePosition.into());
+                        }
+                        let key =
+                            parse_key_name(&note.pitch)
+                                .and_then(Key::new)
+                                .ok_or_else(|| {
+                                    crate::Error::from(IllegalFile::IllegalSimpleJsonFile)
+                                })?;
+                        let (Some(tick_on), Some(tick_off)) =
+                            (Tick::new(to_tick(note.start)), Tick::new(to_tick(note.end)))
+                        else {
+                            return Err(ErrorKind::IllegalNotePosition.into());
+                        };
+                        if tick_off <= tick_on {
+                            return Err(ErrorKind::IllegalNotePosition.into());
+                        }
+                        Ok(Note {
+                            key,
+                            tick_on,
+                            tick_off,
+                            lyric: note.lyric,
+                            phoneme: None,
+                            phoneme_segments: None,
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Track {
+                    name: track.name,
+                    notes,
+                    pitch: None,
+                    muted: None,
+                    solo: None,
+                    order: None,
+                    color: None,
+                    voice: None,
+                    parameters: None,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self::new(UfData::new(UfProject {
+            name: simple.name,
+            tracks,
+            time_signatures: vec![TimeSignature::default()],
+            tempos: vec![Tempo {
+                tick_position: Tick::ZERO,
+                bpm: SIMPLE_JSON_BPM,
+            }],
+            measure_prefix: 0,
+            key_signatures: vec![],
+        })))
+    }
+}
@@ -0,0 +1,99 @@
+//! Human-readable summaries of projects.
+use super::{tempo::TempoMap, Project};
+use crate::model::TimeSignature;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Represents an overview of a project, returned by [`Project::summary`].
+///
+/// This is displayed like `3 tracks, 1,204 notes, 120–128 BPM, 4/4, 3:52`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectSummary {
+    /// The number of tracks.
+    pub tracks: usize,
+    /// The number of notes of all tracks.
+    pub notes: usize,
+    /// The lowest and the highest BPM.
+    pub bpm: (i32, i32),
+    /// The first time signature.
+    pub time_signature: TimeSignature,
+    /// The end of the last note, in seconds.
+    pub duration: f64,
+}
+
+impl fmt::Display for ProjectSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = |count: usize| if count == 1 { "" } else { "s" };
+        write!(
+            f,
+            "{} track{}, {} note{}, ",
+            thousands(self.tracks),
+            plural(self.tracks),
+            thousands(self.notes),
+            plural(self.notes)
+        )?;
+        match self.bpm {
+            (min, max) if min == max => write!(f, "{} BPM, ", min)?,
+            (min, max) => write!(f, "{}–{} BPM, ", min, max)?,
+        }
+        write!(
+            f,
+            "{}/{}, ",
+            self.time_signature.numerator, self.time_signature.denominator
+        )?;
+        let seconds = self.duration.round() as u64;
+        if seconds >= 3600 {
+            write!(
+                f,
+                "{}:{:02}:{:02}",
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60
+            )
+        } else {
+            write!(f, "{}:{:02}", seconds / 60, seconds % 60)
+        }
+    }
+}
+
+/// Formats the number with commas between thousands, e.g. `1,204`.
+fn thousands(number: usize) -> String {
+    let digits = number.to_string();
+    let mut formatted = String::with_capacity(digits.len() * 4 / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+impl Project {
+    /// Returns an overview of the project, e.g. for logs. Projects without tempos are
+    /// summarized as 120 BPM, like editors do.
+    pub fn summary(&self) -> ProjectSummary {
+        let project = self.data.project();
+        let notes = project.tracks.iter().flat_map(|track| &track.notes);
+        let end = notes
+            .clone()
+            .map(|note| note.tick_off)
+            .max()
+            .unwrap_or_default();
+        let bpms = project.tempos.iter().map(|tempo| tempo.bpm);
+        ProjectSummary {
+            tracks: project.tracks.len(),
+            notes: notes.count(),
+            bpm: (bpms.clone().min().unwrap_or(120), bpms.max().unwrap_or(120)),
+            time_signature: project.time_signatures.first().cloned().unwrap_or_default(),
+            duration: TempoMap::new(&project.tempos).tick_to_seconds(end.get()),
+        }
+    }
+}
+
+impl fmt::Display for Project {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.data.project().name, self.summary())
+    }
+}
@@ -0,0 +1,239 @@
+//! Editing utilities of tempo maps.
+use super::Project;
+use crate::{
+    error::{ErrorKind, Result},
+    model::{self, KeySignature, Tempo, Tick, TICKS_PER_BEAT},
+    native::standard_mid,
+};
+
+impl Project {
+    /// Inserts a tempo change, replacing the one at the same tick.
+    pub fn insert_tempo(&mut self, tick: Tick, bpm: i32) -> Result<()> {
+        if bpm <= 0 {
+            return Err(ErrorKind::IllegalTempo.into());
+        }
+        let tempos = &mut self.data.project_mut().tempos;
+        let tempo = Tempo {
+            tick_position: tick,
+            bpm,
+        };
+        match tempos.binary_search_by_key(&tick, |tempo| tempo.tick_position) {
+            Ok(index) => tempos[index] = tempo,
+            Err(index) => tempos.insert(index, tempo),
+        }
+
+        Ok(())
+    }
+
+    /// Removes tempo changes which don't change the tempo.
+    pub fn remove_redundant_tempos(&mut self) {
+        self.data
+            .project_mut()
+            .tempos
+            .dedup_by(|next, previous| next.bpm == previous.bpm);
+    }
+
+    /// Replaces the tempo map with a single tempo, re-timing notes and pitch so that they are
+    /// played at the same time as before.
+    ///
+    /// Time signatures are kept as is, so notes may no longer be aligned to measures.
+    pub fn flatten_tempo(&mut self, bpm: i32) -> Result<()> {
+        if bpm <= 0 {
+            return Err(ErrorKind::IllegalTempo.into());
+        }
+        let project = self.data.project_mut();
+        let tempo_map = TempoMap::new(&project.tempos);
+        let retime = |tick: i64| {
+            (tempo_map.tick_to_seconds(tick) * f64::from(bpm) / 60.0 * TICKS_PER_BEAT as f64)
+                .round() as i64
+        };
+        for track in &mut project.tracks {
+            for note in &mut track.notes {
+                note.tick_on = note.tick_on.map(retime);
+                note.tick_off = note.tick_off.map(retime);
+            }
+            if let Some(pitch) = &mut track.pitch {
+                for tick in &mut pitch.ticks {
+                    *tick = retime(i64::from(*tick)) as i32;
+                }
+            }
+        }
+        project.tempos = vec![Tempo {
+            tick_position: Tick::ZERO,
+            bpm,
+        }];
+
+        Ok(())
+    }
+
+    /// Replaces the tempo map and time signatures with those of a Standard MIDI file, re-timing
+    /// notes and pitch so that they are played at the same time as before.
+    ///
+    /// Key signatures are replaced with those of the file too, if it has any. Otherwise they are
+    /// re-timed, and placed at the start of the measure containing their new tick.
+    ///
+    /// This is useful to match a vocal track with an existing instrumental session.
+    pub fn import_timing_from_midi(&mut self, data: &[u8]) -> Result<()> {
+        let (tempos, time_signatures, key_signatures) = standard_mid::read_timing(data)?;
+        let retimed_key_signatures = self.key_signature_ticks();
+        let project = self.data.project_mut();
+        let old_tempo_map = TempoMap::new(&project.tempos);
+        let new_tempo_map = TempoMap::new(&tempos);
+        let retime = |tick: i64| {
+            new_tempo_map
+                .seconds_to_tick(old_tempo_map.tick_to_seconds(tick))
+                .round() as i64
+        };
+        for track in &mut project.tracks {
+            for note in &mut track.notes {
+                note.tick_on = note.tick_on.map(retime);
+                note.tick_off = note.tick_off.map(retime);
+            }
+            if let Some(pitch) = &mut track.pitch {
+                for tick in &mut pitch.ticks {
+                    *tick = retime(i64::from(*tick)) as i32;
+                }
+            }
+        }
+        project.key_signatures = if key_signatures.is_empty() {
+            let mut moved: Vec<KeySignature> = vec![];
+            for (tick, mut key_signature) in retimed_key_signatures {
+                key_signature.measure_position =
+                    model::tick_to_measure(&time_signatures, retime(tick));
+                moved.retain(|moved| moved.measure_position != key_signature.measure_position);
+                moved.push(key_signature);
+            }
+            moved
+        } else {
+            key_signatures
+        };
+        project.tempos = tempos;
+        project.time_signatures = time_signatures;
+
+        Ok(())
+    }
+
+    /// Stretches notes, pitch, tempo changes and time and key signatures by the factor, keeping
+    /// the BPM.
+    ///
+    /// For example, `2.0` makes a half-time version of the project. Returns
+    /// [`ErrorKind::IllegalTimeSignature`] if a time or key signature would be off a measure
+    /// boundary; the project is left untouched in that case.
+    pub fn scale_time(&mut self, factor: f64) -> Result<()> {
+        if !(factor.is_finite() && factor > 0.0) {
+            return Err(ErrorKind::IllegalNotePosition.into());
+        }
+        let scale = |tick: i64| (tick as f64 * factor).round() as i64;
+        let time_signatures = self
+            .time_signature_ticks()
+            .into_iter()
+            .map(|(tick, time_signature)| (scale(tick), time_signature))
+            .collect();
+        let key_signatures = self
+            .key_signature_ticks()
+            .into_iter()
+            .map(|(tick, key_signature)| (scale(tick), key_signature))
+            .collect();
+        self.rebuild_signatures(time_signatures, key_signatures)?;
+
+        let project = self.data.project_mut();
+        for track in &mut project.tracks {
+            for note in &mut track.notes {
+                note.tick_on = note.tick_on.map(scale);
+                note.tick_off = note.tick_off.map(scale);
+            }
+            if let Some(pitch) = &mut track.pitch {
+                for tick in &mut pitch.ticks {
+                    *tick = scale(i64::from(*tick)) as i32;
+                }
+            }
+        }
+        for tempo in &mut project.tempos {
+            tempo.tick_position = tempo.tick_position.map(scale);
+        }
+
+        Ok(())
+    }
+
+    /// Multiplies every BPM by the factor, keeping the musical positions.
+    ///
+    /// BPMs are rounded, since they are stored as integers.
+    pub fn scale_tempo(&mut self, factor: f64) -> Result<()> {
+        if !(factor.is_finite() && factor > 0.0) {
+            return Err(ErrorKind::IllegalTempo.into());
+        }
+        for tempo in &mut self.data.project_mut().tempos {
+            tempo.bpm = ((f64::from(tempo.bpm) * factor).round() as i32).max(1);
+        }
+
+        Ok(())
+    }
+}
+
+/// Moves tempo changes by ticks, keeping the tempo at the start. If they are moved earlier, the
+/// last one before the start is moved to the start.
+pub(super) fn shift_tempos(tempos: &mut Vec<Tempo>, ticks: i64) {
+    for (index, tempo) in tempos.iter_mut().enumerate() {
+        if index > 0 || tempo.tick_position > Tick::ZERO {
+            tempo.tick_position = tempo.tick_position.saturating_add(ticks);
+        }
+    }
+    let start = tempos
+        .iter()
+        .rposition(|tempo| tempo.tick_position == Tick::ZERO)
+        .unwrap_or(0);
+    tempos.drain(..start);
+}
+
+/// Converts tick positions to seconds, using a tempo map.
+pub(crate) struct TempoMap {
+    /// Tick position, seconds at the tick, and bpm of each tempo.
+    segments: Vec<(i64, f64, f64)>,
+}
+
+impl TempoMap {
+    /// Creates a tempo map. `tempos` must be sorted by tick position.
+    pub(crate) fn new(tempos: &[Tempo]) -> Self {
+        let mut segments: Vec<(i64, f64, f64)> = vec![];
+        for tempo in tempos {
+            let bpm = f64::from(tempo.bpm.max(1));
+            let seconds = match segments.last() {
+                Some(&(tick, seconds, previous_bpm)) => {
+                    seconds + ticks_to_seconds(tempo.tick_position.get() - tick, previous_bpm)
+                }
+                None => ticks_to_seconds(tempo.tick_position.get(), 120.0),
+            };
+            segments.push((tempo.tick_position.get(), seconds, bpm));
+        }
+        if segments.first().map(|(tick, _, _)| *tick) != Some(0) {
+            segments.insert(0, (0, 0.0, 120.0));
+        }
+
+        Self { segments }
+    }
+
+    /// Returns the time of the tick in seconds.
+    pub(crate) fn tick_to_seconds(&self, tick: i64) -> f64 {
+        let index = self
+            .segments
+            .partition_point(|(segment_tick, _, _)| *segment_tick <= tick)
+            .max(1);
+        let (segment_tick, seconds, bpm) = self.segments[index - 1];
+        seconds + ticks_to_seconds(tick - segment_tick, bpm)
+    }
+
+    /// Returns the tick at the time in seconds, which is the inverse of
+    /// [`TempoMap::tick_to_seconds`].
+    pub(crate) fn seconds_to_tick(&self, seconds: f64) -> f64 {
+        let index = self
+            .segments
+            .partition_point(|(_, segment_seconds, _)| *segment_seconds <= seconds)
+            .max(1);
+        let (segment_tick, segment_seconds, bpm) = self.segments[index - 1];
+        segment_tick as f64 + (seconds - segment_seconds) * bpm / 60.0 * TICKS_PER_BEAT as f64
+    }
+}
+
+fn ticks_to_seconds(ticks: i64, bpm: f64) -> f64 {
+    ticks as f64 / TICKS_PER_BEAT as f64 * 60.0 / bpm
+}
@@ -0,0 +1,171 @@
+//! Editing utilities of time signatures.
+use super::Project;
+use crate::{
+    error::{ErrorKind, Result},
+    model::{self, KeySignature, TimeSignature},
+};
+
+impl Project {
+    /// Inserts a time signature, replacing the one at the same measure.
+    ///
+    /// Measure positions of the following time and key signatures are re-derived, so that they
+    /// keep their tick positions. Returns [`ErrorKind::IllegalTimeSignature`] if any of them
+    /// doesn't land on a measure boundary anymore; the project is left untouched in that case.
+    pub fn insert_time_signature(&mut self, time_signature: TimeSignature) -> Result<()> {
+        let tick = self
+            .data
+            .project()
+            .measure_to_tick(time_signature.measure_position)
+            .get();
+        let mut entries = self.time_signature_ticks();
+        entries.retain(|(entry_tick, _)| *entry_tick != tick);
+        entries.push((tick, time_signature));
+        let key_signatures = self.key_signature_ticks();
+        self.rebuild_signatures(entries, key_signatures)
+    }
+
+    /// Removes the time signature at the measure, and returns it.
+    ///
+    /// See [`Project::insert_time_signature`] for how the following time and key signatures
+    /// are handled.
+    pub fn remove_time_signature(&mut self, measure: i32) -> Result<Option<TimeSignature>> {
+        let mut entries = self.time_signature_ticks();
+        let Some(index) = entries
+            .iter()
+            .position(|(_, time_signature)| time_signature.measure_position == measure)
+        else {
+            return Ok(None);
+        };
+        let (_, removed) = entries.remove(index);
+        let key_signatures = self.key_signature_ticks();
+        self.rebuild_signatures(entries, key_signatures)?;
+        Ok(Some(removed))
+    }
+
+    /// Moves the time signature at the measure `from` to the measure `to`.
+    ///
+    /// Both measures are counted in the project before the move. See
+    /// [`Project::insert_time_signature`] for how the following time and key signatures are
+    /// handled.
+    pub fn move_time_signature(&mut self, from: i32, to: i32) -> Result<()> {
+        let tick = self.data.project().measure_to_tick(to).get();
+        let mut entries = self.time_signature_ticks();
+        let index = entries
+            .iter()
+            .position(|(_, time_signature)| time_signature.measure_position == from)
+            .ok_or(ErrorKind::IllegalTimeSignature)?;
+        let (_, time_signature) = entries.remove(index);
+        entries.retain(|(entry_tick, _)| *entry_tick != tick);
+        entries.push((tick, time_signature));
+        let key_signatures = self.key_signature_ticks();
+        self.rebuild_signatures(entries, key_signatures)
+    }
+
+    /// Checks that the time signatures start at the first measure, are sorted without
+    /// duplicates, and have valid values.
+    pub fn validate_time_signatures(&self) -> Result<()> {
+        let time_signatures = &self.data.project().time_signatures;
+        if time_signatures
+            .first()
+            .map(|time_signature| time_signature.measure_position)
+            != Some(0)
+        {
+            return Err(ErrorKind::IllegalTimeSignature.into());
+        }
+        let sorted = time_signatures
+            .windows(2)
+            .all(|pair| pair[0].measure_position < pair[1].measure_position);
+        if !sorted || !time_signatures.iter().all(TimeSignature::is_valid) {
+            return Err(ErrorKind::IllegalTimeSignature.into());
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn time_signature_ticks(&self) -> Vec<(i64, TimeSignature)> {
+        let project = self.data.project();
+        project
+            .time_signatures
+            .iter()
+            .map(|time_signature| {
+                (
+                    project
+                        .measure_to_tick(time_signature.measure_position)
+                        .get(),
+                    time_signature.clone(),
+                )
+            })
+            .collect()
+    }
+
+    pub(super) fn key_signature_ticks(&self) -> Vec<(i64, KeySignature)> {
+        let project = self.data.project();
+        project
+            .key_signatures
+            .iter()
+            .map(|key_signature| {
+                (
+                    project
+                        .measure_to_tick(key_signature.measure_position)
+                        .get(),
+                    *key_signature,
+                )
+            })
+            .collect()
+    }
+
+    /// Replaces the time signatures like [`Project::rebuild_time_signatures`], and then the key
+    /// signatures, deriving their measure positions from the ticks in the new time signatures.
+    /// Later key signatures win when several are at the same tick.
+    ///
+    /// Returns [`ErrorKind::IllegalTimeSignature`] if a time or key signature is off a measure
+    /// boundary; the project is left untouched in that case.
+    pub(super) fn rebuild_signatures(
+        &mut self,
+        time_signatures: Vec<(i64, TimeSignature)>,
+        mut key_signatures: Vec<(i64, KeySignature)>,
+    ) -> Result<()> {
+        let previous = self.data.project().time_signatures.clone();
+        self.rebuild_time_signatures(time_signatures)?;
+
+        let project = self.data.project_mut();
+        key_signatures.sort_by_key(|(tick, _)| *tick);
+        let mut moved: Vec<KeySignature> = Vec::with_capacity(key_signatures.len());
+        for (tick, mut key_signature) in key_signatures {
+            let measure = model::tick_to_measure(&project.time_signatures, tick);
+            if tick < 0 || model::measure_to_tick(&project.time_signatures, measure) != tick {
+                project.time_signatures = previous;
+                return Err(ErrorKind::IllegalTimeSignature.into());
+            }
+            key_signature.measure_position = measure;
+            moved.retain(|key_signature| key_signature.measure_position != measure);
+            moved.push(key_signature);
+        }
+        project.key_signatures = moved;
+
+        Ok(())
+    }
+
+    /// Replaces the time signatures, deriving their measure positions from the ticks.
+    pub(super) fn rebuild_time_signatures(
+        &mut self,
+        mut entries: Vec<(i64, TimeSignature)>,
+    ) -> Result<()> {
+        entries.sort_by_key(|(tick, _)| *tick);
+        let mut time_signatures: Vec<TimeSignature> = Vec::with_capacity(entries.len());
+        for (tick, mut time_signature) in entries {
+            if tick < 0 || !time_signature.is_valid() {
+                return Err(ErrorKind::IllegalTimeSignature.into());
+            }
+            let measure = model::tick_to_measure(&time_signatures, tick);
+            if model::measure_to_tick(&time_signatures, measure) != tick {
+                return Err(ErrorKind::IllegalTimeSignature.into());
+            }
+            time_signature.measure_position = measure;
+            time_signatures.push(time_signature);
+        }
+        self.data.project_mut().time_signatures = time_signatures;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+//! Normalization of track names.
+use super::Project;
+use crate::model::{Format, Track};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Represents the options for [`Project::normalize_track_names`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TrackNameOptions {
+    /// Format which the names are for. Characters which the format can't store are removed,
+    /// e.g. characters outside Shift_JIS for UST and VOCALOID 1/2.
+    pub format: Option<Format>,
+    /// Names tracks without names after their first lyrics, up to this number of characters.
+    /// They are named `Track n` if this is `None`, or if they don't have lyrics.
+    pub lyrics_name_length: Option<usize>,
+}
+
+impl Project {
+    /// Gives every track a unique name which the target format can store.
+    ///
+    /// Control characters are removed from names, and tracks without names are named by
+    /// [`TrackNameOptions::lyrics_name_length`]. Duplicated names get a number, e.g.
+    /// `Track 1 (2)`, in the order of tracks.
+    pub fn normalize_track_names(&mut self, options: &TrackNameOptions) {
+        let tracks = &mut self.data.project_mut().tracks;
+        let names = tracks
+            .iter()
+            .enumerate()
+            .map(|(index, track)| {
+                let name = clean_track_name(&track.name, options.format);
+                if !name.is_empty() {
+                    return name;
+                }
+                options
+                    .lyrics_name_length
+                    .map(|length| clean_track_name(&lyrics_name(track, length), options.format))
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or_else(|| format!("Track {}", index + 1))
+            })
+            .collect::<Vec<_>>();
+
+        let mut used = HashSet::new();
+        for (track, name) in tracks.iter_mut().zip(&names) {
+            // Numbered names don't take the names of other tracks, e.g. `Track 1 (2)`.
+            let is_free = |name: &String| !used.contains(name) && !names.contains(name);
+            let name = if used.contains(name) {
+                (2..)
+                    .map(|number| format!("{} ({})", name, number))
+                    .find(is_free)
+                    .expect("Ran out of numbers")
+            } else {
+                name.clone()
+            };
+            used.insert(name.clone());
+            track.name = name;
+        }
+    }
+}
+
+/// Removes characters which the format can't store from the name.
+fn clean_track_name(name: &str, format: Option<Format>) -> String {
+    name.chars()
+        .filter(|c| !c.is_control())
+        .filter(|c| format.is_none_or(|format| format.can_encode(c.encode_utf8(&mut [0; 4]))))
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Joins the lyrics of the first notes, up to `length` characters.
+fn lyrics_name(track: &Track, length: usize) -> String {
+    let mut notes = track.notes.iter().collect::<Vec<_>>();
+    notes.sort_by_key(|note| note.tick_on);
+    notes
+        .iter()
+        .flat_map(|note| note.lyric.trim().chars())
+        .take(length)
+        .collect()
+}
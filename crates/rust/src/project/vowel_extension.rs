@@ -0,0 +1,29 @@
+//! Expansion and collapse of long vowel marks, independent of Japanese lyrics conversion.
+use super::Project;
+use crate::model::{collapse_vowel_extensions, expand_vowel_extensions};
+
+impl Project {
+    /// Replaces long vowel marks ("ー") with the vowel of the previous note, e.g. "か", "ー"
+    /// becomes "か", "あ".
+    ///
+    /// The vowel follows the style of the previous lyric: "a" after romaji, and "a あ" after
+    /// VCV lyrics like "a か".
+    pub fn expand_vowel_extensions(&mut self) {
+        for track in &mut self.data.project_mut().tracks {
+            track.notes.sort_by_key(|note| note.tick_on);
+            expand_vowel_extensions(&mut track.notes);
+        }
+    }
+
+    /// Replaces notes which only repeat the vowel of the previous note with long vowel marks,
+    /// e.g. "か", "あ" becomes "か", "ー". This is the reverse of
+    /// [`Project::expand_vowel_extensions`].
+    ///
+    /// Notes after a rest are kept, since they start a new attack.
+    pub fn collapse_vowel_extensions(&mut self) {
+        for track in &mut self.data.project_mut().tracks {
+            track.notes.sort_by_key(|note| note.tick_on);
+            collapse_vowel_extensions(&mut track.notes);
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! Conversions between [`Project`] and [`midly::Smf`].
+//!
+//! These use the same mapping as [`Format::StandardMid`], but don't go through bytes and the
+//! JS engine, so they are cheap enough to use from MIDI tooling directly.
+use crate::{
+    error::{Error, ErrorKind, Result},
+    model::{Format, GenerateOptions, ParseOptions},
+    native::{
+        smf::{self, Event, EventKind},
+        standard_mid,
+    },
+    project::Project,
+};
+use anyhow::anyhow;
+use midly::{
+    num::{u14, u15, u24, u28, u4, u7},
+    Header, MetaMessage, MidiMessage, PitchBend, Smf, Timing, TrackEvent, TrackEventKind,
+};
+use std::borrow::Cow;
+
+/// Maximum delta time of an event, which is a 28-bit variable-length quantity.
+const MAX_DELTA: u64 = (1 << 28) - 1;
+
+/// Converts the project into a format 1 SMF, as [`Format::StandardMid`] is generated with the
+/// default options: a conductor track for tempos, time signatures and key signatures, followed
+/// by one track per [`Track`](crate::Track).
+///
+/// Lyrics are written as UTF-8 lyric events just before their notes. Fails with
+/// [`ErrorKind::IllegalTimeSignature`] if a time signature has a numerator over 255, and with
+/// [`ErrorKind::IllegalTempo`] if a tempo isn't positive or is slower than 4 BPM.
+impl<'a> TryFrom<&'a Project> for Smf<'a> {
+    type Error = Error;
+
+    fn try_from(project: &'a Project) -> Result<Self> {
+        let options = GenerateOptions::default();
+        // The data is only checked instead of prepared, since the events borrow the lyrics.
+        options.validate(Format::StandardMid, &project.data)?;
+        let smf = standard_mid::to_smf(&project.data, &options)?;
+        let tracks = smf
+            .tracks
+            .into_iter()
+            .map(to_track_events)
+            .collect::<Result<_>>()?;
+
+        Ok(Smf {
+            header: Header::new(
+                midly::Format::Parallel,
+                Timing::Metrical(u15::new(smf.division)),
+            ),
+            tracks,
+        })
+    }
+}
+
+/// Encodes the ticks of the events, which are sorted by tick, as deltas.
+fn to_track_events(events: Vec<Event<'_>>) -> Result<Vec<TrackEvent<'_>>> {
+    let mut previous_tick = 0;
+    let mut track = Vec::with_capacity(events.len() + 1);
+    for event in events {
+        let delta = event.tick.saturating_sub(previous_tick);
+        if delta > MAX_DELTA {
+            return Err(ErrorKind::IllegalNotePosition.into());
+        }
+        track.push(TrackEvent {
+            delta: u28::new(delta as u32),
+            kind: to_track_event_kind(event.kind)?,
+        });
+        previous_tick = previous_tick.max(event.tick);
+    }
+    track.push(TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+    });
+
+    Ok(track)
+}
+
+fn to_track_event_kind(kind: EventKind<'_>) -> Result<TrackEventKind<'_>> {
+    let midi = |channel: u8, message| TrackEventKind::Midi {
+        channel: u4::new(channel),
+        message,
+    };
+    let kind = match kind {
+        EventKind::NoteOn {
+            channel,
+            key,
+            velocity,
+        } => midi(
+            channel,
+            MidiMessage::NoteOn {
+                key: u7::new(key),
+                vel: u7::new(velocity),
+            },
+        ),
+        EventKind::NoteOff {
+            channel,
+            key,
+            velocity,
+        } => midi(
+            channel,
+            MidiMessage::NoteOff {
+                key: u7::new(key),
+                vel: u7::new(velocity),
+            },
+        ),
+        EventKind::PitchBend { channel, value } => midi(
+            channel,
+            MidiMessage::PitchBend {
+                bend: PitchBend(u14::new(value)),
+            },
+        ),
+        EventKind::ControlChange {
+            channel,
+            controller,
+            value,
+        } => midi(
+            channel,
+            MidiMessage::Controller {
+                controller: u7::new(controller),
+                value: u7::new(value),
+            },
+        ),
+        EventKind::Meta { kind, data } => TrackEventKind::Meta(to_meta_message(kind, data)?),
+        EventKind::Other(_) => return Err(anyhow!("Unexpected raw MIDI event").into()),
+    };
+    Ok(kind)
+}
+
+fn to_meta_message(kind: u8, data: Cow<'_, [u8]>) -> Result<MetaMessage<'_>> {
+    let message = match (kind, &*data) {
+        (smf::META_TEMPO, &[a, b, c]) => {
+            MetaMessage::Tempo(u24::new(u32::from_be_bytes([0, a, b, c])))
+        }
+        (smf::META_TIME_SIGNATURE, &[numerator, denominator, clocks, notes]) => {
+            MetaMessage::TimeSignature(numerator, denominator, clocks, notes)
+        }
+        (smf::META_KEY_SIGNATURE, &[fifths, mode]) => {
+            MetaMessage::KeySignature(fifths as i8, mode != 0)
+        }
+        _ => {
+            // Texts are always borrowed from the project.
+            let Cow::Borrowed(data) = data else {
+                return Err(anyhow!("Unexpected meta event: {}", kind).into());
+            };
+            match kind {
+                smf::META_TRACK_NAME => MetaMessage::TrackName(data),
+                smf::META_LYRIC => MetaMessage::Lyric(data),
+                _ => MetaMessage::Unknown(kind, data),
+            }
+        }
+    };
+    Ok(message)
+}
+
+/// Reads a metrical SMF of any format, as [`Format::StandardMid`] is parsed with the default
+/// options. See [`Project::from_smf`] to pass the options.
+impl TryFrom<&Smf<'_>> for Project {
+    type Error = Error;
+
+    fn try_from(smf: &Smf<'_>) -> Result<Self> {
+        Self::from_smf(smf, ParseOptions::default())
+    }
+}
+
+impl Project {
+    /// Reads a metrical SMF of any format, as [`Format::StandardMid`] is parsed with the
+    /// options, e.g. notes without a lyric event get [`ParseOptions::default_lyric`].
+    ///
+    /// The project has no name, and tracks without notes are dropped. Fails with
+    /// [`ErrorKind::EmptyProject`] if no track has notes, and with
+    /// [`ErrorKind::UnsupportedFileFormat`] for SMPTE timing.
+    pub fn from_smf(smf: &Smf<'_>, options: ParseOptions) -> Result<Self> {
+        options.validate_fallback_timing()?;
+        let Timing::Metrical(division) = smf.header.timing else {
+            return Err(ErrorKind::UnsupportedFileFormat.into());
+        };
+        let format = match smf.header.format {
+            midly::Format::SingleTrack => 0,
+            midly::Format::Parallel => 1,
+            midly::Format::Sequential => 2,
+        };
+        let smf = smf::Smf {
+            format,
+            division: division.as_int().max(1),
+            tracks: smf
+                .tracks
+                .iter()
+                .map(|events| from_track_events(events))
+                .collect(),
+        };
+        standard_mid::from_smf(&smf, String::new(), &options).map(Self::new)
+    }
+}
+
+/// Converts the events which the mapping reads, with absolute ticks.
+fn from_track_events<'a>(events: &[TrackEvent<'a>]) -> Vec<Event<'a>> {
+    let mut tick = 0;
+    let mut converted = Vec::with_capacity(events.len());
+    for event in events {
+        tick += u64::from(event.delta.as_int());
+        let kind = match event.kind {
+            TrackEventKind::Midi { channel, message } => {
+                let channel = channel.as_int();
+                match message {
+                    MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => EventKind::NoteOn {
+                        channel,
+                        key: key.as_int(),
+                        velocity: vel.as_int(),
+                    },
+                    MidiMessage::NoteOn { key, vel } | MidiMessage::NoteOff { key, vel } => {
+                        EventKind::NoteOff {
+                            channel,
+                            key: key.as_int(),
+                            velocity: vel.as_int(),
+                        }
+                    }
+                    MidiMessage::PitchBend { bend } => EventKind::PitchBend {
+                        channel,
+                        value: bend.0.as_int(),
+                    },
+                    MidiMessage::Controller { controller, value } => EventKind::ControlChange {
+                        channel,
+                        controller: controller.as_int(),
+                        value: value.as_int(),
+                    },
+                    _ => continue,
+                }
+            }
+            TrackEventKind::Meta(message) => {
+                let (kind, data) = match message {
+                    MetaMessage::TrackName(name) => (smf::META_TRACK_NAME, Cow::Borrowed(name)),
+                    MetaMessage::Lyric(lyric) => (smf::META_LYRIC, Cow::Borrowed(lyric)),
+                    MetaMessage::Tempo(microseconds) => (
+                        smf::META_TEMPO,
+                        Cow::Owned(microseconds.as_int().to_be_bytes()[1..].to_vec()),
+                    ),
+                    MetaMessage::TimeSignature(numerator, denominator, clocks, notes) => (
+                        smf::META_TIME_SIGNATURE,
+                        Cow::Owned(vec![numerator, denominator, clocks, notes]),
+                    ),
+                    MetaMessage::KeySignature(fifths, minor) => (
+                        smf::META_KEY_SIGNATURE,
+                        Cow::Owned(vec![fifths as u8, u8::from(minor)]),
+                    ),
+                    _ => continue,
+                };
+                EventKind::Meta { kind, data }
+            }
+            // System exclusive messages aren't read by the mapping.
+            _ => continue,
+        };
+        converted.push(Event { tick, kind });
+    }
+    converted
+}
@@ -30,7 +30,7 @@ fn utaformatix() -> utaformatix_rs::base::UtaFormatix {
 async fn test_name(utaformatix: utaformatix_rs::base::UtaFormatix) {
     let data = include_bytes!(concat!("../utaformatix-ts/testAssets/", path));
     let options = ParseOptions::default();
-    let result = utaformatix.function(data, options).await;
+    let result = utaformatix.function(data.as_slice(), options).await;
 
     let parsed = result.expect("Failed to parse data");
 
@@ -47,7 +47,7 @@ async fn test_name(utaformatix: utaformatix_rs::base::UtaFormatix) {
 async fn test_name(utaformatix: utaformatix_rs::base::UtaFormatix) {
     let data = include_bytes!(concat!("../utaformatix-ts/testAssets/", path));
     let options = ParseOptions::default();
-    let result = utaformatix.function(&[data], options).await;
+    let result = utaformatix.function([data.as_slice()], options).await;
 
     let parsed = result.expect("Failed to parse data");
 
@@ -64,7 +64,7 @@ async fn test_name(utaformatix: utaformatix_rs::base::UtaFormatix) {
 async fn test_name(utaformatix: utaformatix_rs::base::UtaFormatix) {
     let data = include_bytes!("../utaformatix-ts/testAssets/generated/standard.mid");
     let ufdata = utaformatix
-        .parse_standard_mid(data, ParseOptions::default())
+        .parse_standard_mid(data.as_slice(), ParseOptions::default())
         .await
         .expect("Failed to parse data");
     let options = GenerateOptions::default();
@@ -0,0 +1,160 @@
+//! Differential tests between the native implementations and the JS engine.
+//!
+//! These read the test assets of the utaformatix-ts submodule at runtime, and are skipped when
+//! the submodule isn't checked out.
+use duplicate::duplicate_item;
+use tracing_test::traced_test;
+use utaformatix::{base::UtaFormatix, Bytes, Format, GenerateOptions, ParseOptions, UfData};
+
+/// Number of mutated inputs generated for each file.
+const MUTATION_COUNT: usize = 32;
+
+#[rstest::fixture]
+fn engine() -> UtaFormatix {
+    UtaFormatix::new()
+}
+
+/// Reads a file in the test assets, or returns `None` with a message if it's missing.
+fn test_asset(path: &str) -> Option<Bytes> {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("utaformatix-ts/testAssets")
+        .join(path);
+    match std::fs::read(&path) {
+        Ok(data) => Some(data.into()),
+        Err(error) => {
+            eprintln!(
+                "Skipping: {} is not available ({}); check out the utaformatix-ts submodule",
+                path.display(),
+                error
+            );
+            None
+        }
+    }
+}
+
+async fn parse_native(
+    engine: &UtaFormatix,
+    format: Format,
+    data: Bytes,
+    options: ParseOptions,
+) -> utaformatix::Result<UfData> {
+    match format {
+        Format::StandardMid => engine.parse_standard_mid(data, options).await,
+        Format::UfData => engine.parse_uf_data(data, options).await,
+        _ => unreachable!("{:?} is not implemented natively", format),
+    }
+}
+
+async fn generate_native(
+    engine: &UtaFormatix,
+    format: Format,
+    data: &UfData,
+    options: GenerateOptions,
+) -> utaformatix::Result<Vec<u8>> {
+    match format {
+        Format::StandardMid => engine.generate_standard_mid(data, options).await,
+        Format::UfData => engine.generate_uf_data(data, options).await,
+        _ => unreachable!("{:?} is not implemented natively", format),
+    }
+}
+
+/// Generates deterministic mutations of the data, like a dumb fuzzer.
+fn mutations(data: &[u8], count: usize) -> Vec<Vec<u8>> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64 ^ data.len() as u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    (0..count)
+        .map(|i| {
+            let mut data = data.to_vec();
+            if data.is_empty() {
+                return data;
+            }
+            if i % 4 == 0 {
+                data.truncate(next() as usize % data.len());
+            } else {
+                for _ in 0..=(next() % 4) {
+                    let position = next() as usize % data.len();
+                    data[position] = next() as u8;
+                }
+            }
+            data
+        })
+        .collect()
+}
+
+async fn assert_same_parse(engine: &UtaFormatix, format: Format, data: Bytes) {
+    let native = parse_native(engine, format, data.clone(), ParseOptions::default()).await;
+    let from_engine = engine
+        .parse_with_engine(format, data, ParseOptions::default())
+        .await;
+    match (native, from_engine) {
+        (Ok(native), Ok(from_engine)) => assert_eq!(native, from_engine),
+        (Err(_), Err(_)) => {}
+        (native, from_engine) => panic!(
+            "Results differ: native: {:?}, engine: {:?}",
+            native.map(|_| ()),
+            from_engine.map(|_| ())
+        ),
+    }
+}
+
+#[duplicate_item(
+    test_name                   format                path;
+    [standard_mid_parse]        [Format::StandardMid] ["generated/standard.mid"];
+    [vocaloid_mid_as_standard]  [Format::StandardMid] ["generated/vocaloid.mid"];
+    [uf_data_parse]             [Format::UfData]      ["generated/ufdata.ufdata"];
+)]
+#[rstest::rstest]
+#[tokio::test]
+#[traced_test]
+async fn test_name(engine: UtaFormatix) {
+    let Some(data) = test_asset(path) else {
+        return;
+    };
+
+    assert_same_parse(&engine, format, data.clone()).await;
+    for mutated in mutations(&data, MUTATION_COUNT) {
+        assert_same_parse(&engine, format, mutated.into()).await;
+    }
+}
+
+#[duplicate_item(
+    test_name                format                path;
+    [standard_mid_generate]  [Format::StandardMid] ["generated/standard.mid"];
+    [uf_data_generate]       [Format::UfData]      ["generated/ufdata.ufdata"];
+)]
+#[rstest::rstest]
+#[tokio::test]
+#[traced_test]
+async fn test_name(engine: UtaFormatix) {
+    let Some(data) = test_asset(path) else {
+        return;
+    };
+    let ufdata = engine
+        .parse_with_engine(format, data, ParseOptions::default())
+        .await
+        .expect("Failed to parse data");
+
+    let native = generate_native(&engine, format, &ufdata, GenerateOptions::default())
+        .await
+        .expect("Failed to generate data");
+    let from_engine = engine
+        .generate_with_engine(format, &ufdata, GenerateOptions::default())
+        .await
+        .expect("Failed to generate data");
+
+    // The bytes can differ (e.g. event order), so compare what the engine reads back.
+    let native = engine
+        .parse_with_engine(format, native, ParseOptions::default())
+        .await
+        .expect("Failed to parse native output");
+    let from_engine = engine
+        .parse_with_engine(format, from_engine, ParseOptions::default())
+        .await
+        .expect("Failed to parse engine output");
+    assert_eq!(native, from_engine);
+}
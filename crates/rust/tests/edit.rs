@@ -0,0 +1,1452 @@
+use utaformatix::{
+    AlignOptions, CompatibilityIssue, DefaultLyric, EditSession, ErrorKind, Format,
+    HumanizeOptions, IllegalFile, JapaneseLyricsType, Key, KeySignature, LyricNormalization,
+    LyricsTypeAnalysis, LyricsTypeCandidate, Mode, Note, Oto, Pitch, PrefixMap, Project,
+    RomajiCase, RomajiOptions, RomajiStyle, ScaleDegreeOffset, ShiftOptions, Tempo, Tick,
+    TimeSignature, Track, TrackLyricsTypeAnalysis, UfData, UfProject,
+};
+
+fn tick(ticks: i64) -> Tick {
+    Tick::new(ticks).unwrap()
+}
+
+fn key(key: i32) -> Key {
+    Key::new(key).unwrap()
+}
+
+#[rstest::fixture]
+fn project() -> Project {
+    let notes = (0..8)
+        .map(|i| Note {
+            key: key(60 + i),
+            tick_on: tick(i64::from(i) * 480),
+            tick_off: tick(i64::from(i + 1) * 480),
+            lyric: "あ".to_string(),
+            phoneme: None,
+            phoneme_segments: None,
+        })
+        .collect();
+    Project::new(UfData::new(UfProject {
+        name: "Project".to_string(),
+        tracks: vec![Track {
+            name: "Track".to_string(),
+            notes,
+            pitch: None,
+            muted: None,
+            solo: None,
+            order: None,
+            color: None,
+            voice: None,
+            parameters: None,
+        }],
+        time_signatures: vec![
+            TimeSignature::default(),
+            TimeSignature {
+                measure_position: 4,
+                numerator: 3,
+                denominator: 4,
+            },
+        ],
+        tempos: vec![Tempo {
+            tick_position: Tick::ZERO,
+            bpm: 120,
+        }],
+        measure_prefix: 0,
+        key_signatures: vec![],
+    }))
+}
+
+fn time_signatures(project: &Project) -> Vec<(i32, i32, i32)> {
+    project
+        .data
+        .project()
+        .time_signatures
+        .iter()
+        .map(|time_signature| {
+            (
+                time_signature.measure_position,
+                time_signature.numerator,
+                time_signature.denominator,
+            )
+        })
+        .collect()
+}
+
+#[rstest::rstest]
+fn insert_time_signature(mut project: Project) {
+    // 2/4 at measure 2 makes the 3/4 at tick 7680 start at measure 6.
+    project
+        .insert_time_signature(TimeSignature {
+            measure_position: 2,
+            numerator: 2,
+            denominator: 4,
+        })
+        .unwrap();
+
+    assert_eq!(
+        time_signatures(&project),
+        vec![(0, 4, 4), (2, 2, 4), (6, 3, 4)]
+    );
+    project.validate_time_signatures().unwrap();
+}
+
+#[rstest::rstest]
+fn edit_time_signatures_moves_key_signatures(mut project: Project) {
+    let key_signature = |measure_position| KeySignature {
+        measure_position,
+        tonic: 7,
+        mode: Mode::Major,
+    };
+    let key_signatures = |project: &Project| {
+        project
+            .data
+            .project()
+            .key_signatures
+            .iter()
+            .map(|key_signature| key_signature.measure_position)
+            .collect::<Vec<_>>()
+    };
+    // Measure 5 is at tick 9120, and measure 8 is at tick 13440.
+    project.data.project_mut().key_signatures = vec![key_signature(0), key_signature(5)];
+    let mut inserted = project.clone();
+    inserted
+        .insert_time_signature(TimeSignature {
+            measure_position: 2,
+            numerator: 2,
+            denominator: 4,
+        })
+        .unwrap();
+    assert_eq!(key_signatures(&inserted), vec![0, 7]);
+
+    project.data.project_mut().key_signatures = vec![key_signature(8)];
+    let before = project.clone();
+    // Tick 13440 is measure 7 in 4/4, but off a measure boundary after moving the 3/4.
+    let error = project.move_time_signature(4, 2).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::IllegalTimeSignature));
+    assert_eq!(project.data, before.data);
+    project.remove_time_signature(4).unwrap();
+    assert_eq!(key_signatures(&project), vec![7]);
+}
+
+#[rstest::rstest]
+fn insert_time_signature_off_boundary(mut project: Project) {
+    let before = time_signatures(&project);
+    let error = project
+        .insert_time_signature(TimeSignature {
+            measure_position: 2,
+            numerator: 3,
+            denominator: 4,
+        })
+        .unwrap_err();
+
+    assert!(matches!(error.kind(), ErrorKind::IllegalTimeSignature));
+    assert_eq!(time_signatures(&project), before);
+}
+
+#[rstest::rstest]
+fn insert_time_signature_shorter_than_a_tick(mut project: Project) {
+    let time_signature = TimeSignature {
+        measure_position: 2,
+        numerator: 1,
+        denominator: 4096,
+    };
+    assert!(!time_signature.is_valid());
+    let error = project.insert_time_signature(time_signature).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::IllegalTimeSignature));
+
+    // Invalid time signatures set directly don't make the conversions panic.
+    project.data.project_mut().time_signatures[0].denominator = 0;
+    assert_eq!(project.data.project().tick_to_measure(Tick::ZERO), 0);
+
+    // Positions out of range saturate instead of overflowing.
+    let data = project.data.project_mut();
+    data.time_signatures = vec![
+        TimeSignature {
+            measure_position: 0,
+            numerator: i32::MAX,
+            denominator: 1,
+        },
+        TimeSignature {
+            measure_position: i32::MAX,
+            numerator: i32::MAX,
+            denominator: 1,
+        },
+    ];
+    assert_eq!(data.measure_to_tick(i32::MAX), Tick::new(i64::MAX).unwrap());
+    assert_eq!(data.measure_to_tick(i32::MIN), Tick::ZERO);
+    data.time_signatures = vec![TimeSignature {
+        measure_position: 0,
+        numerator: 1,
+        denominator: 4,
+    }];
+    assert_eq!(data.tick_to_measure(Tick::new(i64::MAX).unwrap()), i32::MAX);
+}
+
+#[rstest::rstest]
+fn remove_and_move_time_signature(mut project: Project) {
+    project.move_time_signature(4, 2).unwrap();
+    assert_eq!(time_signatures(&project), vec![(0, 4, 4), (2, 3, 4)]);
+
+    let removed = project.remove_time_signature(2).unwrap();
+    assert_eq!(
+        removed.map(|time_signature| time_signature.numerator),
+        Some(3)
+    );
+    assert_eq!(time_signatures(&project), vec![(0, 4, 4)]);
+}
+
+#[rstest::rstest]
+fn set_measure_prefix(mut project: Project) {
+    project
+        .set_measure_prefix(Project::required_measure_prefix(Format::Vsqx))
+        .unwrap();
+    let data = project.data.project();
+    assert_eq!(data.measure_prefix, 1);
+    assert_eq!(data.tracks[0].notes[0].tick_on, 1920);
+    assert_eq!(data.tempos[0].tick_position, 0);
+    assert_eq!(time_signatures(&project), vec![(0, 4, 4), (5, 3, 4)]);
+
+    project.set_measure_prefix(0).unwrap();
+    assert_eq!(project.data, self::project().data);
+}
+
+#[rstest::rstest]
+fn set_measure_prefix_before_start(mut project: Project) {
+    project.data.project_mut().measure_prefix = 1;
+    let error = project.set_measure_prefix(0).unwrap_err();
+
+    assert!(matches!(error.kind(), ErrorKind::IllegalNotePosition));
+}
+
+#[rstest::rstest]
+fn edit_tempos(mut project: Project) {
+    project.insert_tempo(tick(960), 120).unwrap();
+    project.insert_tempo(tick(1920), 240).unwrap();
+    project.insert_tempo(tick(1920), 60).unwrap();
+    assert!(project.insert_tempo(Tick::ZERO, 0).is_err());
+
+    project.remove_redundant_tempos();
+    let tempos = &project.data.project().tempos;
+    assert_eq!(
+        tempos
+            .iter()
+            .map(|tempo| (tempo.tick_position.get(), tempo.bpm))
+            .collect::<Vec<_>>(),
+        vec![(0, 120), (1920, 60)]
+    );
+}
+
+#[rstest::rstest]
+fn flatten_tempo(mut project: Project) {
+    project.insert_tempo(tick(1920), 60).unwrap();
+    project.flatten_tempo(120).unwrap();
+
+    let data = project.data.project();
+    assert_eq!(data.tempos.len(), 1);
+    let notes = &data.tracks[0].notes;
+    // Notes after tick 1920 are twice as long at 120 BPM.
+    assert_eq!(
+        (notes[3].tick_on.get(), notes[3].tick_off.get()),
+        (1440, 1920)
+    );
+    assert_eq!(
+        (notes[4].tick_on.get(), notes[4].tick_off.get()),
+        (1920, 2880)
+    );
+}
+
+#[rstest::rstest]
+fn import_timing_from_midi(mut project: Project) {
+    // A conductor track with 60 BPM and 3/4.
+    let mut midi = b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\x01\xe0MTrk\x00\x00\x00\x13".to_vec();
+    midi.extend_from_slice(b"\x00\xff\x51\x03\x0f\x42\x40");
+    midi.extend_from_slice(b"\x00\xff\x58\x04\x03\x02\x18\x08");
+    midi.extend_from_slice(b"\x00\xff\x2f\x00");
+    assert!(project
+        .clone()
+        .import_timing_from_midi(b"not midi")
+        .is_err());
+    project.data.project_mut().key_signatures = vec![KeySignature {
+        measure_position: 5,
+        tonic: 7,
+        mode: Mode::Major,
+    }];
+    let mut with_key = project.clone();
+    project.import_timing_from_midi(&midi).unwrap();
+
+    assert_eq!(time_signatures(&project), vec![(0, 3, 4)]);
+    let data = project.data.project();
+    assert_eq!(data.tempos.len(), 1);
+    assert_eq!(data.tempos[0].bpm, 60);
+    // Notes are played at the same time, which is half as many beats at half the tempo.
+    let notes = &data.tracks[0].notes;
+    assert_eq!(
+        (notes[3].tick_on.get(), notes[3].tick_off.get()),
+        (720, 960)
+    );
+    // Measure 5 at tick 9120 is re-timed to tick 4560, which is in measure 3 of 3/4.
+    assert_eq!(data.key_signatures[0].measure_position, 3);
+
+    // Key signatures of the file replace those of the project.
+    let mut midi = b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\x01\xe0MTrk\x00\x00\x00\x0a".to_vec();
+    midi.extend_from_slice(b"\x00\xff\x59\x02\x02\x01");
+    midi.extend_from_slice(b"\x00\xff\x2f\x00");
+    with_key.import_timing_from_midi(&midi).unwrap();
+    assert_eq!(
+        with_key.data.project().key_signatures,
+        vec![KeySignature {
+            measure_position: 0,
+            tonic: 11,
+            mode: Mode::Minor,
+        }]
+    );
+}
+
+#[rstest::rstest]
+fn simple_json(project: Project) {
+    let json = project.to_simple_json().unwrap();
+    assert!(json.contains(r#"{"start":0.5,"end":1.0,"pitch":"C#4","lyric":"あ"}"#));
+
+    let converted = Project::from_simple_json(&json).unwrap();
+    assert_eq!(
+        converted.data.project().tracks,
+        project.data.project().tracks
+    );
+
+    let error = Project::from_simple_json(
+        r#"{"tracks":[{"notes":[{"start":0,"end":1,"pitch":"H4","lyric":"あ"}]}]}"#,
+    )
+    .unwrap_err();
+    assert!(matches!(
+        error.kind(),
+        ErrorKind::IllegalFile(IllegalFile::IllegalSimpleJsonFile)
+    ));
+}
+
+#[rstest::rstest]
+fn scale_time_and_tempo(mut project: Project) {
+    project.scale_time(2.0).unwrap();
+    project.scale_tempo(2.0).unwrap();
+
+    let data = project.data.project();
+    assert_eq!(data.tempos[0].bpm, 240);
+    let notes = &data.tracks[0].notes;
+    assert_eq!(
+        (notes[1].tick_on.get(), notes[1].tick_off.get()),
+        (960, 1920)
+    );
+    assert!(project.clone().scale_time(0.0).is_err());
+    // The 3/4 at measure 4 moves to tick 15360, which is measure 8.
+    assert_eq!(time_signatures(&project), vec![(0, 4, 4), (8, 3, 4)]);
+}
+
+#[rstest::rstest]
+fn scale_time_moves_key_signatures(mut project: Project) {
+    project.data.project_mut().key_signatures = vec![KeySignature {
+        measure_position: 5,
+        tonic: 7,
+        mode: Mode::Major,
+    }];
+    let before = project.clone();
+    // The 3/4 at tick 7680 would move to tick 8448, which is off a measure boundary.
+    let error = project.scale_time(1.1).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::IllegalTimeSignature));
+    assert_eq!(project.data, before.data);
+
+    project.scale_time(2.0).unwrap();
+    // Measure 5 at tick 9120 moves to tick 18240, which is measure 10 after the 3/4.
+    assert_eq!(
+        project.data.project().key_signatures[0].measure_position,
+        10
+    );
+}
+
+fn gapped_track() -> Track {
+    let notes = [(240, 480), (490, 960), (1440, 1920)]
+        .into_iter()
+        .map(|(tick_on, tick_off)| Note {
+            key: key(60),
+            tick_on: tick(tick_on),
+            tick_off: tick(tick_off),
+            lyric: "ら".to_string(),
+            phoneme: None,
+            phoneme_segments: None,
+        })
+        .collect();
+    Track {
+        name: "Track".to_string(),
+        notes,
+        pitch: None,
+        muted: None,
+        solo: None,
+        order: None,
+        color: None,
+        voice: None,
+        parameters: None,
+    }
+}
+
+#[test]
+fn shift_inverted_note() {
+    let mut track = gapped_track();
+    // Notes ending before they start are kept until `Track::cleanup`.
+    track.notes[0].tick_off = tick(0);
+    let before = track.clone();
+    let error = track.shift(-100).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::IllegalNotePosition));
+    assert!(track == before);
+    track.shift(100).unwrap();
+    assert_eq!(track.notes[0].tick_off, 100);
+}
+
+#[test]
+fn remove_micro_gaps() {
+    let mut track = gapped_track();
+    track.remove_micro_gaps(30);
+
+    assert_eq!(track.notes[0].tick_off, 490);
+    assert_eq!(track.notes[1].tick_off, 960);
+}
+
+#[test]
+fn insert_and_remove_rest_notes() {
+    let mut track = gapped_track();
+    track.insert_rest_lyrics("R");
+
+    let lyrics = track
+        .notes
+        .iter()
+        .map(|note| (note.tick_on.get(), note.lyric.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lyrics,
+        vec![
+            (0, "R"),
+            (240, "ら"),
+            (480, "R"),
+            (490, "ら"),
+            (960, "R"),
+            (1440, "ら"),
+        ]
+    );
+
+    track.remove_rest_notes(&["R"]);
+    assert_eq!(track, gapped_track());
+}
+
+#[rstest::rstest]
+fn humanize(mut project: Project) {
+    let options = HumanizeOptions {
+        seed: 42,
+        ..Default::default()
+    };
+    let mut other = project.clone();
+    project.humanize(options.clone());
+    other.humanize(options.clone());
+    assert_eq!(project.data, other.data);
+
+    let notes = &project.data.project().tracks[0].notes;
+    let original = self::project().data.project().tracks[0].notes.clone();
+    assert_ne!(notes, &original);
+    for (i, (note, original)) in notes.iter().zip(&original).enumerate() {
+        assert!((note.tick_on - original.tick_on).abs() <= options.timing);
+        assert_eq!(note.tick_off, original.tick_off);
+        if i > 0 {
+            assert!(note.tick_on >= notes[i - 1].tick_off);
+        }
+    }
+}
+
+#[test]
+fn split_and_tie_notes() {
+    let mut track = gapped_track();
+    let mut note = track.notes[2].clone();
+    let second = note.split_at(tick(1680), "ら").unwrap();
+    assert_eq!((note.tick_off.get(), second.tick_on.get()), (1680, 1680));
+    assert!(note.clone().split_at(tick(1680), "ら").is_none());
+    track.notes[2] = note;
+    track.notes.push(second);
+
+    track.tie_notes(1..4, Format::Svp);
+    let notes = track
+        .notes
+        .iter()
+        .map(|note| (note.tick_on.get(), note.tick_off.get(), note.lyric.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        notes,
+        vec![(240, 480, "ら"), (490, 960, "ら"), (1440, 1920, "-"),]
+    );
+}
+
+#[rstest::rstest]
+fn detect_key_and_snap(mut project: Project) {
+    // G major scale, with a C# which is out of it.
+    let keys = [67, 69, 71, 72, 74, 76, 78, 79, 61];
+    let track = &mut project.data.project_mut().tracks[0];
+    track.notes = keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| Note {
+            key: Key::new(*key).unwrap(),
+            tick_on: tick(i as i64 * 480),
+            tick_off: tick((i as i64 + 1) * 480),
+            lyric: "ら".to_string(),
+            phoneme: None,
+            phoneme_segments: None,
+        })
+        .collect();
+
+    let key = project.detect_key();
+    assert_eq!(
+        key,
+        KeySignature {
+            measure_position: 0,
+            tonic: 7,
+            mode: Mode::Major
+        }
+    );
+
+    let track = &mut project.data.project_mut().tracks[0];
+    track.snap_to_scale(&key);
+    assert_eq!(track.notes[8].key, 60);
+    assert_eq!(track.notes[0].key, 67);
+}
+
+#[rstest::rstest]
+fn harmonize(project: Project) {
+    let track = &project.data.project().tracks[0];
+    let key = KeySignature::default();
+    let harmony = track
+        .harmonize(ScaleDegreeOffset::THIRD_ABOVE, &key)
+        .unwrap();
+
+    // Chromatic notes from C move by the same interval as the scale note below them.
+    let keys = harmony
+        .notes
+        .iter()
+        .map(|note| note.key)
+        .collect::<Vec<_>>();
+    assert_eq!(keys, vec![64, 65, 65, 66, 67, 69, 70, 71]);
+    assert_eq!(harmony.notes[0].lyric, track.notes[0].lyric);
+
+    let below = track
+        .harmonize(ScaleDegreeOffset::FIFTH_BELOW, &key)
+        .unwrap();
+    assert_eq!(below.notes[0].key, 53);
+
+    let mut high = track.clone();
+    high.notes[0].key = self::key(125);
+    assert!(high
+        .harmonize(ScaleDegreeOffset::THIRD_ABOVE, &key)
+        .is_none());
+}
+
+#[test]
+fn query_notes() {
+    let mut track = gapped_track();
+    track.notes.push(Note {
+        key: key(48),
+        tick_on: Tick::ZERO,
+        tick_off: tick(1000),
+        lyric: "あ".to_string(),
+        phoneme: None,
+        phoneme_segments: None,
+    });
+    let ticks = |notes: Vec<&Note>| {
+        notes
+            .iter()
+            .map(|note| note.tick_on.get())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(ticks(track.notes_at(tick(480))), vec![0]);
+    assert_eq!(ticks(track.notes_at(tick(500))), vec![0, 490]);
+    let index = track.index();
+    assert_eq!(
+        ticks(index.notes_in_range(tick(900)..tick(1500))),
+        vec![0, 490, 1440]
+    );
+    assert!(index.notes_in_range(tick(2000)..tick(3000)).is_empty());
+}
+
+#[rstest::rstest]
+fn edit_session(project: Project) {
+    let mut session = EditSession::new(project.clone());
+    assert!(!session.is_modified());
+
+    session.transpose(0, 0..2, 12).unwrap();
+    session.set_lyric(0, 2, "い").unwrap();
+    session.move_notes(0, 3..4, 240).unwrap();
+    assert!(session.set_lyric(1, 0, "い").is_err());
+    assert!(session.transpose(0, 0..100, 1).is_err());
+    let error = session.transpose(0, 0..1, 200).unwrap_err();
+    assert!(matches!(
+        error.kind(),
+        ErrorKind::KeyOutOfRange {
+            track: 0,
+            note: 0,
+            ..
+        }
+    ));
+    let error = session.move_notes(0, 0..2, -1_000_000).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::IllegalNotePosition));
+
+    let track = session.track(0).unwrap();
+    assert_eq!(track.notes[1].key, 73);
+    assert_eq!(track.notes[2].lyric, "い");
+    assert_eq!(track.notes[3].tick_on, 1680);
+    assert_eq!(session.changed_tracks(), vec![0]);
+
+    let snapshot = session.clone();
+    assert!(session.undo());
+    assert!(session.undo());
+    assert!(session.undo());
+    assert!(!session.undo());
+    assert_eq!(session.project().data, project.data);
+    assert!(!session.is_modified());
+
+    assert!(session.redo());
+    assert!(session.is_modified());
+    session.mark_saved();
+    assert!(!session.is_modified());
+    assert_eq!(snapshot.track(0).unwrap().notes[1].key, 73);
+}
+
+#[cfg(feature = "preview")]
+#[rstest::rstest]
+fn render_preview(project: Project) {
+    use utaformatix::preview::{PreviewOptions, Waveform};
+
+    let options = PreviewOptions {
+        sample_rate: 8000,
+        waveform: Waveform::Saw,
+        ..Default::default()
+    };
+    let wav = project.render_preview(0, &options).unwrap();
+    assert_eq!(&wav[0..4], b"RIFF");
+    assert_eq!(&wav[8..16], b"WAVEfmt ");
+    // 8 notes of a beat at 120 BPM.
+    let data_length = u32::from_le_bytes(wav[40..44].try_into().unwrap());
+    assert_eq!(data_length, 8000 * 4 * 2);
+    assert_eq!(wav.len(), 44 + data_length as usize);
+    assert!(wav[44..].chunks(2).any(|sample| sample != [0, 0]));
+
+    assert!(project.render_preview(1, &options).is_err());
+}
+
+#[cfg(feature = "ml")]
+#[rstest::rstest]
+fn export_dataset(mut project: Project) {
+    use utaformatix::dataset::{self, DatasetOptions, PhonemeSet, Segment};
+
+    // A rest of a second splits the notes into two phrases.
+    for note in &mut project.data.project_mut().tracks[0].notes[4..] {
+        note.tick_on = note.tick_on.saturating_add(960);
+        note.tick_off = note.tick_off.saturating_add(960);
+    }
+    let options = DatasetOptions {
+        phoneme_set: PhonemeSet::Dictionary([("あ".to_string(), vec!["a".to_string()])].into()),
+        hop_size: 0.01,
+        ..Default::default()
+    };
+    let files = dataset::files(project.data.project(), &options).unwrap();
+    let names = files
+        .iter()
+        .map(|file| file.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            "Track_0001.lab",
+            "Track_0001.f0.csv",
+            "Track_0001.txt",
+            "Track_0001.json",
+            "Track_0002.lab",
+            "Track_0002.f0.csv",
+            "Track_0002.txt",
+            "Track_0002.json",
+        ]
+    );
+
+    let lab = String::from_utf8(files[0].content.clone()).unwrap();
+    assert_eq!(lab.lines().next(), Some("0 5000000 a"));
+    assert_eq!(lab.lines().count(), 4);
+    let f0 = String::from_utf8(files[1].content.clone()).unwrap();
+    let f0 = f0.lines().collect::<Vec<_>>();
+    assert_eq!(f0[0], "time,f0");
+    assert_eq!(f0[1], "0.000,261.626");
+    assert_eq!(f0.len(), 1 + 200);
+    assert_eq!(files[2].content, "あ あ あ あ".as_bytes());
+    let segment: Segment = serde_json::from_slice(&files[7].content).unwrap();
+    assert_eq!(segment.start, 3.0);
+    assert_eq!(segment.notes[0].key, 64);
+    assert_eq!(segment.notes[3].end, 2.0);
+
+    let directory =
+        std::env::temp_dir().join(format!("utaformatix-dataset-{}", std::process::id()));
+    project.export_dataset(&directory, &options).unwrap();
+    assert_eq!(
+        std::fs::read(directory.join("Track_0002.lab")).unwrap(),
+        files[4].content
+    );
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[rstest::rstest]
+fn generate_click_track(mut project: Project) {
+    // Notes last until measure 6, which is in 3/4 from measure 4.
+    project.data.project_mut().tracks[0].notes[7].tick_off = tick(480 * 4 * 4 + 480 * 3 + 1);
+    let track = project.generate_click_track();
+
+    assert_eq!(track.notes.len(), 4 * 4 + 3 * 2);
+    assert_eq!(
+        track.notes[..5]
+            .iter()
+            .map(|note| (note.tick_on.get(), note.key.get()))
+            .collect::<Vec<_>>(),
+        vec![(0, 84), (480, 79), (960, 79), (1440, 79), (1920, 84)]
+    );
+    assert_eq!(track.notes[16].tick_on, 7680);
+    assert_eq!(track.notes[19].tick_on, 7680 + 480 * 3);
+    assert_eq!(track.notes[19].key, 84);
+}
+
+#[rstest::rstest]
+fn lyrics_txt(mut project: Project) {
+    // A rest of a beat between the 4th and 5th notes.
+    project.data.project_mut().tracks[0].notes.remove(4);
+    let text = project.generate_lyrics_txt(0, 480).unwrap();
+    assert_eq!(text, "あ あ あ あ\nあ あ あ\n");
+
+    project
+        .parse_lyrics_txt(0, "ら り る れ\n\nろ わ を\n", 480)
+        .unwrap();
+    let lyrics = project.data.project().tracks[0]
+        .notes
+        .iter()
+        .map(|note| note.lyric.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(lyrics, vec!["ら", "り", "る", "れ", "ろ", "わ", "を"]);
+
+    let original = project.clone();
+    let error = project.parse_lyrics_txt(0, "あ あ あ\n", 480).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::LyricsMismatch));
+    assert_eq!(project.data, original.data);
+    let error = project.generate_lyrics_txt(1, 480).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::IndexOutOfRange));
+}
+
+#[rstest::rstest]
+#[case(DefaultLyric::KeepEmpty, vec!["あ", "", "い", ""])]
+#[case(DefaultLyric::Fill("la".to_string()), vec!["あ", "la", "い", "la"])]
+#[case(DefaultLyric::CopyPrevious, vec!["あ", "あ", "い", "い"])]
+#[case(DefaultLyric::DropNote, vec!["あ", "い"])]
+fn default_lyric(#[case] policy: DefaultLyric, #[case] expected: Vec<&str>) {
+    let mut notes = ["あ", "", "い", ""]
+        .iter()
+        .enumerate()
+        .map(|(i, lyric)| Note {
+            key: key(60),
+            tick_on: tick(i as i64 * 480),
+            tick_off: tick((i as i64 + 1) * 480),
+            lyric: lyric.to_string(),
+            phoneme: None,
+            phoneme_segments: None,
+        })
+        .collect::<Vec<_>>();
+    policy.apply(&mut notes);
+
+    assert_eq!(
+        notes
+            .iter()
+            .map(|note| note.lyric.as_str())
+            .collect::<Vec<_>>(),
+        expected
+    );
+}
+
+#[rstest::rstest]
+#[case("ｌａ", "la")]
+#[case(" あ\u{3000}", "あ")]
+#[case("あ_B4", "あ")]
+#[case("か C#4↑", "か")]
+#[case("さ↓", "さ")]
+#[case("C4", "C4")]
+#[case("Ab", "Ab")]
+fn normalize_lyric(#[case] lyric: &str, #[case] expected: &str) {
+    let normalization = LyricNormalization {
+        unicode_form: None,
+        half_width_romaji: true,
+        trim_whitespace: true,
+        strip_utau_suffixes: true,
+    };
+
+    assert_eq!(normalization.normalize(lyric), expected);
+}
+
+#[test]
+fn prefix_map() {
+    let prefix_map =
+        PrefixMap::parse("C4\t\t_C4\r\nC#4\t\t_C4\nD4\t\tD4\nB7\nX4\t\t_X\nC999999999\t\t_X\n");
+
+    assert_eq!(prefix_map.get(key(60)), Some(("", "_C4")));
+    assert_eq!(prefix_map.get(key(62)), Some(("", "D4")));
+    assert_eq!(prefix_map.get(key(107)), Some(("", "")));
+    assert_eq!(prefix_map.apply("あ", key(61)), "あ_C4");
+    assert_eq!(prefix_map.apply("あ", key(64)), "あ");
+    assert_eq!(prefix_map.strip("あ_C4", key(60)), "あ");
+    // Only the affixes of the note's key are stripped.
+    assert_eq!(prefix_map.strip("あ_C4", key(62)), "あ_C4");
+    assert_eq!(prefix_map.strip("_C4", key(60)), "_C4");
+}
+
+#[rstest::rstest]
+fn validate_lyrics(mut project: Project) {
+    let oto = Oto::parse(
+        "あ.wav=,0,0,0,0,0\r\nsub\\い.wav=,0,0,0,0,0\nか_C4.wav=か_C4,0\nか.wav=か?,0\n",
+    );
+    assert_eq!(
+        oto.aliases().collect::<Vec<_>>(),
+        vec!["あ", "い", "か?", "か_C4"]
+    );
+
+    let mut prefix_map = PrefixMap::default();
+    prefix_map.insert(key(60), "", "_C4");
+    let track = &mut project.data.project_mut().tracks[0];
+    for (note, lyric) in track
+        .notes
+        .iter_mut()
+        .zip(["か", "い", "R", "か", "う", "あ", "", "い"])
+    {
+        note.lyric = lyric.to_string();
+    }
+
+    let unmatched = oto.validate(&project.data.project().tracks, Some(&prefix_map));
+    assert_eq!(
+        unmatched
+            .iter()
+            .map(|lyric| (lyric.note, lyric.lyric.as_str(), lyric.suggestions.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                3,
+                "か",
+                vec!["あ".to_string(), "い".to_string(), "か?".to_string()]
+            ),
+            (4, "う", vec!["あ".to_string(), "い".to_string()]),
+        ]
+    );
+}
+
+#[rstest::rstest]
+#[tokio::test]
+async fn export(mut project: Project) {
+    use utaformatix::{CollisionPolicy, GenerateOptions, NamingTemplate};
+
+    let directory = std::env::temp_dir().join(format!("utaformatix-export-{}", std::process::id()));
+    let template = NamingTemplate::default();
+    let export = |project: &Project, template: &NamingTemplate| {
+        let project = project.clone();
+        let template = template.clone();
+        let directory = directory.clone();
+        async move {
+            project
+                .export(
+                    &directory,
+                    Format::UfData,
+                    GenerateOptions::default(),
+                    &template,
+                )
+                .await
+        }
+    };
+
+    let paths = export(&project, &template).await.unwrap();
+    assert_eq!(paths, [directory.join("Project.ufdata")]);
+    let generated = std::fs::read(&paths[0]).unwrap();
+    assert_eq!(
+        UfData::from_bytes(&generated, Default::default()).unwrap(),
+        project.data
+    );
+    let paths = export(&project, &template).await.unwrap();
+    assert_eq!(paths, [directory.join("Project (2).ufdata")]);
+    let overwrite = NamingTemplate {
+        on_collision: CollisionPolicy::Overwrite,
+        ..Default::default()
+    };
+    let paths = export(&project, &overwrite).await.unwrap();
+    assert_eq!(paths, [directory.join("Project.ufdata")]);
+    let error = NamingTemplate {
+        on_collision: CollisionPolicy::Error,
+        ..Default::default()
+    };
+    assert!(export(&project, &error).await.is_err());
+
+    // Names which Windows doesn't allow.
+    project.data.project_mut().name = "con".to_string();
+    let paths = export(&project, &template).await.unwrap();
+    assert_eq!(paths, [directory.join("_con.ufdata")]);
+    project.data.project_mut().name = "a/b: c?.".to_string();
+    let custom = NamingTemplate {
+        single: "{project}{track}.{ext}.".to_string(),
+        ..Default::default()
+    };
+    let paths = export(&project, &custom).await.unwrap();
+    assert_eq!(paths, [directory.join("a_b_ c_..ufdata")]);
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[rstest::rstest]
+fn normalize_track_names(mut project: Project) {
+    use utaformatix::TrackNameOptions;
+
+    let track = project.data.project().tracks[0].clone();
+    let names = ["", "Track 1", "Vocal", "Vocal", "Vocal (2)", "Ｖ🎤\n", " "];
+    project.data.project_mut().tracks = names
+        .iter()
+        .map(|name| Track {
+            name: name.to_string(),
+            ..track.clone()
+        })
+        .collect();
+    project.data.project_mut().tracks[6].notes.clear();
+    let normalized = |project: &Project| {
+        project
+            .data
+            .project()
+            .tracks
+            .iter()
+            .map(|track| track.name.clone())
+            .collect::<Vec<_>>()
+    };
+
+    let mut default = project.clone();
+    default.normalize_track_names(&TrackNameOptions::default());
+    assert_eq!(
+        normalized(&default),
+        [
+            "Track 1",
+            "Track 1 (2)",
+            "Vocal",
+            "Vocal (3)",
+            "Vocal (2)",
+            "Ｖ🎤",
+            "Track 7"
+        ]
+    );
+
+    project.normalize_track_names(&TrackNameOptions {
+        format: Some(Format::Ust),
+        lyrics_name_length: Some(3),
+    });
+    assert_eq!(
+        normalized(&project),
+        [
+            "あああ",
+            "Track 1",
+            "Vocal",
+            "Vocal (3)",
+            "Vocal (2)",
+            "Ｖ",
+            "Track 7"
+        ]
+    );
+}
+
+#[rstest::rstest]
+#[case::hepburn_to_kunrei(
+    RomajiStyle::Hepburn,
+    RomajiStyle::Kunrei,
+    "shi chi tsu fu ja",
+    "si ti tu hu zya"
+)]
+#[case::kunrei_to_hepburn(
+    RomajiStyle::Kunrei,
+    RomajiStyle::Hepburn,
+    "a si ti tu hu zyo",
+    "a shi chi tsu fu jo"
+)]
+#[case::nihon_shiki(RomajiStyle::NihonShiki, RomajiStyle::Hepburn, "di du zi", "ji zu ji")]
+#[case::geminates(RomajiStyle::Kunrei, RomajiStyle::Hepburn, "atti assya", "atchi assha")]
+#[case::ambiguous_syllables(RomajiStyle::Hepburn, RomajiStyle::Kunrei, "ti tchi", "ti tti")]
+#[case::words(RomajiStyle::Hepburn, RomajiStyle::Kunrei, "kashi nshi", "kasi nsi")]
+#[case::case(RomajiStyle::Hepburn, RomajiStyle::Kunrei, "SHI Chi↑ あ", "SI Ti↑ あ")]
+fn convert_romaji(
+    #[case] from: RomajiStyle,
+    #[case] to: RomajiStyle,
+    #[case] lyric: &str,
+    #[case] expected: &str,
+) {
+    let options = RomajiOptions {
+        from,
+        to,
+        ..Default::default()
+    };
+    assert_eq!(options.convert(lyric), expected);
+}
+
+#[test]
+fn convert_romaji_options() {
+    let options = RomajiOptions {
+        from: RomajiStyle::NihonShiki,
+        to: RomajiStyle::Hepburn,
+        distinct_d_row: true,
+        case: RomajiCase::Upper,
+    };
+    let mut track = Track {
+        name: "Track".to_string(),
+        notes: vec![Note {
+            key: key(60),
+            tick_on: Tick::ZERO,
+            tick_off: tick(480),
+            lyric: "di zi du".to_string(),
+            phoneme: None,
+            phoneme_segments: None,
+        }],
+        pitch: None,
+        muted: None,
+        solo: None,
+        order: None,
+        color: None,
+        voice: None,
+        parameters: None,
+    };
+    track.convert_romaji(&options);
+    assert_eq!(track.notes[0].lyric, "DJI JI DZU");
+
+    let options = RomajiOptions {
+        from: RomajiStyle::Hepburn,
+        to: RomajiStyle::NihonShiki,
+        distinct_d_row: true,
+        case: RomajiCase::Lower,
+    };
+    assert_eq!(options.convert("Dji Ji dzu"), "di zi du");
+}
+
+#[rstest::rstest]
+#[case::kana(&["か", "ー", "ー", "ん", "ー"], &["か", "あ", "あ", "ん", "ん"])]
+#[case::romaji(&["ka", "ー", "ki"], &["ka", "a", "ki"])]
+#[case::vcv(&["- か", "a ー", "a き"], &["- か", "a あ", "a き"])]
+fn vowel_extensions(mut project: Project, #[case] collapsed: &[&str], #[case] expanded: &[&str]) {
+    let notes = &mut project.data.project_mut().tracks[0].notes;
+    notes.truncate(collapsed.len());
+    for (note, lyric) in notes.iter_mut().zip(collapsed) {
+        note.lyric = lyric.to_string();
+    }
+    let lyrics = |project: &Project| {
+        project.data.project().tracks[0]
+            .notes
+            .iter()
+            .map(|note| note.lyric.clone())
+            .collect::<Vec<_>>()
+    };
+
+    project.expand_vowel_extensions();
+    assert_eq!(lyrics(&project), expanded);
+    project.collapse_vowel_extensions();
+    let collapsed = collapsed
+        .iter()
+        .map(|lyric| if lyric.ends_with('ー') { "ー" } else { lyric })
+        .collect::<Vec<_>>();
+    assert_eq!(lyrics(&project), collapsed);
+}
+
+#[rstest::rstest]
+fn collapse_vowel_extensions_after_rest(mut project: Project) {
+    let notes = &mut project.data.project_mut().tracks[0].notes;
+    notes.truncate(3);
+    notes[0].lyric = "か".to_string();
+    notes[1].lyric = "あ".to_string();
+    notes[2].lyric = "あ".to_string();
+    notes[2].tick_on = notes[2].tick_on.saturating_add(240);
+
+    project.collapse_vowel_extensions();
+    let notes = &project.data.project().tracks[0].notes;
+    assert_eq!(notes[1].lyric, "ー");
+    assert_eq!(notes[2].lyric, "あ");
+}
+
+#[test]
+fn lyrics_type_analysis_is_ambiguous() {
+    let candidate = |lyrics_type, ratio| LyricsTypeCandidate { lyrics_type, ratio };
+    let track = |lyrics_type| TrackLyricsTypeAnalysis {
+        lyrics_type,
+        candidates: vec![],
+    };
+    let mut analysis = LyricsTypeAnalysis {
+        lyrics_type: Some(JapaneseLyricsType::KanaVcv),
+        candidates: vec![
+            candidate(JapaneseLyricsType::KanaVcv, 0.9),
+            candidate(JapaneseLyricsType::KanaCv, 0.1),
+        ],
+        tracks: vec![track(Some(JapaneseLyricsType::KanaVcv)), track(None)],
+    };
+    assert!(!analysis.is_ambiguous());
+
+    analysis.tracks[1].lyrics_type = Some(JapaneseLyricsType::RomajiCv);
+    assert!(analysis.is_ambiguous());
+
+    analysis.tracks.clear();
+    analysis.candidates[0].ratio = 0.6;
+    assert!(analysis.is_ambiguous());
+
+    analysis.lyrics_type = None;
+    assert!(analysis.is_ambiguous());
+}
+
+#[rstest::rstest]
+fn check_conversion(mut project: Project) {
+    let mut track = project.data.project().tracks[0].clone();
+    track.name = "トラック🎵".to_string();
+    track.notes[0].key = key(12);
+    track.notes[1].phoneme = Some("a".to_string());
+    track.pitch = Some(Pitch {
+        ticks: vec![0],
+        values: vec![Some(0.5)],
+        is_absolute: false,
+    });
+    project.data.project_mut().tracks.push(track);
+
+    assert!(project.data.check_conversion(Format::Vsqx).is_compatible());
+    assert_eq!(
+        project.data.check_conversion(Format::Ust).issues,
+        vec![
+            CompatibilityIssue::MultipleFiles { tracks: 2 },
+            CompatibilityIssue::PhonemesDropped { track: 1, notes: 1 },
+            CompatibilityIssue::KeysOutOfRange {
+                track: 1,
+                notes: vec![0]
+            },
+            CompatibilityIssue::UnencodableText { track: 1 },
+        ]
+    );
+    assert_eq!(
+        project.data.check_conversion(Format::StandardMid).issues,
+        vec![CompatibilityIssue::PhonemesDropped { track: 1, notes: 1 }]
+    );
+    assert_eq!(
+        project.data.check_conversion(Format::MusicXml).issues,
+        vec![
+            CompatibilityIssue::MultipleFiles { tracks: 2 },
+            CompatibilityIssue::PitchDropped { track: 1 },
+            CompatibilityIssue::PhonemesDropped { track: 1, notes: 1 },
+        ]
+    );
+}
+
+#[rstest::rstest]
+fn shift_all(mut project: Project) {
+    project.insert_tempo(tick(960), 150).unwrap();
+    let before = project.data.clone();
+    let options = ShiftOptions {
+        tempos: true,
+        time_signatures: true,
+    };
+    let error = project.shift_all(-480, options).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::IllegalNotePosition));
+    let error = project.shift_all(480, options).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::IllegalTimeSignature));
+    assert_eq!(project.data, before);
+
+    project.data.project_mut().key_signatures = vec![
+        KeySignature::default(),
+        KeySignature {
+            measure_position: 5,
+            tonic: 7,
+            mode: Mode::Major,
+        },
+    ];
+    let mut off_boundary = project.clone();
+    project.shift_all(1920, options).unwrap();
+    let data = project.data.project();
+    assert_eq!(data.tracks[0].notes[0].tick_on, 1920);
+    // The key signature at the start stays, and the one at tick 9120 moves to tick 11040, which
+    // is measure 6 after the 3/4 at measure 5.
+    assert_eq!(
+        data.key_signatures
+            .iter()
+            .map(|key_signature| key_signature.measure_position)
+            .collect::<Vec<_>>(),
+        vec![0, 6]
+    );
+    assert_eq!(
+        data.tempos
+            .iter()
+            .map(|tempo| (tempo.tick_position.get(), tempo.bpm))
+            .collect::<Vec<_>>(),
+        vec![(0, 120), (2880, 150)]
+    );
+    assert_eq!(time_signatures(&project), vec![(0, 4, 4), (5, 3, 4)]);
+    // Without the 3/4, the key signature at tick 9600 would move to tick 10560, which is off a
+    // measure boundary.
+    off_boundary.data.project_mut().time_signatures.truncate(1);
+    let before = off_boundary.data.clone();
+    let error = off_boundary.shift_all(960, options).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::IllegalTimeSignature));
+    assert_eq!(off_boundary.data, before);
+
+    // Only notes and pitch move by default.
+    let track = &mut project.data.project_mut().tracks[0];
+    track.pitch = Some(Pitch {
+        ticks: vec![0, 1920],
+        values: vec![Some(0.0), Some(1.0)],
+        is_absolute: false,
+    });
+    project.shift_all(-1920, ShiftOptions::default()).unwrap();
+    let data = project.data.project();
+    assert_eq!(data.tracks[0].notes[0].tick_on, 0);
+    assert_eq!(data.tracks[0].pitch.as_ref().unwrap().ticks, vec![0]);
+    assert_eq!(data.tempos[1].tick_position, 2880);
+}
+
+#[rstest::rstest]
+fn align_to_measure(mut project: Project) {
+    project.shift_all(2000, ShiftOptions::default()).unwrap();
+    let mut padded = project.clone();
+
+    let ticks = project.align_to_measure(AlignOptions::default()).unwrap();
+    assert_eq!(ticks, -80);
+    assert_eq!(project.data.project().tracks[0].notes[0].tick_on, 1920);
+
+    let ticks = padded
+        .align_to_measure(AlignOptions {
+            pad: true,
+            measure_prefix: Some(Project::required_measure_prefix(Format::Vsq)),
+        })
+        .unwrap();
+    assert_eq!(ticks, 1840);
+    let data = padded.data.project();
+    assert_eq!(data.measure_prefix, 1);
+    assert_eq!(data.tracks[0].notes[0].tick_on, 5760);
+}
+
+#[test]
+fn cleanup_track() {
+    let note = |tick_on, tick_off, lyric: &str| Note {
+        key: key(60),
+        tick_on: tick(tick_on),
+        tick_off: tick(tick_off),
+        lyric: lyric.to_string(),
+        phoneme: None,
+        phoneme_segments: None,
+    };
+    let mut track = Track {
+        name: "Track".to_string(),
+        notes: vec![
+            note(480, 960, "い"),
+            note(0, 480, "あ"),
+            note(0, 0, "あ"),
+            note(960, 480, "う"),
+            note(480, 960, "い"),
+            note(600, 700, "い"),
+            note(600, 700, "え"),
+            note(0, 960, "あ"),
+        ],
+        pitch: None,
+        muted: None,
+        solo: None,
+        order: None,
+        color: None,
+        voice: None,
+        parameters: None,
+    };
+
+    assert_eq!(track.cleanup(), 5);
+    assert_eq!(
+        track.notes,
+        vec![
+            note(0, 960, "あ"),
+            note(480, 960, "い"),
+            note(600, 700, "え")
+        ]
+    );
+}
+
+#[test]
+fn model_traits() {
+    use std::{collections::HashSet, str::FromStr};
+
+    let formats = Format::all().collect::<HashSet<_>>();
+    assert_eq!(formats.len(), Format::all().count());
+    for format in Format::all() {
+        assert_eq!(Format::from_str(&format.to_string()).unwrap(), format);
+    }
+    assert_eq!(Format::StandardMid.to_string(), "StandardMid");
+
+    let note = |tick_on, note_key, lyric: &str| Note {
+        key: key(note_key),
+        tick_on: tick(tick_on),
+        tick_off: tick(tick_on + 480),
+        lyric: lyric.to_string(),
+        phoneme: None,
+        phoneme_segments: None,
+    };
+    let mut notes = vec![note(480, 60, "あ"), note(0, 62, "い"), note(0, 60, "う")];
+    notes.sort();
+    assert_eq!(
+        notes,
+        vec![note(0, 60, "う"), note(0, 62, "い"), note(480, 60, "あ")]
+    );
+}
+
+#[test]
+fn checked_ticks_and_keys() {
+    assert_eq!(Tick::new(-1), None);
+    assert_eq!(tick(480).checked_add(-960), None);
+    assert_eq!(tick(480).saturating_add(-960), Tick::ZERO);
+    assert_eq!(tick(960) - tick(480), 480);
+    assert_eq!(Tick::from_beats(2), Some(tick(960)));
+
+    assert_eq!(Key::new(128), None);
+    assert_eq!(key(60).checked_add(-61), None);
+    assert_eq!(key(72) - key(60), 12);
+    assert_eq!(key(61).name(), "C#4");
+    assert_eq!("A#3".parse::<Key>().unwrap(), key(58));
+    assert!("B9".parse::<Key>().is_err());
+    // Octaves too far out of range are rejected instead of overflowing.
+    assert!("C999999999".parse::<Key>().is_err());
+    assert!("B-999999999".parse::<Key>().is_err());
+
+    // Both are (de)serialized as numbers, and deserializing checks them.
+    let note: Note =
+        serde_json::from_str(r#"{"key":127,"tickOn":0,"tickOff":480,"lyric":"あ"}"#).unwrap();
+    assert_eq!((note.key, note.tick_off), (key(127), tick(480)));
+    assert_eq!(
+        serde_json::to_string(&note).unwrap(),
+        r#"{"key":127,"tickOn":0,"tickOff":480,"lyric":"あ","phoneme":null}"#
+    );
+    for note in [
+        r#"{"key":130,"tickOn":0,"tickOff":480,"lyric":"あ"}"#,
+        r#"{"key":-1,"tickOn":0,"tickOff":480,"lyric":"あ"}"#,
+        r#"{"key":60,"tickOn":-480,"tickOff":0,"lyric":"あ"}"#,
+    ] {
+        assert!(serde_json::from_str::<Note>(note).is_err(), "{}", note);
+    }
+}
+
+#[rstest::rstest]
+fn project_summary(mut project: Project) {
+    // 8 beats at 120 BPM, and then 1,000 beats at 150 BPM.
+    project.insert_tempo(tick(3840), 150).unwrap();
+    project.data.project_mut().tracks[0].notes[7].tick_off = tick(480 * 1008);
+    assert_eq!(
+        project.to_string(),
+        "Project: 1 track, 8 notes, 120–150 BPM, 4/4, 6:44"
+    );
+
+    let mut tracks = project.data.project().tracks.clone();
+    tracks[0].notes = tracks[0].notes.iter().cycle().take(1600).cloned().collect();
+    project.data.project_mut().tracks.extend(tracks);
+    project.data.project_mut().tempos.truncate(1);
+    let summary = project.summary();
+    assert_eq!(summary.notes, 1608);
+    assert_eq!(
+        summary.to_string(),
+        "2 tracks, 1,608 notes, 120 BPM, 4/4, 8:24"
+    );
+}
+
+#[rstest::rstest]
+fn debug_output(project: Project) {
+    let full = format!("{:?}", project.data.debug_full());
+    assert_eq!(full.matches("Note {").count(), 8);
+
+    let debug = format!("{:?}", project.data);
+    if cfg!(feature = "compact-debug") {
+        assert_eq!(debug.matches("Note {").count(), 3);
+        assert!(debug.contains("... 5 more"));
+    } else {
+        assert_eq!(debug, full);
+    }
+}
+
+#[test]
+fn canonical_json() {
+    let data = UfData::new(UfProject {
+        name: "Project".to_string(),
+        tracks: vec![Track {
+            name: "Track".to_string(),
+            notes: vec![Note {
+                key: key(60),
+                tick_on: Tick::ZERO,
+                tick_off: tick(480),
+                lyric: "あ".to_string(),
+                phoneme: None,
+                phoneme_segments: None,
+            }],
+            pitch: Some(Pitch {
+                ticks: vec![0, 240, 480],
+                values: vec![Some(0.1 + 0.2), None, Some(-0.0000001)],
+                is_absolute: false,
+            }),
+            muted: None,
+            solo: None,
+            order: None,
+            color: None,
+            voice: None,
+            parameters: None,
+        }],
+        time_signatures: vec![TimeSignature::default()],
+        tempos: vec![Tempo {
+            tick_position: Tick::ZERO,
+            bpm: 120,
+        }],
+        measure_prefix: 0,
+        key_signatures: vec![],
+    });
+
+    let json = data.to_canonical_json().unwrap();
+    assert_eq!(
+        json,
+        r#"{
+  "formatVersion": 1,
+  "project": {
+    "measurePrefix": 0,
+    "name": "Project",
+    "tempos": [
+      {
+        "bpm": 120,
+        "tickPosition": 0
+      }
+    ],
+    "timeSignatures": [
+      {
+        "denominator": 4,
+        "measurePosition": 0,
+        "numerator": 4
+      }
+    ],
+    "tracks": [
+      {
+        "name": "Track",
+        "notes": [
+          {
+            "key": 60,
+            "lyric": "あ",
+            "tickOff": 480,
+            "tickOn": 0
+          }
+        ],
+        "pitch": {
+          "isAbsolute": false,
+          "ticks": [
+            0,
+            240,
+            480
+          ],
+          "values": [
+            0.3,
+            null,
+            0.0
+          ]
+        }
+      }
+    ]
+  }
+}
+"#
+    );
+    assert!(UfData::from_bytes(json.as_bytes(), Default::default()).is_ok());
+}
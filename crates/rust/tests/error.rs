@@ -0,0 +1,49 @@
+use utaformatix::{Error, ErrorKind, IllegalFile};
+
+#[test]
+fn illegal_file_fields_flow_through() {
+    let error = Error::from(IllegalFile::IllegalXmlAttribute {
+        name: "note".to_string(),
+        attribute: "pitch".to_string(),
+    });
+
+    assert_eq!(
+        error.to_string(),
+        "The file is illegal: The attribute `pitch` of XML element `note` is illegal."
+    );
+    let ErrorKind::IllegalFile(kind) = error.kind() else {
+        panic!("Unexpected error kind: {:?}", error.kind());
+    };
+    assert_eq!(kind.name(), Some("note"));
+    assert_eq!(kind.attribute(), Some("pitch"));
+    assert_eq!(error.details().element_path.as_deref(), Some("note/@pitch"));
+    assert_eq!(
+        serde_json::to_value(kind).unwrap(),
+        serde_json::json!({
+            "type": "illegalXmlAttribute",
+            "name": "note",
+            "attribute": "pitch",
+        })
+    );
+}
+
+#[rstest::rstest]
+#[case::without_detail(
+    ErrorKind::EmptyProject.into(),
+    serde_json::json!({ "kind": "emptyProject" })
+)]
+#[case::unexpected(
+    ErrorKind::Unexpected("oops".to_string()).into(),
+    serde_json::json!({ "kind": "unexpected", "detail": "oops" })
+)]
+#[case::illegal_file(
+    IllegalFile::XmlElementNotFound { name: "note".to_string() }.into(),
+    serde_json::json!({
+        "kind": "illegalFile",
+        "detail": { "type": "xmlElementNotFound", "name": "note" },
+        "elementPath": "note",
+    })
+)]
+fn serialize_error(#[case] error: Error, #[case] expected: serde_json::Value) {
+    assert_eq!(serde_json::to_value(&error).unwrap(), expected);
+}
@@ -12,7 +12,7 @@ fn utaformatix() -> utaformatix_rs::base::UtaFormatix {
 async fn analyze_japanese_lyrics_type(utaformatix: utaformatix_rs::base::UtaFormatix) {
     let data = include_bytes!("../utaformatix-ts/testAssets/tsukuyomi_vcv.ust");
     let options = ParseOptions::default();
-    let result = utaformatix.parse_ust(&[data], options).await;
+    let result = utaformatix.parse_ust([data.as_slice()], options).await;
 
     let parsed = result.expect("Failed to parse data");
 
@@ -21,7 +21,15 @@ async fn analyze_japanese_lyrics_type(utaformatix: utaformatix_rs::base::UtaForm
         .await
         .expect("Failed to analyze Japanese lyrics type");
 
-    assert_eq!(result, Some(utaformatix_rs::JapaneseLyricsType::KanaVcv));
+    assert_eq!(
+        result.lyrics_type,
+        Some(utaformatix_rs::JapaneseLyricsType::KanaVcv)
+    );
+    assert_eq!(
+        result.candidates[0].lyrics_type,
+        utaformatix_rs::JapaneseLyricsType::KanaVcv
+    );
+    assert_eq!(result.tracks.len(), 1);
 }
 
 #[rstest::rstest]
@@ -30,7 +38,7 @@ async fn analyze_japanese_lyrics_type(utaformatix: utaformatix_rs::base::UtaForm
 async fn convert_japanese_lyrics(utaformatix: utaformatix_rs::base::UtaFormatix) {
     let data = include_bytes!("../utaformatix-ts/testAssets/tsukuyomi_vcv.ust");
     let options = ParseOptions::default();
-    let result = utaformatix.parse_ust(&[data], options).await;
+    let result = utaformatix.parse_ust([data.as_slice()], options).await;
 
     let parsed = result.expect("Failed to parse data");
 
@@ -46,3 +54,173 @@ async fn convert_japanese_lyrics(utaformatix: utaformatix_rs::base::UtaFormatix)
 
     insta::assert_debug_snapshot!(result);
 }
+
+#[rstest::rstest]
+#[tokio::test]
+#[traced_test]
+async fn convert_japanese_lyrics_with_adjustments(utaformatix: utaformatix_rs::base::UtaFormatix) {
+    let data = include_bytes!("../utaformatix-ts/testAssets/tsukuyomi_vcv.ust");
+    let parsed = utaformatix
+        .parse_ust([data.as_slice()], ParseOptions::default())
+        .await
+        .expect("Failed to parse data");
+
+    let result = utaformatix
+        .convert_japanese_lyrics(
+            parsed,
+            utaformatix_rs::JapaneseLyricsType::KanaVcv,
+            utaformatix_rs::JapaneseLyricsType::KanaVcv,
+            utaformatix_rs::ConvertJapaneseLyricsOptions {
+                long_vowel_mark: utaformatix_rs::LongVowelMark::PreviousVowel,
+                n_transition: utaformatix_rs::NTransition::Rest,
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to convert Japanese lyrics");
+
+    for note in &result.project().tracks[0].notes {
+        assert!(!note.lyric.starts_with("n "), "{}", note.lyric);
+        assert_ne!(note.lyric.rsplit(' ').next(), Some("ー"));
+    }
+}
+
+#[rstest::rstest]
+#[tokio::test]
+#[traced_test]
+async fn convert_track_lyrics(utaformatix: utaformatix_rs::base::UtaFormatix) {
+    let data = include_bytes!("../utaformatix-ts/testAssets/tsukuyomi_vcv.ust");
+    let parsed = utaformatix
+        .parse_ust([data.as_slice()], ParseOptions::default())
+        .await
+        .expect("Failed to parse data");
+    let converted = utaformatix
+        .convert_japanese_lyrics(
+            parsed.clone(),
+            utaformatix_rs::JapaneseLyricsType::KanaVcv,
+            utaformatix_rs::JapaneseLyricsType::RomajiCv,
+            Default::default(),
+        )
+        .await
+        .expect("Failed to convert Japanese lyrics");
+
+    // The source type is detected from the track.
+    let mut track = parsed.project().tracks[0].clone();
+    track
+        .convert_lyrics(
+            None,
+            utaformatix_rs::JapaneseLyricsType::RomajiCv,
+            Default::default(),
+        )
+        .await
+        .expect("Failed to convert the lyrics of the track");
+
+    assert_eq!(track, converted.project().tracks[0]);
+}
+
+#[rstest::rstest]
+#[tokio::test]
+#[traced_test]
+async fn convert_japanese_lyrics_keeps_extensions(utaformatix: utaformatix_rs::base::UtaFormatix) {
+    use utaformatix_rs::{
+        Format, Key, KeySignature, Mode, Note, ParameterPoint, PhonemeSegment, Tempo, Tick,
+        TimeSignature, Track, TrackColor, TrackParameters, TrackVoice, UfData, UfProject,
+    };
+
+    let notes = ["か", "さ", "た"]
+        .iter()
+        .enumerate()
+        .map(|(i, lyric)| Note {
+            key: Key::new(60 + i as i32).unwrap(),
+            tick_on: Tick::new(i as i64 * 480).unwrap(),
+            tick_off: Tick::new((i as i64 + 1) * 480).unwrap(),
+            lyric: lyric.to_string(),
+            phoneme: None,
+            phoneme_segments: Some(vec![PhonemeSegment {
+                phoneme: "a".to_string(),
+                duration_scale: 1.5,
+            }]),
+        })
+        .collect();
+    let track = Track {
+        name: "Track".to_string(),
+        notes,
+        pitch: None,
+        muted: Some(true),
+        solo: Some(false),
+        order: Some(2),
+        color: Some(TrackColor {
+            format: Format::Svp,
+            value: "ff7db235".to_string(),
+        }),
+        voice: Some(TrackVoice {
+            format: Format::Ccs,
+            cast: "A".to_string(),
+            language: Some("ja".to_string()),
+        }),
+        parameters: Some(TrackParameters {
+            format: Format::Ustx,
+            curves: [(
+                "dyn".to_string(),
+                vec![ParameterPoint {
+                    tick: Tick::ZERO,
+                    value: 10,
+                }],
+            )]
+            .into(),
+        }),
+    };
+    let data = UfData::new(UfProject {
+        name: "Project".to_string(),
+        tracks: vec![track],
+        time_signatures: vec![TimeSignature::default()],
+        tempos: vec![Tempo {
+            tick_position: Tick::ZERO,
+            bpm: 120,
+        }],
+        measure_prefix: 0,
+        key_signatures: vec![KeySignature {
+            measure_position: 0,
+            tonic: 2,
+            mode: Mode::Major,
+        }],
+    });
+
+    let converted = utaformatix
+        .convert_japanese_lyrics(
+            data.clone(),
+            utaformatix_rs::JapaneseLyricsType::KanaCv,
+            utaformatix_rs::JapaneseLyricsType::RomajiCv,
+            Default::default(),
+        )
+        .await
+        .expect("Failed to convert Japanese lyrics");
+    let lyrics: Vec<_> = converted.project().tracks[0]
+        .notes
+        .iter()
+        .map(|note| note.lyric.as_str())
+        .collect();
+    assert_eq!(lyrics, ["ka", "sa", "ta"]);
+
+    // Everything but the lyrics is kept, including what the engine doesn't know.
+    let mut expected = data.clone();
+    for (note, lyric) in expected.project_mut().tracks[0]
+        .notes
+        .iter_mut()
+        .zip(lyrics)
+    {
+        note.lyric = lyric.to_string();
+    }
+    assert_eq!(converted, expected);
+
+    let mut track = data.project().tracks[0].clone();
+    track
+        .convert_lyrics(
+            Some(utaformatix_rs::JapaneseLyricsType::KanaCv),
+            utaformatix_rs::JapaneseLyricsType::RomajiCv,
+            Default::default(),
+        )
+        .await
+        .expect("Failed to convert the lyrics of the track");
+    assert_eq!(track, expected.project().tracks[0]);
+}
@@ -0,0 +1,2716 @@
+use tracing_test::traced_test;
+use utaformatix::{
+    base::UtaFormatix, BreathPolicy, CcsOptions, CompatibilityIssue, DefaultLyric, DynamicsMapping,
+    ErrorKind, Format, FormatOptions, GenerateOptions, Key, KeyRangePolicy, KeySignature,
+    LyricMarkers, Mode, MusicXmlOptions, MusicXmlParseOptions, Note, ParameterPoint, ParseOptions,
+    PhonemeSegment, Pitch, Ppsf, PpsfInfo, Priority, StandardMidOptions, StandardMidParseOptions,
+    SvpOptions, Tempo, Tick, TimeSignature, Track, TrackColor, TrackParameters, TrackVoice, UfData,
+    UfProject, UstOptions, UstPitchMode,
+};
+
+#[rstest::fixture]
+fn engine() -> UtaFormatix {
+    UtaFormatix::new()
+}
+
+fn sample_data() -> UfData {
+    let notes = ["ど", "れ", "み", "ふぁ"]
+        .iter()
+        .enumerate()
+        .map(|(i, lyric)| Note {
+            key: Key::new(60 + i as i32 * 2).unwrap(),
+            tick_on: Tick::new(1920 + i as i64 * 480).unwrap(),
+            tick_off: Tick::new(1920 + (i as i64 + 1) * 480).unwrap(),
+            lyric: lyric.to_string(),
+            phoneme: None,
+            phoneme_segments: None,
+        })
+        .collect();
+    UfData::new(UfProject {
+        name: "data.mid".to_string(),
+        tracks: vec![Track {
+            name: "Vocal".to_string(),
+            notes,
+            pitch: None,
+            muted: None,
+            solo: None,
+            order: None,
+            color: None,
+            voice: None,
+            parameters: None,
+        }],
+        time_signatures: vec![
+            TimeSignature::default(),
+            TimeSignature {
+                measure_position: 1,
+                numerator: 3,
+                denominator: 4,
+            },
+        ],
+        tempos: vec![
+            Tempo {
+                tick_position: Tick::ZERO,
+                bpm: 120,
+            },
+            Tempo {
+                tick_position: Tick::new(1920).unwrap(),
+                bpm: 150,
+            },
+        ],
+        measure_prefix: 0,
+        key_signatures: vec![],
+    })
+}
+
+#[rstest::rstest]
+#[tokio::test]
+#[traced_test]
+async fn standard_mid_round_trip(engine: UtaFormatix) {
+    let data = sample_data();
+    let generated = engine
+        .generate_standard_mid(&data, GenerateOptions::default())
+        .await
+        .expect("Failed to generate data");
+    let parsed = engine
+        .parse_standard_mid(generated, ParseOptions::default())
+        .await
+        .expect("Failed to parse data");
+
+    assert_eq!(parsed, data);
+}
+
+#[rstest::rstest]
+#[tokio::test]
+#[traced_test]
+async fn uf_data_round_trip(engine: UtaFormatix) {
+    let data = sample_data();
+    let generated = engine
+        .generate_uf_data(
+            &data,
+            GenerateOptions {
+                pitch: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to generate data");
+    let parsed = engine
+        .parse_uf_data(generated, ParseOptions::default())
+        .await
+        .expect("Failed to parse data");
+
+    assert_eq!(parsed, data);
+}
+
+#[rstest::rstest]
+#[tokio::test]
+async fn uf_data_sorts_signatures(engine: UtaFormatix) {
+    let mut data = sample_data();
+    data.project_mut().time_signatures = vec![
+        TimeSignature {
+            measure_position: 2,
+            numerator: 3,
+            denominator: 4,
+        },
+        TimeSignature::default(),
+    ];
+    data.project_mut().key_signatures = vec![
+        KeySignature {
+            measure_position: 1,
+            tonic: 7,
+            mode: Mode::Major,
+        },
+        KeySignature {
+            measure_position: 0,
+            tonic: 0,
+            mode: Mode::Major,
+        },
+    ];
+    let parsed = engine
+        .parse_uf_data(serde_json::to_vec(&data).unwrap(), ParseOptions::default())
+        .await
+        .unwrap();
+    let project = parsed.project();
+    assert_eq!(
+        project
+            .time_signatures
+            .iter()
+            .map(|time_signature| time_signature.measure_position)
+            .collect::<Vec<_>>(),
+        [0, 2]
+    );
+    assert_eq!(
+        project
+            .key_signatures
+            .iter()
+            .map(|key_signature| key_signature.measure_position)
+            .collect::<Vec<_>>(),
+        [0, 1]
+    );
+    assert_eq!(project.measure_to_tick(3), 1920 * 2 + 1440);
+}
+
+#[rstest::rstest]
+#[case::zero(0)]
+#[case::shorter_than_a_tick(16384)]
+#[tokio::test]
+async fn uf_data_illegal_time_signature(engine: UtaFormatix, #[case] denominator: i32) {
+    let mut data = sample_data();
+    data.project_mut().time_signatures[0].denominator = denominator;
+    let error = engine
+        .parse_uf_data(serde_json::to_vec(&data).unwrap(), ParseOptions::default())
+        .await
+        .unwrap_err();
+
+    assert!(matches!(error.kind(), ErrorKind::IllegalTimeSignature));
+}
+
+#[rstest::rstest]
+#[case::keep(BreathPolicy::Keep, vec!["ど", "息", "み", "ふぁ"])]
+#[case::strip(BreathPolicy::Strip, vec!["ど", "み", "ふぁ"])]
+#[case::convert(BreathPolicy::Convert, vec!["ど", "br", "み", "ふぁ"])]
+#[tokio::test]
+#[traced_test]
+async fn breath_policy(
+    engine: UtaFormatix,
+    #[case] breath: BreathPolicy,
+    #[case] expected: Vec<&str>,
+) {
+    let mut data = sample_data();
+    data.project_mut().tracks[0].notes[1].lyric = "息".to_string();
+    let generated = engine
+        .generate_uf_data(
+            &data,
+            GenerateOptions {
+                breath,
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to generate data");
+    let parsed = engine
+        .parse_uf_data(generated, ParseOptions::default())
+        .await
+        .expect("Failed to parse data");
+
+    let lyrics = parsed.project().tracks[0]
+        .notes
+        .iter()
+        .map(|note| note.lyric.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(lyrics, expected);
+}
+
+#[tokio::test]
+async fn key_range_policy() {
+    let utaformatix = UtaFormatix::builder()
+        .bundle(
+            r#"var utaformatix = {
+                generateSvp: async (data, options) => new Uint8Array(__encode(JSON.stringify(
+                    data.project.tracks[0].notes.map((note) => note.key)
+                ))),
+            };"#,
+        )
+        .build();
+    let mut data = sample_data();
+    // B8, which is in the range of MIDI but not of Synthesizer V.
+    data.project_mut().tracks[0].notes[2].key = Key::new(119).unwrap();
+    let error = utaformatix
+        .generate_svp(&data, GenerateOptions::default())
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error.kind(),
+        ErrorKind::KeyOutOfRange {
+            track: 0,
+            note: 2,
+            key: 119
+        }
+    ));
+    assert_eq!(error.details().format, Some(Format::Svp));
+
+    let generated = utaformatix
+        .generate_svp(
+            &data,
+            GenerateOptions {
+                key_range: KeyRangePolicy::FoldOctaves,
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to generate data");
+    let keys: Vec<i32> = serde_json::from_slice(&generated).unwrap();
+    assert_eq!(keys[2], 107);
+    assert_eq!(Format::Ust.key_range(), 24..=107);
+}
+
+#[rstest::rstest]
+#[case::numerator_over_255(256, 4)]
+#[case::denominator_not_power_of_two(4, 3)]
+#[tokio::test]
+async fn standard_mid_illegal_time_signature(
+    engine: UtaFormatix,
+    #[case] numerator: i32,
+    #[case] denominator: i32,
+) {
+    let mut data = sample_data();
+    data.project_mut().time_signatures[0].numerator = numerator;
+    data.project_mut().time_signatures[0].denominator = denominator;
+    let error = engine
+        .generate_standard_mid(&data, GenerateOptions::default())
+        .await
+        .unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::IllegalTimeSignature));
+}
+
+#[rstest::rstest]
+#[case::zero(0)]
+#[case::longer_than_24_bits(3)]
+#[tokio::test]
+async fn standard_mid_illegal_tempo(engine: UtaFormatix, #[case] bpm: i32) {
+    let mut data = sample_data();
+    data.project_mut().tempos[0].bpm = bpm;
+    let error = engine
+        .generate_standard_mid(&data, GenerateOptions::default())
+        .await
+        .unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::IllegalTempo));
+
+    data.project_mut().tempos[0].bpm = 4;
+    engine
+        .generate_standard_mid(&data, GenerateOptions::default())
+        .await
+        .expect("Failed to generate data");
+}
+
+#[rstest::rstest]
+#[tokio::test]
+async fn standard_mid_delta_too_long(engine: UtaFormatix) {
+    let mut data = sample_data();
+    let note = data.project_mut().tracks[0].notes.last_mut().unwrap();
+    note.tick_on = Tick::new(1 << 29).unwrap();
+    note.tick_off = Tick::new((1 << 29) + 480).unwrap();
+    let error = engine
+        .generate_standard_mid(&data, GenerateOptions::default())
+        .await
+        .unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::IllegalNotePosition));
+}
+
+#[rstest::rstest]
+#[case::running_status(b"\x00\x90\x3c\x64\x83\x60\x3c\x00", true)]
+#[case::running_status_after_meta(b"\x00\x90\x3c\x64\x00\xff\x05\x01a\x83\x60\x3c\x00", false)]
+#[case::running_status_after_sysex(b"\x00\x90\x3c\x64\x00\xf0\x01\xf7\x83\x60\x3c\x00", false)]
+#[case::status_as_data(b"\x00\x90\xbc\x64\x83\x60\x80\x3c\x00", false)]
+#[tokio::test]
+async fn standard_mid_running_status(
+    engine: UtaFormatix,
+    #[case] events: &[u8],
+    #[case] is_valid: bool,
+) {
+    let mut midi = b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\x01\xe0MTrk".to_vec();
+    midi.extend_from_slice(&(events.len() as u32 + 4).to_be_bytes());
+    midi.extend_from_slice(events);
+    midi.extend_from_slice(b"\x00\xff\x2f\x00");
+    let result = engine
+        .parse_standard_mid(midi, ParseOptions::default())
+        .await;
+
+    if is_valid {
+        let data = result.unwrap();
+        let note = &data.project().tracks[0].notes[0];
+        assert_eq!((note.tick_on.get(), note.tick_off.get()), (0, 480));
+    } else {
+        assert!(matches!(
+            result.unwrap_err().kind(),
+            ErrorKind::IllegalFile(utaformatix::IllegalFile::IllegalMidiFile)
+        ));
+    }
+}
+
+#[rstest::rstest]
+#[tokio::test]
+#[traced_test]
+async fn standard_mid_format_options(engine: UtaFormatix) {
+    let data = sample_data();
+    let generated = engine
+        .generate_standard_mid(
+            &data,
+            GenerateOptions {
+                format_options: Some(FormatOptions::StandardMid(StandardMidOptions {
+                    channel: 3,
+                    pitch_bend_range: Some(12),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to generate data");
+
+    // Pitch bend sensitivity (RPN 0) on channel 3, and a note on on channel 3.
+    let contains = |bytes: &[u8]| generated.windows(bytes.len()).any(|w| w == bytes);
+    assert!(contains(&[0xb3, 101, 0, 0, 0xb3, 100, 0, 0, 0xb3, 6, 12]));
+    assert!(contains(&[0x93, 60, 100]));
+
+    let parsed = engine
+        .parse_standard_mid(generated, ParseOptions::default())
+        .await
+        .expect("Failed to parse data");
+    assert_eq!(
+        parsed.project().tracks[0].notes,
+        data.project().tracks[0].notes
+    );
+}
+
+#[rstest::rstest]
+#[tokio::test]
+#[traced_test]
+async fn standard_mid_track_selection(engine: UtaFormatix) {
+    let mut data = sample_data();
+    let mut track = data.project().tracks[0].clone();
+    track.name = "Piano".to_string();
+    data.project_mut().tracks.push(track);
+    let generated = engine
+        .generate_standard_mid(&data, GenerateOptions::default())
+        .await
+        .expect("Failed to generate data");
+
+    // The first track chunk is the conductor track.
+    let parsed = engine
+        .parse_standard_mid(
+            generated.clone(),
+            ParseOptions {
+                standard_mid: StandardMidParseOptions {
+                    tracks: Some(vec![2]),
+                    track_names: [("Piano".to_string(), "Accompaniment".to_string())].into(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to parse data");
+    assert_eq!(parsed.project().tracks.len(), 1);
+    assert_eq!(parsed.project().tracks[0].name, "Accompaniment");
+
+    let error = engine
+        .parse_standard_mid(
+            generated,
+            ParseOptions {
+                standard_mid: StandardMidParseOptions {
+                    channels: Some(vec![1]),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::EmptyProject));
+
+    let drums = engine
+        .generate_standard_mid(
+            &data,
+            GenerateOptions {
+                format_options: Some(FormatOptions::StandardMid(StandardMidOptions {
+                    channel: 9,
+                    ..Default::default()
+                })),
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to generate data");
+    let error = engine
+        .parse_standard_mid(
+            drums,
+            ParseOptions {
+                standard_mid: StandardMidParseOptions {
+                    skip_drums: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::EmptyProject));
+}
+
+#[rstest::rstest]
+#[tokio::test]
+#[traced_test]
+async fn standard_mid_pitch_bend(engine: UtaFormatix) {
+    let mut data = sample_data();
+    data.project_mut().tracks[0].pitch = Some(Pitch {
+        ticks: vec![1920, 2040, 2400],
+        values: vec![Some(0.0), Some(1.0), Some(-2.0)],
+        is_absolute: false,
+    });
+    let generated = engine
+        .generate_standard_mid(
+            &data,
+            GenerateOptions {
+                pitch: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to generate data");
+
+    // +1 and -2 semitones in the default range of 2 semitones.
+    let contains = |bytes: &[u8]| generated.windows(bytes.len()).any(|w| w == bytes);
+    assert!(contains(&[0xe0, 0x00, 0x60]));
+    assert!(contains(&[0xe0, 0x00, 0x00]));
+}
+
+#[tokio::test]
+async fn vocaloid_mid_ignores_standard_mid_options() {
+    let utaformatix = UtaFormatix::builder()
+        .bundle(
+            r#"var utaformatix = {
+                generateVocaloidMid: async (data, options) => new Uint8Array([0x4d, 0x54]),
+            };"#,
+        )
+        .build();
+    let generated = utaformatix
+        .generate_vocaloid_mid(
+            &sample_data(),
+            GenerateOptions {
+                pitch: true,
+                format_options: Some(FormatOptions::StandardMid(StandardMidOptions {
+                    pitch_bend_range: Some(12),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(generated, [0x4d, 0x54]);
+}
+
+#[rstest::rstest]
+#[tokio::test]
+#[traced_test]
+async fn music_xml_single_score(engine: UtaFormatix) {
+    let mut data = sample_data();
+    let mut track = data.project().tracks[0].clone();
+    track.name = "Chorus".to_string();
+    data.project_mut().tracks.push(track);
+    let generated = engine
+        .generate_music_xml(
+            &data,
+            GenerateOptions {
+                format_options: Some(FormatOptions::MusicXml(MusicXmlOptions {
+                    single_score: true,
+                })),
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to generate data");
+
+    assert_eq!(generated.len(), 1);
+    let score = String::from_utf8(generated[0].clone()).unwrap();
+    assert_eq!(score.matches("<score-part ").count(), 2);
+    assert!(score.contains("<part id=\"P1\""));
+    assert!(score.contains("<part id=\"P2\""));
+}
+
+#[tokio::test]
+async fn ust_format_options() {
+    let utaformatix = UtaFormatix::builder()
+        .bundle(
+            r#"var utaformatix = { generateUst: async (data, options) => [new Uint8Array(__encode(
+                "[#VERSION]\r\nUST Version1.2\r\nCharset=UTF-8\r\n[#SETTING]\r\nTempo=120\r\nMode2=True\r\n"
+                + "[#0000]\r\nLyric=あ\r\nPBS=-40;0\r\nPBW=80\r\n"
+                + "[#0001]\r\nLyric=い\r\nFlags=B0\r\n[#TRACKEND]\r\n"))] };"#,
+        )
+        .build();
+    async fn generate(utaformatix: &UtaFormatix, options: UstOptions) -> String {
+        let files = utaformatix
+            .generate_ust(
+                &sample_data(),
+                GenerateOptions {
+                    format_options: Some(FormatOptions::Ust(options)),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        String::from_utf8(files[0].clone()).unwrap()
+    }
+
+    let ust = generate(
+        &utaformatix,
+        UstOptions {
+            note_flags: Some("g-5".to_string()),
+            envelope: Some("5,35,0,100,100,100,100".to_string()),
+            ..Default::default()
+        },
+    )
+    .await;
+    assert!(ust.contains(
+        "[#0000]\r\nLyric=あ\r\nPBS=-40;0\r\nPBW=80\r\nFlags=g-5\r\nEnvelope=5,35,0,100,100,100,100\r\n"
+    ));
+    // Flags of the notes are kept.
+    assert!(ust.contains(
+        "[#0001]\r\nLyric=い\r\nFlags=B0\r\nEnvelope=5,35,0,100,100,100,100\r\n[#TRACKEND]"
+    ));
+
+    let ust = generate(
+        &utaformatix,
+        UstOptions {
+            mode2_pitch: Some(false),
+            ..Default::default()
+        },
+    )
+    .await;
+    assert!(!ust.contains("PBS=") && !ust.contains("PBW=") && !ust.contains("Mode2="));
+    assert!(ust.contains("[#0000]\r\nLyric=あ\r\n[#0001]"));
+}
+
+#[tokio::test]
+async fn ust_mode1_pitch() {
+    let utaformatix = UtaFormatix::builder()
+        .bundle(
+            r#"var utaformatix = {
+                parseUst: async (files, options) => ({
+                    formatVersion: 1,
+                    project: {
+                        name: 'Project',
+                        tracks: [{ name: 'Track', notes: [], pitch: null }],
+                        timeSignatures: [{ measurePosition: 0, numerator: 4, denominator: 4 }],
+                        tempos: [{ tickPosition: 0, bpm: 120 }],
+                        measurePrefix: 0,
+                    },
+                }),
+                generateUst: async (data, options) => [new Uint8Array(__encode(
+                    "[#SETTING]\nMode2=True\n[#0000]\nLength=1920\nLyric=R\n"
+                    + "[#0001]\nLength=480\nLyric=ど\nPBS=-40;0\n"
+                    + "[#0002]\nLength=480\nLyric=れ\n[#TRACKEND]\n"))],
+            };"#,
+        )
+        .build();
+
+    // -2.5 ms is -2 ticks at 120 BPM, so the first pitch bend is before the note.
+    let ust = b"[#0000]\nLength=480\nLyric=R\n\
+        [#0001]\nLength=20\nLyric=a\nPBStart=-2.5\nPitchBend=5,10,20,30\n[#TRACKEND]\n";
+    let mode1 = ParseOptions {
+        ust_pitch_mode: UstPitchMode::Mode1,
+        ..Default::default()
+    };
+    let parsed = utaformatix
+        .parse_ust([&ust[..]], mode1.clone())
+        .await
+        .unwrap();
+    let pitch = parsed.project().tracks[0].pitch.clone().unwrap();
+    assert!(!pitch.is_absolute);
+    assert_eq!(pitch.ticks, [0, 483, 488, 493]);
+    assert_eq!(pitch.values, [Some(0.0), Some(0.1), Some(0.2), Some(0.3)]);
+    let parsed = utaformatix
+        .parse_ust([&ust[..]], ParseOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(parsed.project().tracks[0].pitch, None);
+
+    let mut data = sample_data();
+    data.project_mut().tracks[0].pitch = Some(Pitch {
+        ticks: vec![1920, 1930, 2400],
+        values: vec![Some(0.0), Some(0.5), Some(0.0)],
+        is_absolute: false,
+    });
+    let files = utaformatix
+        .generate_ust(
+            &data,
+            GenerateOptions {
+                pitch: true,
+                format_options: Some(FormatOptions::Ust(UstOptions {
+                    pitch_mode: UstPitchMode::Mode1,
+                    ..Default::default()
+                })),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    let generated = String::from_utf8(files[0].clone()).unwrap();
+    let pitch_bend = format!("0,0{}", ",50".repeat(94));
+    assert_eq!(
+        generated,
+        format!(
+            "[#SETTING]\n[#0000]\nLength=1920\nLyric=R\n\
+            [#0001]\nLength=480\nLyric=ど\nPBType=5\nPBStart=0\nPitchBend={pitch_bend}\n\
+            [#0002]\nLength=480\nLyric=れ\n[#TRACKEND]\n"
+        )
+    );
+}
+
+#[tokio::test]
+async fn convert_s5p_to_svp() {
+    let utaformatix = UtaFormatix::builder()
+        .bundle(
+            r#"var utaformatix = {
+                parseS5p: async (data, options) => ({
+                    formatVersion: 1,
+                    project: {
+                        name: 'Project',
+                        tracks: [
+                            { name: 'Vocal', notes: [], pitch: null },
+                            { name: 'Chorus', notes: [], pitch: null },
+                        ],
+                        timeSignatures: [{ measurePosition: 0, numerator: 4, denominator: 4 }],
+                        tempos: [{ tickPosition: 0, bpm: 120 }],
+                        measurePrefix: 0,
+                    },
+                }),
+                generateSvp: async (data, options) => new Uint8Array(__encode(JSON.stringify({
+                    tracks: data.project.tracks.map((track) => ({
+                        name: track.name,
+                        mainRef: { database: { name: '' } },
+                    })),
+                }) + '\0')),
+            };"#,
+        )
+        .build();
+    let s5p =
+        r#"{"tracks":[{"name":"Vocal","dbName":"Eleanor Forte"},{"name":"Chorus","dbName":""}]}"#;
+    let database_names = |svp: Vec<u8>| {
+        assert_eq!(svp.last(), Some(&0));
+        let svp: serde_json::Value = serde_json::from_slice(&svp[..svp.len() - 1]).unwrap();
+        svp["tracks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|track| {
+                track["mainRef"]["database"]["name"]
+                    .as_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let svp = utaformatix
+        .convert_s5p_to_svp(s5p.as_bytes().to_vec(), SvpOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(database_names(svp), ["Eleanor Forte", ""]);
+
+    let svp = utaformatix
+        .convert_s5p_to_svp(
+            s5p.as_bytes().to_vec(),
+            SvpOptions {
+                database_name: Some("Solaria".to_string()),
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(database_names(svp), ["Solaria", "Solaria"]);
+}
+
+#[tokio::test]
+async fn svp_phoneme_segments() {
+    let utaformatix = UtaFormatix::builder()
+        .bundle(
+            r#"var utaformatix = {
+                parseSvp: async (data, options) => ({
+                    formatVersion: 1,
+                    project: {
+                        name: 'Project',
+                        tracks: [{
+                            name: 'Track',
+                            notes: [
+                                { key: 60, tickOn: 0, tickOff: 480, lyric: 'か', phoneme: 'k a' },
+                                { key: 62, tickOn: 480, tickOff: 960, lyric: 'な', phoneme: null },
+                            ],
+                            pitch: null,
+                        }],
+                        timeSignatures: [{ measurePosition: 0, numerator: 4, denominator: 4 }],
+                        tempos: [{ tickPosition: 0, bpm: 120 }],
+                        measurePrefix: 0,
+                    },
+                }),
+                generateSvp: async (data, options) => new Uint8Array(__encode(JSON.stringify({
+                    tracks: data.project.tracks.map((track) => ({
+                        mainGroup: {
+                            notes: track.notes.map((note) => ({
+                                onset: note.tickOn * 1470000,
+                                pitch: note.key,
+                                phonemes: '',
+                            })),
+                        },
+                    })),
+                }) + '\0')),
+            };"#,
+        )
+        .build();
+    let svp = r#"{"tracks":[{"mainGroup":{"notes":[
+        {"onset":0,"pitch":60,"phonemes":"","attributes":{"dur":[1.5,0.8]}},
+        {"onset":705600000,"pitch":62,"phonemes":"n a"}
+    ]}}]}"#;
+    let parsed = utaformatix
+        .parse_svp(svp.as_bytes().to_vec(), ParseOptions::default())
+        .await
+        .unwrap();
+    let notes = &parsed.project().tracks[0].notes;
+    let segment = |phoneme: &str, duration_scale: f64| PhonemeSegment {
+        phoneme: phoneme.to_string(),
+        duration_scale,
+    };
+    assert_eq!(
+        notes[0].phoneme_segments,
+        Some(vec![segment("k", 1.5), segment("a", 0.8)])
+    );
+    assert_eq!(notes[1].phoneme_segments, None);
+
+    let generated = utaformatix
+        .generate_svp(&parsed, GenerateOptions::default())
+        .await
+        .unwrap();
+    let generated: serde_json::Value =
+        serde_json::from_slice(&generated[..generated.len() - 1]).unwrap();
+    assert_eq!(
+        generated["tracks"][0]["mainGroup"]["notes"],
+        serde_json::json!([
+            { "onset": 0, "pitch": 60, "phonemes": "k a", "attributes": { "dur": [1.5, 0.8] } },
+            { "onset": 705600000, "pitch": 62, "phonemes": "" },
+        ])
+    );
+
+    let report = parsed.check_conversion(Format::Ust);
+    assert!(report
+        .issues
+        .contains(&CompatibilityIssue::PhonemeTimingDropped { track: 0, notes: 1 }));
+    assert!(!parsed
+        .check_conversion(Format::Svp)
+        .issues
+        .iter()
+        .any(|issue| matches!(issue, CompatibilityIssue::PhonemeTimingDropped { .. })));
+}
+
+#[tokio::test]
+async fn track_colors() {
+    let utaformatix = UtaFormatix::builder()
+        .bundle(
+            r#"var project = (names) => ({
+                formatVersion: 1,
+                project: {
+                    name: 'Project',
+                    tracks: names.map((name) => ({ name, notes: [], pitch: null })),
+                    timeSignatures: [{ measurePosition: 0, numerator: 4, denominator: 4 }],
+                    tempos: [{ tickPosition: 0, bpm: 120 }],
+                    measurePrefix: 0,
+                },
+            });
+            var utaformatix = {
+                parseUstx: async (data, options) => project(['Vocal', 'Chorus']),
+                parseSvp: async (data, options) => project(['Vocal']),
+                generateUstx: async (data, options) => new Uint8Array(__encode('name: Project\n'
+                    + 'tracks:\n'
+                    + data.project.tracks.map((track) => '- track_name: ' + track.name
+                        + '\n  renderer_settings:\n    renderer: CLASSIC\n'
+                        + '  track_color: Blue\n').join('')
+                    + 'voice_parts: []\n')),
+                generateSvp: async (data, options) => new Uint8Array(__encode(JSON.stringify({
+                    tracks: data.project.tracks.map((track) => ({ dispColor: 'ff7db235' })),
+                }) + '\0')),
+            };"#,
+        )
+        .build();
+    let ustx = "ustx_version: 0.6\n\
+        tracks:\n\
+        - singer: null\n  \
+          track_color: Pink\n  \
+          renderer_settings:\n    \
+            track_color: Green\n\
+        - track_color: 'Orange: \"#1\"'\n  \
+          mute: false\n\
+        voice_parts: []\n";
+    let parsed = utaformatix
+        .parse_ustx(ustx.as_bytes().to_vec(), ParseOptions::default())
+        .await
+        .unwrap();
+    let colors = parsed
+        .project()
+        .tracks
+        .iter()
+        .map(|track| track.color.clone())
+        .collect::<Vec<_>>();
+    let color = |format: Format, value: &str| TrackColor {
+        format,
+        value: value.to_string(),
+    };
+    assert_eq!(
+        colors,
+        [
+            Some(color(Format::Ustx, "Pink")),
+            Some(color(Format::Ustx, "Orange: \"#1\"")),
+        ]
+    );
+
+    let generated = utaformatix
+        .generate_ustx(&parsed, GenerateOptions::default())
+        .await
+        .unwrap();
+    // Colors are replaced in place, and quoted if they aren't plain YAML scalars.
+    assert_eq!(
+        String::from_utf8(generated).unwrap(),
+        "name: Project\n\
+        tracks:\n\
+        - track_name: Vocal\n  \
+          renderer_settings:\n    \
+            renderer: CLASSIC\n  \
+          track_color: Pink\n\
+        - track_name: Chorus\n  \
+          renderer_settings:\n    \
+            renderer: CLASSIC\n  \
+          track_color: 'Orange: \"#1\"'\n\
+        voice_parts: []\n"
+    );
+
+    // Colors of other formats are left as generated.
+    let mut data = parsed.clone();
+    data.project_mut().tracks.truncate(1);
+    let generated = utaformatix
+        .generate_svp(&data, GenerateOptions::default())
+        .await
+        .unwrap();
+    assert!(String::from_utf8(generated)
+        .unwrap()
+        .contains("\"ff7db235\""));
+    let parsed = utaformatix
+        .parse_svp(
+            b"{\"tracks\":[{\"dispColor\":\"ff3e8bd1\"}]}".to_vec(),
+            ParseOptions::default(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        parsed.project().tracks[0].color,
+        Some(color(Format::Svp, "ff3e8bd1"))
+    );
+    let generated = utaformatix
+        .generate_svp(&parsed, GenerateOptions::default())
+        .await
+        .unwrap();
+    assert!(String::from_utf8(generated)
+        .unwrap()
+        .contains("\"ff3e8bd1\""));
+}
+
+#[tokio::test]
+async fn svp_track_states() {
+    let utaformatix = UtaFormatix::builder()
+        .bundle(
+            r#"var utaformatix = {
+                parseSvp: async (data, options) => ({
+                    formatVersion: 1,
+                    project: {
+                        name: 'Project',
+                        tracks: ['Vocal', 'Chorus'].map((name) => ({ name, notes: [], pitch: null })),
+                        timeSignatures: [{ measurePosition: 0, numerator: 4, denominator: 4 }],
+                        tempos: [{ tickPosition: 0, bpm: 120 }],
+                        measurePrefix: 0,
+                    },
+                }),
+                generateSvp: async (data, options) => new Uint8Array(__encode(JSON.stringify({
+                    tracks: data.project.tracks.map((track, index) => ({
+                        dispOrder: index,
+                        mixer: { gainDecibel: 0, mute: false, solo: false },
+                    })),
+                }) + '\0')),
+            };"#,
+        )
+        .build();
+    let svp = r#"{"tracks":[
+        {"dispOrder":1,"mixer":{"mute":true,"solo":false}},
+        {"dispOrder":0}
+    ]}"#;
+    let parsed = utaformatix
+        .parse_svp(svp.as_bytes().to_vec(), ParseOptions::default())
+        .await
+        .unwrap();
+    let states = parsed
+        .project()
+        .tracks
+        .iter()
+        .map(|track| (track.muted, track.solo, track.order))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        states,
+        [(Some(true), Some(false), Some(1)), (None, None, Some(0))]
+    );
+    let round_tripped = UfData::from_bytes(
+        &parsed.to_bytes(GenerateOptions::default()).unwrap(),
+        ParseOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(round_tripped, parsed);
+
+    let generated = utaformatix
+        .generate_svp(&parsed, GenerateOptions::default())
+        .await
+        .unwrap();
+    let generated: serde_json::Value =
+        serde_json::from_slice(&generated[..generated.len() - 1]).unwrap();
+    assert_eq!(
+        generated["tracks"],
+        serde_json::json!([
+            { "dispOrder": 1, "mixer": { "gainDecibel": 0, "mute": true, "solo": false } },
+            { "dispOrder": 0, "mixer": { "gainDecibel": 0, "mute": false, "solo": false } },
+        ])
+    );
+}
+
+fn vpr(sequence: &serde_json::Value) -> Vec<u8> {
+    use std::io::Write;
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+    writer
+        .start_file(
+            "Project/sequence.json",
+            zip::write::SimpleFileOptions::default(),
+        )
+        .unwrap();
+    writer
+        .write_all(&serde_json::to_vec(sequence).unwrap())
+        .unwrap();
+    writer
+        .start_file(
+            "Project/Audio/vocal.wav",
+            zip::write::SimpleFileOptions::default(),
+        )
+        .unwrap();
+    writer.write_all(b"RIFF").unwrap();
+    writer.finish().unwrap().into_inner()
+}
+
+fn vpr_sequence(vpr: &[u8]) -> serde_json::Value {
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(vpr)).unwrap();
+    serde_json::from_reader(archive.by_name("Project/sequence.json").unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn vpr_track_states() {
+    let generated_vpr = vpr(&serde_json::json!({
+        "tracks": [
+            { "type": 0, "isMuted": false, "isSoloMode": false },
+            { "type": 0, "isMuted": false, "isSoloMode": false },
+        ],
+    }));
+    let utaformatix = UtaFormatix::builder()
+        .bundle(format!(
+            r#"var utaformatix = {{
+                parseVpr: async (data, options) => ({{
+                    formatVersion: 1,
+                    project: {{
+                        name: 'Project',
+                        tracks: ['Vocal', 'Chorus'].map((name) => ({{ name, notes: [], pitch: null }})),
+                        timeSignatures: [{{ measurePosition: 0, numerator: 4, denominator: 4 }}],
+                        tempos: [{{ tickPosition: 0, bpm: 120 }}],
+                        measurePrefix: 0,
+                    }},
+                }}),
+                generateVpr: async (data, options) => new Uint8Array({generated_vpr:?}),
+            }};"#
+        ))
+        .build();
+    let source = vpr(&serde_json::json!({
+        "tracks": [
+            { "type": 0, "isMuted": true, "isSoloMode": false },
+            { "type": 1, "isMuted": false, "isSoloMode": true },
+            { "type": 0, "isSoloMode": true },
+        ],
+    }));
+    let parsed = utaformatix
+        .parse_vpr(source, ParseOptions::default())
+        .await
+        .unwrap();
+    let states = parsed
+        .project()
+        .tracks
+        .iter()
+        .map(|track| (track.muted, track.solo))
+        .collect::<Vec<_>>();
+    assert_eq!(states, [(Some(true), Some(false)), (None, Some(true))]);
+
+    let generated = utaformatix
+        .generate_vpr(&parsed, GenerateOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        vpr_sequence(&generated)["tracks"],
+        serde_json::json!([
+            { "type": 0, "isMuted": true, "isSoloMode": false },
+            { "type": 0, "isMuted": false, "isSoloMode": true },
+        ])
+    );
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(generated)).unwrap();
+    let mut audio = vec![];
+    std::io::Read::read_to_end(
+        &mut archive.by_name("Project/Audio/vocal.wav").unwrap(),
+        &mut audio,
+    )
+    .unwrap();
+    assert_eq!(audio, b"RIFF");
+}
+
+#[rstest::rstest]
+#[tokio::test]
+#[traced_test]
+async fn generate_stream(engine: UtaFormatix) {
+    let mut data = sample_data();
+    let mut track = data.project().tracks[0].clone();
+    track.name = "Chorus".to_string();
+    data.project_mut().tracks.push(track);
+    let generated = engine
+        .generate_ust(&data, GenerateOptions::default())
+        .await
+        .expect("Failed to generate data");
+
+    let mut stream = engine
+        .generate_ust_stream(&data, GenerateOptions::default())
+        .await
+        .expect("Failed to start generating");
+    let mut streamed = vec![];
+    while let Some(file) = stream.next().await {
+        streamed.push(file.expect("Failed to generate data"));
+    }
+    assert_eq!(streamed, generated);
+    assert!(stream.next().await.is_none());
+}
+
+#[tokio::test]
+async fn generate_stream_track_by_track() {
+    // Each file is the number of calls to the generator and the number of tracks in the call.
+    let utaformatix = UtaFormatix::builder()
+        .bundle(
+            "var count = 0;
+            var utaformatix = {
+                generateUst: async (data, options) => {
+                    count++;
+                    return data.project.tracks.map(() =>
+                        new Uint8Array([count, data.project.tracks.length]));
+                },
+            };",
+        )
+        .build();
+    let mut data = sample_data();
+    let track = data.project().tracks[0].clone();
+    data.project_mut().tracks.extend([track.clone(), track]);
+
+    let mut stream = utaformatix
+        .generate_ust_stream(&data, GenerateOptions::default())
+        .await
+        .expect("Failed to start generating");
+    let mut streamed = vec![];
+    while let Some(file) = stream.next().await {
+        streamed.push(file.expect("Failed to generate data"));
+    }
+    assert_eq!(streamed, [[1, 1], [2, 1], [3, 1]]);
+
+    // Errors have the indices of the tracks in the whole project.
+    data.project_mut().tracks[2].notes[0].key = Key::MIN;
+    let mut stream = utaformatix
+        .generate_ust_stream(&data, GenerateOptions::default())
+        .await
+        .expect("Failed to start generating");
+    let error = stream.next().await.unwrap().unwrap_err();
+    assert!(matches!(
+        error.kind(),
+        ErrorKind::KeyOutOfRange { track: 2, .. }
+    ));
+}
+
+#[rstest::rstest]
+#[tokio::test]
+#[traced_test]
+async fn music_xml_ties_and_melismas(engine: UtaFormatix) {
+    let note = |step: &str, lyric: &str, notations: &str| {
+        format!(
+            "<note><pitch><step>{}</step><octave>4</octave></pitch><duration>1</duration>\
+             <type>quarter</type>{}{}</note>",
+            step, notations, lyric
+        )
+    };
+    let notes = [
+        note("C", "<lyric><text>あ</text></lyric>", "<notations><tied type=\"start\"/></notations>"),
+        note("C", "", "<tie type=\"stop\"/><notations><tied type=\"stop\"/><slur type=\"start\"/></notations>"),
+        note("D", "", "<notations><slur type=\"stop\"/></notations>"),
+        note("E", "<lyric><text>い</text></lyric>", ""),
+    ];
+    let score = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?><score-partwise version=\"3.1\">\
+         <part-list><score-part id=\"P1\"><part-name>Vocal</part-name></score-part></part-list>\
+         <part id=\"P1\"><measure number=\"1\"><attributes><divisions>1</divisions>\
+         <time><beats>4</beats><beat-type>4</beat-type></time></attributes>{}</measure></part>\
+         </score-partwise>",
+        notes.concat()
+    );
+
+    let parsed = engine
+        .parse_music_xml(
+            score.into_bytes(),
+            ParseOptions {
+                music_xml: MusicXmlParseOptions {
+                    merge_ties: true,
+                    melismas: true,
+                },
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to parse data");
+    let notes = parsed.project().tracks[0]
+        .notes
+        .iter()
+        .map(|note| (note.tick_off - note.tick_on, note.lyric.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(notes, vec![(960, "あ"), (480, "-"), (480, "い")]);
+}
+
+#[rstest::rstest]
+#[tokio::test]
+#[traced_test]
+async fn music_xml_notations_with_reordered_attributes(engine: UtaFormatix) {
+    // Attributes in any order and quoting, comments and CDATA are read as XML.
+    let score = "<?xml version='1.0' encoding='UTF-8'?><score-partwise version='3.1'>\
+        <part-list><score-part id='P1'><part-name>Vocal</part-name></score-part></part-list>\
+        <part id='P1'><measure number='1'><attributes><divisions>1</divisions>\
+        <time><beats>4</beats><beat-type>4</beat-type></time></attributes>\
+        <note><pitch><step>C</step><octave>4</octave></pitch><duration>1</duration>\
+        <notations><tied type='start'/></notations><lyric><text><![CDATA[あ]]></text></lyric></note>\
+        <!-- <note><rest/></note> -->\
+        <note><pitch><step>C</step><octave>4</octave></pitch><duration>1</duration>\
+        <tie number='1' type='stop'/><notations><tied type='stop'/>\
+        <slur number=\"1\" placement=\"above\" type=\"start\"/></notations></note>\
+        <note><pitch><step>D</step><octave>4</octave></pitch><duration>1</duration>\
+        <notations><slur placement='above' type = 'stop' number='1'/></notations></note>\
+        <note><pitch><step>E</step><octave>4</octave></pitch><duration>1</duration>\
+        <lyric number='1'><text>い</text></lyric></note>\
+        </measure></part></score-partwise>";
+
+    let parsed = engine
+        .parse_music_xml(
+            score.as_bytes().to_vec(),
+            ParseOptions {
+                music_xml: MusicXmlParseOptions {
+                    merge_ties: true,
+                    melismas: true,
+                },
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to parse data");
+    let notes = parsed.project().tracks[0]
+        .notes
+        .iter()
+        .map(|note| (note.tick_off - note.tick_on, note.lyric.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(notes, vec![(960, "あ"), (480, "-"), (480, "い")]);
+}
+
+#[rstest::rstest]
+#[tokio::test]
+#[traced_test]
+async fn high_priority_requests_jump_the_queue(engine: UtaFormatix) {
+    let data = sample_data();
+    let order = std::sync::Mutex::new(vec![]);
+    let request = |name: &'static str, priority| {
+        let (engine, data, order) = (&engine, &data, &order);
+        async move {
+            engine
+                .generate_svp(
+                    data,
+                    GenerateOptions {
+                        priority,
+                        ..Default::default()
+                    },
+                )
+                .await
+                .expect("Failed to generate data");
+            order.lock().unwrap().push(name);
+        }
+    };
+    tokio::join!(
+        request("low 1", Priority::Low),
+        request("low 2", Priority::Low),
+        request("low 3", Priority::Low),
+        request("high", Priority::High),
+    );
+
+    // The first request may already be running, but the others have to wait.
+    let order = order.into_inner().unwrap();
+    assert_eq!(order.len(), 4);
+    assert_ne!(order.last(), Some(&"high"), "{:?}", order);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn builder() {
+    let engine = UtaFormatix::builder()
+        .workers(2)
+        .queue_bound(4)
+        .timeout(std::time::Duration::from_secs(60))
+        .parse_options(ParseOptions {
+            pitch: false,
+            ..Default::default()
+        })
+        .generate_options(GenerateOptions {
+            pitch: true,
+            ..Default::default()
+        })
+        .build();
+    assert!(!engine.parse_options().pitch);
+    assert!(engine.generate_options().pitch);
+
+    let mut data = sample_data();
+    let generated = engine
+        .generate_uf_data(&data, engine.generate_options())
+        .await
+        .expect("Failed to generate data");
+    let parsed = engine
+        .parse_uf_data(generated, engine.parse_options())
+        .await
+        .expect("Failed to parse data");
+    assert_eq!(parsed, data);
+
+    // The default options of the instance are used when a call passes `None`.
+    data.project_mut().tracks[0].pitch = Some(Pitch {
+        ticks: vec![1920, 2040],
+        values: vec![Some(0.0), Some(1.0)],
+        is_absolute: false,
+    });
+    let generated = engine
+        .generate_uf_data(&data, None)
+        .await
+        .expect("Failed to generate data");
+    let value: serde_json::Value = serde_json::from_slice(&generated).unwrap();
+    assert!(value["project"]["tracks"][0]["pitch"].is_object());
+    let parsed = engine
+        .parse_uf_data(generated.clone(), None)
+        .await
+        .expect("Failed to parse data");
+    assert_eq!(parsed.project().tracks[0].pitch, None);
+
+    // Options passed explicitly are used as they are, even if they are the default ones.
+    let parsed = engine
+        .parse_uf_data(generated, ParseOptions::default())
+        .await
+        .expect("Failed to parse data");
+    assert!(parsed.project().tracks[0].pitch.is_some());
+    let generated = engine
+        .generate_uf_data(&data, GenerateOptions::default())
+        .await
+        .expect("Failed to generate data");
+    let value: serde_json::Value = serde_json::from_slice(&generated).unwrap();
+    assert!(value["project"]["tracks"][0]["pitch"].is_null());
+}
+
+#[tokio::test]
+async fn ccs_voices() {
+    let utaformatix = UtaFormatix::builder()
+        .bundle(
+            r#"var utaformatix = {
+                parseCcs: async (data, options) => ({
+                    formatVersion: 1,
+                    project: {
+                        name: 'Project',
+                        tracks: ['Vocal', 'Chorus'].map((name) => ({ name, notes: [], pitch: null })),
+                        timeSignatures: [{ measurePosition: 0, numerator: 4, denominator: 4 }],
+                        tempos: [{ tickPosition: 0, bpm: 120 }],
+                        measurePrefix: 0,
+                    },
+                }),
+                generateCcs: async (data, options) => new Uint8Array(__encode('<Scenario><Units>'
+                    + data.project.tracks.map((track, i) => '<Unit Category="SingerSong" Group="g'
+                        + i + '" CastId="A" Language="Japanese"><Song /></Unit>').join('')
+                    + '</Units><Groups>'
+                    + data.project.tracks.map((track, i) => '<Group Id="g' + i
+                        + '" Category="SingerSong" Name="' + track.name + '" CastId="A" />').join('')
+                    + '</Groups></Scenario>')),
+            };"#,
+        )
+        .build();
+    let ccs = r#"<Scenario><Units>
+        <Unit Category="SingerSong" Group="b" CastId="XSV-JPF-W" Language="Japanese" />
+        <Unit Category="TalkText" Group="c" CastId="A" />
+        <Unit Category="SingerSong" Group="a" CastId="KAFU" />
+        </Units><Groups>
+        <Group Id="a" Category="SingerSong" Language="Japanese" />
+        <Group Id="b" Category="SingerSong" CastId="XSV-JPF-W" Language="English" />
+        <Group Id="c" Category="TalkText" CastId="A" />
+        </Groups></Scenario>"#;
+    let parsed = utaformatix
+        .parse_ccs(ccs.as_bytes().to_vec(), ParseOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(parsed.project().tracks[0].voice, None);
+
+    let options = ParseOptions {
+        ccs_voices: true,
+        ..Default::default()
+    };
+    let parsed = utaformatix
+        .parse_ccs(ccs.as_bytes().to_vec(), options)
+        .await
+        .unwrap();
+    let voice = |cast: &str, language: &str| TrackVoice {
+        format: Format::Ccs,
+        cast: cast.to_string(),
+        language: Some(language.to_string()),
+    };
+    let voices = parsed
+        .project()
+        .tracks
+        .iter()
+        .map(|track| track.voice.clone())
+        .collect::<Vec<_>>();
+    // The cast of a group without one is taken from its units.
+    assert_eq!(
+        voices,
+        [
+            Some(voice("KAFU", "Japanese")),
+            Some(voice("XSV-JPF-W", "English")),
+        ]
+    );
+
+    let generated = utaformatix
+        .generate_ccs(&parsed, GenerateOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(generated).unwrap(),
+        "<Scenario><Units>\
+        <Unit Category=\"SingerSong\" Group=\"g0\" CastId=\"KAFU\" Language=\"Japanese\"><Song /></Unit>\
+        <Unit Category=\"SingerSong\" Group=\"g1\" CastId=\"XSV-JPF-W\" Language=\"English\"><Song /></Unit>\
+        </Units><Groups>\
+        <Group Id=\"g0\" Category=\"SingerSong\" Name=\"Vocal\" CastId=\"KAFU\" Language=\"Japanese\" />\
+        <Group Id=\"g1\" Category=\"SingerSong\" Name=\"Chorus\" CastId=\"XSV-JPF-W\" Language=\"English\" />\
+        </Groups></Scenario>"
+    );
+
+    // The cast of the format options overrides the voices.
+    let generated = utaformatix
+        .generate_ccs(
+            &parsed,
+            GenerateOptions {
+                format_options: Some(FormatOptions::Ccs(CcsOptions {
+                    cast_id: Some("B".to_string()),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    let generated = String::from_utf8(generated).unwrap();
+    assert!(!generated.contains("KAFU"));
+    assert!(generated.contains("Language=\"English\""));
+}
+
+#[tokio::test]
+async fn track_parameters() {
+    let utaformatix = UtaFormatix::builder()
+        .bundle(
+            r#"var utaformatix = {
+                parseUstx: async (data, options) => ({
+                    formatVersion: 1,
+                    project: {
+                        name: 'Project',
+                        tracks: ['Vocal', 'Chorus'].map((name) => ({ name, notes: [], pitch: null })),
+                        timeSignatures: [{ measurePosition: 0, numerator: 4, denominator: 4 }],
+                        tempos: [{ tickPosition: 0, bpm: 120 }],
+                        measurePrefix: 0,
+                    },
+                }),
+                generateUstx: async (data, options) => new Uint8Array(__encode('name: Project\n'
+                    + 'tracks:\n'
+                    + data.project.tracks.map((track) => '- track_name: ' + track.name + '\n').join('')
+                    + 'voice_parts:\n'
+                    + '- curves: []\n  track_no: 0\n  position: 960\n  notes: []\n'
+                    + '- name: Chorus\n  track_no: 1\n  position: 0\n  curves: []\n')),
+            };"#,
+        )
+        .build();
+    let ustx = "ustx_version: 0.6\n\
+        tracks:\n\
+        - track_name: Vocal\n\
+        - track_name: Chorus\n\
+        voice_parts:\n\
+        - name: a\n  \
+          track_no: 0\n  \
+          position: 480\n  \
+          notes: []\n  \
+          curves:\n  \
+          - xs: [0, 240,\n      \
+              480]\n    \
+            ys: [0, 10, -5]\n    \
+            abbr: dyn\n\
+        - name: b\n  \
+          track_no: 0\n  \
+          position: 1920\n  \
+          curves:\n  \
+          - xs:\n    \
+            - 0\n    \
+            ys:\n    \
+            - 20\n    \
+            abbr: dyn\n  \
+          - xs: [-960, 0]\n    \
+            ys: [1, 2]\n    \
+            abbr: 'user: a'\n";
+    let parsed = utaformatix
+        .parse_ustx(ustx.as_bytes().to_vec(), ParseOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(parsed.project().tracks[0].parameters, None);
+
+    let options = ParseOptions {
+        track_parameters: true,
+        ..Default::default()
+    };
+    let parsed = utaformatix
+        .parse_ustx(ustx.as_bytes().to_vec(), options)
+        .await
+        .unwrap();
+    let curve = |points: &[(i64, i32)]| {
+        points
+            .iter()
+            .map(|&(tick, value)| ParameterPoint {
+                tick: Tick::new(tick).unwrap(),
+                value,
+            })
+            .collect::<Vec<_>>()
+    };
+    // Curves of the parts of a track are merged at the positions of the parts.
+    assert_eq!(
+        parsed.project().tracks[0].parameters,
+        Some(TrackParameters {
+            format: Format::Ustx,
+            curves: [
+                (
+                    "dyn".to_string(),
+                    curve(&[(480, 0), (720, 10), (960, -5), (1920, 20)]),
+                ),
+                ("user: a".to_string(), curve(&[(960, 1), (1920, 2)])),
+            ]
+            .into(),
+        })
+    );
+    assert_eq!(parsed.project().tracks[1].parameters, None);
+
+    let generated = utaformatix
+        .generate_ustx(&parsed, GenerateOptions::default())
+        .await
+        .unwrap();
+    // Curves are replaced in place, and names which aren't plain YAML scalars are quoted.
+    assert_eq!(
+        String::from_utf8(generated).unwrap(),
+        "name: Project\n\
+        tracks:\n\
+        - track_name: Vocal\n\
+        - track_name: Chorus\n\
+        voice_parts:\n\
+        - curves:\n  \
+          - xs:\n    \
+            - -480\n    \
+            - -240\n    \
+            - 0\n    \
+            - 960\n    \
+            ys:\n    \
+            - 0\n    \
+            - 10\n    \
+            - -5\n    \
+            - 20\n    \
+            abbr: dyn\n  \
+          - xs:\n    \
+            - 0\n    \
+            - 960\n    \
+            ys:\n    \
+            - 1\n    \
+            - 2\n    \
+            abbr: 'user: a'\n  \
+          track_no: 0\n  \
+          position: 960\n  \
+          notes: []\n\
+        - name: Chorus\n  \
+          track_no: 1\n  \
+          position: 0\n  \
+          curves: []\n"
+    );
+}
+
+#[tokio::test]
+#[traced_test]
+async fn notes_out_of_range_from_engine() {
+    // Like utaformatix-ts does for a UST file with `NoteNum=130`, or with notes in the measure
+    // prefix, which come before the start of the project.
+    let utaformatix = UtaFormatix::builder()
+        .bundle(
+            r#"var utaformatix = {
+                parseUst: async (data, options) => ({
+                    formatVersion: 1,
+                    project: {
+                        name: 'Project',
+                        tracks: [{
+                            name: 'Vocal',
+                            notes: JSON.parse(__decode(data[0])).map(([key, tickOn, tickOff]) =>
+                                ({ key, tickOn, tickOff, lyric: 'a' })),
+                            pitch: null,
+                        }],
+                        timeSignatures: [{ measurePosition: 0, numerator: 4, denominator: 4 }],
+                        tempos: [{ tickPosition: 0, bpm: 120 }],
+                        measurePrefix: 1,
+                    },
+                }),
+            };"#,
+        )
+        .build();
+    let parse =
+        |notes: &'static str| utaformatix.parse_ust([notes.as_bytes()], ParseOptions::default());
+
+    let error = parse("[[60, 0, 480], [130, 480, 960]]").await.unwrap_err();
+    assert!(matches!(
+        error.kind(),
+        ErrorKind::KeyOutOfRange {
+            track: 0,
+            note: 1,
+            key: 130
+        }
+    ));
+
+    let parsed = parse("[[60, -1920, -1440], [62, -240, 240], [64, 240, 480]]")
+        .await
+        .unwrap();
+    let notes = parsed.project().tracks[0]
+        .notes
+        .iter()
+        .map(|note| (note.key.get(), note.tick_on.get(), note.tick_off.get()))
+        .collect::<Vec<_>>();
+    assert_eq!(notes, [(62, 0, 240), (64, 240, 480)]);
+}
+
+#[tokio::test]
+async fn svp_dynamics() {
+    let utaformatix = UtaFormatix::builder()
+        .bundle(
+            r#"var utaformatix = {
+                parseSvp: async (data, options) => ({
+                    formatVersion: 1,
+                    project: {
+                        name: 'Project',
+                        tracks: [{ name: 'Vocal', notes: [], pitch: null }],
+                        timeSignatures: [{ measurePosition: 0, numerator: 4, denominator: 4 }],
+                        tempos: [{ tickPosition: 0, bpm: 120 }],
+                        measurePrefix: 0,
+                    },
+                }),
+                generateSvp: async (data, options) => new Uint8Array(__encode(JSON.stringify({
+                    tracks: [{
+                        mainGroup: {
+                            parameters: { loudness: { mode: 'linear', points: [] } },
+                        },
+                    }],
+                }) + '\0')),
+                generateUstx: async (data, options) => new Uint8Array(__encode('name: Project\n'
+                    + 'tracks:\n'
+                    + '- track_name: Vocal\n'
+                    + 'voice_parts:\n'
+                    + '- name: a\n  track_no: 0\n  position: 0\n  curves: []\n')),
+            };"#,
+        )
+        .build();
+    // Positions are in blicks, 705,600,000 per beat.
+    let svp = r#"{
+        "version": 153,
+        "tracks": [{
+            "name": "Vocal",
+            "mainGroup": {
+                "name": "main",
+                "notes": [],
+                "parameters": {
+                    "pitchDelta": { "mode": "cubic", "points": [] },
+                    "loudness": {
+                        "mode": "cubic",
+                        "points": [0, 0.0, 705600000, 6.0, 1411200000, -30.0]
+                    },
+                    "tension": { "mode": "cubic", "points": [705600000, 0.5] }
+                }
+            }
+        }]
+    }"#;
+    let options = ParseOptions {
+        track_parameters: true,
+        ..Default::default()
+    };
+    let parsed = utaformatix
+        .parse_svp(svp.as_bytes().to_vec(), options)
+        .await
+        .unwrap();
+    let curve = |points: &[(i64, i32)]| {
+        points
+            .iter()
+            .map(|&(tick, value)| ParameterPoint {
+                tick: Tick::new(tick).unwrap(),
+                value,
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        parsed.project().tracks[0].parameters,
+        Some(TrackParameters {
+            format: Format::Svp,
+            curves: [
+                (
+                    "loudness".to_string(),
+                    curve(&[(0, 0), (480, 6000), (960, -30000)]),
+                ),
+                ("tension".to_string(), curve(&[(480, 500)])),
+            ]
+            .into(),
+        })
+    );
+
+    let generated = utaformatix
+        .generate_svp(&parsed, GenerateOptions::default())
+        .await
+        .unwrap();
+    let generated: serde_json::Value =
+        serde_json::from_slice(&generated[..generated.len() - 1]).unwrap();
+    assert_eq!(
+        generated["tracks"][0]["mainGroup"]["parameters"],
+        serde_json::json!({
+            "loudness": {
+                "mode": "linear",
+                "points": [0, 0.0, 705600000, 6.0, 1411200000, -30.0],
+            },
+            "tension": { "mode": "cubic", "points": [705600000, 0.5] },
+        })
+    );
+
+    // The curves of Synthesizer V aren't written to other formats unless they are mapped.
+    let generated = utaformatix
+        .generate_ustx(&parsed, GenerateOptions::default())
+        .await
+        .unwrap();
+    assert!(String::from_utf8(generated)
+        .unwrap()
+        .contains("  curves: []\n"));
+
+    let options = GenerateOptions {
+        dynamics: Some(DynamicsMapping {
+            loudness_scale: 1.0,
+            tension_scale: 2.0,
+        }),
+        ..Default::default()
+    };
+    let generated = utaformatix.generate_ustx(&parsed, options).await.unwrap();
+    // The tension of 0.5 adds 1 dB, and -29 dB is clamped to OpenUtau's -24 dB.
+    assert_eq!(
+        String::from_utf8(generated).unwrap(),
+        "name: Project\n\
+        tracks:\n\
+        - track_name: Vocal\n\
+        voice_parts:\n\
+        - name: a\n  \
+          track_no: 0\n  \
+          position: 0\n  \
+          curves:\n  \
+          - xs:\n    \
+            - 0\n    \
+            - 480\n    \
+            - 960\n    \
+            ys:\n    \
+            - 10\n    \
+            - 70\n    \
+            - -240\n    \
+            abbr: dyn\n"
+    );
+}
+
+#[tokio::test]
+async fn bounded_queue() {
+    let engine = UtaFormatix::builder()
+        .queue_bound(2)
+        .bundle(
+            "var utaformatix = { generateCcs: async (data, options) =>
+                new Uint8Array([data.project.tracks.length]) };",
+        )
+        .build();
+    let data = sample_data();
+    let request = |priority| {
+        engine.generate_ccs(
+            &data,
+            GenerateOptions {
+                priority,
+                ..Default::default()
+            },
+        )
+    };
+    // More requests than the bound wait for room in the queue instead of failing.
+    let results = tokio::join!(
+        request(Priority::Low),
+        request(Priority::Low),
+        request(Priority::High),
+        request(Priority::Low),
+        request(Priority::High),
+    );
+    for result in [results.0, results.1, results.2, results.3, results.4] {
+        assert_eq!(result.unwrap(), [1]);
+    }
+}
+
+#[cfg(feature = "cache")]
+#[tokio::test]
+#[traced_test]
+async fn cache() {
+    use utaformatix::{CacheConfig, CacheStats, CacheStorage};
+
+    let directory = std::env::temp_dir().join(format!("utaformatix-cache-{}", std::process::id()));
+    let config = CacheConfig {
+        storage: CacheStorage::Directory(directory.clone()),
+        ..Default::default()
+    };
+    let data = sample_data();
+    let generated = {
+        let engine = UtaFormatix::builder().cache(config.clone()).build();
+        let generated = engine
+            .generate_svp(&data, GenerateOptions::default())
+            .await
+            .expect("Failed to generate data");
+        assert_eq!(
+            engine.cache_stats(),
+            Some(CacheStats { hits: 0, misses: 1 })
+        );
+        generated
+    };
+
+    // Another instance reads the results of the first one, while the priority doesn't matter.
+    let engine = UtaFormatix::builder().cache(config).build();
+    let cached = engine
+        .generate_svp(
+            &data,
+            GenerateOptions {
+                priority: Priority::High,
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to generate data");
+    assert_eq!(cached, generated);
+    assert_eq!(
+        engine.cache_stats(),
+        Some(CacheStats { hits: 1, misses: 0 })
+    );
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[cfg(feature = "cache")]
+#[tokio::test]
+#[traced_test]
+async fn cache_skips_failures() {
+    use utaformatix::{CacheConfig, CacheStats};
+
+    let engine = UtaFormatix::builder().cache(CacheConfig::default()).build();
+    let mut data = sample_data();
+    data.project_mut().tracks.clear();
+    for _ in 0..2 {
+        assert!(engine
+            .generate_svp(&data, GenerateOptions::default())
+            .await
+            .is_err());
+    }
+    assert_eq!(
+        engine.cache_stats(),
+        Some(CacheStats { hits: 0, misses: 2 })
+    );
+    assert_eq!(UtaFormatix::new().cache_stats(), None);
+}
+
+#[cfg(feature = "cache")]
+#[tokio::test]
+async fn cache_keys_prefix_map_and_bundle() {
+    use utaformatix::{CacheConfig, CacheStats, CacheStorage, PrefixMap};
+
+    let directory =
+        std::env::temp_dir().join(format!("utaformatix-cache-keys-{}", std::process::id()));
+    let config = CacheConfig {
+        storage: CacheStorage::Directory(directory.clone()),
+        ..Default::default()
+    };
+    let engine = |lyric: &str| {
+        let mut data = sample_data();
+        data.project_mut().tracks[0].notes[0].lyric = lyric.to_string();
+        UtaFormatix::builder()
+            .bundle(format!(
+                "var utaformatix = {{ parseCcs: async () => ({}) }};",
+                serde_json::to_string(&data).unwrap()
+            ))
+            .cache(config.clone())
+            .build()
+    };
+    let first_lyric = |data: UfData| data.project().tracks[0].notes[0].lyric.clone();
+    let mut prefix_map = PrefixMap::default();
+    prefix_map.insert(Key::new(60).unwrap(), "", "_C4");
+    let with_prefix_map = ParseOptions {
+        prefix_map: Some(prefix_map),
+        ..Default::default()
+    };
+
+    let first = engine("ど_C4");
+    let parse = |options: ParseOptions| first.parse_with_engine(Format::Ccs, vec![0], options);
+    assert_eq!(
+        first_lyric(parse(ParseOptions::default()).await.unwrap()),
+        "ど_C4"
+    );
+    assert_eq!(
+        first_lyric(parse(with_prefix_map.clone()).await.unwrap()),
+        "ど"
+    );
+    assert_eq!(first_lyric(parse(with_prefix_map).await.unwrap()), "ど");
+    assert_eq!(first.cache_stats(), Some(CacheStats { hits: 1, misses: 2 }));
+
+    // Another bundle doesn't share the results, even in the same directory.
+    let second = engine("ら");
+    let data = second
+        .parse_with_engine(Format::Ccs, vec![0], ParseOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(first_lyric(data), "ら");
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[cfg(feature = "cache")]
+#[tokio::test]
+async fn cache_ignores_truncated_entries() {
+    use utaformatix::{CacheConfig, CacheStats, CacheStorage};
+
+    let directory = std::env::temp_dir().join(format!(
+        "utaformatix-cache-truncated-{}",
+        std::process::id()
+    ));
+    let config = CacheConfig {
+        storage: CacheStorage::Directory(directory.clone()),
+        ..Default::default()
+    };
+    let engine = || {
+        UtaFormatix::builder()
+            .bundle("var utaformatix = { generateCcs: async () => new Uint8Array([1, 2, 3]) };")
+            .cache(config.clone())
+            .build()
+    };
+    let generate = |engine: UtaFormatix| async move {
+        let file = engine
+            .generate_ccs(&sample_data(), GenerateOptions::default())
+            .await
+            .unwrap();
+        (file, engine.cache_stats())
+    };
+    assert_eq!(
+        generate(engine()).await,
+        (vec![1, 2, 3], Some(CacheStats { hits: 0, misses: 1 }))
+    );
+    // The entry is renamed into place, so no temporary file is left behind.
+    let entries = std::fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].extension().unwrap(), "bin");
+
+    let entry = std::fs::read(&entries[0]).unwrap();
+    std::fs::write(&entries[0], &entry[..entry.len() - 1]).unwrap();
+    assert_eq!(
+        generate(engine()).await,
+        (vec![1, 2, 3], Some(CacheStats { hits: 0, misses: 1 }))
+    );
+    assert_eq!(
+        generate(engine()).await,
+        (vec![1, 2, 3], Some(CacheStats { hits: 1, misses: 0 }))
+    );
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[cfg(feature = "cache")]
+#[tokio::test]
+async fn cache_per_track() {
+    use utaformatix::{CacheConfig, CacheStats};
+
+    let engine = UtaFormatix::builder()
+        .workers(2)
+        .bundle("var utaformatix = { generateCcs: async () => new Uint8Array([1, 2, 3]) };")
+        .cache(CacheConfig::default())
+        .build();
+    let mut data = sample_data();
+    let track = data.project().tracks[0].clone();
+    data.project_mut().tracks = vec![track.clone(), track];
+
+    let files = engine
+        .generate_ccs_per_track(&data, GenerateOptions::default())
+        .await
+        .unwrap();
+    // Both tracks are the same request, but they are sent before either of them is cached.
+    assert_eq!(files.len(), 2);
+    assert_eq!(
+        engine.cache_stats(),
+        Some(CacheStats { hits: 0, misses: 2 })
+    );
+    let cached = engine
+        .generate_ccs_per_track(&data, GenerateOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(cached, files);
+    assert_eq!(
+        engine.cache_stats(),
+        Some(CacheStats { hits: 2, misses: 2 })
+    );
+}
+
+#[rstest::rstest]
+#[case::unknown_version(br#"{"formatVersion":2,"project":{}}"#)]
+#[case::not_json(b"not json")]
+fn uf_data_rejects_invalid_files(#[case] data: &[u8]) {
+    let error = UfData::from_bytes(data, ParseOptions::default()).unwrap_err();
+
+    assert!(matches!(
+        error.kind(),
+        ErrorKind::UnsupportedFileFormat | ErrorKind::IllegalFile(_)
+    ));
+    assert_eq!(error.details().format, Some(Format::UfData));
+}
+
+#[cfg(feature = "msgpack")]
+#[test]
+fn msgpack_round_trip() {
+    let data = sample_data();
+    let encoded = data.to_msgpack().expect("Failed to encode data");
+
+    assert_eq!(UfData::from_msgpack(&encoded).unwrap(), data);
+}
+
+#[cfg(feature = "cbor")]
+#[test]
+fn cbor_round_trip() {
+    let data = sample_data();
+    let encoded = data.to_cbor().expect("Failed to encode data");
+
+    assert_eq!(UfData::from_cbor(&encoded).unwrap(), data);
+}
+
+#[cfg(feature = "mmap")]
+#[tokio::test]
+async fn parse_from_path() {
+    let engine = UtaFormatix::new();
+    let data = sample_data();
+    let path = std::env::temp_dir().join(format!("utaformatix-{}.ufdata", std::process::id()));
+    std::fs::write(
+        &path,
+        data.to_bytes(GenerateOptions::default())
+            .expect("Failed to serialize data"),
+    )
+    .expect("Failed to write file");
+
+    let parsed = engine
+        .parse_uf_data_from_path(&path, ParseOptions::default())
+        .await;
+    std::fs::remove_file(&path).expect("Failed to remove file");
+    assert_eq!(parsed.expect("Failed to parse data"), data);
+
+    let error = engine
+        .parse_uf_data_from_path(&path, ParseOptions::default())
+        .await
+        .unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::Unexpected(_)));
+}
+
+#[cfg(feature = "midly")]
+#[test]
+fn midly_round_trip() {
+    let project = utaformatix::Project::new(sample_data());
+    let smf = midly::Smf::try_from(&project).expect("Failed to convert to SMF");
+    assert_eq!(smf.tracks.len(), 2);
+
+    let converted = utaformatix::Project::try_from(&smf).expect("Failed to convert from SMF");
+    assert_eq!(
+        converted.data.project().tracks,
+        project.data.project().tracks
+    );
+    assert_eq!(
+        converted.data.project().time_signatures,
+        project.data.project().time_signatures
+    );
+    assert_eq!(
+        converted.data.project().tempos,
+        project.data.project().tempos
+    );
+}
+
+#[cfg(feature = "midly")]
+#[test]
+fn midly_uses_standard_mid_rules() {
+    use midly::{
+        num::{u15, u28, u4, u7},
+        Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind,
+    };
+
+    // A note without a lyric event, at 240 ticks per quarter note.
+    let note = |delta: u32, message| TrackEvent {
+        delta: u28::new(delta),
+        kind: TrackEventKind::Midi {
+            channel: u4::new(0),
+            message,
+        },
+    };
+    let smf = Smf {
+        header: Header::new(midly::Format::SingleTrack, Timing::Metrical(u15::new(240))),
+        tracks: vec![vec![
+            note(
+                0,
+                MidiMessage::NoteOn {
+                    key: u7::new(60),
+                    vel: u7::new(100),
+                },
+            ),
+            note(
+                240,
+                MidiMessage::NoteOn {
+                    key: u7::new(60),
+                    vel: u7::new(0),
+                },
+            ),
+            TrackEvent {
+                delta: u28::new(0),
+                kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+            },
+        ]],
+    };
+    let project = utaformatix::Project::try_from(&smf).unwrap();
+    let notes = &project.data.project().tracks[0].notes;
+    assert_eq!(notes[0].lyric, "あ");
+    assert_eq!(notes[0].tick_off.get(), 480);
+    let project = utaformatix::Project::from_smf(
+        &smf,
+        ParseOptions {
+            default_lyric: DefaultLyric::Fill("ら".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(project.data.project().tracks[0].notes[0].lyric, "ら");
+
+    // Time signatures which MIDI can't store fail instead of being written as they are.
+    let mut data = sample_data();
+    data.project_mut().time_signatures[0].numerator = 256;
+    let project = utaformatix::Project::new(data);
+    let error = midly::Smf::try_from(&project).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::IllegalTimeSignature));
+}
+
+#[cfg(feature = "playback")]
+#[test]
+fn playback_events() {
+    let project = utaformatix::Project::new(sample_data());
+    let events = project.playback_events();
+
+    assert_eq!(events.len(), 8);
+    assert!(events.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+    // The first note starts at the second measure, which is 2 seconds at 120 BPM.
+    assert_eq!(events[0].0, std::time::Duration::from_secs(2));
+    assert!(matches!(events[0].1, midly::MidiMessage::NoteOn { .. }));
+    assert!(matches!(events[1].1, midly::MidiMessage::NoteOff { .. }));
+}
+
+#[cfg(feature = "arbitrary")]
+#[test]
+fn arbitrary_data_is_valid() {
+    use arbitrary::{Arbitrary, Unstructured};
+
+    for seed in 0..32u8 {
+        let bytes = (0..4096)
+            .map(|i: u32| (i.wrapping_mul(31) as u8) ^ seed.wrapping_mul(97))
+            .collect::<Vec<_>>();
+        let data = UfData::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+        let project = data.project();
+
+        assert_eq!(project.tempos[0].tick_position, 0);
+        assert_eq!(project.time_signatures[0].measure_position, 0);
+        for track in &project.tracks {
+            assert!(track.notes.iter().all(|note| note.tick_on < note.tick_off));
+            assert!(track
+                .notes
+                .windows(2)
+                .all(|pair| pair[0].tick_off <= pair[1].tick_on));
+        }
+    }
+}
+
+#[test]
+fn version_info() {
+    let version_info = UtaFormatix::version_info();
+
+    assert_eq!(version_info.crate_version, env!("CARGO_PKG_VERSION"));
+    assert!(!version_info.utaformatix_ts_version.is_empty());
+    assert_eq!(version_info.uf_data_format_version, UfData::FORMAT_VERSION);
+}
+
+#[test]
+fn format_metadata() {
+    assert_eq!(Format::all().count(), 15);
+    for format in Format::all() {
+        let metadata = format.metadata();
+        assert_eq!(metadata.format, format);
+        assert_eq!(metadata.extensions[0], format.extension());
+    }
+    assert!(!Format::Ppsf.metadata().can_generate);
+    assert!(!Format::Ust.metadata().multi_track);
+}
+
+#[tokio::test]
+async fn generate_tracks_in_parallel() {
+    // Each file is the name of the track and the number of tracks which the bundle received.
+    // The files are generated in parallel with multiple workers.
+    let utaformatix = UtaFormatix::builder()
+        .workers(3)
+        .bundle(
+            "var utaformatix = { generateUst: async (data, options) =>
+                data.project.tracks.map((track) =>
+                    new Uint8Array([...(track.name + data.project.tracks.length)]
+                        .map((c) => c.charCodeAt(0)))) };",
+        )
+        .build();
+    let mut data = sample_data();
+    let track = data.project().tracks[0].clone();
+    data.project_mut().tracks = ["a", "b", "c", "d", "e"]
+        .iter()
+        .map(|name| Track {
+            name: name.to_string(),
+            ..track.clone()
+        })
+        .collect();
+
+    let files = utaformatix
+        .generate_ust(&data, GenerateOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(files, [b"a1", b"b1", b"c1", b"d1", b"e1"]);
+
+    // Errors have the index of the track in the whole project.
+    data.project_mut().tracks[3].notes[0].key = Key::MIN;
+    let error = utaformatix
+        .generate_ust(&data, GenerateOptions::default())
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error.kind(),
+        ErrorKind::KeyOutOfRange { track: 3, .. }
+    ));
+}
+
+#[tokio::test]
+async fn warm_up() {
+    let utaformatix = UtaFormatix::builder()
+        .workers(3)
+        .bundle("var utaformatix = { generateUstx: async (data, options) => new Uint8Array([]) };")
+        .build();
+    utaformatix.warm_up().await.unwrap();
+
+    let utaformatix = UtaFormatix::builder()
+        .bundle("var utaformatix = {};")
+        .build();
+    assert!(utaformatix.warm_up().await.is_err());
+}
+
+#[tokio::test]
+async fn recycle_after() {
+    // Each file is the number of requests which the JS context has processed.
+    let utaformatix = UtaFormatix::builder()
+        .recycle_after(2)
+        .bundle(
+            "var count = 0;
+            var utaformatix = { generateSvp: async (data, options) => new Uint8Array([++count]) };",
+        )
+        .build();
+    let mut counts = vec![];
+    for _ in 0..5 {
+        let file = utaformatix
+            .generate_svp(&sample_data(), GenerateOptions::default())
+            .await
+            .unwrap();
+        counts.extend(file);
+    }
+    assert_eq!(counts, [1, 2, 1, 2, 1]);
+}
+
+#[rstest::rstest]
+#[tokio::test]
+async fn fallback_timing(engine: UtaFormatix) {
+    let options = ParseOptions {
+        fallback_tempo: Some(95),
+        fallback_time_signature: Some(TimeSignature {
+            measure_position: 2,
+            numerator: 3,
+            denominator: 4,
+        }),
+        ..Default::default()
+    };
+    let time_signature = TimeSignature {
+        measure_position: 0,
+        numerator: 3,
+        denominator: 4,
+    };
+
+    // A MIDI file without tempo and time signature events.
+    let mut data = sample_data();
+    data.project_mut().tempos.clear();
+    data.project_mut().time_signatures.clear();
+    let generated = engine
+        .generate_standard_mid(&data, GenerateOptions::default())
+        .await
+        .unwrap();
+    let parsed = engine
+        .parse_standard_mid(generated, options.clone())
+        .await
+        .unwrap();
+    assert_eq!(parsed.project().tempos[0].bpm, 95);
+    assert_eq!(
+        parsed.project().time_signatures,
+        vec![time_signature.clone()]
+    );
+
+    let generated = engine
+        .generate_standard_mid(&sample_data(), GenerateOptions::default())
+        .await
+        .unwrap();
+    let parsed = engine
+        .parse_standard_mid(generated, options.clone())
+        .await
+        .unwrap();
+    assert_eq!(parsed.project().tempos, sample_data().project().tempos);
+    assert_eq!(
+        parsed.project().time_signatures,
+        sample_data().project().time_signatures
+    );
+
+    // The JS parser assumes 120 BPM and 4/4 for UST files.
+    let utaformatix = UtaFormatix::builder()
+        .bundle(
+            "var utaformatix = {
+                parseUst: async (files, options) => ({
+                    formatVersion: 1,
+                    project: {
+                        name: 'Project',
+                        tracks: [],
+                        timeSignatures: [{ measurePosition: 0, numerator: 4, denominator: 4 }],
+                        tempos: [{ tickPosition: 0, bpm: 120 }],
+                        measurePrefix: 0,
+                    },
+                }),
+            };",
+        )
+        .build();
+    let parsed = utaformatix
+        .parse_ust([&b"[#SETTING]\nProjectName=Project\n"[..]], options.clone())
+        .await
+        .unwrap();
+    assert_eq!(parsed.project().tempos[0].bpm, 95);
+    assert_eq!(parsed.project().time_signatures, [time_signature]);
+    let parsed = utaformatix
+        .parse_ust([&b"[#SETTING]\nTempo=140.00\n"[..]], options)
+        .await
+        .unwrap();
+    assert_eq!(parsed.project().tempos[0].bpm, 120);
+
+    let error = engine
+        .parse_standard_mid(
+            &b""[..],
+            ParseOptions {
+                fallback_tempo: Some(0),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::IllegalTempo));
+}
+
+#[tokio::test]
+async fn allowed_encodings() {
+    // The name of the project is `<encoding>:<text>`, and the file is the text round-tripped
+    // through the encoding, or the error.
+    let utaformatix = UtaFormatix::builder()
+        .allowed_encodings(["shift-jis", "utf-16le"])
+        .bundle(
+            "var utaformatix = {
+                generateSvp: async (data, options) => {
+                    var [encoding, text] = data.project.name.split(':');
+                    try {
+                        text = __decode(__encode(text, encoding), encoding);
+                    } catch (e) {
+                        text = e.name + ': ' + e.message;
+                    }
+                    return new Uint8Array(__encode(text));
+                },
+            };",
+        )
+        .build();
+    for (name, expected) in [
+        ("Shift_JIS:歌声合成", "歌声合成"),
+        ("UTF-16LE:歌声合成🎵", "歌声合成🎵"),
+        (
+            "Shift_JIS:🎵",
+            "TypeError: String has characters which can't be encoded in Shift_JIS",
+        ),
+        (
+            "EUC-JP:歌声合成",
+            "RangeError: Encoding is not allowed: EUC-JP",
+        ),
+        ("unknown:歌声合成", "RangeError: Unknown encoding: unknown"),
+    ] {
+        let mut data = sample_data();
+        data.project_mut().name = name.to_string();
+        let file = utaformatix
+            .generate_svp(&data, GenerateOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(String::from_utf8(file).unwrap(), expected, "{}", name);
+    }
+}
+
+#[tokio::test]
+async fn broken_bundle() {
+    let builder = UtaFormatix::builder().bundle("throw new Error('broken bundle');");
+    #[cfg(feature = "multi-thread")]
+    let (builder, crashes) = {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let builder = builder.on_crash(move |report| sender.send(report.clone()).unwrap());
+        (builder, receiver)
+    };
+    let utaformatix = builder.build();
+
+    // Every request fails with the error of the bundle, instead of crashing the runner.
+    for _ in 0..2 {
+        let error = utaformatix
+            .generate_ccs(&sample_data(), GenerateOptions::default())
+            .await
+            .unwrap_err();
+        let ErrorKind::Unexpected(message) = error.kind() else {
+            panic!("Unexpected error kind: {:?}", error.kind());
+        };
+        assert!(message.contains("Failed to evaluate script"), "{}", message);
+    }
+    #[cfg(feature = "multi-thread")]
+    assert!(crashes.try_recv().is_err());
+}
+
+#[cfg(all(feature = "multi-thread", debug_assertions))]
+#[tokio::test]
+async fn runner_panic() {
+    let (sender, crashes) = std::sync::mpsc::channel();
+    let utaformatix = UtaFormatix::builder()
+        .bundle(
+            "var utaformatix = {
+                generateCcs: async (data) => {
+                    if (data.project.name === 'crash') {
+                        __panic('crashed by the bundle');
+                    }
+                    return new Uint8Array(__encode('ok'));
+                },
+            };",
+        )
+        .on_crash(move |report| sender.send(report.clone()).unwrap())
+        .build();
+
+    let mut data = sample_data();
+    data.project_mut().name = "crash".to_string();
+    let error = utaformatix
+        .generate_ccs(&data, GenerateOptions::default())
+        .await
+        .unwrap_err();
+    let ErrorKind::RunnerPanicked(report) = error.kind() else {
+        panic!("Unexpected error kind: {:?}", error.kind());
+    };
+    assert_eq!(report.message, "crashed by the bundle");
+    let request = report.request.as_ref().unwrap();
+    assert_eq!(request.kind, "generate");
+    assert_eq!(request.format, Some(Format::Ccs));
+    assert_eq!(&crashes.try_recv().unwrap(), report.as_ref());
+
+    // The same input with other options is another request, which gets another dump.
+    let error = utaformatix
+        .generate_ccs(
+            &data,
+            GenerateOptions {
+                pitch: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+    let ErrorKind::RunnerPanicked(other) = error.kind() else {
+        panic!("Unexpected error kind: {:?}", error.kind());
+    };
+    let other = other.request.as_ref().unwrap();
+    assert_eq!(other.sizes, request.sizes);
+    assert_ne!(other.hash, request.hash);
+
+    // The runner is restarted for the next request.
+    let file = utaformatix
+        .generate_ccs(&sample_data(), GenerateOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(file, b"ok");
+}
+
+#[tokio::test]
+async fn parse_mixed() {
+    let engine = UtaFormatix::new();
+    let lead = sample_data().to_bytes(GenerateOptions::default()).unwrap();
+    let mut harmony = sample_data();
+    harmony.project_mut().measure_prefix = 1;
+    harmony.project_mut().tracks[0].name = "Harmony".to_string();
+    let harmony = harmony.to_bytes(GenerateOptions::default()).unwrap();
+    let midi = engine
+        .generate_standard_mid(&sample_data(), GenerateOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(Format::detect(&lead), Some(Format::UfData));
+    assert_eq!(Format::detect(&midi), Some(Format::StandardMid));
+    assert_eq!(Format::detect(b"not a project"), None);
+
+    let merged = engine
+        .parse_mixed(
+            [(Format::UfData, lead), (Format::UfData, harmony)],
+            ParseOptions::default(),
+        )
+        .await
+        .unwrap();
+    let project = merged.project();
+    assert_eq!(project.measure_prefix, 0);
+    assert_eq!(project.tempos, sample_data().project().tempos);
+    assert_eq!(project.tracks.len(), 2);
+    assert_eq!(project.tracks[0], sample_data().project().tracks[0]);
+    // The notes of the harmony are moved from the end of its prefix measure to the start.
+    assert_eq!(project.tracks[1].name, "Harmony");
+    assert_eq!(project.tracks[1].notes[0].tick_on, 0);
+
+    let error = engine
+        .parse_mixed(Vec::<(Format, Vec<u8>)>::new(), ParseOptions::default())
+        .await
+        .unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::EmptyProject));
+}
+
+#[tokio::test]
+async fn generate_ccs_per_track() {
+    // Each file is the name of the track and the number of tracks which the bundle received.
+    // The files are generated in parallel with multiple workers.
+    let utaformatix = UtaFormatix::builder()
+        .workers(2)
+        .bundle(
+            "var utaformatix = { generateCcs: async (data, options) =>
+                new Uint8Array([...data.project.tracks.map((track) => track.name).join('')
+                    + data.project.tracks.length].map((c) => c.charCodeAt(0))) };",
+        )
+        .build();
+    let mut data = sample_data();
+    let track = data.project().tracks[0].clone();
+    data.project_mut().tracks = ["a", "b", "c"]
+        .iter()
+        .map(|name| Track {
+            name: name.to_string(),
+            ..track.clone()
+        })
+        .collect();
+
+    let files = utaformatix
+        .generate_ccs_per_track(&data, GenerateOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        files,
+        [
+            ("a".to_string(), b"a1".to_vec()),
+            ("b".to_string(), b"b1".to_vec()),
+            ("c".to_string(), b"c1".to_vec()),
+        ]
+    );
+    let file = utaformatix
+        .generate_ccs(&data, GenerateOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(file, b"abc3");
+}
+
+#[tokio::test]
+async fn lyric_markers() {
+    let mut data = sample_data();
+    for (note, lyric) in
+        data.project_mut().tracks[0]
+            .notes
+            .iter_mut()
+            .zip(["[mf]", "ら[Cresc]", "[br]", "[x]"])
+    {
+        note.lyric = lyric.to_string();
+    }
+    let options = GenerateOptions {
+        markers: Some(LyricMarkers::default()),
+        ..Default::default()
+    };
+    let generated = UfData::from_bytes(
+        &data.to_bytes(options.clone()).unwrap(),
+        ParseOptions::default(),
+    )
+    .unwrap();
+    let lyrics = generated.project().tracks[0]
+        .notes
+        .iter()
+        .map(|note| note.lyric.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(lyrics, ["ら", "br", "[x]"]);
+
+    // Each note is written as an element with its lyric.
+    let utaformatix = UtaFormatix::builder()
+        .bundle(
+            "var utaformatix = { generateMusicXml: async (data, options) =>
+                data.project.tracks.map((track) => new Uint8Array(__encode('<part>'
+                    + track.notes.map((note) => '<note>' + note.lyric + '</note>').join('')
+                    + '</part>'))) };",
+        )
+        .build();
+    data.project_mut().tracks[0].notes[2].lyric = "み".to_string();
+    let files = utaformatix
+        .generate_music_xml(&data, options)
+        .await
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(files[0].clone()).unwrap(),
+        "<part>\
+        <direction placement=\"below\"><direction-type><dynamics><mf/></dynamics>\
+        </direction-type></direction>\
+        <direction placement=\"below\"><direction-type><words>cresc.</words>\
+        </direction-type></direction>\
+        <note>ら</note><note>み</note><note>[x]</note></part>"
+    );
+}
+
+#[rstest::rstest]
+#[tokio::test]
+async fn key_signatures(engine: UtaFormatix) {
+    let mut data = sample_data();
+    data.project_mut().key_signatures = vec![
+        KeySignature::from_fifths(2, Mode::Major),
+        KeySignature {
+            measure_position: 1,
+            ..KeySignature::from_fifths(-3, Mode::Minor)
+        },
+    ];
+    let generated = engine
+        .generate_standard_mid(&data, GenerateOptions::default())
+        .await
+        .unwrap();
+    let parsed = engine
+        .parse_standard_mid(generated, ParseOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        parsed.project().key_signatures,
+        data.project().key_signatures
+    );
+    assert_eq!(parsed.project().key_signatures[1].tonic, 0);
+
+    let utaformatix = UtaFormatix::builder()
+        .bundle(
+            r#"var utaformatix = {
+                parseMusicXml: async (data, options) => ({
+                    formatVersion: 1,
+                    project: {
+                        name: 'Project',
+                        tracks: [{
+                            name: 'Track',
+                            notes: [{ key: 60, tickOn: 0, tickOff: 480, lyric: 'ら', phoneme: null }],
+                            pitch: null,
+                        }],
+                        timeSignatures: [{ measurePosition: 0, numerator: 4, denominator: 4 }],
+                        tempos: [{ tickPosition: 0, bpm: 120 }],
+                        measurePrefix: 0,
+                    },
+                }),
+                generateMusicXml: async (data, options) =>
+                    data.project.tracks.map(() => new Uint8Array(__encode('<part id="P1">'
+                        + '<measure number="1"><attributes><divisions>480</divisions>'
+                        + '<key><fifths>0</fifths></key><time><beats>4</beats></time>'
+                        + '</attributes></measure>'
+                        + '<measure number="2"><note>ら</note></measure>'
+                        + '<measure number="3"><attributes><measure-style/></attributes>'
+                        + '</measure></part>'))),
+            };"#,
+        )
+        .build();
+    let music_xml = "<score-partwise><part id=\"P1\">\
+        <measure number=\"1\"><attributes><key><fifths>2</fifths><mode>major</mode></key>\
+        </attributes></measure>\
+        <measure number=\"2\"><attributes><key><fifths>2</fifths></key></attributes></measure>\
+        <measure number=\"3\"><attributes><key><fifths>-3</fifths><mode>minor</mode></key>\
+        </attributes></measure>\
+        </part></score-partwise>";
+    let parsed = utaformatix
+        .parse_music_xml(music_xml.as_bytes().to_vec(), ParseOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        parsed.project().key_signatures,
+        [
+            KeySignature::from_fifths(2, Mode::Major),
+            KeySignature {
+                measure_position: 2,
+                ..KeySignature::from_fifths(-3, Mode::Minor)
+            },
+        ]
+    );
+
+    let files = utaformatix
+        .generate_music_xml(&data, GenerateOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(files[0].clone()).unwrap(),
+        "<part id=\"P1\">\
+        <measure number=\"1\"><attributes><divisions>480</divisions>\
+        <key><fifths>2</fifths><mode>major</mode></key><time><beats>4</beats></time>\
+        </attributes></measure>\
+        <measure number=\"2\"><attributes><key><fifths>-3</fifths><mode>minor</mode></key>\
+        </attributes><note>ら</note></measure>\
+        <measure number=\"3\"><attributes><measure-style/></attributes></measure></part>"
+    );
+}
+
+#[test]
+fn inspect_ppsf() {
+    let legacy = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <!-- <track> -->\n\
+        <Project version=\"2.0.1\" name=\"Rock &amp; Roll\">\n\
+        <Track id=\"1\"><Note/></Track>\n\
+        <track id='2'/>\n\
+        </Project>";
+    assert_eq!(
+        Ppsf::inspect(legacy.as_bytes()),
+        Some(PpsfInfo {
+            legacy: true,
+            version: Some("2.0.1".to_string()),
+            project_name: Some("Rock & Roll".to_string()),
+            track_count: Some(2),
+        })
+    );
+    assert_eq!(
+        Ppsf::inspect(b"PK\x03\x04"),
+        Some(PpsfInfo {
+            legacy: false,
+            ..Default::default()
+        })
+    );
+    assert_eq!(Ppsf::inspect(b"\x00\x01"), None);
+    assert_eq!(Ppsf::inspect(b"<!-- not a project -->"), None);
+}
@@ -0,0 +1,29 @@
+#![cfg(feature = "schemars")]
+use utaformatix::{Key, Note, Tick, UfData};
+
+#[test]
+fn schema_matches_serialization() {
+    let schema = serde_json::to_value(schemars::schema_for!(UfData)).unwrap();
+    assert_eq!(
+        schema["required"],
+        serde_json::json!(["formatVersion", "project"])
+    );
+
+    let note = serde_json::to_value(Note {
+        key: Key::CENTER_C,
+        tick_on: Tick::ZERO,
+        tick_off: Tick::new(480).unwrap(),
+        lyric: "あ".to_string(),
+        phoneme: None,
+        phoneme_segments: None,
+    })
+    .unwrap();
+    let properties = &schema["definitions"]["Note"]["properties"];
+    for key in note.as_object().unwrap().keys() {
+        assert!(
+            properties.get(key).is_some(),
+            "{} is not in the schema",
+            key
+        );
+    }
+}